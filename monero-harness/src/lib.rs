@@ -233,6 +233,10 @@ impl<'c> Monerod {
         &self.client
     }
 
+    pub fn rpc_port(&self) -> u16 {
+        self.rpc_port
+    }
+
     /// Spawns a task to mine blocks in a regular interval to the provided
     /// address
     pub async fn start_miner(&self, miner_wallet_address: &str) -> Result<()> {
@@ -0,0 +1,529 @@
+//! The HTTP transport shared by the monerod and monero-wallet-rpc clients.
+//!
+//! Both daemons protect their RPC with HTTP digest authentication when
+//! started with `--rpc-login`, which `reqwest` does not support by itself.
+
+use anyhow::{bail, Context, Result};
+use md5::{Digest, Md5};
+use rand::Rng;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use reqwest::{Certificate, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// The username and password given to `--rpc-login`.
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl FromStr for Credentials {
+    type Err = anyhow::Error;
+
+    /// Parses credentials in the `username:password` format of `--rpc-login`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (username, password) = s
+            .split_once(':')
+            .context("Login must be of the form <username>:<password>")?;
+
+        Ok(Self {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        })
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// How to connect to an RPC server, in addition to its URL.
+#[derive(Clone, Debug, Default)]
+pub struct Connection {
+    /// Authenticate with HTTP digest authentication.
+    pub login: Option<Credentials>,
+    /// Trust this certificate in addition to the system's root certificates,
+    /// e.g. the certificate of a private CA that signed the server's one.
+    pub root_certificate: Option<Certificate>,
+}
+
+impl Connection {
+    pub fn with_login(login: Option<Credentials>) -> Self {
+        Self {
+            login,
+            root_certificate: None,
+        }
+    }
+
+    /// Reads the PEM encoded certificate at `path` and trusts it.
+    pub fn with_root_certificate_file(self, path: &std::path::Path) -> Result<Self> {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read certificate {}", path.display()))?;
+        let certificate = Certificate::from_pem(&pem)
+            .with_context(|| format!("Invalid certificate {}", path.display()))?;
+
+        Ok(Self {
+            root_certificate: Some(certificate),
+            ..self
+        })
+    }
+}
+
+/// An HTTP client that answers digest authentication challenges.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    inner: reqwest::Client,
+    login: Option<Credentials>,
+    /// The last challenge of the server, so that subsequent requests can be
+    /// authenticated right away instead of being rejected first.
+    challenge: Arc<Mutex<Option<Challenge>>>,
+}
+
+impl HttpClient {
+    pub fn new(connection: Connection) -> Result<Self> {
+        let mut builder = reqwest::ClientBuilder::new();
+        if let Some(certificate) = connection.root_certificate {
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        Ok(Self {
+            inner: builder.build()?,
+            login: connection.login,
+            challenge: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Sends a JSON-RPC request and parses the response.
+    pub async fn send_json<P>(
+        &self,
+        url: Url,
+        body: String,
+    ) -> Result<jsonrpc_client::Response<P>, reqwest::Error>
+    where
+        P: DeserializeOwned,
+    {
+        self.post(url, "application/json", body.into_bytes())
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// Posts `body` to `url`, authenticating if the server asks for it.
+    pub async fn post(
+        &self,
+        url: Url,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<Response, reqwest::Error> {
+        let login = match &self.login {
+            Some(login) => login,
+            None => return self.send(url, content_type, body, None).await,
+        };
+
+        let authorization = self.authorization(login, &url);
+        let response = self
+            .send(url.clone(), content_type, body.clone(), authorization)
+            .await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let challenge = match Challenge::from_response(&response) {
+            Ok(challenge) => challenge,
+            Err(e) => {
+                tracing::debug!("Unable to answer authentication challenge: {:#}", e);
+                return Ok(response);
+            }
+        };
+        *self.challenge.lock().expect("lock is not poisoned") = Some(challenge);
+
+        let authorization = self.authorization(login, &url);
+        self.send(url, content_type, body, authorization).await
+    }
+
+    async fn send(
+        &self,
+        url: Url,
+        content_type: &str,
+        body: Vec<u8>,
+        authorization: Option<String>,
+    ) -> Result<Response, reqwest::Error> {
+        let mut request = self
+            .inner
+            .post(url)
+            .header(CONTENT_TYPE, content_type)
+            .body(body);
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        request.send().await
+    }
+
+    fn authorization(&self, login: &Credentials, url: &Url) -> Option<String> {
+        let mut challenge = self.challenge.lock().expect("lock is not poisoned");
+        let challenge = challenge.as_mut()?;
+
+        challenge.nonce_count += 1;
+        let cnonce = hex::encode(rand::thread_rng().gen::<[u8; 16]>());
+
+        Some(challenge.respond(login, "POST", url.path(), &cnonce))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Algorithm {
+    Md5,
+    Md5Sess,
+}
+
+/// A digest authentication challenge from a `WWW-Authenticate` header.
+#[derive(Clone, Debug, PartialEq)]
+struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    /// Whether the server supports `qop=auth`. Without it the legacy RFC 2069
+    /// digest is used.
+    qop_auth: bool,
+    nonce_count: u32,
+}
+
+impl Challenge {
+    /// Picks the first challenge we can answer, the servers of monerod and
+    /// monero-wallet-rpc offer both `MD5-sess` and `MD5`.
+    fn from_response(response: &Response) -> Result<Self> {
+        let mut headers = response.headers().get_all(WWW_AUTHENTICATE).iter();
+
+        headers
+            .find_map(|header| Self::parse(header.to_str().ok()?).ok())
+            .context("Server did not send a digest challenge")
+    }
+
+    fn parse(header: &str) -> Result<Self> {
+        let params = match header.trim_start().split_once(' ') {
+            Some((scheme, params)) if scheme.eq_ignore_ascii_case("digest") => params,
+            _ => bail!("Not a digest challenge: {}", header),
+        };
+
+        let mut realm = None;
+        let mut nonce = None;
+        let mut opaque = None;
+        let mut algorithm = Algorithm::Md5;
+        let mut qop_auth = false;
+
+        for (key, value) in parse_params(params) {
+            match key.to_ascii_lowercase().as_str() {
+                "realm" => realm = Some(value),
+                "nonce" => nonce = Some(value),
+                "opaque" => opaque = Some(value),
+                "algorithm" => {
+                    algorithm = match value.to_ascii_uppercase().as_str() {
+                        "MD5" => Algorithm::Md5,
+                        "MD5-SESS" => Algorithm::Md5Sess,
+                        other => bail!("Unsupported digest algorithm {}", other),
+                    }
+                }
+                "qop" => qop_auth = value.split(',').any(|qop| qop.trim() == "auth"),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            realm: realm.context("Digest challenge without realm")?,
+            nonce: nonce.context("Digest challenge without nonce")?,
+            opaque,
+            algorithm,
+            qop_auth,
+            nonce_count: 0,
+        })
+    }
+
+    /// Computes the `Authorization` header for a request to `uri`.
+    fn respond(&self, login: &Credentials, method: &str, uri: &str, cnonce: &str) -> String {
+        let nc = format!("{:08x}", self.nonce_count);
+
+        let ha1 = md5_hex(&format!(
+            "{}:{}:{}",
+            login.username, self.realm, login.password
+        ));
+        let ha1 = match self.algorithm {
+            Algorithm::Md5 => ha1,
+            Algorithm::Md5Sess => md5_hex(&format!("{}:{}:{}", ha1, self.nonce, cnonce)),
+        };
+        let ha2 = md5_hex(&format!("{}:{}", method, uri));
+
+        let mut header = format!(
+            r#"Digest username="{}", realm="{}", nonce="{}", uri="{}""#,
+            login.username, self.realm, self.nonce, uri
+        );
+        if self.qop_auth {
+            let response = md5_hex(&format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, self.nonce, nc, cnonce, ha2
+            ));
+            header.push_str(&format!(
+                r#", qop=auth, nc={}, cnonce="{}", response="{}""#,
+                nc, cnonce, response
+            ));
+        } else {
+            let response = md5_hex(&format!("{}:{}:{}", ha1, self.nonce, ha2));
+            header.push_str(&format!(r#", response="{}""#, response));
+        }
+        if self.algorithm == Algorithm::Md5Sess {
+            header.push_str(", algorithm=MD5-sess");
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(r#", opaque="{}""#, opaque));
+        }
+
+        header
+    }
+}
+
+/// Splits the comma separated `key=value` parameters of an authentication
+/// header. Values may be quoted, in which case they can contain commas.
+fn parse_params(params: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut rest = params.trim();
+
+    while let Some((key, after_key)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_owned();
+        let after_key = after_key.trim_start();
+
+        let (value, after_value) = match after_key.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (quoted[..end].to_owned(), &quoted[end + 1..]),
+                None => (quoted.to_owned(), ""),
+            },
+            None => match after_key.find(',') {
+                Some(end) => (after_key[..end].trim().to_owned(), &after_key[end..]),
+                None => (after_key.trim().to_owned(), ""),
+            },
+        };
+
+        result.push((key, value));
+        rest = after_value.trim_start().trim_start_matches(',');
+    }
+
+    result
+}
+
+fn md5_hex(input: &str) -> String {
+    hex::encode(Md5::digest(input.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Server};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    const REALM: &str = "monero-rpc";
+    const NONCE: &str = "dcd98b7102dd2f0e8b11d0f600bfb0c093";
+
+    fn login() -> Credentials {
+        Credentials {
+            username: "monero".to_owned(),
+            password: "hunter2".to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_the_challenges_of_monero_wallet_rpc() {
+        let md5_sess = Challenge::parse(
+            r#"Digest qop="auth",algorithm=MD5-sess,realm="monero-rpc",nonce="9d3dZFnTTsOcMVw/EFUuUQ==",stale=false"#,
+        )
+        .unwrap();
+        let md5 = Challenge::parse(
+            r#"Digest qop="auth",algorithm=MD5,realm="monero-rpc",nonce="9d3dZFnTTsOcMVw/EFUuUQ==",stale=false"#,
+        )
+        .unwrap();
+
+        assert_eq!(md5_sess.algorithm, Algorithm::Md5Sess);
+        assert_eq!(md5.algorithm, Algorithm::Md5);
+        assert_eq!(md5.realm, "monero-rpc");
+        assert_eq!(md5.nonce, "9d3dZFnTTsOcMVw/EFUuUQ==");
+        assert!(md5.qop_auth);
+    }
+
+    #[test]
+    fn given_basic_challenge_then_fails_to_parse() {
+        assert!(Challenge::parse(r#"Basic realm="monero-rpc""#).is_err());
+    }
+
+    #[test]
+    fn computes_digest_of_rfc_2617_example() {
+        let challenge = Challenge::parse(
+            r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        )
+        .unwrap();
+        let challenge = Challenge {
+            nonce_count: 1,
+            ..challenge
+        };
+        let login = Credentials {
+            username: "Mufasa".to_owned(),
+            password: "Circle Of Life".to_owned(),
+        };
+
+        let header = challenge.respond(&login, "GET", "/dir/index.html", "0a4f113b");
+
+        assert!(header.contains(r#"response="6629fae49393a05397450978507c4ef1""#));
+        assert!(header.contains(r#"opaque="5ccc069c403ebaf9f0171e9517f40e41""#));
+        assert!(header.contains("nc=00000001"));
+    }
+
+    #[test]
+    fn parses_login() {
+        let login = Credentials::from_str("monero:pass:word").unwrap();
+
+        assert_eq!(login.username, "monero");
+        assert_eq!(login.password, "pass:word");
+        assert!(Credentials::from_str("monero").is_err());
+    }
+
+    #[tokio::test]
+    async fn answers_digest_challenge_of_server() {
+        let address = spawn_digest_server().await;
+        let client = HttpClient::new(Connection::with_login(Some(login()))).unwrap();
+
+        for _ in 0..2 {
+            let response = client
+                .post(url(address), "application/json", b"{}".to_vec())
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn given_wrong_password_then_request_is_rejected() {
+        let address = spawn_digest_server().await;
+        let client = HttpClient::new(Connection::with_login(Some(Credentials {
+            password: "wrong".to_owned(),
+            ..login()
+        })))
+        .unwrap();
+
+        let response = client
+            .post(url(address), "application/json", b"{}".to_vec())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn given_no_login_then_request_is_rejected() {
+        let address = spawn_digest_server().await;
+        let client = HttpClient::new(Connection::default()).unwrap();
+
+        let response = client
+            .post(url(address), "application/json", b"{}".to_vec())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    fn url(address: SocketAddr) -> Url {
+        format!("http://{}/json_rpc", address).parse().unwrap()
+    }
+
+    /// Serves every request that carries a valid digest for `login()` and
+    /// challenges all others, like monero-wallet-rpc started with
+    /// `--rpc-login`.
+    async fn spawn_digest_server() -> SocketAddr {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+                let authorized = request
+                    .headers()
+                    .get(AUTHORIZATION)
+                    .and_then(|header| header.to_str().ok())
+                    .map(|header| is_valid_digest(header, request.uri().path()))
+                    .unwrap_or(false);
+
+                let response = if authorized {
+                    hyper::Response::new(Body::from("{}"))
+                } else {
+                    hyper::Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .header(
+                            WWW_AUTHENTICATE,
+                            format!(
+                                r#"Digest qop="auth",algorithm=MD5-sess,realm="{}",nonce="{}",stale=false"#,
+                                REALM, NONCE
+                            ),
+                        )
+                        .header(
+                            WWW_AUTHENTICATE,
+                            format!(
+                                r#"Digest qop="auth",algorithm=MD5,realm="{}",nonce="{}",stale=false"#,
+                                REALM, NONCE
+                            ),
+                        )
+                        .body(Body::empty())
+                        .unwrap()
+                };
+
+                Ok::<_, Infallible>(response)
+            }))
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+
+        address
+    }
+
+    fn is_valid_digest(header: &str, path: &str) -> bool {
+        let params = match header.strip_prefix("Digest ") {
+            Some(params) => parse_params(params),
+            None => return false,
+        };
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default()
+        };
+
+        let login = login();
+        let mut ha1 = md5_hex(&format!("{}:{}:{}", login.username, REALM, login.password));
+        if param("algorithm") == "MD5-sess" {
+            ha1 = md5_hex(&format!("{}:{}:{}", ha1, NONCE, param("cnonce")));
+        }
+        let ha2 = md5_hex(&format!("POST:{}", path));
+        let expected = md5_hex(&format!(
+            "{}:{}:{}:{}:auth:{}",
+            ha1,
+            NONCE,
+            param("nc"),
+            param("cnonce"),
+            ha2
+        ));
+
+        param("username") == login.username
+            && param("uri") == path
+            && param("qop") == "auth"
+            && param("response") == expected
+    }
+}
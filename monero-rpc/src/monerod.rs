@@ -1,9 +1,12 @@
+use crate::http::{Connection, HttpClient};
 use anyhow::{Context, Result};
 use monero::cryptonote::hash::Hash;
 use monero::util::ringct;
 use monero::PublicKey;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
 
 #[jsonrpc_client::api(version = "2.0")]
 pub trait MonerodRpc {
@@ -11,38 +14,68 @@ pub trait MonerodRpc {
         -> GenerateBlocks;
     async fn get_block_header_by_height(&self, height: u32) -> BlockHeader;
     async fn get_block_count(&self) -> BlockCount;
+    async fn get_info(&self) -> GetInfo;
     async fn get_block(&self, height: u32) -> GetBlockResponse;
+    async fn get_output_distribution(
+        &self,
+        amounts: Vec<u64>,
+        from_height: u64,
+        to_height: u64,
+        cumulative: bool,
+        binary: bool,
+    ) -> GetOutputDistributionResponse;
 }
 
-#[jsonrpc_client::implement(MonerodRpc)]
 #[derive(Debug, Clone)]
 pub struct Client {
-    inner: reqwest::Client,
+    inner: HttpClient,
     base_url: reqwest::Url,
     get_o_indexes_bin_url: reqwest::Url,
     get_outs_bin_url: reqwest::Url,
+    get_transactions_url: reqwest::Url,
+}
+
+// See `wallet::Client` for why this is not using `jsonrpc_client::implement`.
+#[async_trait::async_trait]
+impl MonerodRpc<reqwest::Client> for Client {
+    async fn send_request<P>(
+        &self,
+        request: String,
+    ) -> Result<jsonrpc_client::Response<P>, reqwest::Error>
+    where
+        P: DeserializeOwned,
+    {
+        self.inner.send_json(self.base_url.clone(), request).await
+    }
 }
 
 impl Client {
     /// New local host monerod RPC client.
     pub fn localhost(port: u16) -> Result<Self> {
-        Self::new("127.0.0.1".to_owned(), port)
+        Self::from_address(&format!("127.0.0.1:{}", port))
     }
 
-    fn new(host: String, port: u16) -> Result<Self> {
-        Ok(Self {
-            inner: reqwest::ClientBuilder::new()
-                .connection_verbose(true)
-                .build()?,
-            base_url: format!("http://{}:{}/json_rpc", host, port)
-                .parse()
-                .context("url is well formed")?,
-            get_o_indexes_bin_url: format!("http://{}:{}/get_o_indexes.bin", host, port)
-                .parse()
-                .context("url is well formed")?,
-            get_outs_bin_url: format!("http://{}:{}/get_outs.bin", host, port)
+    /// New monerod RPC client for a daemon address of the form `host:port`,
+    /// optionally prefixed with `http://` or `https://`.
+    pub fn from_address(address: &str) -> Result<Self> {
+        Self::connect(&address.parse()?, Connection::default())
+    }
+
+    /// New monerod RPC client that logs in and verifies TLS certificates as
+    /// configured in `connection`.
+    pub fn connect(address: &DaemonAddress, connection: Connection) -> Result<Self> {
+        let url = |path: &str| -> Result<reqwest::Url> {
+            format!("{}/{}", address.url(), path)
                 .parse()
-                .context("url is well formed")?,
+                .context("url is well formed")
+        };
+
+        Ok(Self {
+            inner: HttpClient::new(connection)?,
+            base_url: url("json_rpc")?,
+            get_o_indexes_bin_url: url("get_o_indexes.bin")?,
+            get_outs_bin_url: url("get_outs.bin")?,
+            get_transactions_url: url("get_transactions")?,
         })
     }
 
@@ -58,6 +91,38 @@ impl Client {
             .await
     }
 
+    /// Fetches transactions from the blockchain or the transaction pool.
+    pub async fn get_transactions(
+        &self,
+        tx_hashes: Vec<String>,
+    ) -> Result<GetTransactionsResponse> {
+        self.json_request(
+            self.get_transactions_url.clone(),
+            GetTransactionsPayload {
+                txs_hashes: tx_hashes,
+                decode_as_json: false,
+            },
+        )
+        .await
+    }
+
+    async fn json_request<Req, Res>(&self, url: reqwest::Url, request: Req) -> Result<Res>
+    where
+        Req: Serialize,
+        Res: DeserializeOwned,
+    {
+        let response = self
+            .inner
+            .post(url, "application/json", serde_json::to_vec(&request)?)
+            .await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Request failed with status code {}", response.status())
+        }
+
+        Ok(response.json().await?)
+    }
+
     async fn binary_request<Req, Res>(&self, url: reqwest::Url, request: Req) -> Result<Res>
     where
         Req: Serialize,
@@ -65,9 +130,11 @@ impl Client {
     {
         let response = self
             .inner
-            .post(url)
-            .body(monero_epee_bin_serde::to_bytes(&request)?)
-            .send()
+            .post(
+                url,
+                "application/octet-stream",
+                monero_epee_bin_serde::to_bytes(&request)?,
+            )
             .await?;
 
         if !response.status().is_success() {
@@ -80,6 +147,66 @@ impl Client {
     }
 }
 
+/// The address of a monerod, `host:port` optionally prefixed with `http://`
+/// or `https://`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DaemonAddress {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl DaemonAddress {
+    /// The address in the `host:port` form expected by monero-wallet-rpc.
+    pub fn host_and_port(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    fn url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+
+        format!("{}://{}", scheme, self.host_and_port())
+    }
+}
+
+impl FromStr for DaemonAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(address: &str) -> Result<Self> {
+        let (tls, host_and_port) = if let Some(rest) = address.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = address.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            (false, address)
+        };
+        let host_and_port = host_and_port.trim_end_matches('/');
+
+        let (host, port) = host_and_port
+            .rsplit_once(':')
+            .with_context(|| format!("Daemon address {} is missing a port", address))?;
+        let port = port
+            .parse()
+            .with_context(|| format!("Invalid port in daemon address {}", address))?;
+
+        Ok(Self {
+            host: host.to_owned(),
+            port,
+            tls,
+        })
+    }
+}
+
+impl fmt::Display for DaemonAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tls {
+            write!(f, "https://{}", self.host_and_port())
+        } else {
+            write!(f, "{}", self.host_and_port())
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct GenerateBlocks {
     pub blocks: Vec<String>,
@@ -91,6 +218,17 @@ pub struct BlockCount {
     pub count: u32,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct GetInfo {
+    pub height: u64,
+    pub target_height: u64,
+    /// Whether the daemon considers itself in sync with the network.
+    #[serde(default)]
+    pub synchronized: bool,
+    #[serde(default)]
+    pub offline: bool,
+}
+
 // We should be able to use monero-rs for this but it does not include all
 // the fields.
 #[derive(Clone, Debug, Deserialize)]
@@ -116,6 +254,20 @@ pub struct GetBlockResponse {
     pub blob: monero::Block,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetOutputDistributionResponse {
+    pub distributions: Vec<OutputDistribution>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OutputDistribution {
+    pub amount: u64,
+    /// The number of outputs before `start_height`.
+    pub base: u64,
+    pub distribution: Vec<u64>,
+    pub start_height: u64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GetIndexesResponse {
     pub o_indexes: Vec<u32>,
@@ -157,6 +309,32 @@ pub struct OutKey {
     pub unlocked: bool,
 }
 
+#[derive(Clone, Debug, Serialize)]
+struct GetTransactionsPayload {
+    txs_hashes: Vec<String>,
+    decode_as_json: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct GetTransactionsResponse {
+    #[serde(flatten)]
+    pub base: BaseResponse,
+    #[serde(default)]
+    pub txs: Vec<TransactionEntry>,
+    #[serde(default)]
+    pub missed_tx: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TransactionEntry {
+    pub tx_hash: String,
+    pub as_hex: String,
+    pub in_pool: bool,
+    /// Only set if the transaction is not in the pool anymore.
+    #[serde(default)]
+    pub block_height: u64,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct BaseResponse {
     pub credits: u64,
@@ -257,3 +435,89 @@ mod byte_array {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_deserialize_get_transactions_response() {
+        let response = r#"{
+          "credits": 0,
+          "status": "OK",
+          "top_hash": "",
+          "txs": [{
+            "as_hex": "0200",
+            "as_json": "",
+            "block_height": 993442,
+            "block_timestamp": 1457749396,
+            "double_spend_seen": false,
+            "in_pool": false,
+            "output_indices": [198769, 418598],
+            "prunable_as_hex": "",
+            "prunable_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+            "pruned_as_hex": "",
+            "tx_hash": "d6e48158472848e6687173a91ae6eebfa3e1d778e65252ee99d7515d63090408"
+          }],
+          "txs_as_hex": ["0200"],
+          "untrusted": false
+        }"#;
+
+        let response: GetTransactionsResponse = serde_json::from_str(response).unwrap();
+
+        assert_eq!(response.txs[0].block_height, 993442);
+        assert!(response.missed_tx.is_empty());
+    }
+
+    #[test]
+    fn can_deserialize_get_transactions_response_for_pool_transaction() {
+        let response = r#"{
+          "credits": 0,
+          "missed_tx": ["c1d8cfa87d445c1915a59d67be3e93ba8a29018640cf69b465f07b1840a8f8c8"],
+          "status": "OK",
+          "top_hash": "",
+          "txs": [{
+            "as_hex": "0200",
+            "double_spend_seen": false,
+            "in_pool": true,
+            "tx_hash": "d6e48158472848e6687173a91ae6eebfa3e1d778e65252ee99d7515d63090408"
+          }],
+          "untrusted": false
+        }"#;
+
+        let response: GetTransactionsResponse = serde_json::from_str(response).unwrap();
+
+        assert!(response.txs[0].in_pool);
+        assert_eq!(response.missed_tx.len(), 1);
+    }
+
+    #[test]
+    fn parses_daemon_address() {
+        let client = Client::from_address("node.example.com:18081").unwrap();
+
+        assert_eq!(
+            client.get_transactions_url.as_str(),
+            "http://node.example.com:18081/get_transactions"
+        );
+        assert!(Client::from_address("node.example.com").is_err());
+    }
+
+    #[test]
+    fn given_https_address_then_uses_tls() {
+        let address = DaemonAddress::from_str("https://node.example.com:18089").unwrap();
+        let client = Client::connect(&address, Connection::default()).unwrap();
+
+        assert!(address.tls);
+        assert_eq!(address.host_and_port(), "node.example.com:18089");
+        assert_eq!(address.to_string(), "https://node.example.com:18089");
+        assert_eq!(
+            client.base_url.as_str(),
+            "https://node.example.com:18089/json_rpc"
+        );
+        assert!(
+            !DaemonAddress::from_str("http://node.example.com:18081")
+                .unwrap()
+                .tls
+        );
+    }
+}
@@ -1,5 +1,6 @@
+use crate::http::{Connection, HttpClient};
 use anyhow::{Context, Result};
-use serde::de::Error;
+use serde::de::{DeserializeOwned, Error};
 use serde::{Deserialize, Deserializer, Serialize};
 
 #[jsonrpc_client::api(version = "2.0")]
@@ -33,15 +34,38 @@ pub trait MoneroWalletRpc {
     async fn refresh(&self) -> Refreshed;
     async fn sweep_all(&self, address: String) -> SweepAll;
     async fn get_version(&self) -> Version;
+    async fn set_daemon(
+        &self,
+        address: String,
+        trusted: bool,
+        username: String,
+        password: String,
+        ssl_support: String,
+    ) -> DaemonSet;
 }
 
-#[jsonrpc_client::implement(MoneroWalletRpc)]
 #[derive(Debug, Clone)]
 pub struct Client {
-    inner: reqwest::Client,
+    inner: HttpClient,
     base_url: reqwest::Url,
 }
 
+// Implemented by hand instead of with `jsonrpc_client::implement` because the
+// requests go through our own `HttpClient`, which answers digest challenges.
+// Errors are still those of `reqwest`.
+#[async_trait::async_trait]
+impl MoneroWalletRpc<reqwest::Client> for Client {
+    async fn send_request<P>(
+        &self,
+        request: String,
+    ) -> Result<jsonrpc_client::Response<P>, reqwest::Error>
+    where
+        P: DeserializeOwned,
+    {
+        self.inner.send_json(self.base_url.clone(), request).await
+    }
+}
+
 impl Client {
     /// Constructs a monero-wallet-rpc client with localhost endpoint.
     pub fn localhost(port: u16) -> Result<Self> {
@@ -54,10 +78,14 @@ impl Client {
 
     /// Constructs a monero-wallet-rpc client with `url` endpoint.
     pub fn new(url: reqwest::Url) -> Result<Self> {
+        Self::connect(url, Connection::default())
+    }
+
+    /// Constructs a monero-wallet-rpc client with `url` endpoint that logs in
+    /// and verifies TLS certificates as configured in `connection`.
+    pub fn connect(url: reqwest::Url, connection: Connection) -> Result<Self> {
         Ok(Self {
-            inner: reqwest::ClientBuilder::new()
-                .connection_verbose(true)
-                .build()?,
+            inner: HttpClient::new(connection)?,
             base_url: url,
         })
     }
@@ -200,6 +228,7 @@ pub struct Version {
 pub type WalletCreated = Empty;
 pub type WalletClosed = Empty;
 pub type WalletOpened = Empty;
+pub type DaemonSet = Empty;
 
 /// Zero-sized struct to allow serde to deserialize an empty JSON object.
 ///
@@ -0,0 +1,269 @@
+//! Decoy selection following the gamma distribution used by the reference
+//! wallet (`gamma_picker` in Monero's `wallet2.cpp`).
+//!
+//! The distribution is fitted to the age of real spends, measured in seconds.
+//! Ages are mapped to outputs through the cumulative output distribution of
+//! the chain, so decoys are picked in proportion to how busy each block was.
+
+use anyhow::{bail, Result};
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// The number of members of a ring, i.e. the real output and its decoys.
+pub const RING_SIZE: usize = 16;
+
+/// The number of blocks an output needs to be buried under before it can be
+/// spent (`CRYPTONOTE_DEFAULT_TX_SPENDABLE_AGE`).
+pub const SPENDABLE_AGE: usize = 10;
+
+const DIFFICULTY_TARGET_SECS: f64 = 120.0;
+const BLOCKS_PER_YEAR: usize = 365 * 24 * 60 * 60 / 120;
+
+const GAMMA_SHAPE: f64 = 19.28;
+const GAMMA_SCALE: f64 = 1.0 / 1.61;
+
+/// Outputs are at least this old before they can be spent.
+const DEFAULT_UNLOCK_TIME_SECS: f64 = SPENDABLE_AGE as f64 * DIFFICULTY_TARGET_SECS;
+const RECENT_SPEND_WINDOW_SECS: u64 = 15 * 120;
+
+#[derive(Debug, Clone)]
+pub struct GammaPicker {
+    /// The cumulative number of RingCT outputs at each block height.
+    rct_offsets: Vec<u64>,
+    average_output_time: f64,
+    num_spendable_outputs: u64,
+}
+
+impl GammaPicker {
+    /// Creates a picker from the cumulative output distribution starting at
+    /// the genesis block, as returned by monerod's `get_output_distribution`.
+    pub fn new(rct_offsets: Vec<u64>) -> Result<Self> {
+        if rct_offsets.len() <= SPENDABLE_AGE {
+            bail!("Not enough blocks to pick decoys from");
+        }
+
+        let blocks_to_consider = rct_offsets.len().min(BLOCKS_PER_YEAR);
+        let outputs_before = if blocks_to_consider < rct_offsets.len() {
+            rct_offsets[rct_offsets.len() - blocks_to_consider - 1]
+        } else {
+            0
+        };
+        let outputs_to_consider = rct_offsets[rct_offsets.len() - 1] - outputs_before;
+
+        let num_spendable_outputs = rct_offsets[rct_offsets.len() - SPENDABLE_AGE - 1];
+        if num_spendable_outputs == 0 {
+            bail!("No spendable RingCT outputs to pick decoys from");
+        }
+
+        let average_output_time =
+            DIFFICULTY_TARGET_SECS * blocks_to_consider as f64 / outputs_to_consider as f64;
+
+        Ok(Self {
+            rct_offsets,
+            average_output_time,
+            num_spendable_outputs,
+        })
+    }
+
+    /// The number of outputs that are old enough to be spent.
+    pub fn num_spendable_outputs(&self) -> u64 {
+        self.num_spendable_outputs
+    }
+
+    /// Picks the global index of a decoy.
+    ///
+    /// Returns `None` if the pick landed outside of the spendable outputs, in
+    /// which case the caller should pick again.
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> Option<u64> {
+        let age = sample_gamma(rng, GAMMA_SHAPE, GAMMA_SCALE).exp();
+        let age = if age > DEFAULT_UNLOCK_TIME_SECS {
+            age - DEFAULT_UNLOCK_TIME_SECS
+        } else {
+            rng.gen_range(0, RECENT_SPEND_WINDOW_SECS) as f64
+        };
+
+        let outputs_back = (age / self.average_output_time) as u64;
+        if outputs_back >= self.num_spendable_outputs {
+            return None;
+        }
+        let output = self.num_spendable_outputs - 1 - outputs_back;
+
+        // Pick a random output from the block the chosen output is in.
+        let spendable = &self.rct_offsets[..self.rct_offsets.len() - SPENDABLE_AGE];
+        let block = spendable.partition_point(|&offset| offset < output);
+        let first_output = match block {
+            0 => 0,
+            block => spendable[block - 1],
+        };
+        let outputs_in_block = spendable[block] - first_output;
+        if outputs_in_block == 0 {
+            return None;
+        }
+
+        Some(first_output + rng.gen_range(0, outputs_in_block))
+    }
+}
+
+/// Converts the sorted global indices of the ring members into the relative
+/// offsets stored in a transaction input.
+pub fn to_relative_offsets(sorted_indices: &[u64]) -> Vec<u64> {
+    sorted_indices
+        .iter()
+        .scan(0, |previous, &index| {
+            let offset = index - *previous;
+            *previous = index;
+            Some(offset)
+        })
+        .collect()
+}
+
+/// Samples from a gamma distribution using the method of Marsaglia and Tsang.
+///
+/// Only valid for `shape >= 1`.
+fn sample_gamma<R: Rng>(rng: &mut R, shape: f64, scale: f64) -> f64 {
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+
+    loop {
+        let (x, v) = loop {
+            let x = sample_standard_normal(rng);
+            let v = 1.0 + c * x;
+            if v > 0.0 {
+                break (x, v * v * v);
+            }
+        };
+        let u: f64 = rng.gen();
+
+        if u < 1.0 - 0.0331 * x.powi(4) || u.ln() < 0.5 * x * x + d * (1.0 - v + v.ln()) {
+            return d * v * scale;
+        }
+    }
+}
+
+/// Samples from the standard normal distribution using the Box-Muller
+/// transform.
+fn sample_standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    // Shift to (0, 1] so that the logarithm is finite.
+    let u1 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SAMPLES: usize = 50_000;
+
+    fn uniform_chain(blocks: u64, outputs_per_block: u64) -> Vec<u64> {
+        (1..=blocks)
+            .map(|height| height * outputs_per_block)
+            .collect()
+    }
+
+    #[test]
+    fn gamma_samples_have_expected_mean_and_variance() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let samples = (0..SAMPLES)
+            .map(|_| sample_gamma(&mut rng, GAMMA_SHAPE, GAMMA_SCALE))
+            .collect::<Vec<_>>();
+        let mean = samples.iter().sum::<f64>() / SAMPLES as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (SAMPLES - 1) as f64;
+
+        let expected_mean = GAMMA_SHAPE * GAMMA_SCALE;
+        let expected_variance = GAMMA_SHAPE * GAMMA_SCALE * GAMMA_SCALE;
+
+        assert!(
+            (mean - expected_mean).abs() < 0.05,
+            "mean {} too far from {}",
+            mean,
+            expected_mean
+        );
+        assert!(
+            (variance - expected_variance).abs() < 0.2,
+            "variance {} too far from {}",
+            variance,
+            expected_variance
+        );
+    }
+
+    #[test]
+    fn half_of_all_picks_are_younger_than_the_median_age() {
+        let outputs_per_block = 20;
+        let picker = GammaPicker::new(uniform_chain(200_000, outputs_per_block)).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // The median of Gamma(k, θ) is approximately kθ(1 - 1/(9k))³ and exp()
+        // preserves the median. On a chain with a constant number of outputs
+        // per block, the age in seconds minus the unlock time maps directly to
+        // blocks.
+        let gamma_median = GAMMA_SHAPE * GAMMA_SCALE * (1.0 - 1.0 / (9.0 * GAMMA_SHAPE)).powi(3);
+        let median_age_in_blocks =
+            (gamma_median.exp() - DEFAULT_UNLOCK_TIME_SECS) / DIFFICULTY_TARGET_SECS;
+
+        // Rejected picks are older than the chain, so they count as older than
+        // the median as well.
+        let younger = (0..SAMPLES)
+            .filter_map(|_| picker.pick(&mut rng))
+            .map(|output| (picker.num_spendable_outputs() - 1 - output) / outputs_per_block)
+            .filter(|&age_in_blocks| (age_in_blocks as f64) <= median_age_in_blocks)
+            .count();
+        let fraction = younger as f64 / SAMPLES as f64;
+
+        assert!(
+            (fraction - 0.5).abs() < 0.01,
+            "{} of all picks are younger than {} blocks",
+            fraction,
+            median_age_in_blocks
+        );
+    }
+
+    #[test]
+    fn never_picks_outputs_that_are_not_spendable_yet() {
+        let rct_offsets = uniform_chain(1_000, 5);
+        let picker = GammaPicker::new(rct_offsets.clone()).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let first_unspendable = rct_offsets[rct_offsets.len() - SPENDABLE_AGE - 1];
+
+        for output in std::iter::repeat_with(|| picker.pick(&mut rng))
+            .flatten()
+            .take(SAMPLES)
+        {
+            assert!(output < first_unspendable);
+        }
+    }
+
+    #[test]
+    fn given_blocks_without_outputs_then_only_picks_existing_outputs() {
+        // Like on mainnet, the first blocks have no RingCT outputs at all.
+        let rct_offsets = std::iter::repeat(0)
+            .take(500)
+            .chain(1..=1_500)
+            .collect::<Vec<u64>>();
+        let picker = GammaPicker::new(rct_offsets).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for output in std::iter::repeat_with(|| picker.pick(&mut rng))
+            .flatten()
+            .take(SAMPLES)
+        {
+            assert!(output < picker.num_spendable_outputs());
+        }
+    }
+
+    #[test]
+    fn given_too_few_blocks_then_fails() {
+        assert!(GammaPicker::new(uniform_chain(SPENDABLE_AGE as u64, 10)).is_err());
+    }
+
+    #[test]
+    fn converts_indices_to_relative_offsets() {
+        assert_eq!(to_relative_offsets(&[5, 8, 20, 21]), vec![5, 3, 12, 1]);
+    }
+}
@@ -1,57 +1,106 @@
-use anyhow::{Context, Result};
+pub mod decoys;
+
+pub use crate::decoys::RING_SIZE;
+
+use crate::decoys::GammaPicker;
+use anyhow::{bail, Context, Result};
 use monero::consensus::encode::VarInt;
-use monero::cryptonote::hash::Hashable;
 use monero_rpc::monerod;
-use monero_rpc::monerod::{GetBlockResponse, MonerodRpc as _};
-use rand::Rng;
+use monero_rpc::monerod::{GetOutputsOut, MonerodRpc as _};
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+
+/// How often we pick new decoys to replace the ones that are still locked.
+const MAX_DECOY_ROUNDS: usize = 20;
 
 pub struct Wallet {
     client: monerod::Client,
 }
 
 impl Wallet {
-    /// Chooses 10 random key offsets for use within a new confidential
-    /// transactions.
+    /// Chooses the key offsets of a ring that spends the output with the given
+    /// global index.
     ///
-    /// Choosing these offsets randomly is not ideal for privacy, instead they
-    /// should be chosen in a way that mimics a real spending pattern as much as
-    /// possible.
-    pub async fn choose_ten_random_key_offsets(&self) -> Result<[VarInt; 10]> {
-        let latest_block = self.client.get_block_count().await?;
-        let latest_spendable_block = latest_block.count - 10;
-
-        let block: GetBlockResponse = self.client.get_block(latest_spendable_block).await?;
-
-        let tx_hash = block
-            .blob
-            .tx_hashes
-            .first()
-            .copied()
-            .unwrap_or_else(|| block.blob.miner_tx.hash());
-
-        let indices = self.client.get_o_indexes(tx_hash).await?;
-
-        let last_index = indices
-            .o_indexes
+    /// Decoys are picked with the same gamma distribution as the reference
+    /// wallet. Picks that are still locked are replaced.
+    pub async fn choose_key_offsets(&self, real_output: u64) -> Result<[VarInt; RING_SIZE]> {
+        let picker = GammaPicker::new(self.rct_offsets().await?)?;
+        if picker.num_spendable_outputs() < RING_SIZE as u64 {
+            bail!(
+                "Need at least {} spendable outputs to build a ring but only {} exist",
+                RING_SIZE,
+                picker.num_spendable_outputs()
+            );
+        }
+
+        let mut ring = std::iter::once(real_output).collect::<BTreeSet<_>>();
+
+        for _ in 0..MAX_DECOY_ROUNDS {
+            if ring.len() == RING_SIZE {
+                break;
+            }
+
+            let candidates = {
+                let mut rng = rand::thread_rng();
+                let mut candidates = BTreeSet::new();
+                while ring.len() + candidates.len() < RING_SIZE {
+                    if let Some(index) = picker.pick(&mut rng) {
+                        if !ring.contains(&index) {
+                            candidates.insert(index);
+                        }
+                    }
+                }
+                candidates.into_iter().collect::<Vec<_>>()
+            };
+
+            let outs = self
+                .client
+                .get_outs(
+                    candidates
+                        .iter()
+                        .map(|&index| GetOutputsOut { amount: 0, index })
+                        .collect(),
+                )
+                .await?;
+
+            for (index, out) in candidates.into_iter().zip(outs.outs) {
+                if out.unlocked {
+                    ring.insert(index);
+                }
+            }
+        }
+
+        if ring.len() < RING_SIZE {
+            bail!("Failed to find enough unlocked decoys");
+        }
+
+        let indices = ring.into_iter().collect::<Vec<_>>();
+        let offsets = decoys::to_relative_offsets(&indices)
+            .into_iter()
+            .map(VarInt)
+            .collect::<Vec<_>>()
+            .try_into()
+            .expect("ring to have exactly RING_SIZE members");
+
+        Ok(offsets)
+    }
+
+    /// Fetches the cumulative number of RingCT outputs at each block height.
+    async fn rct_offsets(&self) -> Result<Vec<u64>> {
+        let distribution = self
+            .client
+            .get_output_distribution(vec![0], 0, 0, true, false)
+            .await?
+            .distributions
             .into_iter()
-            .max()
-            .context("Expected at least one output index")?;
-        let oldest_index = last_index - (last_index / 100) * 40; // oldest index must be within last 40% TODO: CONFIRM THIS
-
-        let mut rng = rand::thread_rng();
-
-        Ok([
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-        ])
+            .next()
+            .context("Expected an output distribution for RingCT outputs")?;
+
+        // Blocks before the start height contain `base` outputs in total.
+        let mut rct_offsets = vec![distribution.base; distribution.start_height as usize];
+        rct_offsets.extend(distribution.distribution);
+
+        Ok(rct_offsets)
     }
 }
 
@@ -59,7 +108,7 @@ impl Wallet {
 mod tests {
     use super::*;
     use monero_harness::image::Monerod;
-    use monero_rpc::monerod::{Client, GetOutputsOut};
+    use monero_rpc::monerod::Client;
     use testcontainers::clients::Cli;
     use testcontainers::Docker;
 
@@ -73,21 +122,26 @@ mod tests {
             client: rpc_client.clone(),
         };
 
-        let key_offsets = wallet.choose_ten_random_key_offsets().await.unwrap();
+        let key_offsets = wallet.choose_key_offsets(0).await.unwrap();
+        let indices = key_offsets
+            .iter()
+            .scan(0, |index, offset| {
+                *index += offset.0;
+                Some(*index)
+            })
+            .collect::<Vec<_>>();
         let result = rpc_client
             .get_outs(
-                key_offsets
-                    .to_vec()
-                    .into_iter()
-                    .map(|varint| GetOutputsOut {
-                        amount: 0,
-                        index: varint.0,
-                    })
+                indices
+                    .iter()
+                    .map(|&index| GetOutputsOut { amount: 0, index })
                     .collect(),
             )
             .await
             .unwrap();
 
-        assert_eq!(result.outs.len(), 10);
+        assert_eq!(indices[0], 0);
+        assert_eq!(result.outs.len(), RING_SIZE);
+        assert!(result.outs.iter().all(|out| out.unlocked));
     }
 }
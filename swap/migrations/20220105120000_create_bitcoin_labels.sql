@@ -0,0 +1,13 @@
+CREATE TABLE if NOT EXISTS bitcoin_address_labels
+(
+    address     TEXT    PRIMARY KEY NOT NULL,
+    label       TEXT                NOT NULL,
+    swap_id     TEXT
+);
+
+CREATE TABLE if NOT EXISTS bitcoin_transaction_labels
+(
+    txid        TEXT    PRIMARY KEY NOT NULL,
+    label       TEXT                NOT NULL,
+    swap_id     TEXT
+);
@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS bitcoin_wallet
+(
+    id          INTEGER PRIMARY KEY NOT NULL CHECK (id = 0),
+    state       TEXT                NOT NULL
+);
@@ -0,0 +1,6 @@
+CREATE TABLE if NOT EXISTS banned_peers
+(
+    peer_id     TEXT    PRIMARY KEY NOT NULL,
+    expires_at  INTEGER             NOT NULL,
+    reason      TEXT                NOT NULL
+);
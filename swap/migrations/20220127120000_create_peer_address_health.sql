@@ -0,0 +1,9 @@
+CREATE TABLE if NOT EXISTS peer_address_health
+(
+    peer_id       TEXT    NOT NULL,
+    address       TEXT    NOT NULL,
+    last_success  INTEGER,
+    failures      INTEGER NOT NULL,
+    latency_ms    INTEGER,
+    PRIMARY KEY (peer_id, address)
+);
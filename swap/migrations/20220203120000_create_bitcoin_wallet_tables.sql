@@ -0,0 +1,37 @@
+CREATE TABLE if NOT EXISTS bitcoin_wallet_script_pubkeys
+(
+    script      TEXT    PRIMARY KEY NOT NULL,
+    keychain    TEXT                NOT NULL,
+    child       INTEGER             NOT NULL,
+    UNIQUE (keychain, child)
+);
+
+CREATE TABLE if NOT EXISTS bitcoin_wallet_utxos
+(
+    outpoint    TEXT    PRIMARY KEY NOT NULL,
+    utxo        TEXT                NOT NULL
+);
+
+CREATE TABLE if NOT EXISTS bitcoin_wallet_raw_txs
+(
+    txid        TEXT    PRIMARY KEY NOT NULL,
+    tx          TEXT                NOT NULL
+);
+
+CREATE TABLE if NOT EXISTS bitcoin_wallet_txs
+(
+    txid        TEXT    PRIMARY KEY NOT NULL,
+    details     TEXT                NOT NULL
+);
+
+CREATE TABLE if NOT EXISTS bitcoin_wallet_last_indices
+(
+    keychain    TEXT    PRIMARY KEY NOT NULL,
+    last_index  INTEGER             NOT NULL
+);
+
+CREATE TABLE if NOT EXISTS bitcoin_wallet_checksums
+(
+    keychain    TEXT    PRIMARY KEY NOT NULL,
+    checksum    TEXT                NOT NULL
+);
@@ -1,14 +1,18 @@
 pub mod command;
 pub mod config;
 mod event_loop;
+pub mod limits;
 mod network;
+pub mod quote_book;
 mod rate;
 mod recovery;
 pub mod tracing;
 
 pub use event_loop::{EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate};
+pub use limits::{BanList, RateLimiter};
 pub use network::behaviour::{Behaviour, OutEvent};
 pub use network::transport;
+pub use quote_book::QuoteBook;
 pub use rate::Rate;
 pub use recovery::cancel::cancel;
 pub use recovery::punish::punish;
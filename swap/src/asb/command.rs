@@ -1,7 +1,6 @@
-use crate::asb::config::GetDefaults;
-use crate::bitcoin::Amount;
+use crate::asb::config::defaults_for;
+use crate::bitcoin::{address_network, Amount, CoinControl, RawCoinControl};
 use crate::env;
-use crate::env::GetConfig;
 use anyhow::{bail, Result};
 use bitcoin::Address;
 use serde::Serialize;
@@ -22,6 +21,7 @@ where
     let disable_timestamp = args.disable_timestamp;
     let testnet = args.testnet;
     let config = args.config;
+    let env_config = env::select(args.testnet, args.signet, args.env_config.as_deref())?;
     let command: RawCommand = args.cmd;
 
     let arguments = match command {
@@ -29,51 +29,72 @@ where
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::Start { resume_only },
         },
         RawCommand::History => Arguments {
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::History,
         },
-        RawCommand::WithdrawBtc { amount, address } => Arguments {
+        RawCommand::WithdrawBtc {
+            amount,
+            address,
+            coin_control,
+        } => Arguments {
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::WithdrawBtc {
                 amount,
-                address: bitcoin_address(address, testnet)?,
+                address: bitcoin_address(address, env_config.bitcoin_network)?,
+                coin_control: coin_control.into(),
             },
         },
+        RawCommand::ListUtxos => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
+            cmd: Command::ListUtxos,
+        },
+        RawCommand::BitcoinHistory => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
+            cmd: Command::BitcoinHistory,
+        },
         RawCommand::Balance => Arguments {
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::Balance,
         },
         RawCommand::Config => Arguments {
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::Config,
         },
         RawCommand::ExportBitcoinWallet => Arguments {
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::ExportBitcoinWallet,
         },
         RawCommand::ManualRecovery(ManualRecovery::Redeem {
@@ -83,8 +104,8 @@ where
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::Redeem {
                 swap_id,
 
@@ -97,8 +118,8 @@ where
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::Cancel { swap_id },
         },
         RawCommand::ManualRecovery(ManualRecovery::Refund {
@@ -107,8 +128,8 @@ where
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::Refund { swap_id },
         },
         RawCommand::ManualRecovery(ManualRecovery::Punish {
@@ -117,16 +138,16 @@ where
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::Punish { swap_id },
         },
         RawCommand::ManualRecovery(ManualRecovery::SafelyAbort { swap_id }) => Arguments {
             testnet,
             json,
             disable_timestamp,
-            config_path: config_path(config, testnet)?,
-            env_config: env_config(testnet),
+            config_path: config_path(config, env_config.bitcoin_network)?,
+            env_config,
             cmd: Command::SafelyAbort { swap_id },
         },
     };
@@ -134,12 +155,8 @@ where
     Ok(arguments)
 }
 
-fn bitcoin_address(address: Address, is_testnet: bool) -> Result<Address> {
-    let network = if is_testnet {
-        bitcoin::Network::Testnet
-    } else {
-        bitcoin::Network::Bitcoin
-    };
+fn bitcoin_address(address: Address, network: bitcoin::Network) -> Result<Address> {
+    let network = address_network(network);
 
     if address.network != network {
         bail!(BitcoinAddressNetworkMismatch {
@@ -151,26 +168,16 @@ fn bitcoin_address(address: Address, is_testnet: bool) -> Result<Address> {
     Ok(address)
 }
 
-fn config_path(config: Option<PathBuf>, is_testnet: bool) -> Result<PathBuf> {
+fn config_path(config: Option<PathBuf>, network: bitcoin::Network) -> Result<PathBuf> {
     let config_path = if let Some(config_path) = config {
         config_path
-    } else if is_testnet {
-        env::Testnet::getConfigFileDefaults()?.config_path
     } else {
-        env::Mainnet::getConfigFileDefaults()?.config_path
+        defaults_for(network)?.config_path
     };
 
     Ok(config_path)
 }
 
-fn env_config(is_testnet: bool) -> env::Config {
-    if is_testnet {
-        env::Testnet::get_config()
-    } else {
-        env::Mainnet::get_config()
-    }
-}
-
 #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Serialize)]
 #[error("Invalid Bitcoin address provided, expected address on network {expected:?}  but address provided is on {actual:?}")]
 pub struct BitcoinAddressNetworkMismatch {
@@ -200,8 +207,11 @@ pub enum Command {
     WithdrawBtc {
         amount: Option<Amount>,
         address: Address,
+        coin_control: CoinControl,
     },
     Balance,
+    ListUtxos,
+    BitcoinHistory,
     Redeem {
         swap_id: Uuid,
         do_not_await_finality: bool,
@@ -232,6 +242,16 @@ pub struct RawArguments {
     #[structopt(long, help = "Swap on testnet")]
     pub testnet: bool,
 
+    #[structopt(long, help = "Swap on signet", conflicts_with = "testnet")]
+    pub signet: bool,
+
+    #[structopt(
+        long = "env-config",
+        help = "Provide a custom path to a toml file that defines the timelocks, finality confirmations, timeouts and networks to use. Takes precedence over --testnet and --signet.",
+        parse(from_os_str)
+    )]
+    pub env_config: Option<PathBuf>,
+
     #[structopt(
         short,
         long = "json",
@@ -281,11 +301,21 @@ pub enum RawCommand {
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
+        #[structopt(flatten)]
+        coin_control: RawCoinControl,
     },
     #[structopt(
         about = "Prints the Bitcoin and Monero balance. Requires the monero-wallet-rpc to be running."
     )]
     Balance,
+    #[structopt(
+        about = "Prints the unspent outputs of the internal Bitcoin wallet together with the swap they belong to."
+    )]
+    ListUtxos,
+    #[structopt(
+        about = "Prints the transactions of the internal Bitcoin wallet together with their fees and the swap they belong to."
+    )]
+    BitcoinHistory,
     #[structopt(about = "Print the internal bitcoin wallet descriptor.")]
     ExportBitcoinWallet,
     #[structopt(about = "Contains sub-commands for recovering a swap manually.")]
@@ -350,6 +380,9 @@ pub struct RecoverCommandParams {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::asb::config::GetDefaults;
+    use crate::env::GetConfig;
+    use bitcoin::OutPoint;
     use std::str::FromStr;
 
     const BINARY_NAME: &str = "asb";
@@ -375,6 +408,68 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_list_utxos_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "list-utxos"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::ListUtxos,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_bitcoin_history_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "bitcoin-history"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::BitcoinHistory,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_utxos_then_withdraw_btc_uses_coin_control() {
+        let included = "0101010101010101010101010101010101010101010101010101010101010101:0";
+        let excluded = "0202020202020202020202020202020202020202020202020202020202020202:1";
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-btc",
+            "--address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--utxo",
+            included,
+            "--exclude-utxo",
+            excluded,
+        ];
+
+        let args = parse_args(raw_ars).unwrap();
+
+        match args.cmd {
+            Command::WithdrawBtc { coin_control, .. } => assert_eq!(coin_control, CoinControl {
+                include: vec![OutPoint::from_str(included).unwrap()],
+                exclude: vec![OutPoint::from_str(excluded).unwrap()],
+            }),
+            _ => panic!("expected withdraw-btc command"),
+        }
+    }
+
     #[test]
     fn ensure_history_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -430,6 +525,7 @@ mod tests {
             cmd: Command::WithdrawBtc {
                 amount: None,
                 address: Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+                coin_control: CoinControl::default(),
             },
         };
         let args = parse_args(raw_ars).unwrap();
@@ -615,6 +711,7 @@ mod tests {
             cmd: Command::WithdrawBtc {
                 amount: None,
                 address: Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(),
+                coin_control: CoinControl::default(),
             },
         };
         let args = parse_args(raw_ars).unwrap();
@@ -750,17 +847,20 @@ mod tests {
     fn given_user_provides_config_path_then_no_default_config_path_returned() {
         let cp = PathBuf::from_str("/some/config/path").unwrap();
 
-        let expected = config_path(Some(cp.clone()), true).unwrap();
+        let expected = config_path(Some(cp.clone()), bitcoin::Network::Testnet).unwrap();
         assert_eq!(expected, cp);
 
-        let expected = config_path(Some(cp.clone()), false).unwrap();
+        let expected = config_path(Some(cp.clone()), bitcoin::Network::Bitcoin).unwrap();
         assert_eq!(expected, cp)
     }
 
     #[test]
     fn given_bitcoin_address_network_mismatch_then_error() {
-        let error =
-            bitcoin_address(Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(), true).unwrap_err();
+        let error = bitcoin_address(
+            Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+            bitcoin::Network::Testnet,
+        )
+        .unwrap_err();
 
         assert_eq!(
             error
@@ -772,8 +872,11 @@ mod tests {
             }
         );
 
-        let error = bitcoin_address(Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(), false)
-            .unwrap_err();
+        let error = bitcoin_address(
+            Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(),
+            bitcoin::Network::Bitcoin,
+        )
+        .unwrap_err();
 
         assert_eq!(
             error
@@ -785,4 +888,69 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn ensure_start_command_mapping_for_signet() {
+        let default_signet_conf_path = env::Signet::getConfigFileDefaults().unwrap().config_path;
+        let signet_env_config = env::Signet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--signet", "start"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_signet_conf_path,
+            env_config: signet_env_config,
+            cmd: Command::Start { resume_only: false },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_testnet_and_signet_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "--signet", "start"];
+
+        assert!(parse_args(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_signet_then_testnet_addresses_are_accepted() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--signet",
+            "withdraw-btc",
+            "--address",
+            BITCOIN_TESTNET_ADDRESS,
+        ];
+
+        let args = parse_args(raw_ars).unwrap();
+
+        assert!(matches!(args.cmd, Command::WithdrawBtc { .. }));
+    }
+
+    #[test]
+    fn given_env_config_then_custom_profile_is_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("env.toml");
+        let custom_env_config = env::Config {
+            bitcoin_finality_confirmations: 6,
+            ..env::Regtest::get_config()
+        };
+        std::fs::write(&path, toml::to_string(&custom_env_config).unwrap()).unwrap();
+
+        let raw_ars = vec![
+            BINARY_NAME.to_owned(),
+            "--env-config".to_owned(),
+            path.display().to_string(),
+            "start".to_owned(),
+        ];
+        let args = parse_args(raw_ars).unwrap();
+
+        assert_eq!(args.env_config, custom_env_config);
+        assert_eq!(
+            args.config_path,
+            env::Regtest::getConfigFileDefaults().unwrap().config_path
+        );
+    }
 }
@@ -1,4 +1,5 @@
-use crate::env::{Mainnet, Testnet};
+use crate::asb::limits::{self, BanList, Limit, RateLimiter};
+use crate::env::{Mainnet, Regtest, Signet, Testnet};
 use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
 use anyhow::{bail, Context, Result};
@@ -6,13 +7,17 @@ use config::ConfigError;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Input;
 use libp2p::core::Multiaddr;
+use libp2p::PeerId;
+use monero_rpc::http::{Connection, Credentials};
 use rust_decimal::prelude::FromPrimitive;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use url::Url;
 
 pub trait GetDefaults {
@@ -68,6 +73,44 @@ impl GetDefaults for Mainnet {
     }
 }
 
+impl GetDefaults for Signet {
+    fn getConfigFileDefaults() -> Result<Defaults> {
+        let defaults = Defaults {
+            config_path: default_asb_config_dir()?.join("signet").join("config.toml"),
+            data_dir: default_asb_data_dir()?.join("signet"),
+            electrum_rpc_url: Url::parse("ssl://mempool.space:60602")?,
+            ..Testnet::getConfigFileDefaults()?
+        };
+
+        Ok(defaults)
+    }
+}
+
+impl GetDefaults for Regtest {
+    fn getConfigFileDefaults() -> Result<Defaults> {
+        let defaults = Defaults {
+            config_path: default_asb_config_dir()?
+                .join("regtest")
+                .join("config.toml"),
+            data_dir: default_asb_data_dir()?.join("regtest"),
+            electrum_rpc_url: Url::parse("tcp://127.0.0.1:60401")?,
+            ..Testnet::getConfigFileDefaults()?
+        };
+
+        Ok(defaults)
+    }
+}
+
+/// The defaults for running on the given Bitcoin network.
+pub fn defaults_for(network: bitcoin::Network) -> Result<Defaults> {
+    match network {
+        bitcoin::Network::Bitcoin => Mainnet::getConfigFileDefaults(),
+        bitcoin::Network::Testnet => Testnet::getConfigFileDefaults(),
+        bitcoin::Network::Signet => Signet::getConfigFileDefaults(),
+        bitcoin::Network::Regtest => Regtest::getConfigFileDefaults(),
+    }
+}
+
 fn default_asb_config_dir() -> Result<PathBuf> {
     system_config_dir()
         .map(|dir| Path::join(&dir, "asb"))
@@ -93,6 +136,8 @@ pub struct Config {
     pub monero: Monero,
     pub tor: TorConf,
     pub maker: Maker,
+    #[serde(default)]
+    pub limits: Limits,
 }
 
 impl Config {
@@ -114,33 +159,97 @@ pub struct Data {
     pub dir: PathBuf,
 }
 
+#[serde_as]
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Network {
     pub listen: Vec<Multiaddr>,
+    /// Kept for config files written before `rendezvous_points` existed.
     #[serde(default)]
     pub rendezvous_point: Option<Multiaddr>,
+    /// The rendezvous nodes we register with, each one independently of the
+    /// others.
+    #[serde(default)]
+    pub rendezvous_points: Vec<Multiaddr>,
     #[serde(default)]
     pub external_addresses: Vec<Multiaddr>,
+    /// Nodes to join the DHT through. If set, we announce ourselves as a
+    /// seller in the DHT.
+    #[serde(default)]
+    pub dht_bootstrap_nodes: Vec<Multiaddr>,
+    /// Peers we refuse to talk to.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub banned_peers: Vec<PeerId>,
+}
+
+impl Network {
+    /// All configured rendezvous points, without duplicates.
+    pub fn all_rendezvous_points(&self) -> Vec<Multiaddr> {
+        let mut points = self.rendezvous_points.clone();
+        if let Some(point) = &self.rendezvous_point {
+            if !points.contains(point) {
+                points.insert(0, point.clone());
+            }
+        }
+
+        points
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Bitcoin {
     pub electrum_rpc_url: Url,
+    /// Electrum servers we fail over to if `electrum_rpc_url` is unavailable.
+    #[serde(default)]
+    pub electrum_rpc_fallback_urls: Vec<Url>,
     pub target_block: usize,
     pub finality_confirmations: Option<u32>,
     #[serde(with = "crate::bitcoin::network")]
     pub network: bitcoin::Network,
 }
 
+impl Bitcoin {
+    /// All configured Electrum servers, starting with the primary one.
+    pub fn electrum_rpc_urls(&self) -> Vec<Url> {
+        std::iter::once(self.electrum_rpc_url.clone())
+            .chain(self.electrum_rpc_fallback_urls.iter().cloned())
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Monero {
     pub wallet_rpc_url: Url,
+    /// Additional `monero-wallet-rpc` instances on which refund wallets are
+    /// generated, so that refunds of concurrent swaps don't have to take
+    /// turns on the main wallet.
+    #[serde(default)]
+    pub ephemeral_wallet_rpc_urls: Vec<Url>,
     pub finality_confirmations: Option<u64>,
     #[serde(with = "crate::monero::network")]
     pub network: monero::Network,
+    /// A PEM certificate to trust in addition to the system's root
+    /// certificates when connecting to `monero-wallet-rpc` over `https`.
+    #[serde(default)]
+    pub wallet_rpc_root_certificate: Option<PathBuf>,
+    /// The `--rpc-login` of the `monero-wallet-rpc` instances.
+    #[serde(default)]
+    pub wallet_rpc_login: Option<Credentials>,
+}
+
+impl Monero {
+    /// How to connect to the configured `monero-wallet-rpc` instances.
+    pub fn wallet_rpc_connection(&self) -> Result<Connection> {
+        let connection = Connection::with_login(self.wallet_rpc_login.clone());
+
+        match &self.wallet_rpc_root_certificate {
+            Some(path) => connection.with_root_certificate_file(path),
+            None => Ok(connection),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -159,6 +268,76 @@ pub struct Maker {
     pub max_buy_btc: bitcoin::Amount,
     pub ask_spread: Decimal,
     pub price_ticker_ws_url: Url,
+    /// How many seconds a signed quote is honoured for, defaults to 60.
+    #[serde(default)]
+    pub quote_validity_secs: Option<u64>,
+    /// How far the market may move against a signed quote before it is no
+    /// longer honoured, defaults to 0.01 (1%).
+    #[serde(default)]
+    pub quote_tolerance: Option<Decimal>,
+}
+
+/// Limits that protect us against peers that spam us. Unset limits take the
+/// defaults from [`crate::asb::limits`].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    #[serde(default)]
+    pub quotes_per_peer_per_minute: Option<u32>,
+    #[serde(default)]
+    pub quotes_per_minute: Option<u32>,
+    #[serde(default)]
+    pub swap_setups_per_peer_per_hour: Option<u32>,
+    #[serde(default)]
+    pub swap_setups_per_hour: Option<u32>,
+    /// How many swap setups a peer may abort within an hour before we ban
+    /// it.
+    #[serde(default)]
+    pub max_aborted_setups: Option<u32>,
+    /// How many seconds a peer that aborted too many swap setups is banned
+    /// for.
+    #[serde(default)]
+    pub ban_duration_secs: Option<u64>,
+}
+
+impl Limits {
+    pub fn quote_rate_limiter(&self) -> RateLimiter {
+        RateLimiter::new(
+            Limit::per_minute(
+                self.quotes_per_peer_per_minute
+                    .unwrap_or(limits::DEFAULT_QUOTES_PER_PEER_PER_MINUTE),
+            ),
+            Limit::per_minute(
+                self.quotes_per_minute
+                    .unwrap_or(limits::DEFAULT_QUOTES_PER_MINUTE),
+            ),
+        )
+    }
+
+    pub fn swap_setup_rate_limiter(&self) -> RateLimiter {
+        RateLimiter::new(
+            Limit::per_hour(
+                self.swap_setups_per_peer_per_hour
+                    .unwrap_or(limits::DEFAULT_SWAP_SETUPS_PER_PEER_PER_HOUR),
+            ),
+            Limit::per_hour(
+                self.swap_setups_per_hour
+                    .unwrap_or(limits::DEFAULT_SWAP_SETUPS_PER_HOUR),
+            ),
+        )
+    }
+
+    /// The ban list, starting out with the peers banned in the config.
+    pub fn ban_list(&self, banned_peers: Vec<PeerId>) -> BanList {
+        BanList::new(
+            banned_peers,
+            self.max_aborted_setups
+                .unwrap_or(limits::DEFAULT_MAX_ABORTED_SETUPS),
+            self.ban_duration_secs
+                .map(Duration::from_secs)
+                .unwrap_or(limits::DEFAULT_BAN_DURATION),
+        )
+    }
 }
 
 impl Default for TorConf {
@@ -203,23 +382,13 @@ pub fn initial_setup(config_path: PathBuf, config: Config) -> Result<()> {
     Ok(())
 }
 
-pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
-    let (bitcoin_network, monero_network, defaults) = if testnet {
-        tracing::info!("Running initial setup for testnet");
-
-        let bitcoin_network = bitcoin::Network::Testnet;
-        let monero_network = monero::Network::Stagenet;
-        let defaults = Testnet::getConfigFileDefaults()?;
-
-        (bitcoin_network, monero_network, defaults)
-    } else {
-        tracing::info!("Running initial setup for mainnet");
-        let bitcoin_network = bitcoin::Network::Bitcoin;
-        let monero_network = monero::Network::Mainnet;
-        let defaults = Mainnet::getConfigFileDefaults()?;
+pub fn query_user_for_initial_config(
+    bitcoin_network: bitcoin::Network,
+    monero_network: monero::Network,
+) -> Result<Config> {
+    tracing::info!(%bitcoin_network, ?monero_network, "Running initial setup");
 
-        (bitcoin_network, monero_network, defaults)
-    };
+    let defaults = defaults_for(bitcoin_network)?;
 
     println!();
     let data_dir = Input::with_theme(&ColorfulTheme::default())
@@ -253,6 +422,17 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         .default(defaults.electrum_rpc_url)
         .interact_text()?;
 
+    let electrum_rpc_fallback_urls = Input::<String>::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter fallback Electrum RPC URLs (comma separated) to use if the first one is unavailable or hit return to use none")
+        .allow_empty(true)
+        .interact_text()?;
+    let electrum_rpc_fallback_urls = electrum_rpc_fallback_urls
+        .split(',')
+        .map(str::trim)
+        .filter(|str| !str.is_empty())
+        .map(Url::parse)
+        .collect::<Result<Vec<Url>, _>>()?;
+
     let monero_wallet_rpc_url = Input::with_theme(&ColorfulTheme::default())
         .with_prompt("Enter Monero Wallet RPC URL or hit enter to use default")
         .default(defaults.monero_wallet_rpc_url)
@@ -300,23 +480,30 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         data: Data { dir: data_dir },
         network: Network {
             listen: listen_addresses,
-            rendezvous_point: if rendezvous_point.is_empty() {
-                None
+            rendezvous_point: None,
+            rendezvous_points: if rendezvous_point.is_empty() {
+                vec![]
             } else {
-                Some(rendezvous_point)
+                vec![rendezvous_point]
             },
             external_addresses: vec![],
+            dht_bootstrap_nodes: vec![],
+            banned_peers: vec![],
         },
         bitcoin: Bitcoin {
             electrum_rpc_url,
+            electrum_rpc_fallback_urls,
             target_block,
             finality_confirmations: None,
             network: bitcoin_network,
         },
         monero: Monero {
             wallet_rpc_url: monero_wallet_rpc_url,
+            ephemeral_wallet_rpc_urls: vec![],
             finality_confirmations: None,
             network: monero_network,
+            wallet_rpc_root_certificate: None,
+            wallet_rpc_login: None,
         },
         tor: TorConf {
             control_port: tor_control_port,
@@ -327,7 +514,10 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             max_buy_btc: max_buy,
             ask_spread,
             price_ticker_ws_url: defaults.price_ticker_ws_url,
+            quote_validity_secs: None,
+            quote_tolerance: None,
         },
+        limits: Limits::default(),
     })
 }
 
@@ -349,6 +539,7 @@ mod tests {
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
+                electrum_rpc_fallback_urls: vec![],
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Testnet,
@@ -356,13 +547,19 @@ mod tests {
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
                 rendezvous_point: None,
+                rendezvous_points: vec![],
                 external_addresses: vec![],
+                dht_bootstrap_nodes: vec![],
+                banned_peers: vec![],
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
+                ephemeral_wallet_rpc_urls: vec![],
                 finality_confirmations: None,
                 network: monero::Network::Stagenet,
+                wallet_rpc_root_certificate: None,
+                wallet_rpc_login: None,
             },
             tor: Default::default(),
             maker: Maker {
@@ -370,7 +567,10 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                quote_validity_secs: None,
+                quote_tolerance: None,
             },
+            limits: Limits::default(),
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -392,6 +592,7 @@ mod tests {
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
+                electrum_rpc_fallback_urls: vec![],
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Bitcoin,
@@ -399,13 +600,19 @@ mod tests {
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
                 rendezvous_point: None,
+                rendezvous_points: vec![],
                 external_addresses: vec![],
+                dht_bootstrap_nodes: vec![],
+                banned_peers: vec![],
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
+                ephemeral_wallet_rpc_urls: vec![],
                 finality_confirmations: None,
                 network: monero::Network::Mainnet,
+                wallet_rpc_root_certificate: None,
+                wallet_rpc_login: None,
             },
             tor: Default::default(),
             maker: Maker {
@@ -413,7 +620,10 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                quote_validity_secs: None,
+                quote_tolerance: None,
             },
+            limits: Limits::default(),
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -421,4 +631,77 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn combines_legacy_and_new_rendezvous_points() {
+        let first = Multiaddr::from_str(
+            "/dnsaddr/first.example.com/p2p/12D3KooWQUt9DkNZxEn2R5ymJzWj15MpG6mTW84kyd8vDaRZi46o",
+        )
+        .unwrap();
+        let second = Multiaddr::from_str(
+            "/dnsaddr/second.example.com/p2p/12D3KooWQUt9DkNZxEn2R5ymJzWj15MpG6mTW84kyd8vDaRZi46o",
+        )
+        .unwrap();
+
+        let network = Network {
+            listen: vec![],
+            rendezvous_point: Some(first.clone()),
+            rendezvous_points: vec![second.clone(), first.clone()],
+            external_addresses: vec![],
+            dht_bootstrap_nodes: vec![],
+            banned_peers: vec![],
+        };
+
+        assert_eq!(network.all_rendezvous_points(), vec![second, first]);
+    }
+
+    #[test]
+    fn reads_monero_wallet_rpc_login() {
+        let monero = toml::from_str::<Monero>(
+            r#"
+            wallet_rpc_url = "https://wallet.example.com:18083/json_rpc"
+            network = "Mainnet"
+
+            [wallet_rpc_login]
+            username = "asb"
+            password = "hunter2"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            monero.wallet_rpc_login,
+            Some(Credentials {
+                username: "asb".to_owned(),
+                password: "hunter2".to_owned()
+            })
+        );
+        assert!(monero.wallet_rpc_connection().unwrap().login.is_some());
+    }
+
+    #[test]
+    fn reads_banned_peers() {
+        let network = toml::from_str::<Network>(
+            r#"
+            listen = ["/ip4/0.0.0.0/tcp/9939"]
+            banned_peers = ["12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            network.banned_peers,
+            vec![PeerId::from_str("12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi").unwrap()]
+        );
+    }
+
+    #[test]
+    fn limits_default_if_unset() {
+        let limits = toml::from_str::<Limits>("quotes_per_minute = 100").unwrap();
+
+        assert_eq!(limits, Limits {
+            quotes_per_minute: Some(100),
+            ..Limits::default()
+        });
+    }
 }
@@ -1,5 +1,8 @@
-use crate::asb::{Behaviour, OutEvent, Rate};
-use crate::network::quote::BidQuote;
+use crate::asb::limits::Ban;
+use crate::asb::{BanList, Behaviour, OutEvent, QuoteBook, Rate, RateLimiter};
+use crate::network::capabilities::Capabilities;
+use crate::network::quote::{unix_timestamp, BidQuote};
+use crate::network::quote_subscription::Subscribers;
 use crate::network::swap_setup::alice::WalletSnapshot;
 use crate::network::transfer_proof;
 use crate::protocol::alice::{AliceState, State3, Swap};
@@ -9,6 +12,7 @@ use anyhow::{Context, Result};
 use futures::future;
 use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
+use libp2p::kad::{KademliaEvent, QueryResult};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
 use libp2p::{PeerId, Swarm};
@@ -17,6 +21,7 @@ use std::collections::HashMap;
 use std::convert::{Infallible, TryInto};
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
@@ -30,6 +35,17 @@ use uuid::Uuid;
 type OutgoingTransferProof =
     BoxFuture<'static, Result<(PeerId, transfer_proof::Request, bmrng::Responder<()>)>>;
 
+/// How often we check whether our quote changed and push it to subscribers.
+/// Bounds the number of updates a subscriber receives, no matter how often
+/// the price moves.
+const QUOTE_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often we lift bans that expired.
+const BAN_EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often we log the status of our rendezvous registrations.
+const RENDEZVOUS_STATUS_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 #[allow(missing_debug_implementations)]
 pub struct EventLoop<LR>
 where
@@ -41,6 +57,7 @@ where
     monero_wallet: Arc<monero::Wallet>,
     db: Arc<dyn Database + Send + Sync>,
     latest_rate: LR,
+    quote_book: QuoteBook,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
 
@@ -59,6 +76,13 @@ where
     /// Tracks [`transfer_proof::Request`]s which are currently inflight and
     /// awaiting an acknowledgement.
     inflight_transfer_proofs: HashMap<RequestId, bmrng::Responder<()>>,
+
+    /// Peers that subscribed to our quote and the quote they saw last.
+    quote_subscribers: Subscribers,
+
+    quote_limiter: RateLimiter,
+    swap_setup_limiter: RateLimiter,
+    ban_list: BanList,
 }
 
 impl<LR> EventLoop<LR>
@@ -73,8 +97,12 @@ where
         monero_wallet: Arc<monero::Wallet>,
         db: Arc<dyn Database + Send + Sync>,
         latest_rate: LR,
+        quote_book: QuoteBook,
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        quote_limiter: RateLimiter,
+        swap_setup_limiter: RateLimiter,
+        ban_list: BanList,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
 
@@ -85,6 +113,7 @@ where
             monero_wallet,
             db,
             latest_rate,
+            quote_book,
             swap_sender: swap_channel.sender,
             min_buy,
             max_buy,
@@ -93,6 +122,10 @@ where
             send_transfer_proof: Default::default(),
             buffered_transfer_proofs: Default::default(),
             inflight_transfer_proofs: Default::default(),
+            quote_subscribers: Default::default(),
+            quote_limiter,
+            swap_setup_limiter,
+            ban_list,
         };
         Ok((event_loop, swap_channel.receiver))
     }
@@ -108,6 +141,17 @@ where
         self.inflight_encrypted_signatures
             .push(future::pending().boxed());
 
+        match self.db.get_bans().await {
+            Ok(bans) => self.ban_list.restore(bans),
+            Err(e) => {
+                tracing::error!("Failed to load bans from database: {}", e);
+                return;
+            }
+        }
+        for peer in self.ban_list.banned() {
+            self.swarm.ban_peer_id(peer);
+        }
+
         let swaps = match self.db.all().await {
             Ok(swaps) => swaps,
             Err(e) => {
@@ -150,11 +194,22 @@ where
             }
         }
 
+        let mut quote_updates = tokio::time::interval(QUOTE_UPDATE_INTERVAL);
+        let mut ban_expiry = tokio::time::interval(BAN_EXPIRY_INTERVAL);
+        let mut rendezvous_status = tokio::time::interval(RENDEZVOUS_STATUS_INTERVAL);
+        // The first tick completes immediately, before we had a chance to register.
+        rendezvous_status.tick().await;
+
         loop {
             tokio::select! {
                 swarm_event = self.swarm.select_next_some() => {
                     match swarm_event {
-                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { mut send_wallet_snapshot }) => {
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { peer, mut send_wallet_snapshot }) => {
+                            // Dropping `send_wallet_snapshot` fails the swap setup before we query our wallets.
+                            if let Err(error) = self.swap_setup_limiter.check(peer, Instant::now()) {
+                                tracing::warn!(%peer, "Ignoring swap request: {}", error);
+                                continue;
+                            }
 
                             let (btc, responder) = match send_wallet_snapshot.recv().await {
                                 Ok((btc, responder)) => (btc, responder),
@@ -178,11 +233,23 @@ where
                         SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted{peer_id, swap_id, state3}) => {
                             let _ = self.handle_execution_setup_done(peer_id, swap_id, state3).await;
                         }
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupAborted { peer, error }) => {
+                            tracing::warn!(%peer, "Swap setup failed: {:#}", error);
+
+                            if let Some(ban) = self.ban_list.record_aborted_setup(peer, unix_timestamp()) {
+                                self.ban(ban).await;
+                            }
+                        }
                         SwarmEvent::Behaviour(OutEvent::SwapDeclined { peer, error }) => {
                             tracing::warn!(%peer, "Ignoring spot price request: {}", error);
                         }
                         SwarmEvent::Behaviour(OutEvent::QuoteRequested { channel, peer }) => {
-                            let quote = match self.make_quote(self.min_buy, self.max_buy).await {
+                            if let Err(error) = self.quote_limiter.check(peer, Instant::now()) {
+                                tracing::debug!(%peer, "Ignoring quote request: {}", error);
+                                continue;
+                            }
+
+                            let quote = match self.make_quote(self.min_buy, self.max_buy).await.and_then(|quote| self.quote_book.issue(quote)) {
                                 Ok(quote) => quote,
                                 Err(error) => {
                                     tracing::warn!(%peer, "Failed to make quote: {:#}", error);
@@ -194,6 +261,33 @@ where
                                 tracing::debug!(%peer, "Failed to respond with quote");
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::CapabilitiesRequested { channel, peer }) => {
+                            if self.swarm.behaviour_mut().capabilities.send_response(channel, Capabilities::ours()).is_err() {
+                                tracing::debug!(%peer, "Failed to respond with capabilities");
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::QuoteSubscriptionRequested { channel, peer }) => {
+                            if let Err(error) = self.quote_limiter.check(peer, Instant::now()) {
+                                tracing::debug!(%peer, "Ignoring quote subscription: {}", error);
+                                continue;
+                            }
+
+                            let quote = match self.make_quote(self.min_buy, self.max_buy).await {
+                                Ok(quote) => quote,
+                                Err(error) => {
+                                    tracing::warn!(%peer, "Failed to make quote: {:#}", error);
+                                    continue;
+                                }
+                            };
+
+                            if self.swarm.behaviour_mut().quote_subscribe.send_response(channel, quote).is_err() {
+                                tracing::debug!(%peer, "Failed to respond to quote subscription");
+                                continue;
+                            }
+
+                            tracing::debug!(%peer, "Peer subscribed to our quote");
+                            self.quote_subscribers.insert(peer, quote);
+                        }
                         SwarmEvent::Behaviour(OutEvent::TransferProofAcknowledged { peer, id }) => {
                             tracing::debug!(%peer, "Bob acknowledged transfer proof");
                             if let Some(responder) = self.inflight_transfer_proofs.remove(&id) {
@@ -249,11 +343,20 @@ where
                                 channel
                             }.boxed());
                         }
-                        SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::Registered { .. })) => {
-                            tracing::info!("Successfully registered with rendezvous node");
+                        SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::Registered { rendezvous_node, ttl, .. })) => {
+                            tracing::info!(%rendezvous_node, %ttl, "Successfully registered with rendezvous node");
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::RegisterFailed(libp2p::rendezvous::client::RegisterError::Remote { rendezvous_node, error, .. }))) => {
+                            tracing::error!(%rendezvous_node, "Registration with rendezvous node failed: {:?}", error);
                         }
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::RegisterFailed(error))) => {
-                            tracing::error!("Registration with rendezvous node failed: {:?}", error);
+                            tracing::error!("Registration with rendezvous nodes failed: {:?}", error);
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Dht(KademliaEvent::OutboundQueryCompleted { result: QueryResult::StartProviding(Ok(_)), .. })) => {
+                            tracing::info!("Successfully announced ourselves as a seller in the DHT");
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Dht(KademliaEvent::OutboundQueryCompleted { result: QueryResult::StartProviding(Err(error)), .. })) => {
+                            tracing::warn!("Failed to announce ourselves as a seller in the DHT, will try again later: {:?}", error);
                         }
                         SwarmEvent::Behaviour(OutEvent::Failure {peer, error}) => {
                             tracing::error!(
@@ -276,9 +379,11 @@ where
                             tracing::warn!(%address, "Failed to set up connection with peer: {:#}", error);
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: Some(error) } if num_established == 0 => {
+                            self.quote_subscribers.remove(&peer);
                             tracing::debug!(%peer, address = %endpoint.get_remote_address(), "Lost connection to peer: {:#}", error);
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: None } if num_established == 0 => {
+                            self.quote_subscribers.remove(&peer);
                             tracing::info!(%peer, address = %endpoint.get_remote_address(), "Successfully closed connection");
                         }
                         SwarmEvent::NewListenAddr{address, ..} => {
@@ -310,10 +415,49 @@ where
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                _ = quote_updates.tick(), if !self.quote_subscribers.is_empty() => {
+                    let quote = match self.make_quote(self.min_buy, self.max_buy).await {
+                        Ok(quote) => quote,
+                        Err(error) => {
+                            tracing::warn!("Failed to make quote for subscribers: {:#}", error);
+                            continue;
+                        }
+                    };
+
+                    for peer in self.quote_subscribers.outdated(quote) {
+                        self.swarm.behaviour_mut().quote_update.send_request(&peer, quote);
+                    }
+                }
+                _ = ban_expiry.tick() => {
+                    for peer in self.ban_list.lift_expired(unix_timestamp()) {
+                        self.swarm.unban_peer_id(peer);
+
+                        if let Err(error) = self.db.remove_ban(peer).await {
+                            tracing::warn!(%peer, "Failed to remove expired ban from database: {:#}", error);
+                        }
+
+                        tracing::info!(%peer, "Ban expired");
+                    }
+                }
+                _ = rendezvous_status.tick() => {
+                    if let Some(rendezvous) = self.swarm.behaviour().rendezvous.as_ref() {
+                        rendezvous.log_status();
+                    }
+                }
             }
         }
     }
 
+    /// Disconnects `peer` and refuses its connections until the ban expires.
+    async fn ban(&mut self, ban: Ban) {
+        tracing::warn!(peer = %ban.peer_id, expires_at = ban.expires_at, "Banning peer: {}", ban.reason);
+        self.swarm.ban_peer_id(ban.peer_id);
+
+        if let Err(error) = self.db.insert_ban(ban.clone()).await {
+            tracing::warn!(peer = %ban.peer_id, "Failed to store ban in database, it will be lifted on restart: {:#}", error);
+        }
+    }
+
     async fn make_quote(
         &mut self,
         min_buy: bitcoin::Amount,
@@ -0,0 +1,370 @@
+//! Protects the ASB against peers that spam or abuse it.
+//!
+//! Quote requests and swap setups are rate limited per peer and globally.
+//! Swap setups are expensive for us because every one of them queries the
+//! Monero wallet and generates fresh Bitcoin addresses, which is why peers
+//! that repeatedly abort them after agreeing on a price are banned for a
+//! while.
+
+use libp2p::PeerId;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_QUOTES_PER_PEER_PER_MINUTE: u32 = 20;
+pub const DEFAULT_QUOTES_PER_MINUTE: u32 = 600;
+pub const DEFAULT_SWAP_SETUPS_PER_PEER_PER_HOUR: u32 = 10;
+pub const DEFAULT_SWAP_SETUPS_PER_HOUR: u32 = 100;
+
+/// Peers that abort this many swap setups within [`ABORT_WINDOW`] are banned.
+pub const DEFAULT_MAX_ABORTED_SETUPS: u32 = 3;
+pub const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(60 * 60 * 24);
+
+const ABORT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub max: u32,
+    pub per: Duration,
+}
+
+impl Limit {
+    pub fn per_minute(max: u32) -> Self {
+        Self {
+            max,
+            per: Duration::from_secs(60),
+        }
+    }
+
+    pub fn per_hour(max: u32) -> Self {
+        Self {
+            max,
+            per: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+pub enum Exceeded {
+    #[error("Peer exceeded the limit of {} requests per {}s", .0.max, .0.per.as_secs())]
+    PerPeer(Limit),
+    #[error("Exceeded the global limit of {} requests per {}s", .0.max, .0.per.as_secs())]
+    Global(Limit),
+}
+
+/// Limits how many requests we serve per peer and in total within a sliding
+/// window.
+///
+/// Only requests we serve count towards the limits, so a peer that keeps
+/// sending requests while it is limited does not lock itself out forever.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_peer: Limit,
+    global: Limit,
+    served: HashMap<PeerId, VecDeque<Instant>>,
+    served_globally: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(per_peer: Limit, global: Limit) -> Self {
+        Self {
+            per_peer,
+            global,
+            served: HashMap::new(),
+            served_globally: VecDeque::new(),
+        }
+    }
+
+    /// Records a request of `peer` if it is within the limits.
+    pub fn check(&mut self, peer: PeerId, now: Instant) -> Result<(), Exceeded> {
+        let per_peer = self.per_peer;
+        let global = self.global;
+
+        prune(&mut self.served_globally, global.per, now);
+        self.served.retain(|_, served| {
+            prune(served, per_peer.per, now);
+            !served.is_empty()
+        });
+
+        let served_to_peer = self.served.get(&peer).map_or(0, VecDeque::len);
+        if served_to_peer >= per_peer.max as usize {
+            return Err(Exceeded::PerPeer(per_peer));
+        }
+        if self.served_globally.len() >= global.max as usize {
+            return Err(Exceeded::Global(global));
+        }
+
+        self.served.entry(peer).or_default().push_back(now);
+        self.served_globally.push_back(now);
+
+        Ok(())
+    }
+}
+
+fn prune(served: &mut VecDeque<Instant>, window: Duration, now: Instant) {
+    while let Some(oldest) = served.front() {
+        if now.saturating_duration_since(*oldest) < window {
+            break;
+        }
+        served.pop_front();
+    }
+}
+
+/// A peer we refuse to talk to until `expires_at`, a unix timestamp.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ban {
+    pub peer_id: PeerId,
+    pub expires_at: u64,
+    pub reason: String,
+}
+
+/// The peers we refuse to talk to.
+///
+/// Peers listed in the config are banned for good, peers that abort too many
+/// swap setups are banned for a while. The latter are stored in the database
+/// so they outlive a restart.
+#[derive(Debug)]
+pub struct BanList {
+    permanent: HashSet<PeerId>,
+    temporary: HashMap<PeerId, u64>,
+    aborted_setups: HashMap<PeerId, VecDeque<u64>>,
+    max_aborted_setups: u32,
+    ban_duration: Duration,
+}
+
+impl BanList {
+    pub fn new(permanent: Vec<PeerId>, max_aborted_setups: u32, ban_duration: Duration) -> Self {
+        Self {
+            permanent: permanent.into_iter().collect(),
+            temporary: HashMap::new(),
+            aborted_setups: HashMap::new(),
+            max_aborted_setups,
+            ban_duration,
+        }
+    }
+
+    /// Reinstates bans that were stored in the database.
+    pub fn restore(&mut self, bans: Vec<Ban>) {
+        for ban in bans {
+            self.temporary.insert(ban.peer_id, ban.expires_at);
+        }
+    }
+
+    /// All peers that are currently banned.
+    pub fn banned(&self) -> Vec<PeerId> {
+        self.permanent
+            .iter()
+            .chain(self.temporary.keys())
+            .copied()
+            .collect()
+    }
+
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        self.permanent.contains(peer) || self.temporary.contains_key(peer)
+    }
+
+    /// Records that `peer` aborted a swap setup, returning a ban if it did so
+    /// too often.
+    pub fn record_aborted_setup(&mut self, peer: PeerId, now: u64) -> Option<Ban> {
+        let window = ABORT_WINDOW.as_secs();
+        let aborted = self.aborted_setups.entry(peer).or_default();
+        aborted.retain(|aborted_at| now.saturating_sub(*aborted_at) < window);
+        aborted.push_back(now);
+
+        let count = aborted.len();
+        if count < self.max_aborted_setups as usize {
+            return None;
+        }
+
+        let ban = Ban {
+            peer_id: peer,
+            expires_at: now + self.ban_duration.as_secs(),
+            reason: format!("Aborted {} swap setups within {}s", count, window),
+        };
+        self.aborted_setups.remove(&peer);
+        self.temporary.insert(peer, ban.expires_at);
+
+        Some(ban)
+    }
+
+    /// Lifts the temporary bans that expired, returning the peers that are no
+    /// longer banned.
+    ///
+    /// Peers banned in the config stay banned even if they also had a
+    /// temporary ban.
+    pub fn lift_expired(&mut self, now: u64) -> Vec<PeerId> {
+        let expired = self
+            .temporary
+            .iter()
+            .filter(|(_, expires_at)| now > **expires_at)
+            .map(|(peer, _)| *peer)
+            .collect::<Vec<_>>();
+
+        for peer in &expired {
+            self.temporary.remove(peer);
+        }
+
+        let expired = expired
+            .into_iter()
+            .filter(|peer| !self.permanent.contains(peer))
+            .collect();
+
+        // Forget aborts that are too old to ever lead to a ban.
+        let window = ABORT_WINDOW.as_secs();
+        self.aborted_setups.retain(|_, aborted| {
+            aborted
+                .back()
+                .map_or(false, |aborted_at| now.saturating_sub(*aborted_at) < window)
+        });
+
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limiter() -> RateLimiter {
+        RateLimiter::new(Limit::per_minute(2), Limit::per_minute(3))
+    }
+
+    #[test]
+    fn given_peer_exceeds_its_limit_then_only_it_is_limited() {
+        let mut limiter = rate_limiter();
+        let spammer = PeerId::random();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check(spammer, now), Ok(()));
+        assert_eq!(limiter.check(spammer, now), Ok(()));
+        assert_eq!(
+            limiter.check(spammer, now),
+            Err(Exceeded::PerPeer(Limit::per_minute(2)))
+        );
+        assert_eq!(limiter.check(PeerId::random(), now), Ok(()));
+    }
+
+    #[test]
+    fn given_global_limit_reached_then_every_peer_is_limited() {
+        let mut limiter = rate_limiter();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            limiter.check(PeerId::random(), now).unwrap();
+        }
+
+        assert_eq!(
+            limiter.check(PeerId::random(), now),
+            Err(Exceeded::Global(Limit::per_minute(3)))
+        );
+    }
+
+    #[test]
+    fn limits_apply_to_a_sliding_window() {
+        let mut limiter = rate_limiter();
+        let peer = PeerId::random();
+        let start = Instant::now();
+
+        limiter.check(peer, start).unwrap();
+        limiter
+            .check(peer, start + Duration::from_secs(30))
+            .unwrap();
+
+        assert!(limiter
+            .check(peer, start + Duration::from_secs(59))
+            .is_err());
+        assert_eq!(limiter.check(peer, start + Duration::from_secs(60)), Ok(()));
+    }
+
+    #[test]
+    fn limited_requests_do_not_count() {
+        let mut limiter = rate_limiter();
+        let peer = PeerId::random();
+        let start = Instant::now();
+
+        limiter.check(peer, start).unwrap();
+        limiter.check(peer, start).unwrap();
+        for _ in 0..10 {
+            let _ = limiter.check(peer, start + Duration::from_secs(30));
+        }
+
+        assert_eq!(limiter.check(peer, start + Duration::from_secs(60)), Ok(()));
+    }
+
+    #[test]
+    fn given_too_many_aborted_setups_then_bans_peer() {
+        let mut bans = BanList::new(vec![], 3, Duration::from_secs(100));
+        let peer = PeerId::random();
+
+        assert_eq!(bans.record_aborted_setup(peer, 1_000), None);
+        assert_eq!(bans.record_aborted_setup(peer, 1_010), None);
+        let ban = bans.record_aborted_setup(peer, 1_020).unwrap();
+
+        assert_eq!(ban.peer_id, peer);
+        assert_eq!(ban.expires_at, 1_120);
+        assert!(bans.is_banned(&peer));
+    }
+
+    #[test]
+    fn aborted_setups_outside_of_window_are_forgiven() {
+        let mut bans = BanList::new(vec![], 2, Duration::from_secs(100));
+        let peer = PeerId::random();
+
+        bans.record_aborted_setup(peer, 1_000);
+
+        assert_eq!(
+            bans.record_aborted_setup(peer, 1_000 + ABORT_WINDOW.as_secs()),
+            None
+        );
+        assert!(!bans.is_banned(&peer));
+    }
+
+    #[test]
+    fn expired_bans_are_lifted() {
+        let mut bans = BanList::new(vec![], 1, Duration::from_secs(100));
+        let peer = PeerId::random();
+        bans.record_aborted_setup(peer, 1_000).unwrap();
+
+        assert!(bans.lift_expired(1_100).is_empty());
+        assert_eq!(bans.lift_expired(1_101), vec![peer]);
+        assert!(!bans.is_banned(&peer));
+    }
+
+    #[test]
+    fn bans_from_config_never_expire() {
+        let peer = PeerId::random();
+        let mut bans = BanList::new(vec![peer], 1, Duration::from_secs(100));
+
+        assert!(bans.lift_expired(u64::MAX).is_empty());
+        assert!(bans.is_banned(&peer));
+    }
+
+    #[test]
+    fn expired_temporary_ban_does_not_lift_ban_from_config() {
+        let peer = PeerId::random();
+        let mut bans = BanList::new(vec![peer], 1, Duration::from_secs(100));
+
+        bans.restore(vec![Ban {
+            peer_id: peer,
+            expires_at: 1_000,
+            reason: "Aborted 3 swap setups within 3600s".to_owned(),
+        }]);
+
+        assert!(bans.lift_expired(1_001).is_empty());
+        assert!(bans.is_banned(&peer));
+    }
+
+    #[test]
+    fn restored_bans_are_lifted_once_expired() {
+        let peer = PeerId::random();
+        let mut bans = BanList::new(vec![], 1, Duration::from_secs(100));
+
+        bans.restore(vec![Ban {
+            peer_id: peer,
+            expires_at: 1_000,
+            reason: "Aborted 3 swap setups within 3600s".to_owned(),
+        }]);
+
+        assert!(bans.is_banned(&peer));
+        assert_eq!(bans.lift_expired(1_001), vec![peer]);
+    }
+}
@@ -1,11 +1,15 @@
 use crate::asb::event_loop::LatestRate;
+use crate::asb::QuoteBook;
 use crate::env;
-use crate::network::quote::BidQuote;
+use crate::network::capabilities::Capabilities;
+use crate::network::quote::{BidQuote, SignedBidQuote};
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::network::swap_setup::alice;
 use crate::network::swap_setup::alice::WalletSnapshot;
 use crate::network::transport::authenticate_and_multiplex;
-use crate::network::{encrypted_signature, quote, transfer_proof};
+use crate::network::{
+    capabilities, dht, encrypted_signature, identify, quote, quote_subscription, transfer_proof,
+};
 use crate::protocol::alice::State3;
 use anyhow::{anyhow, Error, Result};
 use futures::FutureExt;
@@ -13,6 +17,8 @@ use libp2p::core::connection::ConnectionId;
 use libp2p::core::muxing::StreamMuxerBox;
 use libp2p::core::transport::Boxed;
 use libp2p::dns::TokioDnsConfig;
+use libp2p::identify::{Identify, IdentifyEvent};
+use libp2p::kad::KademliaEvent;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::{
@@ -48,6 +54,7 @@ pub mod behaviour {
     #[derive(Debug)]
     pub enum OutEvent {
         SwapSetupInitiated {
+            peer: PeerId,
             send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
         },
         SwapSetupCompleted {
@@ -55,14 +62,26 @@ pub mod behaviour {
             swap_id: Uuid,
             state3: State3,
         },
+        SwapSetupAborted {
+            peer: PeerId,
+            error: Error,
+        },
         SwapDeclined {
             peer: PeerId,
             error: alice::Error,
         },
         QuoteRequested {
+            channel: ResponseChannel<SignedBidQuote>,
+            peer: PeerId,
+        },
+        QuoteSubscriptionRequested {
             channel: ResponseChannel<BidQuote>,
             peer: PeerId,
         },
+        CapabilitiesRequested {
+            channel: ResponseChannel<Capabilities>,
+            peer: PeerId,
+        },
         TransferProofAcknowledged {
             peer: PeerId,
             id: RequestId,
@@ -73,6 +92,7 @@ pub mod behaviour {
             peer: PeerId,
         },
         Rendezvous(libp2p::rendezvous::client::Event),
+        Dht(KademliaEvent),
         Failure {
             peer: PeerId,
             error: Error,
@@ -107,7 +127,11 @@ pub mod behaviour {
         LR: LatestRate + Send + 'static,
     {
         pub rendezvous: libp2p::swarm::toggle::Toggle<rendezous::Behaviour>,
+        pub dht: libp2p::swarm::toggle::Toggle<dht::Behaviour>,
         pub quote: quote::Behaviour,
+        pub quote_subscribe: quote_subscription::subscribe::Behaviour,
+        pub quote_update: quote_subscription::update::Behaviour,
+        pub capabilities: capabilities::Behaviour,
         pub swap_setup: alice::Behaviour<LR>,
         pub transfer_proof: transfer_proof::Behaviour,
         pub encrypted_signature: encrypted_signature::Behaviour,
@@ -116,6 +140,10 @@ pub mod behaviour {
         /// is still alive. If the ping fails a connection close event
         /// will be emitted that is picked up as swarm event.
         ping: Ping,
+
+        /// Tells takers which addresses we listen on, so they can still reach
+        /// us if the address they know stops working.
+        identify: Identify,
     }
 
     impl<LR> Behaviour<LR>
@@ -126,33 +154,45 @@ pub mod behaviour {
             min_buy: bitcoin::Amount,
             max_buy: bitcoin::Amount,
             latest_rate: LR,
+            quote_book: QuoteBook,
             resume_only: bool,
             env_config: env::Config,
-            rendezvous_params: Option<(identity::Keypair, PeerId, Multiaddr, XmrBtcNamespace)>,
+            rendezvous_params: Option<(
+                identity::Keypair,
+                Vec<(PeerId, Multiaddr)>,
+                XmrBtcNamespace,
+            )>,
+            dht: Option<dht::Behaviour>,
+            public_key: identity::PublicKey,
         ) -> Self {
             Self {
                 rendezvous: libp2p::swarm::toggle::Toggle::from(rendezvous_params.map(
-                    |(identity, rendezvous_peer_id, rendezvous_address, namespace)| {
+                    |(identity, rendezvous_nodes, namespace)| {
                         rendezous::Behaviour::new(
                             identity,
-                            rendezvous_peer_id,
-                            rendezvous_address,
+                            rendezvous_nodes,
                             namespace,
                             None, // use default ttl on rendezvous point
                         )
                     },
                 )),
+                dht: libp2p::swarm::toggle::Toggle::from(dht),
                 quote: quote::asb(),
+                quote_subscribe: quote_subscription::subscribe::asb(),
+                quote_update: quote_subscription::update::asb(),
+                capabilities: capabilities::asb(),
                 swap_setup: alice::Behaviour::new(
                     min_buy,
                     max_buy,
                     env_config,
                     latest_rate,
+                    quote_book,
                     resume_only,
                 ),
                 transfer_proof: transfer_proof::alice(),
                 encrypted_signature: encrypted_signature::alice(),
                 ping: Ping::new(PingConfig::new().with_keep_alive(true)),
+                identify: identify::new(public_key),
             }
         }
     }
@@ -163,18 +203,37 @@ pub mod behaviour {
         }
     }
 
+    impl From<IdentifyEvent> for OutEvent {
+        fn from(_: IdentifyEvent) -> Self {
+            OutEvent::Other
+        }
+    }
+
     impl From<libp2p::rendezvous::client::Event> for OutEvent {
         fn from(event: libp2p::rendezvous::client::Event) -> Self {
             OutEvent::Rendezvous(event)
         }
     }
+
+    impl From<KademliaEvent> for OutEvent {
+        fn from(event: KademliaEvent) -> Self {
+            OutEvent::Dht(event)
+        }
+    }
 }
 
 pub mod rendezous {
     use super::*;
+    use backoff::backoff::Backoff;
+    use backoff::ExponentialBackoff;
+    use libp2p::rendezvous::client::RegisterError;
     use libp2p::swarm::DialError;
     use std::pin::Pin;
 
+    /// The longest we wait before dialling a rendezvous node again or retrying
+    /// a failed registration.
+    const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
     #[derive(PartialEq)]
     enum ConnectionStatus {
         Disconnected,
@@ -188,43 +247,206 @@ pub mod rendezous {
         Registered {
             re_register_in: Pin<Box<tokio::time::Sleep>>,
         },
+        /// Dialling or registering failed, try again once the backoff elapsed.
+        Failed {
+            retry_in: Pin<Box<tokio::time::Sleep>>,
+        },
+    }
+
+    /// The connection and registration state of a single rendezvous node.
+    struct RendezvousNode {
+        address: Multiaddr,
+        peer_id: PeerId,
+        connection_status: ConnectionStatus,
+        registration_status: RegistrationStatus,
+        backoff: ExponentialBackoff,
     }
 
+    impl RendezvousNode {
+        fn new(peer_id: PeerId, address: Multiaddr) -> Self {
+            Self {
+                address,
+                peer_id,
+                connection_status: ConnectionStatus::Disconnected,
+                registration_status: RegistrationStatus::RegisterOnNextConnection,
+                backoff: ExponentialBackoff {
+                    initial_interval: Duration::from_secs(1),
+                    current_interval: Duration::from_secs(1),
+                    max_interval: MAX_BACKOFF,
+                    max_elapsed_time: None,
+                    ..ExponentialBackoff::default()
+                },
+            }
+        }
+
+        fn retry_later(&mut self) {
+            let retry_in = self.backoff.next_backoff().unwrap_or(MAX_BACKOFF);
+
+            tracing::debug!(
+                rendezvous_node = %self.peer_id,
+                address = %self.address,
+                ?retry_in,
+                "Retrying to register with rendezvous node later"
+            );
+
+            self.registration_status = RegistrationStatus::Failed {
+                retry_in: Box::pin(tokio::time::sleep(retry_in)),
+            };
+        }
+    }
+
+    /// Whether we are connected and registered with a rendezvous node.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Status {
+        pub rendezvous_node: PeerId,
+        pub address: Multiaddr,
+        pub connected: bool,
+        pub registered: bool,
+    }
+
+    /// Registers with any number of rendezvous nodes and keeps each
+    /// registration alive independently of the others.
     pub struct Behaviour {
         inner: libp2p::rendezvous::client::Behaviour,
-        rendezvous_point: Multiaddr,
-        rendezvous_peer_id: PeerId,
+        rendezvous_nodes: Vec<RendezvousNode>,
         namespace: XmrBtcNamespace,
-        registration_status: RegistrationStatus,
-        connection_status: ConnectionStatus,
         registration_ttl: Option<u64>,
     }
 
     impl Behaviour {
         pub fn new(
             identity: identity::Keypair,
-            rendezvous_peer_id: PeerId,
-            rendezvous_address: Multiaddr,
+            rendezvous_nodes: Vec<(PeerId, Multiaddr)>,
             namespace: XmrBtcNamespace,
             registration_ttl: Option<u64>,
         ) -> Self {
             Self {
                 inner: libp2p::rendezvous::client::Behaviour::new(identity),
-                rendezvous_point: rendezvous_address,
-                rendezvous_peer_id,
+                rendezvous_nodes: rendezvous_nodes
+                    .into_iter()
+                    .map(|(peer_id, address)| RendezvousNode::new(peer_id, address))
+                    .collect(),
                 namespace,
-                registration_status: RegistrationStatus::RegisterOnNextConnection,
-                connection_status: ConnectionStatus::Disconnected,
                 registration_ttl,
             }
         }
 
-        fn register(&mut self) {
-            self.inner.register(
-                self.namespace.into(),
-                self.rendezvous_peer_id,
-                self.registration_ttl,
-            );
+        /// The status of every rendezvous node, in the order they were
+        /// configured.
+        pub fn statuses(&self) -> Vec<Status> {
+            self.rendezvous_nodes
+                .iter()
+                .map(|node| Status {
+                    rendezvous_node: node.peer_id,
+                    address: node.address.clone(),
+                    connected: node.connection_status == ConnectionStatus::Connected,
+                    registered: matches!(
+                        node.registration_status,
+                        RegistrationStatus::Registered { .. }
+                    ),
+                })
+                .collect()
+        }
+
+        /// Logs how many rendezvous nodes we are registered with.
+        ///
+        /// The counts are logged as the `registered` and `configured` fields,
+        /// so they can be tracked from the JSON logs.
+        pub fn log_status(&self) {
+            let statuses = self.statuses();
+
+            for status in &statuses {
+                tracing::debug!(
+                    rendezvous_node = %status.rendezvous_node,
+                    address = %status.address,
+                    connected = status.connected,
+                    registered = status.registered,
+                    "Rendezvous node status"
+                );
+            }
+
+            let registered = statuses.iter().filter(|status| status.registered).count();
+            let configured = statuses.len();
+
+            if registered == 0 {
+                tracing::warn!(
+                    %registered,
+                    %configured,
+                    "Not registered with any rendezvous node, takers cannot discover us"
+                );
+            } else {
+                tracing::info!(%registered, %configured, "Registered with rendezvous nodes");
+            }
+        }
+
+        fn node_mut(&mut self, peer_id: &PeerId) -> Option<&mut RendezvousNode> {
+            self.rendezvous_nodes
+                .iter_mut()
+                .find(|node| &node.peer_id == peer_id)
+        }
+
+        fn register(
+            inner: &mut libp2p::rendezvous::client::Behaviour,
+            node: &mut RendezvousNode,
+            namespace: XmrBtcNamespace,
+            registration_ttl: Option<u64>,
+        ) {
+            inner.register(namespace.into(), node.peer_id, registration_ttl);
+            node.registration_status = RegistrationStatus::Pending;
+        }
+
+        fn dial(
+            node: &mut RendezvousNode,
+        ) -> NetworkBehaviourAction<
+            libp2p::rendezvous::client::Event,
+            <Self as NetworkBehaviour>::ProtocolsHandler,
+        > {
+            node.connection_status = ConnectionStatus::Dialling;
+
+            NetworkBehaviourAction::DialPeer {
+                peer_id: node.peer_id,
+                condition: DialPeerCondition::Disconnected,
+                handler: <Self as NetworkBehaviour>::ProtocolsHandler::new(Duration::from_secs(30)),
+            }
+        }
+
+        /// Updates the state of the rendezvous nodes with the outcome of a
+        /// registration.
+        fn on_event(&mut self, event: &libp2p::rendezvous::client::Event) {
+            match event {
+                libp2p::rendezvous::client::Event::Registered {
+                    rendezvous_node,
+                    ttl,
+                    ..
+                } => {
+                    if let Some(node) = self.node_mut(rendezvous_node) {
+                        let half_of_ttl = Duration::from_secs(*ttl) / 2;
+
+                        node.backoff.reset();
+                        node.registration_status = RegistrationStatus::Registered {
+                            re_register_in: Box::pin(tokio::time::sleep(half_of_ttl)),
+                        };
+                    }
+                }
+                libp2p::rendezvous::client::Event::RegisterFailed(RegisterError::Remote {
+                    rendezvous_node,
+                    ..
+                }) => {
+                    if let Some(node) = self.node_mut(rendezvous_node) {
+                        node.retry_later();
+                    }
+                }
+                libp2p::rendezvous::client::Event::RegisterFailed(_) => {
+                    // Not specific to a rendezvous node, e.g. we don't know our
+                    // external addresses yet.
+                    for node in self.rendezvous_nodes.iter_mut().filter(|node| {
+                        matches!(node.registration_status, RegistrationStatus::Pending)
+                    }) {
+                        node.retry_later();
+                    }
+                }
+                _ => {}
+            }
         }
     }
 
@@ -238,31 +460,48 @@ pub mod rendezous {
         }
 
         fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
-            if peer_id == &self.rendezvous_peer_id {
-                return vec![self.rendezvous_point.clone()];
-            }
-
-            vec![]
+            self.rendezvous_nodes
+                .iter()
+                .filter(|node| &node.peer_id == peer_id)
+                .map(|node| node.address.clone())
+                .collect()
         }
 
         fn inject_connected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Connected;
+            let namespace = self.namespace;
+            let registration_ttl = self.registration_ttl;
 
-                match &self.registration_status {
+            if let Some(node) = self
+                .rendezvous_nodes
+                .iter_mut()
+                .find(|node| &node.peer_id == peer_id)
+            {
+                tracing::debug!(
+                    rendezvous_node = %node.peer_id,
+                    address = %node.address,
+                    "Connected to rendezvous node"
+                );
+                node.connection_status = ConnectionStatus::Connected;
+
+                match &node.registration_status {
                     RegistrationStatus::RegisterOnNextConnection => {
-                        self.register();
-                        self.registration_status = RegistrationStatus::Pending;
+                        Self::register(&mut self.inner, node, namespace, registration_ttl);
                     }
                     RegistrationStatus::Registered { .. } => {}
                     RegistrationStatus::Pending => {}
+                    RegistrationStatus::Failed { .. } => {}
                 }
             }
         }
 
         fn inject_disconnected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Disconnected;
+            if let Some(node) = self.node_mut(peer_id) {
+                tracing::debug!(
+                    rendezvous_node = %node.peer_id,
+                    address = %node.address,
+                    "Disconnected from rendezvous node"
+                );
+                node.connection_status = ConnectionStatus::Disconnected;
             }
         }
 
@@ -279,10 +518,22 @@ pub mod rendezous {
             &mut self,
             peer_id: &PeerId,
             _handler: Self::ProtocolsHandler,
-            _error: DialError,
+            error: DialError,
         ) {
-            if peer_id == &self.rendezvous_peer_id {
-                self.connection_status = ConnectionStatus::Disconnected;
+            if let Some(node) = self.node_mut(peer_id) {
+                tracing::warn!(
+                    rendezvous_node = %node.peer_id,
+                    address = %node.address,
+                    "Failed to dial rendezvous node: {}", error
+                );
+                node.connection_status = ConnectionStatus::Disconnected;
+
+                if !matches!(
+                    node.registration_status,
+                    RegistrationStatus::Registered { .. }
+                ) {
+                    node.retry_later();
+                }
             }
         }
 
@@ -292,59 +543,57 @@ pub mod rendezous {
             cx: &mut std::task::Context<'_>,
             params: &mut impl PollParameters,
         ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
-            match &mut self.registration_status {
-                RegistrationStatus::RegisterOnNextConnection => match self.connection_status {
-                    ConnectionStatus::Disconnected => {
-                        self.connection_status = ConnectionStatus::Dialling;
-
-                        return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                            peer_id: self.rendezvous_peer_id,
-                            condition: DialPeerCondition::Disconnected,
-                            handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                        });
-                    }
-                    ConnectionStatus::Dialling => {}
-                    ConnectionStatus::Connected => {
-                        self.registration_status = RegistrationStatus::Pending;
-                        self.register();
+            let namespace = self.namespace;
+            let registration_ttl = self.registration_ttl;
+
+            for node in self.rendezvous_nodes.iter_mut() {
+                if let RegistrationStatus::Failed { retry_in } = &mut node.registration_status {
+                    if retry_in.poll_unpin(cx).is_pending() {
+                        continue;
                     }
-                },
-                RegistrationStatus::Registered { re_register_in } => {
-                    if let Poll::Ready(()) = re_register_in.poll_unpin(cx) {
-                        match self.connection_status {
-                            ConnectionStatus::Connected => {
-                                self.registration_status = RegistrationStatus::Pending;
-                                self.register();
-                            }
-                            ConnectionStatus::Disconnected => {
-                                self.registration_status =
-                                    RegistrationStatus::RegisterOnNextConnection;
-
-                                return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                                    peer_id: self.rendezvous_peer_id,
-                                    condition: DialPeerCondition::Disconnected,
-                                    handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                                });
+                    node.registration_status = RegistrationStatus::RegisterOnNextConnection;
+                }
+
+                match &mut node.registration_status {
+                    RegistrationStatus::RegisterOnNextConnection => match node.connection_status {
+                        ConnectionStatus::Disconnected => {
+                            return Poll::Ready(Self::dial(node));
+                        }
+                        ConnectionStatus::Dialling => {}
+                        ConnectionStatus::Connected => {
+                            Self::register(&mut self.inner, node, namespace, registration_ttl);
+                        }
+                    },
+                    RegistrationStatus::Registered { re_register_in } => {
+                        if let Poll::Ready(()) = re_register_in.poll_unpin(cx) {
+                            match node.connection_status {
+                                ConnectionStatus::Connected => {
+                                    Self::register(
+                                        &mut self.inner,
+                                        node,
+                                        namespace,
+                                        registration_ttl,
+                                    );
+                                }
+                                ConnectionStatus::Disconnected => {
+                                    node.registration_status =
+                                        RegistrationStatus::RegisterOnNextConnection;
+
+                                    return Poll::Ready(Self::dial(node));
+                                }
+                                ConnectionStatus::Dialling => {}
                             }
-                            ConnectionStatus::Dialling => {}
                         }
                     }
+                    RegistrationStatus::Pending => {}
+                    RegistrationStatus::Failed { .. } => {}
                 }
-                RegistrationStatus::Pending => {}
             }
 
             let inner_poll = self.inner.poll(cx, params);
 
-            // reset the timer if we successfully registered
-            if let Poll::Ready(NetworkBehaviourAction::GenerateEvent(
-                libp2p::rendezvous::client::Event::Registered { ttl, .. },
-            )) = &inner_poll
-            {
-                let half_of_ttl = Duration::from_secs(*ttl) / 2;
-
-                self.registration_status = RegistrationStatus::Registered {
-                    re_register_in: Box::pin(tokio::time::sleep(half_of_ttl)),
-                };
+            if let Poll::Ready(NetworkBehaviourAction::GenerateEvent(event)) = &inner_poll {
+                self.on_event(event);
             }
 
             inner_poll
@@ -355,9 +604,32 @@ pub mod rendezous {
     mod tests {
         use super::*;
         use crate::network::test::{new_swarm, SwarmExt};
+        use crate::tracing_ext::capture_logs;
         use futures::StreamExt;
         use libp2p::rendezvous;
         use libp2p::swarm::SwarmEvent;
+        use std::collections::HashSet;
+        use std::iter::FromIterator;
+        use tracing::level_filters::LevelFilter;
+
+        #[test]
+        fn given_no_registration_then_warns_that_we_cannot_be_discovered() {
+            let writer = capture_logs(LevelFilter::INFO);
+            let behaviour = rendezous::Behaviour::new(
+                identity::Keypair::generate_ed25519(),
+                vec![(PeerId::random(), "/memory/1".parse().unwrap())],
+                XmrBtcNamespace::Testnet,
+                None,
+            );
+
+            behaviour.log_status();
+
+            assert_eq!(
+                writer.captured(),
+                r" WARN swap::asb::network::rendezous: Not registered with any rendezvous node, takers cannot discover us registered=0 configured=1
+"
+            );
+        }
 
         #[tokio::test]
         async fn given_no_initial_connection_when_constructed_asb_connects_and_registers_with_rendezvous_node(
@@ -370,8 +642,7 @@ pub mod rendezous {
             let mut asb = new_swarm(|_, identity| {
                 rendezous::Behaviour::new(
                     identity,
-                    *rendezvous_node.local_peer_id(),
-                    rendezvous_address,
+                    vec![(*rendezvous_node.local_peer_id(), rendezvous_address)],
                     XmrBtcNamespace::Testnet,
                     None,
                 )
@@ -411,8 +682,7 @@ pub mod rendezous {
             let mut asb = new_swarm(|_, identity| {
                 rendezous::Behaviour::new(
                     identity,
-                    *rendezvous_node.local_peer_id(),
-                    rendezvous_address,
+                    vec![(*rendezvous_node.local_peer_id(), rendezvous_address)],
                     XmrBtcNamespace::Testnet,
                     Some(5),
                 )
@@ -445,5 +715,110 @@ pub mod rendezous {
                 .unwrap()
                 .unwrap();
         }
+
+        #[tokio::test]
+        async fn registers_with_every_rendezvous_node() {
+            let (first_peer_id, first_address) = spawn_rendezvous_node().await;
+            let (second_peer_id, second_address) = spawn_rendezvous_node().await;
+
+            let mut asb = new_swarm(|_, identity| {
+                rendezous::Behaviour::new(
+                    identity,
+                    vec![
+                        (first_peer_id, first_address),
+                        (second_peer_id, second_address),
+                    ],
+                    XmrBtcNamespace::Testnet,
+                    None,
+                )
+            });
+            asb.listen_on_random_memory_address().await; // this adds an external address
+
+            let registered_with = tokio::time::timeout(Duration::from_secs(10), async {
+                let mut registered_with = HashSet::new();
+
+                while registered_with.len() < 2 {
+                    if let SwarmEvent::Behaviour(rendezvous::client::Event::Registered {
+                        rendezvous_node,
+                        ..
+                    }) = asb.select_next_some().await
+                    {
+                        registered_with.insert(rendezvous_node);
+                    }
+                }
+
+                registered_with
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(
+                registered_with,
+                HashSet::from_iter(vec![first_peer_id, second_peer_id])
+            );
+            assert!(asb
+                .behaviour()
+                .statuses()
+                .iter()
+                .all(|status| status.connected && status.registered));
+        }
+
+        #[tokio::test]
+        async fn given_one_rendezvous_node_is_down_then_registers_with_the_other() {
+            let (live_peer_id, live_address) = spawn_rendezvous_node().await;
+            let (dead_peer_id, dead_address) = {
+                let mut dead_node = new_swarm(|_, _| {
+                    rendezvous::server::Behaviour::new(rendezvous::server::Config::default())
+                });
+                let address = dead_node.listen_on_random_memory_address().await;
+
+                (*dead_node.local_peer_id(), address)
+            };
+
+            let mut asb = new_swarm(|_, identity| {
+                rendezous::Behaviour::new(
+                    identity,
+                    vec![(dead_peer_id, dead_address), (live_peer_id, live_address)],
+                    XmrBtcNamespace::Testnet,
+                    None,
+                )
+            });
+            asb.listen_on_random_memory_address().await; // this adds an external address
+
+            let registered_with = tokio::time::timeout(Duration::from_secs(10), async {
+                loop {
+                    if let SwarmEvent::Behaviour(rendezvous::client::Event::Registered {
+                        rendezvous_node,
+                        ..
+                    }) = asb.select_next_some().await
+                    {
+                        break rendezvous_node;
+                    }
+                }
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(registered_with, live_peer_id);
+            let statuses = asb.behaviour().statuses();
+            assert!(!statuses[0].connected && !statuses[0].registered);
+            assert!(statuses[1].connected && statuses[1].registered);
+        }
+
+        async fn spawn_rendezvous_node() -> (PeerId, Multiaddr) {
+            let mut rendezvous_node = new_swarm(|_, _| {
+                rendezvous::server::Behaviour::new(rendezvous::server::Config::default())
+            });
+            let address = rendezvous_node.listen_on_random_memory_address().await;
+            let peer_id = *rendezvous_node.local_peer_id();
+
+            tokio::spawn(async move {
+                loop {
+                    rendezvous_node.next().await;
+                }
+            });
+
+            (peer_id, address)
+        }
     }
 }
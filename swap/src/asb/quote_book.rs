@@ -0,0 +1,202 @@
+use crate::bitcoin;
+use crate::network::quote::{unix_timestamp, BidQuote, SignedBidQuote};
+use anyhow::Result;
+use libp2p::identity;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const DEFAULT_QUOTE_VALIDITY: Duration = Duration::from_secs(60);
+
+/// By default we honour a quote as long as the market did not move more than
+/// 1% against it.
+pub const DEFAULT_QUOTE_TOLERANCE: Decimal = dec!(0.01);
+
+/// The quotes we signed and still honour.
+///
+/// Shared between the event loop, which hands out quotes, and the swap setup,
+/// which honours them.
+#[derive(Debug, Clone)]
+pub struct QuoteBook {
+    identity: identity::Keypair,
+    validity: Duration,
+    tolerance: Decimal,
+    issued: Arc<Mutex<HashMap<Uuid, SignedBidQuote>>>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    #[error("Quote {0} is unknown or expired")]
+    UnknownOrExpired(Uuid),
+    #[error("Price moved beyond tolerance, quoted {quoted} but asking {ask}")]
+    BeyondTolerance {
+        quoted: bitcoin::Amount,
+        ask: bitcoin::Amount,
+    },
+}
+
+impl QuoteBook {
+    /// `tolerance` is the fraction the market may move against a quote
+    /// before we stop honouring it, e.g. `0.01` for 1%.
+    pub fn new(identity: identity::Keypair, validity: Duration, tolerance: Decimal) -> Self {
+        Self {
+            identity,
+            validity,
+            tolerance,
+            issued: Default::default(),
+        }
+    }
+
+    /// Signs `quote` and commits to honouring it until it expires.
+    pub fn issue(&self, quote: BidQuote) -> Result<SignedBidQuote> {
+        self.issue_at(quote, unix_timestamp())
+    }
+
+    /// Returns the price at which we sell for a swap referencing the quote
+    /// `id`, given that we are currently asking `ask`.
+    ///
+    /// The taker gets the better of the quoted and the current price.
+    pub fn honoured_price(&self, id: Uuid, ask: bitcoin::Amount) -> Result<bitcoin::Amount, Error> {
+        self.honoured_price_at(id, ask, unix_timestamp())
+    }
+
+    fn issue_at(&self, quote: BidQuote, now: u64) -> Result<SignedBidQuote> {
+        let signed = SignedBidQuote::sign(
+            quote,
+            Uuid::new_v4(),
+            now + self.validity.as_secs(),
+            &self.identity,
+        )?;
+        let id = signed.signature.as_ref().expect("just signed").id;
+
+        let mut issued = self.issued.lock().expect("lock not to be poisoned");
+        issued.retain(|_, quote| !is_expired(quote, now));
+        issued.insert(id, signed.clone());
+
+        Ok(signed)
+    }
+
+    fn honoured_price_at(
+        &self,
+        id: Uuid,
+        ask: bitcoin::Amount,
+        now: u64,
+    ) -> Result<bitcoin::Amount, Error> {
+        let issued = self.issued.lock().expect("lock not to be poisoned");
+        let quoted = match issued.get(&id) {
+            Some(quote) if !is_expired(quote, now) => quote.quote.price,
+            _ => return Err(Error::UnknownOrExpired(id)),
+        };
+
+        let min_price = Decimal::from(ask.as_sat()) * (dec!(1) - self.tolerance);
+        let min_price = min_price.ceil().to_u64().unwrap_or(u64::MAX);
+        if quoted.as_sat() < min_price {
+            return Err(Error::BeyondTolerance { quoted, ask });
+        }
+
+        Ok(min(quoted, ask))
+    }
+}
+
+fn is_expired(quote: &SignedBidQuote, now: u64) -> bool {
+    match &quote.signature {
+        Some(signature) => now > signature.expires_at,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(price: u64) -> BidQuote {
+        BidQuote {
+            price: bitcoin::Amount::from_sat(price),
+            min_quantity: bitcoin::Amount::from_sat(42),
+            max_quantity: bitcoin::Amount::from_sat(9001),
+        }
+    }
+
+    fn quote_book() -> QuoteBook {
+        QuoteBook::new(
+            identity::Keypair::generate_ed25519(),
+            Duration::from_secs(60),
+            DEFAULT_QUOTE_TOLERANCE,
+        )
+    }
+
+    fn id(quote: &SignedBidQuote) -> Uuid {
+        quote.signature.as_ref().unwrap().id
+    }
+
+    #[test]
+    fn given_price_moved_against_us_within_tolerance_then_honours_quoted_price() {
+        let quote_book = quote_book();
+        let signed = quote_book.issue_at(quote(10_000), 1_000).unwrap();
+
+        let price = quote_book
+            .honoured_price_at(id(&signed), bitcoin::Amount::from_sat(10_100), 1_060)
+            .unwrap();
+
+        assert_eq!(price, bitcoin::Amount::from_sat(10_000));
+    }
+
+    #[test]
+    fn given_price_moved_in_favour_of_taker_then_uses_current_price() {
+        let quote_book = quote_book();
+        let signed = quote_book.issue_at(quote(10_000), 1_000).unwrap();
+
+        let price = quote_book
+            .honoured_price_at(id(&signed), bitcoin::Amount::from_sat(9_000), 1_000)
+            .unwrap();
+
+        assert_eq!(price, bitcoin::Amount::from_sat(9_000));
+    }
+
+    #[test]
+    fn given_price_moved_beyond_tolerance_then_fails() {
+        let quote_book = quote_book();
+        let signed = quote_book.issue_at(quote(10_000), 1_000).unwrap();
+
+        let result =
+            quote_book.honoured_price_at(id(&signed), bitcoin::Amount::from_sat(10_200), 1_000);
+
+        assert_eq!(
+            result,
+            Err(Error::BeyondTolerance {
+                quoted: bitcoin::Amount::from_sat(10_000),
+                ask: bitcoin::Amount::from_sat(10_200)
+            })
+        );
+    }
+
+    #[test]
+    fn given_expired_quote_then_fails() {
+        let quote_book = quote_book();
+        let signed = quote_book.issue_at(quote(10_000), 1_000).unwrap();
+
+        let result =
+            quote_book.honoured_price_at(id(&signed), bitcoin::Amount::from_sat(10_000), 1_061);
+
+        assert_eq!(result, Err(Error::UnknownOrExpired(id(&signed))));
+    }
+
+    #[test]
+    fn expired_quotes_are_forgotten() {
+        let quote_book = quote_book();
+        let expired = quote_book.issue_at(quote(10_000), 1_000).unwrap();
+
+        quote_book.issue_at(quote(10_000), 2_000).unwrap();
+
+        assert!(!quote_book
+            .issued
+            .lock()
+            .unwrap()
+            .contains_key(&id(&expired)));
+    }
+}
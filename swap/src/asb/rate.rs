@@ -47,6 +47,12 @@ impl Rate {
         Self::quote(self.ask()?, quote)
     }
 
+    /// Calculate a sell quote for a given BTC amount at a price we committed
+    /// to earlier instead of the current one.
+    pub fn sell_quote_at(price: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
+        Self::quote(price, quote)
+    }
+
     fn quote(rate: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
         // quote (btc) = rate * base (xmr)
         // base = quote / rate
@@ -14,28 +14,30 @@
 
 use anyhow::{bail, Context, Result};
 use comfy_table::Table;
-use libp2p::core::multiaddr::Protocol;
-use libp2p::core::Multiaddr;
 use libp2p::swarm::AddressScore;
 use libp2p::Swarm;
+use monero_rpc::wallet;
 use std::convert::TryInto;
 use std::env;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::clap;
 use structopt::clap::ErrorKind;
 use swap::asb::command::{parse_args, Arguments, Command};
 use swap::asb::config::{
     initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
 };
-use swap::asb::{cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate};
+use swap::asb::quote_book::{DEFAULT_QUOTE_TOLERANCE, DEFAULT_QUOTE_VALIDITY};
+use swap::asb::{
+    cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate, QuoteBook,
+};
 use swap::database::open_db;
 use swap::monero::Amount;
 use swap::network::rendezvous::XmrBtcNamespace;
 use swap::network::swarm;
 use swap::protocol::alice::{run, AliceState};
+use swap::protocol::Database;
 use swap::seed::Seed;
-use swap::tor::AuthenticatedClient;
 use swap::{asb, bitcoin, kraken, monero, tor};
 use tracing_subscriber::filter::LevelFilter;
 
@@ -44,12 +46,12 @@ const DEFAULT_WALLET_NAME: &str = "asb-wallet";
 #[tokio::main]
 async fn main() -> Result<()> {
     let Arguments {
-        testnet,
         json,
         disable_timestamp,
         config_path,
         env_config,
         cmd,
+        ..
     } = match parse_args(env::args_os()) {
         Ok(args) => args,
         Err(e) => {
@@ -73,7 +75,13 @@ async fn main() -> Result<()> {
     let config = match read_config(config_path.clone())? {
         Ok(config) => config,
         Err(ConfigNotInitialized {}) => {
-            initial_setup(config_path.clone(), query_user_for_initial_config(testnet)?)?;
+            initial_setup(
+                config_path.clone(),
+                query_user_for_initial_config(
+                    env_config.bitcoin_network,
+                    env_config.monero_network,
+                )?,
+            )?;
             read_config(config_path)?.expect("after initial setup config can be read")
         }
     };
@@ -98,7 +106,8 @@ async fn main() -> Result<()> {
 
     match cmd {
         Command::Start { resume_only } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
 
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
 
@@ -124,9 +133,12 @@ async fn main() -> Result<()> {
             let _ac = match tor_client.assert_tor_running().await {
                 Ok(_) => {
                     tracing::info!("Setting up Tor hidden service");
-                    let ac =
-                        register_tor_services(config.network.clone().listen, tor_client, &seed)
-                            .await?;
+                    let ac = tor::register_hidden_services(
+                        config.network.clone().listen,
+                        tor_client,
+                        &seed,
+                    )
+                    .await?;
                     Some(ac)
                 }
                 Err(_) => {
@@ -136,23 +148,42 @@ async fn main() -> Result<()> {
             };
 
             let kraken_rate = KrakenRate::new(config.maker.ask_spread, kraken_price_updates);
+            let quote_book = QuoteBook::new(
+                seed.derive_libp2p_identity(),
+                config
+                    .maker
+                    .quote_validity_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_QUOTE_VALIDITY),
+                config
+                    .maker
+                    .quote_tolerance
+                    .unwrap_or(DEFAULT_QUOTE_TOLERANCE),
+            );
             let mut swarm = swarm::asb(
                 &seed,
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
                 kraken_rate.clone(),
+                quote_book.clone(),
                 resume_only,
                 env_config,
-                config.network.rendezvous_point.map(|rendezvous_point| {
-                    (
-                        rendezvous_point,
-                        if testnet {
-                            XmrBtcNamespace::Testnet
-                        } else {
-                            XmrBtcNamespace::Mainnet
-                        },
-                    )
-                }),
+                Some(config.network.all_rendezvous_points())
+                    .filter(|rendezvous_points| !rendezvous_points.is_empty())
+                    .map(|rendezvous_points| {
+                        (
+                            rendezvous_points,
+                            XmrBtcNamespace::for_network(env_config.bitcoin_network),
+                        )
+                    }),
+                Some(config.network.dht_bootstrap_nodes.clone())
+                    .filter(|bootstrap_nodes| !bootstrap_nodes.is_empty())
+                    .map(|bootstrap_nodes| {
+                        (
+                            bootstrap_nodes,
+                            XmrBtcNamespace::for_network(env_config.bitcoin_network),
+                        )
+                    }),
             )?;
 
             for listen in config.network.listen.clone() {
@@ -177,8 +208,12 @@ async fn main() -> Result<()> {
                 Arc::new(monero_wallet),
                 db,
                 kraken_rate.clone(),
+                quote_book,
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
+                config.limits.quote_rate_limiter(),
+                config.limits.swap_setup_rate_limiter(),
+                config.limits.ban_list(config.network.banned_peers.clone()),
             )
             .unwrap();
 
@@ -217,27 +252,61 @@ async fn main() -> Result<()> {
             let config_json = serde_json::to_string_pretty(&config)?;
             println!("{}", config_json);
         }
-        Command::WithdrawBtc { amount, address } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+        Command::WithdrawBtc {
+            amount,
+            address,
+            coin_control,
+        } => {
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
 
             let amount = match amount {
                 Some(amount) => amount,
                 None => {
                     bitcoin_wallet
-                        .max_giveable(address.script_pubkey().len())
+                        .max_giveable(address.script_pubkey().len(), &coin_control)
                         .await?
                 }
             };
 
             let psbt = bitcoin_wallet
-                .send_to_address(address, amount, None)
+                .send_to_address(address, amount, None, &coin_control)
                 .await?;
             let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
 
-            bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+            let (txid, _) = bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+            db.insert_bitcoin_transaction_label(txid, bitcoin::Label::Withdraw, None)
+                .await?;
+        }
+        Command::ListUtxos => {
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
+
+            let utxos = bitcoin_wallet.list_unspent().await?;
+            let address_labels = db.get_bitcoin_address_labels().await?;
+            let transaction_labels = db.get_bitcoin_transaction_labels().await?;
+
+            bitcoin::print_utxos(utxos, &address_labels, &transaction_labels, json)?;
+        }
+        Command::BitcoinHistory => {
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
+
+            let history = bitcoin_wallet.history().await?;
+            let transaction_labels = db.get_bitcoin_transaction_labels().await?;
+            let swap_transactions = transaction_labels
+                .iter()
+                .filter(|labelled| labelled.swap_id.is_some())
+                .map(|labelled| labelled.inner)
+                .collect();
+            let swap_fees = bitcoin_wallet
+                .swap_transaction_fees(&swap_transactions)
+                .await?;
+
+            bitcoin::print_history(&history, &transaction_labels, &swap_fees, json)?;
         }
         Command::Balance => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, db, &seed, env_config).await?;
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
 
             let bitcoin_balance = bitcoin_wallet.balance().await?;
@@ -249,14 +318,16 @@ async fn main() -> Result<()> {
                 "Current balance");
         }
         Command::Cancel { swap_id } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
 
             let (txid, _) = cancel(swap_id, Arc::new(bitcoin_wallet), db).await?;
 
             tracing::info!("Cancel transaction successfully published with id {}", txid);
         }
         Command::Refund { swap_id } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
 
             refund(
@@ -270,7 +341,8 @@ async fn main() -> Result<()> {
             tracing::info!("Monero successfully refunded");
         }
         Command::Punish { swap_id } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
 
             let (txid, _) = punish(swap_id, Arc::new(bitcoin_wallet), db).await?;
 
@@ -285,7 +357,8 @@ async fn main() -> Result<()> {
             swap_id,
             do_not_await_finality,
         } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet =
+                init_bitcoin_wallet(&config, db.clone(), &seed, env_config).await?;
 
             let (txid, _) = redeem(
                 swap_id,
@@ -298,7 +371,7 @@ async fn main() -> Result<()> {
             tracing::info!("Redeem transaction successfully published with id {}", txid);
         }
         Command::ExportBitcoinWallet => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(&config, db, &seed, env_config).await?;
             let wallet_export = bitcoin_wallet.wallet_export("asb").await?;
             println!("{}", wallet_export.to_string())
         }
@@ -309,15 +382,16 @@ async fn main() -> Result<()> {
 
 async fn init_bitcoin_wallet(
     config: &Config,
+    db: Arc<dyn Database + Send + Sync>,
     seed: &Seed,
     env_config: swap::env::Config,
 ) -> Result<bitcoin::Wallet> {
     tracing::debug!("Opening Bitcoin wallet");
-    let wallet_dir = config.data.dir.join("wallet");
 
     let wallet = bitcoin::Wallet::new(
-        config.bitcoin.electrum_rpc_url.clone(),
-        &wallet_dir,
+        config.bitcoin.electrum_rpc_urls(),
+        db,
+        &config.data.dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
         config.bitcoin.target_block,
@@ -325,6 +399,7 @@ async fn init_bitcoin_wallet(
     .await
     .context("Failed to initialize Bitcoin wallet")?;
 
+    wallet.start_processing_notifications()?;
     wallet.sync().await?;
 
     Ok(wallet)
@@ -335,55 +410,21 @@ async fn init_monero_wallet(
     env_config: swap::env::Config,
 ) -> Result<monero::Wallet> {
     tracing::debug!("Opening Monero wallet");
+    let connection = config.monero.wallet_rpc_connection()?;
     let wallet = monero::Wallet::open_or_create(
-        config.monero.wallet_rpc_url.clone(),
+        wallet::Client::connect(config.monero.wallet_rpc_url.clone(), connection.clone())?,
         DEFAULT_WALLET_NAME.to_string(),
         env_config,
     )
-    .await?;
+    .await?
+    .with_ephemeral_wallet_rpcs(
+        config
+            .monero
+            .ephemeral_wallet_rpc_urls
+            .iter()
+            .map(|url| wallet::Client::connect(url.clone(), connection.clone()))
+            .collect::<Result<_>>()?,
+    );
 
     Ok(wallet)
 }
-
-/// Registers a hidden service for each network.
-/// Note: Once ac goes out of scope, the services will be de-registered.
-async fn register_tor_services(
-    networks: Vec<Multiaddr>,
-    tor_client: tor::Client,
-    seed: &Seed,
-) -> Result<AuthenticatedClient> {
-    let mut ac = tor_client.into_authenticated_client().await?;
-
-    let hidden_services_details = networks
-        .iter()
-        .flat_map(|network| {
-            network.iter().map(|protocol| match protocol {
-                Protocol::Tcp(port) => Some((
-                    port,
-                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), port),
-                )),
-                _ => {
-                    // We only care for Tcp for now.
-                    None
-                }
-            })
-        })
-        .flatten()
-        .collect::<Vec<_>>();
-
-    let key = seed.derive_torv3_key();
-
-    ac.add_services(&hidden_services_details, &key).await?;
-
-    let onion_address = key
-        .public()
-        .get_onion_address()
-        .get_address_without_dot_onion();
-
-    hidden_services_details.iter().for_each(|(port, _)| {
-        let onion_address = format!("/onion3/{}:{}", onion_address, port);
-        tracing::info!(%onion_address, "Successfully created hidden service");
-    });
-
-    Ok(ac)
-}
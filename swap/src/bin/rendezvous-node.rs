@@ -0,0 +1,140 @@
+#![warn(
+    unused_extern_crates,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+
+use anyhow::{Context, Result};
+use libp2p::swarm::AddressScore;
+use libp2p::{Multiaddr, Swarm};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use swap::fs::system_data_dir;
+use swap::network::swarm;
+use swap::rendezvous_node::{self, EventLoop};
+use swap::seed::Seed;
+use swap::{asb, tor};
+use tracing_subscriber::filter::LevelFilter;
+
+#[derive(structopt::StructOpt, Debug)]
+#[structopt(
+    name = "rendezvous-node",
+    about = "Rendezvous point that xmr-btc-swap sellers register at and takers discover them through",
+    author
+)]
+struct Arguments {
+    #[structopt(
+        long = "data-dir",
+        help = "Directory the seed of the rendezvous node is stored in",
+        parse(from_os_str)
+    )]
+    data_dir: Option<PathBuf>,
+
+    #[structopt(
+        long = "listen",
+        help = "Address to listen on, can be given multiple times",
+        default_value = "/ip4/0.0.0.0/tcp/8888",
+        number_of_values = 1
+    )]
+    listen: Vec<Multiaddr>,
+
+    #[structopt(
+        long = "external-address",
+        help = "Address the rendezvous node is reachable at, can be given multiple times",
+        number_of_values = 1
+    )]
+    external_addresses: Vec<Multiaddr>,
+
+    #[structopt(
+        long = "tor",
+        help = "Also make the rendezvous node reachable as a Tor hidden service"
+    )]
+    tor: bool,
+
+    #[structopt(long = "tor-socks5-port", default_value = "9050")]
+    tor_socks5_port: u16,
+
+    #[structopt(long = "tor-control-port", default_value = "9051")]
+    tor_control_port: u16,
+
+    #[structopt(
+        long = "min-ttl",
+        help = "Refuse registrations that expire sooner than this many seconds",
+        default_value = "7200"
+    )]
+    min_ttl: u64,
+
+    #[structopt(
+        long = "max-ttl",
+        help = "Refuse registrations that expire later than this many seconds",
+        default_value = "259200"
+    )]
+    max_ttl: u64,
+
+    #[structopt(
+        long = "max-registrations",
+        help = "Ban new peers until their registration expires once this many registrations are served",
+        default_value = "1000"
+    )]
+    max_registrations: usize,
+
+    #[structopt(long, help = "Outputs all logs in JSON format instead of plain text")]
+    json: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Arguments::from_args();
+
+    asb::tracing::init(LevelFilter::DEBUG, args.json, true).expect("initialize tracing");
+
+    let data_dir = match args.data_dir {
+        Some(data_dir) => data_dir,
+        None => system_data_dir()?.join("rendezvous-node"),
+    };
+    let seed =
+        Seed::from_file_or_generate(&data_dir).context("Failed to retrieve or initialize seed")?;
+
+    let _ac = if args.tor {
+        let tor_client =
+            tor::Client::new(args.tor_socks5_port).with_control_port(args.tor_control_port);
+        tor_client
+            .assert_tor_running()
+            .await
+            .context("Tor is not running")?;
+
+        tracing::info!("Setting up Tor hidden service");
+        Some(tor::register_hidden_services(args.listen.clone(), tor_client, &seed).await?)
+    } else {
+        None
+    };
+
+    let config = rendezvous_node::Config {
+        min_ttl: args.min_ttl,
+        max_ttl: args.max_ttl,
+        max_registrations: args.max_registrations,
+    };
+    let mut swarm = swarm::rendezvous_node(&seed, config)?;
+
+    for listen in args.listen {
+        Swarm::listen_on(&mut swarm, listen.clone())
+            .with_context(|| format!("Failed to listen on network interface {}", listen))?;
+    }
+
+    for external_address in args.external_addresses {
+        let _ = Swarm::add_external_address(&mut swarm, external_address, AddressScore::Infinite);
+    }
+
+    tracing::info!(peer_id = %swarm.local_peer_id(), "Rendezvous node initialized");
+
+    EventLoop::new(swarm, config).run().await;
+
+    Ok(())
+}
@@ -14,6 +14,8 @@
 
 use anyhow::{bail, Context, Result};
 use comfy_table::Table;
+use monero_rpc::http::{Connection, Credentials};
+use monero_rpc::wallet;
 use qrcode::render::unicode;
 use qrcode::QrCode;
 use std::cmp::min;
@@ -29,12 +31,16 @@ use swap::cli::{list_sellers, EventLoop, SellerStatus};
 use swap::database::open_db;
 use swap::env::Config;
 use swap::libp2p_ext::MultiAddrExt;
+use swap::network::address_book::AddressPreference;
+use swap::network::capabilities::{QUOTE_SUBSCRIPTION, XMR_FOR_BTC};
 use swap::network::quote::BidQuote;
-use swap::network::swarm;
+use swap::network::{dht, swarm};
 use swap::protocol::bob;
 use swap::protocol::bob::{BobState, Swap};
+use swap::protocol::Database;
 use swap::seed::Seed;
 use swap::{bitcoin, cli, monero};
+use tokio::sync::watch;
 use url::Url;
 use uuid::Uuid;
 
@@ -57,12 +63,15 @@ async fn main() -> Result<()> {
     match cmd {
         Command::BuyXmr {
             seller,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
             bitcoin_change_address,
             monero_receive_address,
-            monero_daemon_address,
+            monero_daemon_addresses,
+            monero_daemon_login,
+            monero_wallet_rpc,
             tor_socks5_port,
+            coin_control,
         } => {
             let swap_id = Uuid::new_v4();
 
@@ -72,36 +81,64 @@ async fn main() -> Result<()> {
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                db.clone(),
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
+            let (monero_wallet, _process) = init_monero_wallet(
+                data_dir,
+                monero_daemon_addresses,
+                monero_daemon_login,
+                monero_wallet_rpc,
+                env_config,
+            )
+            .await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
             let seller_peer_id = seller
                 .extract_peer_id()
                 .context("Seller address must contain peer ID")?;
-            db.insert_address(seller_peer_id, seller.clone()).await?;
+            db.insert_address(seller_peer_id, seller).await?;
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
-            let mut swarm =
-                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
-            swarm.behaviour_mut().add_address(seller_peer_id, seller);
+            let identity = seed.derive_libp2p_identity();
+            let behaviour = cli::Behaviour::new(
+                seller_peer_id,
+                env_config,
+                bitcoin_wallet.clone(),
+                identity.public(),
+                AddressPreference::detect(tor_socks5_port).await,
+            );
+            let mut swarm = swarm::cli(identity, tor_socks5_port, behaviour).await?;
+            let seller_addresses = cli::known_seller_addresses(db.clone(), seller_peer_id).await?;
+            for (address, health) in seller_addresses {
+                swarm.behaviour_mut().restore_address(address, health);
+            }
 
             tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
 
             let (event_loop, mut event_loop_handle) =
-                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
+                EventLoop::new(swap_id, swarm, seller_peer_id, env_config, db.clone())?;
             let event_loop = tokio::spawn(event_loop.run());
 
-            let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
+            let capabilities = event_loop_handle.request_capabilities().await?;
+            if !capabilities.offers(XMR_FOR_BTC) {
+                bail!("Seller does not sell Monero for Bitcoin")
+            }
+            let quote_updates = if capabilities.supports(QUOTE_SUBSCRIPTION) {
+                Some(event_loop_handle.subscribe_to_quotes().await?)
+            } else {
+                tracing::debug!("Seller does not push quote updates");
+                None
+            };
+
+            let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size(), &coin_control);
             let (amount, fees) = determine_btc_to_swap(
                 json,
                 event_loop_handle.request_quote(),
+                quote_updates,
                 bitcoin_wallet.new_address(),
                 || bitcoin_wallet.balance(),
                 max_givable,
@@ -125,6 +162,7 @@ async fn main() -> Result<()> {
                 monero_receive_address,
                 bitcoin_change_address,
                 amount,
+                coin_control,
             );
 
             tokio::select! {
@@ -169,19 +207,21 @@ async fn main() -> Result<()> {
             tracing::info!(path=%format!("{}/sqlite", data_dir.display()), "Sqlite file location");
             tracing::info!(path=%format!("{}/seed.pem", data_dir.display()), "Seed file location");
             tracing::info!(path=%format!("{}/monero", data_dir.display()), "Monero-wallet-rpc directory");
-            tracing::info!(path=%format!("{}/wallet", data_dir.display()), "Internal bitcoin wallet directory");
         }
         Command::WithdrawBtc {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
             amount,
             address,
+            coin_control,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = open_db(data_dir.join("sqlite")).await?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                db.clone(),
                 &seed,
                 data_dir.clone(),
                 env_config,
@@ -193,28 +233,88 @@ async fn main() -> Result<()> {
                 Some(amount) => amount,
                 None => {
                     bitcoin_wallet
-                        .max_giveable(address.script_pubkey().len())
+                        .max_giveable(address.script_pubkey().len(), &coin_control)
                         .await?
                 }
             };
 
             let psbt = bitcoin_wallet
-                .send_to_address(address, amount, None)
+                .send_to_address(address, amount, None, &coin_control)
                 .await?;
             let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
 
-            bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+            let (txid, _) = bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+            db.insert_bitcoin_transaction_label(txid, bitcoin::Label::Withdraw, None)
+                .await?;
+        }
+        Command::ListUtxos {
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = open_db(data_dir.join("sqlite")).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                db.clone(),
+                &seed,
+                data_dir.clone(),
+                env_config,
+                bitcoin_target_block,
+            )
+            .await?;
+
+            let utxos = bitcoin_wallet.list_unspent().await?;
+            let address_labels = db.get_bitcoin_address_labels().await?;
+            let transaction_labels = db.get_bitcoin_transaction_labels().await?;
+
+            bitcoin::print_utxos(utxos, &address_labels, &transaction_labels, json)?;
+        }
+
+        Command::BitcoinHistory {
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = open_db(data_dir.join("sqlite")).await?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                db.clone(),
+                &seed,
+                data_dir.clone(),
+                env_config,
+                bitcoin_target_block,
+            )
+            .await?;
+
+            let history = bitcoin_wallet.history().await?;
+            let transaction_labels = db.get_bitcoin_transaction_labels().await?;
+            let swap_transactions = transaction_labels
+                .iter()
+                .filter(|labelled| labelled.swap_id.is_some())
+                .map(|labelled| labelled.inner)
+                .collect();
+            let swap_fees = bitcoin_wallet
+                .swap_transaction_fees(&swap_transactions)
+                .await?;
+
+            bitcoin::print_history(&history, &transaction_labels, &swap_fees, json)?;
         }
 
         Command::Balance {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = open_db(data_dir.join("sqlite")).await?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                db,
                 &seed,
                 data_dir.clone(),
                 env_config,
@@ -230,9 +330,11 @@ async fn main() -> Result<()> {
         }
         Command::Resume {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
-            monero_daemon_address,
+            monero_daemon_addresses,
+            monero_daemon_login,
+            monero_wallet_rpc,
             tor_socks5_port,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
@@ -241,34 +343,47 @@ async fn main() -> Result<()> {
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                db.clone(),
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
+            let (monero_wallet, _process) = init_monero_wallet(
+                data_dir,
+                monero_daemon_addresses,
+                monero_daemon_login,
+                monero_wallet_rpc,
+                env_config,
+            )
+            .await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
 
             let seller_peer_id = db.get_peer_id(swap_id).await?;
-            let seller_addresses = db.get_addresses(seller_peer_id).await?;
+            let seller_addresses = cli::known_seller_addresses(db.clone(), seller_peer_id).await?;
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
-            let mut swarm =
-                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
+            let identity = seed.derive_libp2p_identity();
+            let behaviour = cli::Behaviour::new(
+                seller_peer_id,
+                env_config,
+                bitcoin_wallet.clone(),
+                identity.public(),
+                AddressPreference::detect(tor_socks5_port).await,
+            );
+            let mut swarm = swarm::cli(identity, tor_socks5_port, behaviour).await?;
             let our_peer_id = swarm.local_peer_id();
             tracing::debug!(peer_id = %our_peer_id, "Network layer initialized");
 
-            for seller_address in seller_addresses {
+            for (seller_address, health) in seller_addresses {
                 swarm
                     .behaviour_mut()
-                    .add_address(seller_peer_id, seller_address);
+                    .restore_address(seller_address, health);
             }
 
             let (event_loop, event_loop_handle) =
-                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
+                EventLoop::new(swap_id, swarm, seller_peer_id, env_config, db.clone())?;
             let handle = tokio::spawn(event_loop.run());
 
             let monero_receive_address = db.get_monero_address(swap_id).await?;
@@ -294,7 +409,7 @@ async fn main() -> Result<()> {
         }
         Command::Cancel {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
@@ -303,7 +418,8 @@ async fn main() -> Result<()> {
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                db.clone(),
                 &seed,
                 data_dir,
                 env_config,
@@ -316,7 +432,7 @@ async fn main() -> Result<()> {
         }
         Command::Refund {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
@@ -325,7 +441,8 @@ async fn main() -> Result<()> {
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                db.clone(),
                 &seed,
                 data_dir,
                 env_config,
@@ -337,12 +454,21 @@ async fn main() -> Result<()> {
         }
         Command::ListSellers {
             rendezvous_point,
+            dht_bootstrap_nodes,
             namespace,
             tor_socks5_port,
         } => {
-            let rendezvous_node_peer_id = rendezvous_point
-                .extract_peer_id()
-                .context("Rendezvous node address must contain peer ID")?;
+            let rendezvous_point = match rendezvous_point {
+                Some(rendezvous_point) => {
+                    let rendezvous_node_peer_id = rendezvous_point
+                        .extract_peer_id()
+                        .context("Rendezvous node address must contain peer ID")?;
+
+                    Some((rendezvous_node_peer_id, rendezvous_point))
+                }
+                None => None,
+            };
+            let dht_bootstrap_nodes = dht::bootstrap_nodes(dht_bootstrap_nodes)?;
 
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
@@ -350,8 +476,8 @@ async fn main() -> Result<()> {
             let identity = seed.derive_libp2p_identity();
 
             let sellers = list_sellers(
-                rendezvous_node_peer_id,
                 rendezvous_point,
+                dht_bootstrap_nodes,
                 namespace,
                 tor_socks5_port,
                 identity,
@@ -420,15 +546,17 @@ async fn main() -> Result<()> {
             }
         }
         Command::ExportBitcoinWallet {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
             cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
 
+            let db = open_db(data_dir.join("sqlite")).await?;
             let seed = Seed::from_file_or_generate(data_dir.as_path())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                db,
                 &seed,
                 data_dir.clone(),
                 env_config,
@@ -480,17 +608,17 @@ async fn main() -> Result<()> {
 }
 
 async fn init_bitcoin_wallet(
-    electrum_rpc_url: Url,
+    electrum_rpc_urls: Vec<Url>,
+    db: Arc<dyn Database + Send + Sync>,
     seed: &Seed,
     data_dir: PathBuf,
     env_config: Config,
     bitcoin_target_block: usize,
 ) -> Result<bitcoin::Wallet> {
-    let wallet_dir = data_dir.join("wallet");
-
     let wallet = bitcoin::Wallet::new(
-        electrum_rpc_url.clone(),
-        &wallet_dir,
+        electrum_rpc_urls,
+        db,
+        &data_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
         bitcoin_target_block,
@@ -498,6 +626,7 @@ async fn init_bitcoin_wallet(
     .await
     .context("Failed to initialize Bitcoin wallet")?;
 
+    wallet.start_processing_notifications()?;
     wallet.sync().await?;
 
     Ok(wallet)
@@ -505,25 +634,37 @@ async fn init_bitcoin_wallet(
 
 async fn init_monero_wallet(
     data_dir: PathBuf,
-    monero_daemon_address: String,
+    monero_daemon_addresses: Vec<String>,
+    monero_daemon_login: Option<Credentials>,
+    wallet_rpc_binary: monero::WalletRpcBinary,
     env_config: Config,
 ) -> Result<(monero::Wallet, monero::WalletRpcProcess)> {
     let network = env_config.monero_network;
 
     const MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
 
-    let monero_wallet_rpc = monero::WalletRpc::new(data_dir.join("monero")).await?;
+    let monero_wallet_rpc =
+        monero::WalletRpc::new(data_dir.join("monero"), wallet_rpc_binary).await?;
+
+    let daemon = monero::RemoteDaemon::choose(
+        monero_daemon_addresses,
+        Connection::with_login(monero_daemon_login),
+    )
+    .await?;
 
     let monero_wallet_rpc_process = monero_wallet_rpc
-        .run(network, monero_daemon_address.as_str())
+        .run(network, daemon.address().await.as_str(), daemon.login())
         .await?;
 
+    let daemon = daemon.with_wallet_rpc(wallet::Client::new(monero_wallet_rpc_process.endpoint())?);
+
     let monero_wallet = monero::Wallet::open_or_create(
-        monero_wallet_rpc_process.endpoint(),
+        wallet::Client::new(monero_wallet_rpc_process.endpoint())?,
         MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
         env_config,
     )
-    .await?;
+    .await?
+    .with_daemon(daemon);
 
     Ok((monero_wallet, monero_wallet_rpc_process))
 }
@@ -538,9 +679,15 @@ fn qr_code(value: &impl ToString) -> Result<String> {
     Ok(qr_code)
 }
 
+/// Waits until enough Bitcoin are available and returns how much of it to
+/// swap and the fees.
+///
+/// If we subscribed to Alice's quotes, the limits of the latest quote she
+/// pushed to us apply.
 async fn determine_btc_to_swap<FB, TB, FMG, TMG, FS, TS>(
     json: bool,
     bid_quote: impl Future<Output = Result<BidQuote>>,
+    quote_updates: Option<watch::Receiver<Option<BidQuote>>>,
     get_new_address: impl Future<Output = Result<bitcoin::Address>>,
     balance: FB,
     max_giveable_fn: FMG,
@@ -555,7 +702,7 @@ where
     FS: Fn() -> TS,
 {
     tracing::debug!("Requesting quote");
-    let bid_quote = bid_quote.await?;
+    let mut bid_quote = bid_quote.await?;
     tracing::info!(
         price = %bid_quote.price,
         minimum_amount = %bid_quote.min_quantity,
@@ -597,6 +744,7 @@ where
             let new_balance = balance().await?;
             tracing::info!(%new_balance, %max_giveable, "Received Bitcoin");
 
+            update_quote(&mut bid_quote, quote_updates.as_ref());
             if max_giveable < bid_quote.min_quantity {
                 tracing::info!("Deposited amount is less than `min_quantity`");
                 continue;
@@ -609,6 +757,7 @@ where
     let balance = balance().await?;
     let fees = balance - max_giveable;
 
+    update_quote(&mut bid_quote, quote_updates.as_ref());
     let max_accepted = bid_quote.max_quantity;
 
     let btc_swap_amount = min(max_giveable, max_accepted);
@@ -616,6 +765,27 @@ where
     Ok((btc_swap_amount, fees))
 }
 
+/// Replaces `bid_quote` with the latest quote Alice pushed to us, if any.
+fn update_quote(
+    bid_quote: &mut BidQuote,
+    quote_updates: Option<&watch::Receiver<Option<BidQuote>>>,
+) {
+    let latest_quote = match quote_updates.and_then(|updates| *updates.borrow()) {
+        Some(latest_quote) => latest_quote,
+        None => return,
+    };
+
+    if latest_quote != *bid_quote {
+        tracing::info!(
+            price = %latest_quote.price,
+            minimum_amount = %latest_quote.min_quantity,
+            maximum_amount = %latest_quote.max_quantity,
+            "Alice updated her quote",
+        );
+        *bid_quote = latest_quote;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -636,6 +806,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.001)?) },
             || async {
@@ -671,6 +842,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.1001)?) },
             || async {
@@ -695,6 +867,44 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn given_quote_update_while_waiting_for_deposit_then_swaps_new_max_quantity() {
+        let writer = capture_logs(LevelFilter::INFO);
+        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+            Amount::ZERO,
+            Amount::from_btc(0.1).unwrap(),
+        ])));
+        let (_quote_sender, quote_updates) = watch::channel(Some(quote_with_max(0.05)));
+
+        let (amount, fees) = determine_btc_to_swap(
+            true,
+            async { Ok(quote_with_max(0.01)) },
+            Some(quote_updates),
+            get_dummy_address(),
+            || async { Ok(Amount::from_btc(0.1001)?) },
+            || async {
+                let mut result = givable.lock().unwrap();
+                result.give()
+            },
+            || async { Ok(()) },
+        )
+        .await
+        .unwrap();
+
+        let expected_amount = Amount::from_btc(0.05).unwrap();
+        let expected_fees = Amount::from_btc(0.0001).unwrap();
+
+        assert_eq!((amount, fees), (expected_amount, expected_fees));
+        assert_eq!(
+            writer.captured(),
+            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+ INFO swap: Received Bitcoin new_balance=0.10010000 BTC max_giveable=0.10000000 BTC
+ INFO swap: Alice updated her quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.05000000 BTC
+"
+        );
+    }
+
     #[tokio::test]
     async fn given_initial_balance_below_max_quantity_swaps_max_givable() {
         let writer = capture_logs(LevelFilter::INFO);
@@ -706,6 +916,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             async { panic!("should not request new address when initial balance  is > 0") },
             || async { Ok(Amount::from_btc(0.005)?) },
             || async {
@@ -739,6 +950,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             async { panic!("should not request new address when initial balance is > 0") },
             || async { Ok(Amount::from_btc(0.1001)?) },
             || async {
@@ -772,6 +984,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
             || async {
@@ -807,6 +1020,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
             || async {
@@ -847,6 +1061,7 @@ mod tests {
             determine_btc_to_swap(
                 true,
                 async { Ok(quote_with_min(0.1)) },
+                None,
                 get_dummy_address(),
                 || async { Ok(Amount::from_btc(0.0101)?) },
                 || async {
@@ -892,6 +1107,7 @@ mod tests {
             determine_btc_to_swap(
                 true,
                 async { Ok(quote_with_min(0.1)) },
+                None,
                 get_dummy_address(),
                 || async { Ok(Amount::from_btc(0.21)?) },
                 || async {
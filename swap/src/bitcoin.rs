@@ -1,25 +1,34 @@
 pub mod wallet;
 
 mod cancel;
+mod electrum;
+mod label;
 mod lock;
 mod punish;
+mod report;
 mod redeem;
 mod refund;
 mod timelocks;
+mod wallet_state;
 
 pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
+pub use crate::bitcoin::label::{
+    fees_per_swap, label_of_transaction, label_of_utxo, Label, Labelled, SwapLabels,
+};
 pub use crate::bitcoin::lock::TxLock;
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
+pub use crate::bitcoin::report::{print_history, print_utxos};
 pub use crate::bitcoin::refund::TxRefund;
 pub use crate::bitcoin::timelocks::{BlockHeight, ExpiredTimelocks};
+pub use crate::bitcoin::wallet_state::WalletState;
 pub use ::bitcoin::util::amount::Amount;
 pub use ::bitcoin::util::psbt::PartiallySignedTransaction;
 pub use ::bitcoin::{Address, Network, Transaction, Txid};
 pub use ecdsa_fun::adaptor::EncryptedSignature;
 pub use ecdsa_fun::fun::Scalar;
 pub use ecdsa_fun::Signature;
-pub use wallet::Wallet;
+pub use wallet::{CoinControl, HistoryEntry, RawCoinControl, Utxo, Wallet};
 
 #[cfg(test)]
 pub use wallet::WalletBuilder;
@@ -51,6 +60,18 @@ pub enum network {
     Regtest,
 }
 
+/// The network addresses of the given network are parsed as.
+///
+/// Signet reuses the address encoding of testnet, hence addresses parsed from
+/// strings never carry [`Network::Signet`]. Compare networks of addresses
+/// through this function.
+pub fn address_network(network: Network) -> Network {
+    match network {
+        Network::Signet => Network::Testnet,
+        network => network,
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct SecretKey {
     inner: Scalar,
@@ -421,7 +442,7 @@ mod tests {
         let alice_message1 = alice_state1.next_message();
 
         let bob_state1 = bob_state0
-            .receive(&bob_wallet, alice_message1)
+            .receive(&bob_wallet, alice_message1, &CoinControl::default())
             .await
             .unwrap();
         let bob_message2 = bob_state1.next_message();
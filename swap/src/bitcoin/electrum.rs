@@ -0,0 +1,672 @@
+use bdk::blockchain::{noop_progress, Blockchain, Capability, ElectrumBlockchain, Progress};
+use bdk::database::BatchDatabase;
+use bdk::electrum_client::{self, ConfigBuilder, ElectrumApi};
+use bdk::FeeRate;
+use bitcoin::{Transaction, Txid};
+use reqwest::Url;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long we wait for an Electrum server to respond before we consider it
+/// dead.
+const TIMEOUT_SECS: u8 = 10;
+
+/// How many blocks a server's tip may differ from the tip the other servers
+/// agree on before we stop trusting it.
+const MAX_TIP_DIFFERENCE: u32 = 2;
+
+/// How long a server that failed is left out of the cross-checks of the tip.
+const RECENT_FAILURE: Duration = Duration::from_secs(10 * 60);
+
+/// A set of Electrum servers of which we talk to one at a time.
+///
+/// If the active server fails or times out, we mark it as unhealthy and retry
+/// the request on the healthiest of the remaining servers. Connections are
+/// established lazily, i.e. a server that is down on startup is only connected
+/// to once we actually fail over to it.
+pub struct Failover<T> {
+    inner: Mutex<Servers<T>>,
+    connect: Box<dyn Fn(&Url) -> Result<T, electrum_client::Error> + Send + Sync>,
+}
+
+struct Servers<T> {
+    servers: Vec<Server<T>>,
+    active: usize,
+}
+
+struct Server<T> {
+    url: Url,
+    connection: Option<T>,
+    /// The number of failures since the last successful request.
+    failures: u32,
+    last_failure: Option<Instant>,
+}
+
+impl<T> Server<T> {
+    fn failed(&mut self) {
+        self.connection = None;
+        self.failures += 1;
+        self.last_failure = Some(Instant::now());
+    }
+
+    fn failed_recently(&self) -> bool {
+        matches!(self.last_failure, Some(at) if at.elapsed() < RECENT_FAILURE)
+    }
+}
+
+impl<T> Failover<T> {
+    /// Connects to all given servers and checks that they respond.
+    ///
+    /// Fails if none of them is reachable. Unreachable servers are kept around
+    /// and retried if we fail over to them later.
+    pub fn new(
+        urls: Vec<Url>,
+        connect: impl Fn(&Url) -> Result<T, electrum_client::Error> + Send + Sync + 'static,
+        health_check: impl Fn(&T) -> Result<u32, electrum_client::Error>,
+    ) -> anyhow::Result<Self> {
+        if urls.is_empty() {
+            anyhow::bail!("At least one Electrum server is required")
+        }
+
+        let servers = urls
+            .into_iter()
+            .map(|url| {
+                let connection = connect(&url).and_then(|connection| {
+                    let tip = health_check(&connection)?;
+                    tracing::debug!(%url, %tip, "Connected to Electrum server");
+
+                    Ok(connection)
+                });
+
+                match connection {
+                    Ok(connection) => Server {
+                        url,
+                        connection: Some(connection),
+                        failures: 0,
+                        last_failure: None,
+                    },
+                    Err(error) => {
+                        tracing::warn!(%url, "Electrum server is not available: {}", error);
+
+                        Server {
+                            url,
+                            connection: None,
+                            failures: 1,
+                            last_failure: Some(Instant::now()),
+                        }
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let active = servers
+            .iter()
+            .position(|server| server.connection.is_some())
+            .ok_or_else(|| {
+                anyhow::anyhow!("None of the configured Electrum servers is available")
+            })?;
+
+        Ok(Self {
+            inner: Mutex::new(Servers { servers, active }),
+            connect: Box::new(connect),
+        })
+    }
+
+    /// The URL of the server we are currently talking to.
+    pub fn active_url(&self) -> Url {
+        self.lock().active().url.clone()
+    }
+
+    /// Executes the given request against the active server.
+    ///
+    /// Should the server fail, the request is retried once on every other
+    /// server before we give up and return the last error.
+    pub fn call<R, E>(&self, mut request: impl FnMut(&T) -> Result<R, E>) -> Result<R, E>
+    where
+        E: ServerError + From<electrum_client::Error>,
+    {
+        let mut servers = self.lock();
+        let mut attempts = servers.servers.len();
+
+        loop {
+            attempts -= 1;
+
+            let result = match servers.active_connection(&*self.connect) {
+                Ok(connection) => request(connection),
+                Err(error) => Err(E::from(error)),
+            };
+
+            match result {
+                Ok(response) => {
+                    servers.active_mut().failures = 0;
+                    return Ok(response);
+                }
+                Err(error) if !error.is_server_failure() => return Err(error),
+                Err(error) => {
+                    tracing::warn!(
+                        url = %servers.active().url,
+                        "Electrum request failed: {}",
+                        error
+                    );
+                    servers.fail_over();
+
+                    if attempts == 0 {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Compares the tip of the active server with the tips of all other
+    /// servers.
+    ///
+    /// A server that is lagging behind, or claims blocks no one else knows
+    /// about, can't be trusted to tell us about the state of our transactions.
+    /// If the active server deviates from the tip the others agree on by more
+    /// than [`MAX_TIP_DIFFERENCE`] blocks, we switch to one that doesn't.
+    ///
+    /// Every server is asked on a connection of its own, without holding on to
+    /// the servers while waiting for the answers, so requests on the active
+    /// server are not held up by a server that doesn't respond. Servers that
+    /// failed within [`RECENT_FAILURE`] are left out.
+    ///
+    /// Returns `true` if we switched servers.
+    pub fn cross_check_tip(&self, tip: impl Fn(&T) -> Result<u32, electrum_client::Error>) -> bool {
+        let (active, candidates) = {
+            let servers = self.lock();
+
+            if servers.servers.len() < 2 {
+                return false;
+            }
+
+            let candidates = servers
+                .servers
+                .iter()
+                .enumerate()
+                .filter(|(index, server)| *index == servers.active || !server.failed_recently())
+                .map(|(index, server)| (index, server.url.clone()))
+                .collect::<Vec<_>>();
+
+            (servers.active, candidates)
+        };
+
+        let tips = candidates
+            .into_iter()
+            .map(|(index, url)| {
+                let tip = (self.connect)(&url).and_then(|connection| tip(&connection));
+
+                (index, tip.ok())
+            })
+            .collect::<Vec<_>>();
+
+        let mut servers = self.lock();
+
+        for (index, tip) in &tips {
+            if tip.is_none() {
+                servers.servers[*index].failed();
+            }
+        }
+
+        if servers.active != active {
+            // A request failed over while we were asking around.
+            return false;
+        }
+
+        let reference = match reference_tip(tips.iter().filter_map(|(_, tip)| *tip).collect()) {
+            Some(reference) => reference,
+            None => return false,
+        };
+        let deviates = |tip: &Option<u32>| match tip {
+            Some(tip) => reference.max(*tip) - reference.min(*tip) > MAX_TIP_DIFFERENCE,
+            None => true,
+        };
+
+        let active_tip = tips
+            .iter()
+            .find(|(index, _)| *index == active)
+            .and_then(|(_, tip)| *tip);
+        if !deviates(&active_tip) {
+            return false;
+        }
+
+        let replacement = tips
+            .iter()
+            .filter(|(_, tip)| !deviates(tip))
+            .min_by_key(|(index, _)| servers.servers[*index].failures)
+            .map(|(index, _)| *index);
+
+        match replacement {
+            Some(replacement) => {
+                tracing::warn!(
+                    url = %servers.active().url,
+                    tip = ?active_tip,
+                    %reference,
+                    "Electrum server disagrees with the other servers about the tip of the chain"
+                );
+
+                servers.active_mut().failed();
+                servers.switch_to(replacement);
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Servers<T>> {
+        self.inner
+            .lock()
+            .expect("no panic while holding the Electrum servers lock")
+    }
+}
+
+impl<T> Servers<T> {
+    fn active(&self) -> &Server<T> {
+        &self.servers[self.active]
+    }
+
+    fn active_mut(&mut self) -> &mut Server<T> {
+        &mut self.servers[self.active]
+    }
+
+    fn active_connection(
+        &mut self,
+        connect: &dyn Fn(&Url) -> Result<T, electrum_client::Error>,
+    ) -> Result<&T, electrum_client::Error> {
+        let server = &mut self.servers[self.active];
+
+        if server.connection.is_none() {
+            server.connection = Some(connect(&server.url)?);
+        }
+
+        Ok(server
+            .connection
+            .as_ref()
+            .expect("connection was established above"))
+    }
+
+    /// Drops the connection to the active server and switches to the
+    /// healthiest remaining one.
+    fn fail_over(&mut self) {
+        self.active_mut().failed();
+
+        let len = self.servers.len();
+        let next = (1..len)
+            .map(|offset| (self.active + offset) % len)
+            .min_by_key(|index| self.servers[*index].failures);
+
+        if let Some(next) = next {
+            self.switch_to(next);
+        }
+    }
+
+    fn switch_to(&mut self, index: usize) {
+        if index == self.active {
+            return;
+        }
+
+        self.active = index;
+        tracing::info!(url = %self.servers[index].url, "Switched to Electrum server");
+    }
+}
+
+/// The tip we consider to be correct, given the tips reported by all servers.
+///
+/// With three or more servers, we go with the majority. With fewer, we can't
+/// tell a lying server from a lagging one and assume the latter, which is far
+/// more common.
+fn reference_tip(mut tips: Vec<u32>) -> Option<u32> {
+    tips.sort_unstable();
+
+    match tips.len() {
+        0 => None,
+        1 | 2 => tips.last().copied(),
+        len => Some(tips[len / 2]),
+    }
+}
+
+/// Tells apart errors caused by a misbehaving server from errors the server
+/// deliberately responded with.
+pub trait ServerError: fmt::Display {
+    fn is_server_failure(&self) -> bool;
+}
+
+impl ServerError for electrum_client::Error {
+    fn is_server_failure(&self) -> bool {
+        // A protocol error means the server processed our request and rejected it, e.g.
+        // a transaction that is already in the chain. Another server would do the same.
+        !matches!(
+            self,
+            electrum_client::Error::Protocol(_)
+                | electrum_client::Error::AlreadySubscribed(_)
+                | electrum_client::Error::NotSubscribed(_)
+        )
+    }
+}
+
+impl ServerError for bdk::Error {
+    fn is_server_failure(&self) -> bool {
+        match self {
+            bdk::Error::Electrum(error) => error.is_server_failure(),
+            _ => false,
+        }
+    }
+}
+
+impl ServerError for anyhow::Error {
+    fn is_server_failure(&self) -> bool {
+        if let Some(error) = self.downcast_ref::<electrum_client::Error>() {
+            return error.is_server_failure();
+        }
+
+        if let Some(error) = self.downcast_ref::<bdk::Error>() {
+            return error.is_server_failure();
+        }
+
+        false
+    }
+}
+
+/// Connects to the given Electrum server.
+pub fn connect(url: &Url, retries: u8) -> Result<electrum_client::Client, electrum_client::Error> {
+    let config = ConfigBuilder::default()
+        .retry(retries)
+        .timeout(Some(TIMEOUT_SECS))?
+        .build();
+
+    electrum_client::Client::from_config(url.as_str(), config)
+}
+
+/// Subscribes to header notifications and returns the height of the tip.
+///
+/// Drains all pending header notifications so they don't pile up on
+/// connections we only use for cross-checking.
+pub fn tip(electrum: &electrum_client::Client) -> Result<u32, electrum_client::Error> {
+    let mut tip = electrum.block_headers_subscribe()?.height;
+
+    while let Some(header) = electrum.block_headers_pop()? {
+        tip = tip.max(header.height);
+    }
+
+    u32::try_from(tip)
+        .map_err(|_| electrum_client::Error::Message(format!("Invalid block height {}", tip)))
+}
+
+/// The blockchain backend of our BDK wallet, spreading syncing and
+/// broadcasting over several Electrum servers.
+pub struct FailoverBlockchain {
+    servers: Failover<ElectrumBlockchain>,
+}
+
+impl FailoverBlockchain {
+    pub fn new(urls: Vec<Url>, retries: u8) -> anyhow::Result<Self> {
+        let servers = Failover::new(
+            urls,
+            move |url| connect(url, retries).map(ElectrumBlockchain::from),
+            |blockchain| {
+                blockchain
+                    .get_height()
+                    .map_err(|error| electrum_client::Error::Message(error.to_string()))
+            },
+        )?;
+
+        Ok(Self { servers })
+    }
+}
+
+impl Blockchain for FailoverBlockchain {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        vec![
+            Capability::FullHistory,
+            Capability::GetAnyTx,
+            Capability::AccurateFees,
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn setup<D: BatchDatabase, P: 'static + Progress>(
+        &self,
+        database: &mut D,
+        progress_update: P,
+    ) -> Result<(), bdk::Error> {
+        // The progress can only be reported once, retries on other servers go unreported.
+        let mut progress_update = Some(progress_update);
+
+        self.servers
+            .call(|blockchain| match progress_update.take() {
+                Some(progress_update) => blockchain.setup(&mut *database, progress_update),
+                None => blockchain.setup(&mut *database, noop_progress()),
+            })
+    }
+
+    fn sync<D: BatchDatabase, P: 'static + Progress>(
+        &self,
+        database: &mut D,
+        progress_update: P,
+    ) -> Result<(), bdk::Error> {
+        let mut progress_update = Some(progress_update);
+
+        self.servers
+            .call(|blockchain| match progress_update.take() {
+                Some(progress_update) => blockchain.sync(&mut *database, progress_update),
+                None => blockchain.sync(&mut *database, noop_progress()),
+            })
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        self.servers.call(|blockchain| blockchain.get_tx(txid))
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        self.servers.call(|blockchain| blockchain.broadcast(tx))
+    }
+
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        self.servers.call(|blockchain| blockchain.get_height())
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        self.servers
+            .call(|blockchain| blockchain.estimate_fee(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct FakeServer {
+        down: AtomicBool,
+        tip: AtomicU32,
+        connections: AtomicU32,
+    }
+
+    struct FakeConnection(Arc<FakeServer>);
+
+    impl FakeConnection {
+        fn tip(&self) -> Result<u32, electrum_client::Error> {
+            if self.0.down.load(Ordering::SeqCst) {
+                return Err(connection_refused());
+            }
+
+            Ok(self.0.tip.load(Ordering::SeqCst))
+        }
+    }
+
+    fn connection_refused() -> electrum_client::Error {
+        electrum_client::Error::IOError(io::Error::from(io::ErrorKind::ConnectionRefused))
+    }
+
+    fn fake_servers(tips: &[u32]) -> (Vec<Url>, Vec<Arc<FakeServer>>, Failover<FakeConnection>) {
+        let servers = tips
+            .iter()
+            .map(|tip| {
+                Arc::new(FakeServer {
+                    down: AtomicBool::new(false),
+                    tip: AtomicU32::new(*tip),
+                    connections: AtomicU32::new(0),
+                })
+            })
+            .collect::<Vec<_>>();
+        let urls = (0..tips.len())
+            .map(|index| Url::parse(&format!("tcp://electrum-{}:50001", index)).unwrap())
+            .collect::<Vec<_>>();
+
+        let by_url = urls
+            .iter()
+            .cloned()
+            .zip(servers.iter().cloned())
+            .collect::<HashMap<_, _>>();
+        let failover = Failover::new(
+            urls.clone(),
+            move |url| {
+                let server = by_url[url].clone();
+                server.connections.fetch_add(1, Ordering::SeqCst);
+
+                if server.down.load(Ordering::SeqCst) {
+                    return Err(connection_refused());
+                }
+
+                Ok(FakeConnection(server))
+            },
+            FakeConnection::tip,
+        )
+        .unwrap();
+
+        (urls, servers, failover)
+    }
+
+    #[test]
+    fn given_active_server_fails_then_request_is_retried_on_next_server() {
+        let (urls, servers, failover) = fake_servers(&[100, 100]);
+
+        servers[0].down.store(true, Ordering::SeqCst);
+        let tip = failover.call(FakeConnection::tip).unwrap();
+
+        assert_eq!(tip, 100);
+        assert_eq!(failover.active_url(), urls[1]);
+    }
+
+    #[test]
+    fn given_all_servers_fail_then_returns_error() {
+        let (_, servers, failover) = fake_servers(&[100, 100]);
+
+        servers[0].down.store(true, Ordering::SeqCst);
+        servers[1].down.store(true, Ordering::SeqCst);
+
+        assert!(failover.call(FakeConnection::tip).is_err());
+    }
+
+    #[test]
+    fn given_failed_server_recovers_then_it_is_used_again() {
+        let (urls, servers, failover) = fake_servers(&[100, 100]);
+
+        servers[0].down.store(true, Ordering::SeqCst);
+        failover.call(FakeConnection::tip).unwrap();
+        servers[0].down.store(false, Ordering::SeqCst);
+        servers[1].down.store(true, Ordering::SeqCst);
+        failover.call(FakeConnection::tip).unwrap();
+
+        assert_eq!(failover.active_url(), urls[0]);
+    }
+
+    #[test]
+    fn given_primary_is_down_on_startup_then_uses_fallback() {
+        let servers = vec![
+            Url::parse("tcp://electrum-0:50001").unwrap(),
+            Url::parse("tcp://electrum-1:50001").unwrap(),
+        ];
+
+        let failover = Failover::new(
+            servers.clone(),
+            |url| {
+                if url.host_str() == Some("electrum-0") {
+                    return Err(connection_refused());
+                }
+
+                Ok(FakeConnection(Arc::new(FakeServer::default())))
+            },
+            FakeConnection::tip,
+        )
+        .unwrap();
+
+        assert_eq!(failover.active_url(), servers[1]);
+    }
+
+    #[test]
+    fn given_no_server_is_available_on_startup_then_fails() {
+        let result = Failover::<FakeConnection>::new(
+            vec![Url::parse("tcp://electrum-0:50001").unwrap()],
+            |_| Err(connection_refused()),
+            FakeConnection::tip,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_active_server_lags_behind_then_switches_to_up_to_date_server() {
+        let (urls, _, failover) = fake_servers(&[90, 100, 100]);
+
+        let switched = failover.cross_check_tip(FakeConnection::tip);
+
+        assert!(switched);
+        assert_eq!(failover.active_url(), urls[1]);
+    }
+
+    #[test]
+    fn given_active_server_claims_unknown_blocks_then_switches_to_majority() {
+        let (urls, _, failover) = fake_servers(&[150, 100, 101]);
+
+        let switched = failover.cross_check_tip(FakeConnection::tip);
+
+        assert!(switched);
+        assert_eq!(failover.active_url(), urls[1]);
+    }
+
+    #[test]
+    fn given_tips_are_close_then_keeps_active_server() {
+        let (urls, _, failover) = fake_servers(&[99, 100, 101]);
+
+        let switched = failover.cross_check_tip(FakeConnection::tip);
+
+        assert!(!switched);
+        assert_eq!(failover.active_url(), urls[0]);
+    }
+
+    #[test]
+    fn given_server_failed_recently_then_cross_check_skips_it() {
+        let (urls, servers, failover) = fake_servers(&[100, 100, 100]);
+
+        servers[2].down.store(true, Ordering::SeqCst);
+        failover.cross_check_tip(FakeConnection::tip);
+        servers[2].down.store(false, Ordering::SeqCst);
+        let connections = servers[2].connections.load(Ordering::SeqCst);
+        let switched = failover.cross_check_tip(FakeConnection::tip);
+
+        assert!(!switched);
+        assert_eq!(failover.active_url(), urls[0]);
+        assert_eq!(servers[2].connections.load(Ordering::SeqCst), connections);
+    }
+
+    #[test]
+    fn protocol_errors_are_not_server_failures() {
+        let protocol_error = electrum_client::Error::Protocol(serde_json::Value::Null);
+        let io_error = connection_refused();
+
+        assert!(!protocol_error.is_server_failure());
+        assert!(io_error.is_server_failure());
+        assert!(!anyhow::Error::from(protocol_error).is_server_failure());
+        assert!(anyhow::Error::from(io_error).is_server_failure());
+    }
+}
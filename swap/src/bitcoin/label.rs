@@ -0,0 +1,180 @@
+use crate::bitcoin::wallet::Utxo;
+use crate::bitcoin::{Address, Amount, Txid};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// The purpose of an address or a transaction of the internal Bitcoin wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, Serialize)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Label {
+    Lock,
+    Cancel,
+    Redeem,
+    Refund,
+    Punish,
+    Withdraw,
+}
+
+/// A label together with the swap it belongs to.
+///
+/// Withdrawals are not part of a swap and are therefore recorded without a
+/// swap id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Labelled<T> {
+    pub inner: T,
+    pub label: Label,
+    pub swap_id: Option<Uuid>,
+}
+
+/// The addresses and transactions of a swap that involve our wallet.
+///
+/// All transactions of a swap are known once the swap setup is completed
+/// because their ids do not depend on the witness data.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SwapLabels {
+    pub addresses: Vec<(Address, Label)>,
+    pub transactions: Vec<(Txid, Label)>,
+}
+
+/// Finds the label of an unspent output of our wallet.
+///
+/// The label of the transaction that created the output takes precedence over
+/// the label of the address it pays to.
+pub fn label_of_utxo(
+    utxo: &Utxo,
+    address_labels: &[Labelled<Address>],
+    transaction_labels: &[Labelled<Txid>],
+) -> Option<(Label, Option<Uuid>)> {
+    label_of_transaction(utxo.outpoint.txid, transaction_labels).or_else(|| {
+        address_labels
+            .iter()
+            .find(|labelled| Some(&labelled.inner) == utxo.address.as_ref())
+            .map(|labelled| (labelled.label, labelled.swap_id))
+    })
+}
+
+/// Finds the label of a transaction of our wallet.
+pub fn label_of_transaction(
+    txid: Txid,
+    transaction_labels: &[Labelled<Txid>],
+) -> Option<(Label, Option<Uuid>)> {
+    transaction_labels
+        .iter()
+        .find(|labelled| labelled.inner == txid)
+        .map(|labelled| (labelled.label, labelled.swap_id))
+}
+
+/// Sums up the fees of the transactions of each swap.
+pub fn fees_per_swap(
+    transaction_fees: &BTreeMap<Txid, Amount>,
+    transaction_labels: &[Labelled<Txid>],
+) -> BTreeMap<Uuid, Amount> {
+    let mut fees = BTreeMap::new();
+
+    for (txid, fee) in transaction_fees {
+        if let Some((_, Some(swap_id))) = label_of_transaction(*txid, transaction_labels) {
+            *fees.entry(swap_id).or_insert(Amount::ZERO) += *fee;
+        }
+    }
+
+    fees
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::hashes::Hash;
+    use ::bitcoin::OutPoint;
+    use std::str::FromStr;
+
+    #[test]
+    fn label_roundtrips_through_string() {
+        for label in [
+            Label::Lock,
+            Label::Cancel,
+            Label::Redeem,
+            Label::Refund,
+            Label::Punish,
+            Label::Withdraw,
+        ] {
+            assert_eq!(Label::from_str(&label.to_string()).unwrap(), label);
+        }
+    }
+
+    #[test]
+    fn transaction_label_takes_precedence_over_address_label() {
+        let swap_id = Uuid::new_v4();
+        let address = Address::from_str("bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw").unwrap();
+        let txid =
+            Txid::from_str("0101010101010101010101010101010101010101010101010101010101010101")
+                .unwrap();
+        let utxo = Utxo {
+            outpoint: OutPoint::new(txid, 1),
+            amount: Amount::from_sat(10_000),
+            address: Some(address.clone()),
+            confirmed: true,
+        };
+        let address_labels = vec![Labelled {
+            inner: address,
+            label: Label::Refund,
+            swap_id: Some(swap_id),
+        }];
+        let transaction_labels = vec![Labelled {
+            inner: txid,
+            label: Label::Lock,
+            swap_id: Some(swap_id),
+        }];
+
+        assert_eq!(
+            label_of_utxo(&utxo, &address_labels, &transaction_labels),
+            Some((Label::Lock, Some(swap_id)))
+        );
+        assert_eq!(
+            label_of_utxo(&utxo, &address_labels, &[]),
+            Some((Label::Refund, Some(swap_id)))
+        );
+        assert_eq!(label_of_utxo(&utxo, &[], &[]), None);
+    }
+
+    #[test]
+    fn sums_up_fees_paid_per_swap() {
+        let swap_id = Uuid::new_v4();
+        let txid = |byte: u8| Txid::from_slice(&[byte; 32]).unwrap();
+        let transaction_fees = vec![
+            (txid(1), Amount::from_sat(300)),
+            (txid(2), Amount::from_sat(200)),
+            (txid(4), Amount::from_sat(1_000)),
+        ]
+        .into_iter()
+        .collect();
+        let transaction_labels = vec![
+            Labelled {
+                inner: txid(1),
+                label: Label::Lock,
+                swap_id: Some(swap_id),
+            },
+            Labelled {
+                inner: txid(2),
+                label: Label::Refund,
+                swap_id: Some(swap_id),
+            },
+            Labelled {
+                inner: txid(3),
+                label: Label::Redeem,
+                swap_id: Some(swap_id),
+            },
+            Labelled {
+                inner: txid(4),
+                label: Label::Withdraw,
+                swap_id: None,
+            },
+        ];
+
+        let fees = fees_per_swap(&transaction_fees, &transaction_labels);
+
+        assert_eq!(fees.len(), 1);
+        assert_eq!(fees[&swap_id], Amount::from_sat(500));
+    }
+}
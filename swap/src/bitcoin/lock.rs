@@ -1,4 +1,4 @@
-use crate::bitcoin::wallet::{EstimateFeeRate, Watchable};
+use crate::bitcoin::wallet::{CoinControl, EstimateFeeRate, Watchable};
 use crate::bitcoin::{
     build_shared_output_descriptor, Address, Amount, PublicKey, Transaction, Wallet,
 };
@@ -25,6 +25,7 @@ impl TxLock {
         A: PublicKey,
         B: PublicKey,
         change: bitcoin::Address,
+        coin_control: &CoinControl,
     ) -> Result<Self>
     where
         C: EstimateFeeRate,
@@ -36,7 +37,7 @@ impl TxLock {
             .expect("can derive address from descriptor");
 
         let psbt = wallet
-            .send_to_address(address, amount, Some(change))
+            .send_to_address(address, amount, Some(change), coin_control)
             .await?;
 
         Ok(Self {
@@ -262,7 +263,7 @@ mod tests {
         amount: Amount,
     ) -> PartiallySignedTransaction {
         let change = wallet.new_address().await.unwrap();
-        TxLock::new(&wallet, amount, A, B, change)
+        TxLock::new(&wallet, amount, A, B, change, &CoinControl::default())
             .await
             .unwrap()
             .into()
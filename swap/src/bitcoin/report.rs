@@ -0,0 +1,205 @@
+//! Renders the wallet's unspent outputs and history for the `list-utxos` and
+//! `bitcoin-history` commands of both binaries, either as a table or as JSON.
+
+use crate::bitcoin::label::{fees_per_swap, label_of_transaction, label_of_utxo, Label, Labelled};
+use crate::bitcoin::wallet::{HistoryEntry, Utxo};
+use crate::bitcoin::{Address, Amount, Txid};
+use anyhow::Result;
+use comfy_table::Table;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+struct UtxoRow {
+    outpoint: String,
+    amount_sat: u64,
+    address: Option<Address>,
+    confirmed: bool,
+    label: Option<Label>,
+    swap_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+struct History {
+    transactions: Vec<HistoryRow>,
+    fees_per_swap: Vec<SwapFees>,
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryRow {
+    txid: Txid,
+    incoming: bool,
+    received_sat: u64,
+    sent_sat: u64,
+    fee_sat: Option<u64>,
+    confirmations: u32,
+    timestamp: Option<u64>,
+    label: Option<Label>,
+    swap_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+struct SwapFees {
+    swap_id: Uuid,
+    fees_sat: u64,
+}
+
+/// Prints the unspent outputs of the wallet and the swap they belong to.
+pub fn print_utxos(
+    utxos: Vec<Utxo>,
+    address_labels: &[Labelled<Address>],
+    transaction_labels: &[Labelled<Txid>],
+    json: bool,
+) -> Result<()> {
+    let utxos = utxos
+        .into_iter()
+        .map(|utxo| {
+            let (label, swap_id) = label_of_utxo(&utxo, address_labels, transaction_labels)
+                .map_or((None, None), |(label, swap_id)| (Some(label), swap_id));
+
+            UtxoRow {
+                outpoint: utxo.outpoint.to_string(),
+                amount_sat: utxo.amount.as_sat(),
+                address: utxo.address,
+                confirmed: utxo.confirmed,
+                label,
+                swap_id,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if json {
+        println!("{}", serde_json::to_string(&utxos)?);
+
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+
+    table.set_header(vec![
+        "OUTPOINT",
+        "AMOUNT",
+        "ADDRESS",
+        "CONFIRMED",
+        "LABEL",
+        "SWAP ID",
+    ]);
+
+    for row in utxos {
+        table.add_row(vec![
+            row.outpoint,
+            Amount::from_sat(row.amount_sat).to_string(),
+            row.address
+                .map(|address| address.to_string())
+                .unwrap_or_default(),
+            row.confirmed.to_string(),
+            row.label.map(|label| label.to_string()).unwrap_or_default(),
+            row.swap_id
+                .map(|swap_id| swap_id.to_string())
+                .unwrap_or_default(),
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
+
+/// Prints the transactions of the wallet and the fees paid for each swap.
+///
+/// `swap_fees` are the fees of the published swap transactions, which are
+/// shown instead of the fee bdk reports if it does not know one.
+pub fn print_history(
+    history: &[HistoryEntry],
+    transaction_labels: &[Labelled<Txid>],
+    swap_fees: &BTreeMap<Txid, Amount>,
+    json: bool,
+) -> Result<()> {
+    let transactions = history
+        .iter()
+        .map(|entry| {
+            let (label, swap_id) = label_of_transaction(entry.txid, transaction_labels)
+                .map_or((None, None), |(label, swap_id)| (Some(label), swap_id));
+
+            HistoryRow {
+                txid: entry.txid,
+                incoming: entry.is_incoming(),
+                received_sat: entry.received.as_sat(),
+                sent_sat: entry.sent.as_sat(),
+                fee_sat: entry
+                    .fee
+                    .or_else(|| swap_fees.get(&entry.txid).copied())
+                    .map(Amount::as_sat),
+                confirmations: entry.confirmations,
+                timestamp: entry.timestamp,
+                label,
+                swap_id,
+            }
+        })
+        .collect::<Vec<_>>();
+    let fees_per_swap = fees_per_swap(swap_fees, transaction_labels)
+        .into_iter()
+        .map(|(swap_id, fees)| SwapFees {
+            swap_id,
+            fees_sat: fees.as_sat(),
+        })
+        .collect::<Vec<_>>();
+
+    if json {
+        let history = History {
+            transactions,
+            fees_per_swap,
+        };
+        println!("{}", serde_json::to_string(&history)?);
+
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+
+    table.set_header(vec![
+        "TXID",
+        "DIRECTION",
+        "RECEIVED",
+        "SENT",
+        "FEE",
+        "CONFIRMATIONS",
+        "LABEL",
+        "SWAP ID",
+    ]);
+
+    for row in transactions {
+        table.add_row(vec![
+            row.txid.to_string(),
+            if row.incoming { "in" } else { "out" }.to_string(),
+            Amount::from_sat(row.received_sat).to_string(),
+            Amount::from_sat(row.sent_sat).to_string(),
+            row.fee_sat
+                .map(|fee| Amount::from_sat(fee).to_string())
+                .unwrap_or_default(),
+            row.confirmations.to_string(),
+            row.label.map(|label| label.to_string()).unwrap_or_default(),
+            row.swap_id
+                .map(|swap_id| swap_id.to_string())
+                .unwrap_or_default(),
+        ]);
+    }
+
+    println!("{}", table);
+
+    let mut fees_table = Table::new();
+
+    fees_table.set_header(vec!["SWAP ID", "FEES"]);
+
+    for fees in fees_per_swap {
+        fees_table.add_row(vec![
+            fees.swap_id.to_string(),
+            Amount::from_sat(fees.fees_sat).to_string(),
+        ]);
+    }
+
+    println!("{}", fees_table);
+
+    Ok(())
+}
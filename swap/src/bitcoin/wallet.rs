@@ -1,87 +1,125 @@
+use crate::bitcoin::electrum::{self, Failover, FailoverBlockchain};
 use crate::bitcoin::timelocks::BlockHeight;
-use crate::bitcoin::{Address, Amount, Transaction};
+use crate::bitcoin::wallet_state::{self, WalletLock};
+use crate::bitcoin::{address_network, Address, Amount, Transaction};
+use crate::database::BitcoinWalletDatabase;
 use crate::env;
+use crate::protocol::Database;
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::Txid;
 use anyhow::{bail, Context, Result};
-use bdk::blockchain::{noop_progress, Blockchain, ElectrumBlockchain};
+use bdk::blockchain::{noop_progress, Blockchain};
 use bdk::database::BatchDatabase;
 use bdk::descriptor::Segwitv0;
-use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
+use bdk::electrum_client::{ElectrumApi, GetHistoryRes, HeaderNotification};
 use bdk::keys::DerivableKey;
 use bdk::wallet::export::WalletExport;
 use bdk::wallet::AddressIndex;
 use bdk::{FeeRate, KeychainKind, SignOptions};
-use bitcoin::{Network, Script};
+use bitcoin::{BlockHash, Network, OutPoint, Script};
 use reqwest::Url;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 use tokio::sync::{watch, Mutex};
 
-const SLED_TREE_NAME: &str = "default_tree";
-
 /// Assuming we add a spread of 3% we don't want to pay more than 3% of the
 /// amount for tx fees.
 const MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
 const MAX_ABSOLUTE_TX_FEE: Decimal = dec!(100_000);
 const DUST_AMOUNT: u64 = 546;
 
-pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
+/// How often we check the Electrum connection for pushed notifications.
+const NOTIFICATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often we compare the tip of the active Electrum server against the
+/// other configured servers.
+const TIP_CROSS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Blocks this deep in the chain are not expected to be reorganized anymore,
+/// so we stop checking whether the ones of our watched transactions were
+/// replaced.
+const REORG_SAFE_DEPTH: u32 = 100;
+
+pub struct Wallet<B = FailoverBlockchain, D = BitcoinWalletDatabase, C = Client> {
     client: Arc<Mutex<C>>,
     wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
+    _lock: Option<WalletLock>,
     finality_confirmations: u32,
     network: Network,
     target_block: usize,
 }
 
 impl Wallet {
+    /// Creates a wallet that talks to the first of the given Electrum servers
+    /// that is available and fails over to the others.
+    ///
+    /// The wallet is stored in the given database and locked in `data_dir`
+    /// for as long as it is open. A sled database left in `data_dir` by an
+    /// earlier version is migrated on first use.
     pub async fn new(
-        electrum_rpc_url: Url,
-        wallet_dir: &Path,
+        electrum_rpc_urls: Vec<Url>,
+        db: Arc<dyn Database + Send + Sync>,
+        data_dir: &Path,
         key: impl DerivableKey<Segwitv0> + Clone,
         env_config: env::Config,
         target_block: usize,
     ) -> Result<Self> {
-        let config = bdk::electrum_client::ConfigBuilder::default()
-            .retry(5)
-            .build();
-        let client = bdk::electrum_client::Client::from_config(electrum_rpc_url.as_str(), config)
+        // With fallback servers available, moving on is quicker than retrying a flaky one.
+        let retries = if electrum_rpc_urls.len() > 1 { 1 } else { 5 };
+
+        let blockchain = FailoverBlockchain::new(electrum_rpc_urls.clone(), retries)
             .context("Failed to initialize Electrum RPC client")?;
 
-        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+        let lock = WalletLock::acquire(data_dir)?;
+        let database = wallet_state::load_or_migrate(db.as_ref(), &data_dir.join("wallet")).await?;
 
         let wallet = bdk::Wallet::new(
             bdk::template::Bip84(key.clone(), KeychainKind::External),
             Some(bdk::template::Bip84(key, KeychainKind::Internal)),
             env_config.bitcoin_network,
-            db,
-            ElectrumBlockchain::from(client),
+            database,
+            blockchain,
         )?;
 
-        let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
+        let electrum = Failover::new(
+            electrum_rpc_urls,
+            move |url| electrum::connect(url, retries),
+            electrum::tip,
+        )
+        .context("Failed to initialize Electrum RPC client")?;
 
         let network = wallet.network();
 
+        let client = Arc::new(Mutex::new(Client::new(
+            electrum,
+            env_config.bitcoin_sync_interval(),
+        )?));
+
         Ok(Self {
-            client: Arc::new(Mutex::new(Client::new(
-                electrum,
-                env_config.bitcoin_sync_interval(),
-            )?)),
+            client,
             wallet: Arc::new(Mutex::new(wallet)),
+            _lock: Some(lock),
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
             target_block,
         })
     }
 
+    /// Starts processing the notifications the Electrum server pushes to us,
+    /// so subscriptions learn about new blocks and transactions without
+    /// waiting for the next poll.
+    pub fn start_processing_notifications(&self) -> Result<()> {
+        spawn_notification_processor(Arc::downgrade(&self.client))
+    }
+
     /// Broadcast the given transaction to the network and emit a log statement
     /// if done so successfully.
     ///
@@ -112,6 +150,44 @@ impl Wallet {
         Ok((txid, subscription))
     }
 
+    /// Waits until the given transaction, which must have been published
+    /// already, meets the configured finality confirmations.
+    ///
+    /// In case the transaction is affected by a chain reorganization, we
+    /// publish it again because it might not have made it back into the
+    /// mempool.
+    pub async fn ensure_final(&self, transaction: Transaction, kind: &str) -> Result<()> {
+        let txid = transaction.txid();
+        let subscription = self
+            .subscribe_to((txid, transaction.output[0].script_pubkey.clone()))
+            .await;
+
+        loop {
+            tokio::select! {
+                result = subscription.wait_until_final() => return result,
+                result = subscription.wait_until_reorged() => {
+                    result?;
+                    self.republish(transaction.clone(), kind).await;
+                }
+            }
+        }
+    }
+
+    /// Publishes a transaction again after it lost its confirmations to a
+    /// chain reorganization.
+    ///
+    /// Failing to publish is not an error, the transaction may well have made
+    /// it back into the mempool on its own.
+    pub async fn republish(&self, transaction: Transaction, kind: &str) {
+        let txid = transaction.txid();
+
+        tracing::warn!(%txid, %kind, "Bitcoin transaction was reorged, publishing it again");
+
+        if let Err(error) = self.wallet.lock().await.broadcast(transaction) {
+            tracing::debug!(%txid, "Failed to re-publish Bitcoin transaction: {:#}", error);
+        }
+    }
+
     pub async fn get_raw_transaction(&self, txid: Txid) -> Result<Transaction> {
         self.get_tx(txid)
             .await?
@@ -129,10 +205,10 @@ impl Wallet {
         let txid = tx.id();
         let script = tx.script();
 
-        let sub = self
-            .client
-            .lock()
-            .await
+        let mut client = self.client.lock().await;
+        let mut updates = client.updates.clone();
+
+        let sub = client
             .subscriptions
             .entry((txid, script.clone()))
             .or_insert_with(|| {
@@ -143,7 +219,10 @@ impl Wallet {
                     let mut last_status = None;
 
                     loop {
-                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        // React as soon as the Electrum server pushes an update but also
+                        // re-check periodically in case a notification got lost.
+                        let _ =
+                            tokio::time::timeout(Duration::from_secs(5), updates.changed()).await;
 
                         let new_status = match client.lock().await.status_of_script(&tx) {
                             Ok(new_status) => new_status,
@@ -190,6 +269,171 @@ impl Wallet {
             Err(err_msg) => Err(anyhow::Error::msg(err_msg)),
         }
     }
+
+    /// Lists the transactions of this wallet, unconfirmed and most recent
+    /// ones first.
+    pub async fn history(&self) -> Result<Vec<HistoryEntry>> {
+        let latest_block = {
+            let mut client = self.client.lock().await;
+            client.update_state()?;
+            u32::from(client.latest_block_height)
+        };
+
+        let mut transactions = self
+            .wallet
+            .lock()
+            .await
+            .list_transactions(false)
+            .context("Failed to list transactions of Bitcoin wallet")?;
+        transactions.sort_by_key(|tx| {
+            std::cmp::Reverse(
+                tx.confirmation_time
+                    .as_ref()
+                    .map_or(u32::MAX, |time| time.height),
+            )
+        });
+
+        let history = transactions
+            .into_iter()
+            .map(|tx| HistoryEntry {
+                txid: tx.txid,
+                received: Amount::from_sat(tx.received),
+                sent: Amount::from_sat(tx.sent),
+                // Fees of incoming transactions are paid by the sender.
+                fee: match tx.sent {
+                    0 => None,
+                    _ => tx.fee.map(Amount::from_sat),
+                },
+                confirmations: tx.confirmation_time.as_ref().map_or(0, |time| {
+                    Confirmed::from_inclusion_and_latest_block(time.height, latest_block)
+                        .confirmations()
+                }),
+                timestamp: tx.confirmation_time.map(|time| time.timestamp),
+            })
+            .collect();
+
+        Ok(history)
+    }
+
+    /// The fees of the published transactions among `swap_transactions`.
+    ///
+    /// bdk only knows the fee of a transaction if it spends outputs of our
+    /// wallet. Swap transactions spend the shared lock output, so their fees
+    /// are computed from the outputs they spend instead. A cancel transaction
+    /// does not involve our wallet at all and is found through the refund or
+    /// punish transaction spending it.
+    pub async fn swap_transaction_fees(
+        &self,
+        swap_transactions: &HashSet<Txid>,
+    ) -> Result<BTreeMap<Txid, Amount>> {
+        let mut pending = self
+            .wallet
+            .lock()
+            .await
+            .list_transactions(true)
+            .context("Failed to list transactions of Bitcoin wallet")?
+            .into_iter()
+            .filter(|tx| swap_transactions.contains(&tx.txid))
+            .filter_map(|tx| tx.transaction)
+            .collect::<Vec<_>>();
+
+        let mut fees = BTreeMap::new();
+        while let Some(tx) = pending.pop() {
+            let txid = tx.txid();
+            if fees.contains_key(&txid) {
+                continue;
+            }
+
+            let mut spent = 0;
+            for input in &tx.input {
+                let outpoint = input.previous_output;
+                let previous = self.get_raw_transaction(outpoint.txid).await?;
+                let output = previous
+                    .output
+                    .get(outpoint.vout as usize)
+                    .with_context(|| {
+                        format!("Transaction {} spends unknown output {}", txid, outpoint)
+                    })?;
+                spent += output.value;
+
+                if swap_transactions.contains(&outpoint.txid) {
+                    pending.push(previous);
+                }
+            }
+
+            let created = tx.output.iter().map(|output| output.value).sum::<u64>();
+            let fee = spent
+                .checked_sub(created)
+                .with_context(|| format!("Transaction {} creates more than it spends", txid))?;
+            fees.insert(txid, Amount::from_sat(fee));
+        }
+
+        Ok(fees)
+    }
+}
+
+/// Processes the notifications the Electrum server pushes to us on a thread of
+/// its own until the client is dropped.
+///
+/// Talking to the Electrum server blocks, which must not happen on the async
+/// runtime.
+fn spawn_notification_processor(client: Weak<Mutex<Client>>) -> Result<()> {
+    std::thread::Builder::new()
+        .name("electrum-notifications".to_owned())
+        .spawn(move || process_notifications(client))
+        .context("Failed to spawn thread for Electrum notifications")?;
+
+    Ok(())
+}
+
+fn process_notifications(client: Weak<Mutex<Client>>) {
+    let mut last_tip_cross_check = Instant::now();
+
+    loop {
+        std::thread::sleep(NOTIFICATION_POLL_INTERVAL);
+
+        let client = match client.upgrade() {
+            Some(client) => client,
+            None => return,
+        };
+
+        let servers = {
+            let mut client = client.blocking_lock();
+
+            match client.process_notifications() {
+                Ok(true) => {
+                    let _ = client.updates_sender.send(());
+                }
+                Ok(false) => {}
+                Err(error) => {
+                    tracing::debug!("Failed to process Electrum notifications: {:#}", error);
+                }
+            }
+
+            client.electrum.clone()
+        };
+
+        if last_tip_cross_check.elapsed() >= TIP_CROSS_CHECK_INTERVAL {
+            last_tip_cross_check = Instant::now();
+            cross_check_tip(&servers);
+        }
+    }
+}
+
+/// Makes sure the Electrum server we talk to agrees with the other configured
+/// servers on the tip of the chain.
+///
+/// Asking the other servers can take a while, which is why this runs without
+/// holding the lock on the [`Client`].
+fn cross_check_tip(servers: &Failover<bdk::electrum_client::Client>) {
+    if servers.cross_check_tip(electrum::tip) {
+        // The new server knows nothing about our subscriptions yet, the next poll
+        // notices that and renews them.
+        tracing::debug!(
+            url = %servers.active_url(),
+            "Switched Electrum server after cross-checking the tip"
+        );
+    }
 }
 
 fn print_status_change(txid: Txid, old: Option<ScriptStatus>, new: ScriptStatus) -> ScriptStatus {
@@ -197,6 +441,11 @@ fn print_status_change(txid: Txid, old: Option<ScriptStatus>, new: ScriptStatus)
         (None, new_status) => {
             tracing::debug!(%txid, status = %new_status, "Found relevant Bitcoin transaction");
         }
+        (Some(old_status), new_status)
+            if new_status.confirmations() < old_status.confirmations() =>
+        {
+            tracing::warn!(%txid, %new_status, %old_status, "Bitcoin transaction was reorged");
+        }
         (Some(old_status), new_status) if old_status != new_status => {
             tracing::debug!(%txid, %new_status, %old_status, "Bitcoin transaction status changed");
         }
@@ -255,6 +504,24 @@ impl Subscription {
             .await
     }
 
+    /// Waits until the transaction loses confirmations it already had, i.e.
+    /// it was affected by a chain reorganization.
+    pub async fn wait_until_reorged(&self) -> Result<()> {
+        let mut seen_confirmations = 0;
+
+        self.wait_until(|status| {
+            let confirmations = status.confirmations();
+
+            if confirmations < seen_confirmations {
+                return true;
+            }
+
+            seen_confirmations = confirmations;
+            false
+        })
+        .await
+    }
+
     async fn wait_until(&self, mut predicate: impl FnMut(&ScriptStatus) -> bool) -> Result<()> {
         let mut receiver = self.receiver.clone();
 
@@ -331,6 +598,32 @@ where
         Ok(Amount::from_sat(fees))
     }
 
+    /// Lists the unspent outputs of this wallet.
+    pub async fn list_unspent(&self) -> Result<Vec<Utxo>> {
+        let wallet = self.wallet.lock().await;
+
+        let confirmed_txids = wallet
+            .list_transactions(false)?
+            .into_iter()
+            .filter(|tx| tx.confirmation_time.is_some())
+            .map(|tx| tx.txid)
+            .collect::<HashSet<_>>();
+
+        let utxos = wallet
+            .list_unspent()
+            .context("Failed to list unspent outputs of Bitcoin wallet")?
+            .into_iter()
+            .map(|utxo| Utxo {
+                outpoint: utxo.outpoint,
+                amount: Amount::from_sat(utxo.txout.value),
+                address: Address::from_script(&utxo.txout.script_pubkey, self.network),
+                confirmed: confirmed_txids.contains(&utxo.outpoint.txid),
+            })
+            .collect();
+
+        Ok(utxos)
+    }
+
     /// Builds a partially signed transaction
     ///
     /// Ensures that the address script is at output index `0`
@@ -340,13 +633,14 @@ where
         address: Address,
         amount: Amount,
         change_override: Option<Address>,
+        coin_control: &CoinControl,
     ) -> Result<PartiallySignedTransaction> {
-        if self.network != address.network {
+        if address_network(self.network) != address_network(address.network) {
             bail!("Cannot build PSBT because network of given address is {} but wallet is on network {}", address.network, self.network);
         }
 
         if let Some(change) = change_override.as_ref() {
-            if self.network != change.network {
+            if address_network(self.network) != address_network(change.network) {
                 bail!("Cannot build PSBT because network of given address is {} but wallet is on network {}", change.network, self.network);
             }
         }
@@ -359,6 +653,11 @@ where
         let mut tx_builder = wallet.build_tx();
         tx_builder.add_recipient(script.clone(), amount.as_sat());
         tx_builder.fee_rate(fee_rate);
+        if !coin_control.include.is_empty() {
+            tx_builder.add_utxos(&coin_control.include)?;
+            tx_builder.manually_selected_only();
+        }
+        tx_builder.unspendable(coin_control.exclude.clone());
         let (psbt, _details) = tx_builder.finish()?;
         let mut psbt: PartiallySignedTransaction = psbt;
 
@@ -395,8 +694,13 @@ where
     ///
     /// We define this as the maximum amount we can pay to a single output,
     /// already accounting for the fees we need to spend to get the
-    /// transaction confirmed.
-    pub async fn max_giveable(&self, locking_script_size: usize) -> Result<Amount> {
+    /// transaction confirmed. Only outputs allowed by the given coin control
+    /// are taken into account.
+    pub async fn max_giveable(
+        &self,
+        locking_script_size: usize,
+        coin_control: &CoinControl,
+    ) -> Result<Amount> {
         let wallet = self.wallet.lock().await;
         let balance = wallet.get_balance()?;
         if balance < DUST_AMOUNT {
@@ -417,6 +721,11 @@ where
         tx_builder.drain_to(dummy_script);
         tx_builder.fee_rate(fee_rate);
         tx_builder.drain_wallet();
+        if !coin_control.include.is_empty() {
+            tx_builder.add_utxos(&coin_control.include)?;
+            tx_builder.manually_selected_only();
+        }
+        tx_builder.unspendable(coin_control.exclude.clone());
 
         let response = tx_builder.finish();
         match response {
@@ -622,12 +931,11 @@ impl WalletBuilder {
     }
 
     pub fn build(self) -> Wallet<(), bdk::database::MemoryDatabase, StaticFeeRate> {
-        use bdk::database::MemoryDatabase;
         use bdk::testutils;
 
         let descriptors = testutils!(@descriptors (&format!("wpkh({}/*)", self.key)));
 
-        let mut database = MemoryDatabase::new();
+        let mut database = bdk::database::MemoryDatabase::new();
 
         for index in 0..self.num_utxos {
             bdk::populate_test_db!(
@@ -648,6 +956,7 @@ impl WalletBuilder {
                 min_relay_fee: bitcoin::Amount::from_sat(self.min_relay_fee_sats),
             })),
             wallet: Arc::new(Mutex::new(wallet)),
+            _lock: None,
             finality_confirmations: 1,
             network: Network::Regtest,
             target_block: 1,
@@ -655,6 +964,73 @@ impl WalletBuilder {
     }
 }
 
+/// Restricts the outputs of the wallet that may be spent by a transaction.
+///
+/// This allows users to avoid mixing coins from different sources into a
+/// single transaction.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoinControl {
+    /// If not empty, only these outputs are spent.
+    pub include: Vec<OutPoint>,
+    /// These outputs are never spent.
+    pub exclude: Vec<OutPoint>,
+}
+
+/// The command line arguments of both binaries for [`CoinControl`].
+#[derive(structopt::StructOpt, Debug)]
+pub struct RawCoinControl {
+    #[structopt(
+        long = "utxo",
+        number_of_values = 1,
+        help = "Only spend the given unspent output, formatted as <txid>:<vout>. Repeat to spend several outputs. Use list-utxos to see the outputs of the wallet."
+    )]
+    pub include: Vec<OutPoint>,
+
+    #[structopt(
+        long = "exclude-utxo",
+        number_of_values = 1,
+        help = "Never spend the given unspent output, formatted as <txid>:<vout>. Repeat to exclude several outputs."
+    )]
+    pub exclude: Vec<OutPoint>,
+}
+
+impl From<RawCoinControl> for CoinControl {
+    fn from(raw: RawCoinControl) -> Self {
+        CoinControl {
+            include: raw.include,
+            exclude: raw.exclude,
+        }
+    }
+}
+
+/// An unspent output of the wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub amount: Amount,
+    pub address: Option<Address>,
+    pub confirmed: bool,
+}
+
+/// A transaction that moved funds in or out of the wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub txid: Txid,
+    pub received: Amount,
+    pub sent: Amount,
+    /// The fee paid by this wallet, if any.
+    pub fee: Option<Amount>,
+    pub confirmations: u32,
+    /// The timestamp of the block the transaction was confirmed in.
+    pub timestamp: Option<u64>,
+}
+
+impl HistoryEntry {
+    pub fn is_incoming(&self) -> bool {
+        self.received > self.sent
+    }
+}
+
 /// Defines a watchable transaction.
 ///
 /// For a transaction to be watchable, we need to know two things: Its
@@ -677,32 +1053,47 @@ impl Watchable for (Txid, Script) {
 }
 
 pub struct Client {
-    electrum: bdk::electrum_client::Client,
+    electrum: Arc<Failover<bdk::electrum_client::Client>>,
     latest_block_height: BlockHeight,
+    latest_block_hash: BlockHash,
     last_sync: Instant,
     sync_interval: Duration,
     script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
     subscriptions: HashMap<(Txid, Script), Subscription>,
+    inclusion_blocks: InclusionBlocks,
+    push_subscriptions: PushSubscriptions,
+    updates_sender: watch::Sender<()>,
+    updates: watch::Receiver<()>,
 }
 
 impl Client {
-    fn new(electrum: bdk::electrum_client::Client, interval: Duration) -> Result<Self> {
-        // Initially fetch the latest block for storing the height.
-        // We do not act on this subscription after this call.
-        let latest_block = electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
+    fn new(electrum: Failover<bdk::electrum_client::Client>, interval: Duration) -> Result<Self> {
+        let push_subscriptions = PushSubscriptions::default();
+        let latest_block =
+            electrum.call(|electrum| push_subscriptions.subscribe_headers(electrum))?;
+        let (updates_sender, updates) = watch::channel(());
 
         Ok(Self {
-            electrum,
+            electrum: Arc::new(electrum),
+            latest_block_hash: latest_block.header.block_hash(),
             latest_block_height: BlockHeight::try_from(latest_block)?,
             last_sync: Instant::now(),
             sync_interval: interval,
             script_history: Default::default(),
             subscriptions: Default::default(),
+            inclusion_blocks: Default::default(),
+            push_subscriptions,
+            updates_sender,
+            updates,
         })
     }
 
+    /// Polls the tip and all script histories if the last sync is older than
+    /// the sync interval.
+    ///
+    /// Most updates are picked up through [`Client::process_notifications`].
+    /// Polling is only a fallback in case the Electrum server does not push a
+    /// notification to us.
     fn update_state(&mut self) -> Result<()> {
         let now = Instant::now();
         if now < self.last_sync + self.sync_interval {
@@ -716,6 +1107,35 @@ impl Client {
         Ok(())
     }
 
+    /// Applies the notifications pushed by the Electrum server since the last
+    /// call.
+    ///
+    /// Returns `true` if the latest block or any script history changed.
+    fn process_notifications(&mut self) -> Result<bool> {
+        let push_subscriptions = &mut self.push_subscriptions;
+        let notifications = self
+            .electrum
+            .call(|electrum| push_subscriptions.poll(electrum))?;
+        let mut changed = false;
+
+        if let Some(latest_block) = notifications.latest_block {
+            changed |= self.apply_latest_block(latest_block)?;
+        }
+
+        if !notifications.scripts.is_empty() {
+            let histories = self
+                .electrum
+                .call(|electrum| electrum.batch_script_get_history(notifications.scripts.iter()))
+                .context("Failed to get script histories")?;
+
+            self.script_history
+                .extend(notifications.scripts.into_iter().zip(histories));
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+
     fn status_of_script<T>(&mut self, tx: &T) -> Result<ScriptStatus>
     where
         T: Watchable,
@@ -724,7 +1144,15 @@ impl Client {
         let script = tx.script();
 
         if !self.script_history.contains_key(&script) {
-            self.script_history.insert(script.clone(), vec![]);
+            let push_subscriptions = &mut self.push_subscriptions;
+            self.electrum
+                .call(|electrum| push_subscriptions.subscribe_script(electrum, &script))?;
+
+            let history = self
+                .electrum
+                .call(|electrum| electrum.script_get_history(&script))
+                .context("Failed to get script history")?;
+            self.script_history.insert(script.clone(), history);
         }
 
         self.update_state()?;
@@ -746,9 +1174,12 @@ impl Client {
                 if last.height <= 0 {
                     Ok(ScriptStatus::InMempool)
                 } else {
+                    let inclusion_height = u32::try_from(last.height)?;
+                    self.track_inclusion_block(inclusion_height)?;
+
                     Ok(ScriptStatus::Confirmed(
                         Confirmed::from_inclusion_and_latest_block(
-                            u32::try_from(last.height)?,
+                            inclusion_height,
                             u32::from(self.latest_block_height),
                         ),
                     ))
@@ -757,33 +1188,118 @@ impl Client {
         }
     }
 
-    fn update_latest_block(&mut self) -> Result<()> {
-        // Fetch the latest block for storing the height.
-        // We do not act on this subscription after this call, as we cannot rely on
-        // subscription push notifications because eventually the Electrum server will
-        // close the connection and subscriptions are not automatically renewed
-        // upon renewing the connection.
-        let latest_block = self
+    fn track_inclusion_block(&mut self, height: u32) -> Result<()> {
+        if !self
+            .inclusion_blocks
+            .needs(height, u32::from(self.latest_block_height))
+        {
+            return Ok(());
+        }
+
+        let header = self
             .electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
+            .call(|electrum| electrum.block_header(height as usize))
+            .context("Failed to get block header")?;
+        self.inclusion_blocks.insert(height, header.block_hash());
+
+        Ok(())
+    }
+
+    /// Moves our view of the chain to a new tip.
+    ///
+    /// A tip that builds on the previous one cannot have replaced any block.
+    /// Otherwise, be it a competing block, a shorter chain or blocks we did
+    /// not get notified about, we compare the blocks our watched transactions
+    /// were included in against the current chain.
+    ///
+    /// Returns `true` if the tip changed.
+    fn apply_latest_block(&mut self, latest_block: HeaderNotification) -> Result<bool> {
+        let latest_block_hash = latest_block.header.block_hash();
+        if latest_block_hash == self.latest_block_hash {
+            return Ok(false);
+        }
+
+        let extends_tip = latest_block.header.prev_blockhash == self.latest_block_hash;
         let latest_block_height = BlockHeight::try_from(latest_block)?;
 
-        if latest_block_height > self.latest_block_height {
+        if latest_block_height < self.latest_block_height {
+            tracing::warn!(
+                block_height = u32::from(latest_block_height),
+                "Bitcoin chain tip moved back to a lower block"
+            );
+        } else {
             tracing::debug!(
                 block_height = u32::from(latest_block_height),
                 "Got notification for new block"
             );
-            self.latest_block_height = latest_block_height;
         }
 
+        self.latest_block_height = latest_block_height;
+        self.latest_block_hash = latest_block_hash;
+        self.inclusion_blocks
+            .prune(u32::from(self.latest_block_height));
+
+        if !extends_tip {
+            self.check_for_reorgs()?;
+        }
+
+        Ok(true)
+    }
+
+    /// Compares the blocks our watched transactions were included in against
+    /// the current chain.
+    ///
+    /// If any of them got replaced, we re-fetch all script histories so the
+    /// affected transactions report their new status.
+    fn check_for_reorgs(&mut self) -> Result<()> {
+        let heights = self.inclusion_blocks.heights();
+        if heights.is_empty() {
+            return Ok(());
+        }
+
+        let headers = self
+            .electrum
+            .call(|electrum| electrum.batch_block_header(heights.iter().copied()))
+            .context("Failed to get block headers")?;
+
+        let reorged_heights = self.inclusion_blocks.remove_replaced(
+            heights
+                .into_iter()
+                .zip(headers.iter().map(|header| header.block_hash())),
+        );
+
+        if reorged_heights.is_empty() {
+            return Ok(());
+        }
+
+        for height in reorged_heights {
+            tracing::warn!(
+                block_height = height,
+                "Detected Bitcoin chain reorganization"
+            );
+        }
+
+        self.update_script_histories()
+    }
+
+    fn update_latest_block(&mut self) -> Result<()> {
+        // Subscribing again is harmless if the subscription is still active and renews
+        // it in case the connection was re-established in the meantime.
+        let push_subscriptions = &self.push_subscriptions;
+        let latest_block = self
+            .electrum
+            .call(|electrum| push_subscriptions.subscribe_headers(electrum))?;
+
+        self.apply_latest_block(latest_block)?;
+
         Ok(())
     }
 
     fn update_script_histories(&mut self) -> Result<()> {
+        let scripts = &self.script_history;
         let histories = self
             .electrum
-            .batch_script_get_history(self.script_history.keys())
+            .call(|electrum| electrum.batch_script_get_history(scripts.keys()))
             .context("Failed to get script histories")?;
 
         if histories.len() != self.script_history.len() {
@@ -803,11 +1319,150 @@ impl Client {
     }
 }
 
+/// The hashes of the blocks our watched transactions were included in,
+/// indexed by height.
+///
+/// Blocks deeper than [`REORG_SAFE_DEPTH`] are not tracked.
+#[derive(Debug, Default)]
+struct InclusionBlocks {
+    blocks: BTreeMap<u32, BlockHash>,
+}
+
+impl InclusionBlocks {
+    /// Whether the block at `height` should be tracked but is not yet.
+    fn needs(&self, height: u32, tip: u32) -> bool {
+        height >= Self::safe_height(tip) && !self.blocks.contains_key(&height)
+    }
+
+    fn insert(&mut self, height: u32, hash: BlockHash) {
+        self.blocks.insert(height, hash);
+    }
+
+    fn heights(&self) -> Vec<u32> {
+        self.blocks.keys().copied().collect()
+    }
+
+    /// Forgets the blocks that are too deep to be reorganized.
+    fn prune(&mut self, tip: u32) {
+        self.blocks = self.blocks.split_off(&Self::safe_height(tip));
+    }
+
+    /// Forgets the blocks that are no longer part of the chain, returning
+    /// their heights.
+    fn remove_replaced(&mut self, chain: impl IntoIterator<Item = (u32, BlockHash)>) -> Vec<u32> {
+        let replaced = chain
+            .into_iter()
+            .filter(|(height, hash)| {
+                matches!(self.blocks.get(height), Some(tracked) if tracked != hash)
+            })
+            .map(|(height, _)| height)
+            .collect::<Vec<_>>();
+
+        for height in &replaced {
+            self.blocks.remove(height);
+        }
+
+        replaced
+    }
+
+    fn safe_height(tip: u32) -> u32 {
+        tip.saturating_sub(REORG_SAFE_DEPTH)
+    }
+}
+
+/// Keeps track of the push subscriptions we hold with the Electrum server.
+///
+/// Subscriptions are bound to the connection. The Electrum client reconnects
+/// transparently on errors, which silently drops all subscriptions. We notice
+/// that when popping notifications for a script fails with `NotSubscribed` and
+/// subscribe to everything again.
+#[derive(Default)]
+struct PushSubscriptions {
+    scripts: HashSet<Script>,
+}
+
+/// The notifications received since the last poll.
+#[derive(Default)]
+struct Notifications {
+    latest_block: Option<HeaderNotification>,
+    scripts: Vec<Script>,
+}
+
+impl PushSubscriptions {
+    fn subscribe_headers(
+        &self,
+        electrum: &bdk::electrum_client::Client,
+    ) -> Result<HeaderNotification> {
+        electrum
+            .block_headers_subscribe()
+            .context("Failed to subscribe to header notifications")
+    }
+
+    fn subscribe_script(
+        &mut self,
+        electrum: &bdk::electrum_client::Client,
+        script: &Script,
+    ) -> Result<()> {
+        match electrum.script_subscribe(script) {
+            Ok(_) | Err(bdk::electrum_client::Error::AlreadySubscribed(_)) => {}
+            Err(error) => return Err(error).context("Failed to subscribe to script notifications"),
+        }
+
+        self.scripts.insert(script.clone());
+
+        Ok(())
+    }
+
+    fn poll(&mut self, electrum: &bdk::electrum_client::Client) -> Result<Notifications> {
+        // The Electrum client only reads from the socket while waiting for the response
+        // to a request. Pinging is the cheapest way of collecting queued notifications.
+        electrum.ping().context("Failed to ping Electrum server")?;
+
+        let mut notifications = Notifications::default();
+
+        while let Some(header) = electrum.block_headers_pop()? {
+            notifications.latest_block = Some(header);
+        }
+
+        let mut lost_subscriptions = false;
+
+        for script in self.scripts.iter() {
+            match electrum.script_pop(script) {
+                Ok(Some(_)) => {
+                    while electrum.script_pop(script)?.is_some() {}
+                    notifications.scripts.push(script.clone());
+                }
+                Ok(None) => {}
+                Err(bdk::electrum_client::Error::NotSubscribed(_)) => {
+                    electrum
+                        .script_subscribe(script)
+                        .context("Failed to renew script subscription")?;
+                    // We might have missed a notification while we were not subscribed.
+                    notifications.scripts.push(script.clone());
+                    lost_subscriptions = true;
+                }
+                Err(error) => {
+                    return Err(error).context("Failed to get script notifications");
+                }
+            }
+        }
+
+        if lost_subscriptions {
+            tracing::debug!("Renewed Electrum subscriptions after reconnect");
+            notifications.latest_block = Some(self.subscribe_headers(electrum)?);
+        }
+
+        Ok(notifications)
+    }
+}
+
 impl EstimateFeeRate for Client {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L213
         // Returned estimated fees are per BTC/kb.
-        let fee_per_byte = self.electrum.estimate_fee(target_block)?;
+        let fee_per_byte = self
+            .electrum
+            .call(|electrum| electrum.estimate_fee(target_block))?;
         // we do not expect fees being that high.
         #[allow(clippy::cast_possible_truncation)]
         Ok(FeeRate::from_btc_per_kvb(fee_per_byte as f32))
@@ -816,7 +1471,8 @@ impl EstimateFeeRate for Client {
     fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L219
         // Returned fee is in BTC/kb
-        let relay_fee = bitcoin::Amount::from_btc(self.electrum.relay_fee()?)?;
+        let relay_fee =
+            bitcoin::Amount::from_btc(self.electrum.call(|electrum| electrum.relay_fee())?)?;
         Ok(relay_fee)
     }
 }
@@ -876,6 +1532,14 @@ impl Confirmed {
 }
 
 impl ScriptStatus {
+    /// The number of confirmations, zero if the script is not confirmed.
+    pub fn confirmations(&self) -> u32 {
+        match self {
+            ScriptStatus::Confirmed(inner) => inner.confirmations(),
+            _ => 0,
+        }
+    }
+
     /// Check if the script has any confirmations.
     pub fn is_confirmed(&self) -> bool {
         matches!(self, ScriptStatus::Confirmed(_))
@@ -1107,7 +1771,10 @@ mod tests {
     #[tokio::test]
     async fn given_no_balance_returns_amount_0() {
         let wallet = WalletBuilder::new(0).with_fees(1.0, 1).build();
-        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let amount = wallet
+            .max_giveable(TxLock::script_size(), &CoinControl::default())
+            .await
+            .unwrap();
 
         assert_eq!(amount, Amount::ZERO);
     }
@@ -1115,7 +1782,10 @@ mod tests {
     #[tokio::test]
     async fn given_balance_below_min_relay_fee_returns_amount_0() {
         let wallet = WalletBuilder::new(1000).with_fees(1.0, 1001).build();
-        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let amount = wallet
+            .max_giveable(TxLock::script_size(), &CoinControl::default())
+            .await
+            .unwrap();
 
         assert_eq!(amount, Amount::ZERO);
     }
@@ -1123,7 +1793,10 @@ mod tests {
     #[tokio::test]
     async fn given_balance_above_relay_fee_returns_amount_greater_0() {
         let wallet = WalletBuilder::new(10_000).build();
-        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let amount = wallet
+            .max_giveable(TxLock::script_size(), &CoinControl::default())
+            .await
+            .unwrap();
 
         assert!(amount.as_sat() > 0);
     }
@@ -1149,9 +1822,16 @@ mod tests {
         for amount in above_dust..(balance - (above_dust - 1)) {
             let (A, B) = (PublicKey::random(), PublicKey::random());
             let change = wallet.new_address().await.unwrap();
-            let txlock = TxLock::new(&wallet, bitcoin::Amount::from_sat(amount), A, B, change)
-                .await
-                .unwrap();
+            let txlock = TxLock::new(
+                &wallet,
+                bitcoin::Amount::from_sat(amount),
+                A,
+                B,
+                change,
+                &CoinControl::default(),
+            )
+            .await
+            .unwrap();
             let txlock_output = txlock.script_pubkey();
 
             let tx = wallet.sign_and_finalize(txlock.into()).await.unwrap();
@@ -1177,6 +1857,7 @@ mod tests {
                 wallet.new_address().await.unwrap(),
                 Amount::from_sat(10_000),
                 Some(custom_change.clone()),
+                &CoinControl::default(),
             )
             .await
             .unwrap();
@@ -1191,6 +1872,68 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn only_spends_included_utxos() {
+        let wallet = WalletBuilder::new(50_000).with_num_utxos(3).build();
+        let utxos = wallet.list_unspent().await.unwrap();
+        assert_eq!(utxos.len(), 3);
+        let coin_control = CoinControl {
+            include: vec![utxos[1].outpoint],
+            exclude: vec![],
+        };
+
+        let psbt = wallet
+            .send_to_address(
+                wallet.new_address().await.unwrap(),
+                Amount::from_sat(10_000),
+                None,
+                &coin_control,
+            )
+            .await
+            .unwrap();
+
+        let inputs = psbt
+            .global
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect::<Vec<_>>();
+        assert_eq!(inputs, vec![utxos[1].outpoint]);
+    }
+
+    #[tokio::test]
+    async fn never_spends_excluded_utxos() {
+        let wallet = WalletBuilder::new(50_000).with_num_utxos(3).build();
+        let utxos = wallet.list_unspent().await.unwrap();
+        let coin_control = CoinControl {
+            include: vec![],
+            exclude: vec![utxos[0].outpoint, utxos[2].outpoint],
+        };
+
+        let max_giveable = wallet
+            .max_giveable(TxLock::script_size(), &coin_control)
+            .await
+            .unwrap();
+        let psbt = wallet
+            .send_to_address(
+                wallet.new_address().await.unwrap(),
+                max_giveable,
+                None,
+                &coin_control,
+            )
+            .await
+            .unwrap();
+
+        assert!(max_giveable < Amount::from_sat(50_000));
+        assert!(psbt
+            .global
+            .unsigned_tx
+            .input
+            .iter()
+            .all(|input| input.previous_output == utxos[1].outpoint));
+    }
+
     #[test]
     fn printing_status_change_doesnt_spam_on_same_status() {
         let writer = capture_logs(LevelFilter::DEBUG);
@@ -1221,6 +1964,177 @@ DEBUG swap::bitcoin::wallet: Bitcoin transaction status changed txid=00000000000
         )
     }
 
+    #[test]
+    fn printing_status_change_warns_on_lost_confirmations() {
+        let writer = capture_logs(LevelFilter::WARN);
+
+        let tx = Txid::default();
+        let mut old = None;
+        old = Some(print_status_change(tx, old, confs(1)));
+        old = Some(print_status_change(tx, old, confs(2)));
+        print_status_change(tx, old, ScriptStatus::InMempool);
+
+        assert_eq!(
+            writer.captured(),
+            r" WARN swap::bitcoin::wallet: Bitcoin transaction was reorged txid=0000000000000000000000000000000000000000000000000000000000000000 new_status=in mempool old_status=confirmed with 2 blocks
+"
+        )
+    }
+
+    #[tokio::test]
+    async fn given_confirmations_drop_then_reports_reorg() {
+        let (sender, receiver) = watch::channel(confs(2));
+        let subscription = Subscription {
+            receiver,
+            finality_confirmations: 1,
+            txid: Txid::default(),
+        };
+
+        let reorged = subscription.wait_until_reorged();
+        tokio::pin!(reorged);
+
+        sender.send(confs(3)).unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), &mut reorged)
+                .await
+                .is_err()
+        );
+
+        sender.send(confs(1)).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), reorged)
+            .await
+            .expect("reorg to be reported")
+            .unwrap();
+    }
+
+    /// Serves the tip as an Electrum server would and pushes a new block once
+    /// the client starts polling for notifications.
+    fn spawn_electrum_server_pushing_new_block() -> Url {
+        use bitcoin::consensus::encode::serialize_hex;
+        use bitcoin::BlockHeader;
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        let tip = BlockHeader {
+            version: 1,
+            prev_blockhash: BlockHash::default(),
+            merkle_root: Default::default(),
+            time: 0,
+            bits: 0,
+            nonce: 0,
+        };
+        let next = BlockHeader {
+            prev_blockhash: tip.block_hash(),
+            ..tip
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut pushed = false;
+
+            for line in BufReader::new(stream).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => return,
+                };
+                let request = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+
+                let result = match request["method"].as_str().unwrap() {
+                    "blockchain.headers.subscribe" => {
+                        serde_json::json!({ "hex": serialize_hex(&tip), "height": 100 })
+                    }
+                    "server.ping" if !pushed => {
+                        let notification = serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "method": "blockchain.headers.subscribe",
+                            "params": [{ "hex": serialize_hex(&next), "height": 101 }],
+                        });
+                        writeln!(writer, "{}", notification).unwrap();
+                        pushed = true;
+
+                        serde_json::Value::Null
+                    }
+                    _ => serde_json::Value::Null,
+                };
+
+                let response = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": result,
+                });
+                writeln!(writer, "{}", response).unwrap();
+            }
+        });
+
+        Url::parse(&format!("tcp://127.0.0.1:{}", port)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn given_pushed_block_then_notifies_subscribers() {
+        let url = spawn_electrum_server_pushing_new_block();
+        let electrum =
+            Failover::new(vec![url], |url| electrum::connect(url, 1), electrum::tip).unwrap();
+        let client = Arc::new(Mutex::new(
+            Client::new(electrum, Duration::from_secs(600)).unwrap(),
+        ));
+        let mut updates = client.lock().await.updates.clone();
+
+        spawn_notification_processor(Arc::downgrade(&client)).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), updates.changed())
+            .await
+            .expect("pushed block to notify subscribers")
+            .unwrap();
+        assert_eq!(u32::from(client.lock().await.latest_block_height), 101);
+    }
+
+    fn block_hash(byte: u8) -> BlockHash {
+        use bitcoin::hashes::Hash;
+
+        BlockHash::from_inner([byte; 32])
+    }
+
+    #[test]
+    fn given_replaced_inclusion_block_then_reports_its_height() {
+        let mut blocks = InclusionBlocks::default();
+        blocks.insert(100, block_hash(1));
+        blocks.insert(105, block_hash(2));
+
+        let replaced = blocks.remove_replaced(vec![(100, block_hash(1)), (105, block_hash(3))]);
+
+        assert_eq!(replaced, vec![105]);
+        assert_eq!(blocks.heights(), vec![100]);
+        assert!(blocks.needs(105, 110));
+    }
+
+    #[test]
+    fn given_unchanged_chain_then_reports_no_reorg() {
+        let mut blocks = InclusionBlocks::default();
+        blocks.insert(100, block_hash(1));
+
+        let replaced = blocks.remove_replaced(vec![(100, block_hash(1))]);
+
+        assert!(replaced.is_empty());
+        assert_eq!(blocks.heights(), vec![100]);
+    }
+
+    #[test]
+    fn inclusion_blocks_beyond_safe_depth_are_forgotten() {
+        let mut blocks = InclusionBlocks::default();
+        blocks.insert(100, block_hash(1));
+        blocks.insert(150, block_hash(2));
+
+        blocks.prune(100 + REORG_SAFE_DEPTH + 1);
+
+        assert_eq!(blocks.heights(), vec![150]);
+        assert!(!blocks.needs(100, 100 + REORG_SAFE_DEPTH + 1));
+        assert!(!blocks.needs(150, 100 + REORG_SAFE_DEPTH + 1));
+    }
+
     fn confs(confirmations: u32) -> ScriptStatus {
         ScriptStatus::from_confirmations(confirmations)
     }
@@ -1233,8 +2147,8 @@ DEBUG swap::bitcoin::wallet: Bitcoin transaction status changed txid=00000000000
             tokio::runtime::Runtime::new().unwrap().block_on(async move {
                 let wallet = WalletBuilder::new(funding_amount as u64).with_key(key).with_num_utxos(num_utxos).with_fees(sats_per_vb, 1000).build();
 
-                let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
-                let psbt: PartiallySignedTransaction = TxLock::new(&wallet, amount, PublicKey::from(alice), PublicKey::from(bob), wallet.new_address().await.unwrap()).await.unwrap().into();
+                let amount = wallet.max_giveable(TxLock::script_size(), &CoinControl::default()).await.unwrap();
+                let psbt: PartiallySignedTransaction = TxLock::new(&wallet, amount, PublicKey::from(alice), PublicKey::from(bob), wallet.new_address().await.unwrap(), &CoinControl::default()).await.unwrap().into();
                 let result = wallet.sign_and_finalize(psbt).await;
 
                 result.expect("transaction to be signed");
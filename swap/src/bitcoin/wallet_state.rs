@@ -0,0 +1,301 @@
+use crate::database::BitcoinWalletDatabase;
+use crate::protocol::Database;
+use anyhow::{Context, Result};
+use bdk::database::{BatchDatabase, BatchOperations};
+use bdk::{KeychainKind, LocalUtxo, TransactionDetails};
+use bitcoin::{Script, Transaction};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+const SLED_TREE_NAME: &str = "default_tree";
+
+/// Everything bdk knows about our Bitcoin wallet.
+///
+/// This is how wallets of earlier versions are carried over into the
+/// [`BitcoinWalletDatabase`]. Anything missing from it is recovered by the
+/// next sync with the Electrum server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WalletState {
+    script_pubkeys: Vec<(Script, KeychainKind, u32)>,
+    last_indices: Vec<(KeychainKind, u32)>,
+    utxos: Vec<LocalUtxo>,
+    raw_txs: Vec<Transaction>,
+    txs: Vec<TransactionDetails>,
+}
+
+impl WalletState {
+    pub fn read_from<D>(database: &D) -> Result<Self>
+    where
+        D: bdk::database::Database,
+    {
+        let script_pubkeys = database
+            .iter_script_pubkeys(None)?
+            .into_iter()
+            .filter_map(
+                |script| match database.get_path_from_script_pubkey(&script) {
+                    Ok(Some((keychain, child))) => Some(Ok((script, keychain, child))),
+                    Ok(None) => None,
+                    Err(e) => Some(Err(e)),
+                },
+            )
+            .collect::<Result<_, _>>()?;
+
+        let mut last_indices = Vec::new();
+        for keychain in [KeychainKind::External, KeychainKind::Internal] {
+            if let Some(index) = database.get_last_index(keychain)? {
+                last_indices.push((keychain, index));
+            }
+        }
+
+        Ok(Self {
+            script_pubkeys,
+            last_indices,
+            utxos: database.iter_utxos()?,
+            raw_txs: database.iter_raw_txs()?,
+            txs: database.iter_txs(false)?,
+        })
+    }
+
+    pub fn write_to<D>(self, database: &mut D) -> Result<()>
+    where
+        D: BatchDatabase,
+    {
+        let mut batch = database.begin_batch();
+
+        for (script, keychain, child) in self.script_pubkeys {
+            batch.set_script_pubkey(&script, keychain, child)?;
+        }
+        for (keychain, index) in self.last_indices {
+            batch.set_last_index(keychain, index)?;
+        }
+        for utxo in self.utxos {
+            batch.set_utxo(&utxo)?;
+        }
+        for tx in self.raw_txs {
+            batch.set_raw_tx(&tx)?;
+        }
+        for tx in self.txs {
+            batch.set_tx(&tx)?;
+        }
+
+        database.commit_batch(batch)?;
+
+        Ok(())
+    }
+}
+
+/// Exclusive access to the Bitcoin wallet stored in a data directory.
+///
+/// Two processes syncing the same wallet would overwrite each other's view of
+/// it, and two processes building transactions from it would spend the same
+/// outputs. The lock is released when this is dropped.
+#[derive(Debug)]
+pub struct WalletLock {
+    _file: File,
+}
+
+impl WalletLock {
+    pub fn acquire(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join("wallet.lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        file.try_lock_exclusive().with_context(|| {
+            format!(
+                "The Bitcoin wallet in {} is in use by another process",
+                data_dir.display()
+            )
+        })?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// Opens the Bitcoin wallet in the swap database.
+///
+/// Wallets created by earlier versions were stored in a sled tree under
+/// `legacy_wallet_dir`. If it is still there, the sled tree is imported once
+/// and moved aside afterwards.
+pub async fn load_or_migrate(
+    db: &(dyn Database + Send + Sync),
+    legacy_wallet_dir: &Path,
+) -> Result<BitcoinWalletDatabase> {
+    let mut database = db.open_bitcoin_wallet().await?;
+
+    if !legacy_wallet_dir.exists() {
+        return Ok(database);
+    }
+
+    tracing::info!(
+        path = %legacy_wallet_dir.display(),
+        "Migrating Bitcoin wallet from sled to sqlite"
+    );
+
+    let state = read_sled_tree(legacy_wallet_dir)
+        .context("Failed to read Bitcoin wallet from sled database")?;
+    state.write_to(&mut database)?;
+
+    let backup_dir = sled_backup_dir(legacy_wallet_dir);
+    std::fs::rename(legacy_wallet_dir, &backup_dir).with_context(|| {
+        format!(
+            "Failed to move migrated sled database to {}",
+            backup_dir.display()
+        )
+    })?;
+
+    tracing::info!(
+        backup = %backup_dir.display(),
+        "Migrated Bitcoin wallet to sqlite"
+    );
+
+    Ok(database)
+}
+
+fn read_sled_tree(wallet_dir: &Path) -> Result<WalletState> {
+    let sled = bdk::sled::open(wallet_dir)?;
+    let tree = sled.open_tree(SLED_TREE_NAME)?;
+
+    WalletState::read_from(&tree)
+}
+
+fn sled_backup_dir(wallet_dir: &Path) -> PathBuf {
+    let mut name = wallet_dir
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(".sled-backup");
+
+    wallet_dir.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SqliteDatabase;
+    use bdk::database::{Database as _, MemoryDatabase};
+    use bitcoin::{Address, TxIn, TxOut};
+    use std::str::FromStr;
+    use tempfile::tempdir;
+
+    async fn swap_database() -> Result<SqliteDatabase> {
+        let dir = tempdir()?.into_path();
+        let path = dir.join("sqlite");
+        File::create(&path)?;
+
+        SqliteDatabase::open(path).await
+    }
+
+    fn populate(tree: &mut impl BatchOperations) -> Result<Transaction> {
+        let script =
+            Address::from_str("bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw")?.script_pubkey();
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: script.clone(),
+            }],
+        };
+
+        tree.set_script_pubkey(&script, KeychainKind::External, 7)?;
+        tree.set_last_index(KeychainKind::External, 7)?;
+        tree.set_last_index(KeychainKind::Internal, 3)?;
+        tree.set_raw_tx(&tx)?;
+
+        Ok(tx)
+    }
+
+    #[test]
+    fn wallet_state_roundtrips_through_memory_database() {
+        let mut source = MemoryDatabase::new();
+        let tx = populate(&mut source).unwrap();
+
+        let state = WalletState::read_from(&source).unwrap();
+        let mut target = MemoryDatabase::new();
+        state.write_to(&mut target).unwrap();
+
+        assert_eq!(
+            target.get_last_index(KeychainKind::External).unwrap(),
+            Some(7)
+        );
+        assert_eq!(
+            target.get_last_index(KeychainKind::Internal).unwrap(),
+            Some(3)
+        );
+        assert_eq!(target.get_raw_tx(&tx.txid()).unwrap(), Some(tx));
+        assert_eq!(
+            target
+                .iter_script_pubkeys(Some(KeychainKind::External))
+                .unwrap(),
+            source
+                .iter_script_pubkeys(Some(KeychainKind::External))
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn given_sled_wallet_then_migrates_it_once() {
+        let db = swap_database().await.unwrap();
+        let data_dir = tempdir().unwrap();
+        let wallet_dir = data_dir.path().join("wallet");
+
+        let tx = {
+            let sled = bdk::sled::open(&wallet_dir).unwrap();
+            let mut tree = sled.open_tree(SLED_TREE_NAME).unwrap();
+            let tx = populate(&mut tree).unwrap();
+            tree.flush().unwrap();
+            tx
+        };
+
+        let database = load_or_migrate(&db, &wallet_dir).await.unwrap();
+
+        assert_eq!(
+            database.get_last_index(KeychainKind::External).unwrap(),
+            Some(7)
+        );
+        assert_eq!(database.get_raw_tx(&tx.txid()).unwrap(), Some(tx.clone()));
+        assert!(!wallet_dir.exists());
+        assert!(data_dir.path().join("wallet.sled-backup").exists());
+        drop(database);
+
+        let reloaded = load_or_migrate(&db, &wallet_dir).await.unwrap();
+        assert_eq!(reloaded.iter_raw_txs().unwrap(), vec![tx]);
+        assert_eq!(
+            reloaded.get_last_index(KeychainKind::Internal).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn wallet_cannot_be_locked_twice() {
+        let data_dir = tempdir().unwrap();
+
+        let lock = WalletLock::acquire(data_dir.path()).unwrap();
+        assert!(WalletLock::acquire(data_dir.path()).is_err());
+
+        drop(lock);
+        WalletLock::acquire(data_dir.path()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn given_no_sled_wallet_then_starts_empty() {
+        let db = swap_database().await.unwrap();
+        let data_dir = tempdir().unwrap();
+
+        let database = load_or_migrate(&db, &data_dir.path().join("wallet"))
+            .await
+            .unwrap();
+
+        assert!(database.iter_script_pubkeys(None).unwrap().is_empty());
+        assert_eq!(
+            database.get_last_index(KeychainKind::External).unwrap(),
+            None
+        );
+    }
+}
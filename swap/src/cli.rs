@@ -9,7 +9,7 @@ pub mod transport;
 
 pub use behaviour::{Behaviour, OutEvent};
 pub use cancel::cancel;
-pub use event_loop::{EventLoop, EventLoopHandle};
+pub use event_loop::{known_seller_addresses, EventLoop, EventLoopHandle};
 pub use list_sellers::{list_sellers, Seller, Status as SellerStatus};
 pub use refund::refund;
 
@@ -18,11 +18,12 @@ mod tests {
     use super::*;
     use crate::asb;
     use crate::cli::list_sellers::{Seller, Status};
-    use crate::network::quote;
+    use crate::network::{dht, quote};
     use crate::network::quote::BidQuote;
     use crate::network::rendezvous::XmrBtcNamespace;
     use crate::network::test::{new_swarm, SwarmExt};
     use futures::StreamExt;
+    use libp2p::kad::{KademliaEvent, QueryResult};
     use libp2p::multiaddr::Protocol;
     use libp2p::request_response::RequestResponseEvent;
     use libp2p::swarm::{AddressScore, NetworkBehaviourEventProcess};
@@ -41,8 +42,8 @@ mod tests {
             setup_asb(rendezvous_peer_id, rendezvous_address.clone(), namespace).await;
 
         let list_sellers = list_sellers(
-            rendezvous_peer_id,
-            rendezvous_address,
+            Some((rendezvous_peer_id, rendezvous_address)),
+            vec![],
             namespace,
             0,
             identity::Keypair::generate_ed25519(),
@@ -58,6 +59,56 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn list_sellers_should_report_asbs_announced_in_the_dht() {
+        let namespace = XmrBtcNamespace::Mainnet;
+        let (bootstrap_address, bootstrap_peer_id) = setup_dht_bootstrap_node().await;
+        let expected_seller =
+            setup_dht_asb(bootstrap_peer_id, bootstrap_address.clone(), namespace).await;
+
+        let list_sellers = list_sellers(
+            None,
+            vec![(bootstrap_peer_id, bootstrap_address)],
+            namespace,
+            0,
+            identity::Keypair::generate_ed25519(),
+        );
+        let sellers = tokio::time::timeout(Duration::from_secs(15), list_sellers)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(sellers, vec![expected_seller])
+    }
+
+    #[tokio::test]
+    async fn list_sellers_should_merge_asbs_from_rendezvous_point_and_dht() {
+        let namespace = XmrBtcNamespace::Mainnet;
+        let (rendezvous_address, rendezvous_peer_id) = setup_rendezvous_point().await;
+        let (bootstrap_address, bootstrap_peer_id) = setup_dht_bootstrap_node().await;
+        let rendezvous_seller =
+            setup_asb(rendezvous_peer_id, rendezvous_address.clone(), namespace).await;
+        let dht_seller =
+            setup_dht_asb(bootstrap_peer_id, bootstrap_address.clone(), namespace).await;
+
+        let list_sellers = list_sellers(
+            Some((rendezvous_peer_id, rendezvous_address)),
+            vec![(bootstrap_peer_id, bootstrap_address)],
+            namespace,
+            0,
+            identity::Keypair::generate_ed25519(),
+        );
+        let sellers = tokio::time::timeout(Duration::from_secs(15), list_sellers)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            HashSet::<Seller>::from_iter(sellers),
+            HashSet::<Seller>::from_iter([rendezvous_seller, dht_seller])
+        )
+    }
+
     async fn setup_rendezvous_point() -> (Multiaddr, PeerId) {
         let mut rendezvous_node = new_swarm(|_, _| RendezvousPointBehaviour::default());
         let rendezvous_address = rendezvous_node.listen_on_tcp_localhost().await;
@@ -72,6 +123,66 @@ mod tests {
         (rendezvous_address, rendezvous_peer_id)
     }
 
+    async fn setup_dht_bootstrap_node() -> (Multiaddr, PeerId) {
+        let mut bootstrap_node = new_swarm(|peer_id, _| dht::new(peer_id, vec![]));
+        let bootstrap_address = bootstrap_node.listen_on_tcp_localhost().await;
+        let bootstrap_peer_id = *bootstrap_node.local_peer_id();
+
+        tokio::spawn(async move {
+            loop {
+                bootstrap_node.next().await;
+            }
+        });
+
+        (bootstrap_address, bootstrap_peer_id)
+    }
+
+    async fn setup_dht_asb(
+        bootstrap_peer_id: PeerId,
+        bootstrap_address: Multiaddr,
+        namespace: XmrBtcNamespace,
+    ) -> Seller {
+        let static_quote = BidQuote {
+            price: bitcoin::Amount::from_sat(1337),
+            min_quantity: bitcoin::Amount::from_sat(42),
+            max_quantity: bitcoin::Amount::from_sat(9001),
+        };
+
+        let mut asb = new_swarm(|peer_id, _| StaticQuoteDhtAsbBehaviour {
+            dht: dht::provider(
+                peer_id,
+                vec![(bootstrap_peer_id, bootstrap_address)],
+                namespace,
+            ),
+            ping: Default::default(),
+            quote: quote::asb(),
+            static_quote,
+            announced: false,
+        });
+
+        let asb_address = asb.listen_on_tcp_localhost().await;
+        asb.add_external_address(asb_address.clone(), AddressScore::Infinite);
+
+        let asb_peer_id = *asb.local_peer_id();
+
+        // block until the bootstrap node knows about us, otherwise `list_sellers` might
+        // not find us
+        while !asb.behaviour().announced {
+            asb.next().await;
+        }
+
+        tokio::spawn(async move {
+            loop {
+                asb.next().await;
+            }
+        });
+
+        Seller {
+            multiaddr: asb_address.with(Protocol::P2p(asb_peer_id.into())),
+            status: Status::Online(static_quote),
+        }
+    }
+
     async fn setup_asb(
         rendezvous_peer_id: PeerId,
         rendezvous_address: Multiaddr,
@@ -86,8 +197,7 @@ mod tests {
         let mut asb = new_swarm(|_, identity| StaticQuoteAsbBehaviour {
             rendezvous: asb::rendezous::Behaviour::new(
                 identity,
-                rendezvous_peer_id,
-                rendezvous_address,
+                vec![(rendezvous_peer_id, rendezvous_address)],
                 namespace,
                 None,
             ),
@@ -152,7 +262,48 @@ mod tests {
             } = event
             {
                 self.quote
-                    .send_response(channel, self.static_quote)
+                    .send_response(channel, self.static_quote.into())
+                    .unwrap();
+            }
+        }
+    }
+
+    #[derive(libp2p::NetworkBehaviour)]
+    #[behaviour(event_process = true)]
+    struct StaticQuoteDhtAsbBehaviour {
+        dht: dht::Behaviour,
+        ping: libp2p::ping::Ping,
+        quote: quote::Behaviour,
+
+        #[behaviour(ignore)]
+        static_quote: BidQuote,
+        #[behaviour(ignore)]
+        announced: bool,
+    }
+    impl NetworkBehaviourEventProcess<KademliaEvent> for StaticQuoteDhtAsbBehaviour {
+        fn inject_event(&mut self, event: KademliaEvent) {
+            if let KademliaEvent::OutboundQueryCompleted {
+                result: QueryResult::StartProviding(Ok(_)),
+                ..
+            } = event
+            {
+                self.announced = true;
+            }
+        }
+    }
+
+    impl NetworkBehaviourEventProcess<libp2p::ping::PingEvent> for StaticQuoteDhtAsbBehaviour {
+        fn inject_event(&mut self, _: libp2p::ping::PingEvent) {}
+    }
+    impl NetworkBehaviourEventProcess<quote::OutEvent> for StaticQuoteDhtAsbBehaviour {
+        fn inject_event(&mut self, event: quote::OutEvent) {
+            if let RequestResponseEvent::Message {
+                message: quote::Message::Request { channel, .. },
+                ..
+            } = event
+            {
+                self.quote
+                    .send_response(channel, self.static_quote.into())
                     .unwrap();
             }
         }
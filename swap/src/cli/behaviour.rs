@@ -1,22 +1,40 @@
-use crate::network::quote::BidQuote;
+use crate::network::address_book::{AddressHealth, AddressPreference};
+use crate::network::capabilities::Capabilities;
+use crate::network::quote::{BidQuote, SignedBidQuote};
 use crate::network::swap_setup::bob;
-use crate::network::{encrypted_signature, quote, redial, transfer_proof};
+use crate::network::{
+    capabilities, encrypted_signature, identify, quote, quote_subscription, redial, transfer_proof,
+};
 use crate::protocol::bob::State2;
 use crate::{bitcoin, env};
 use anyhow::{anyhow, Error, Result};
 use libp2p::core::Multiaddr;
-use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::identify::{Identify, IdentifyEvent};
+use libp2p::ping::{Ping, PingConfig, PingEvent, PingSuccess};
 use libp2p::request_response::{RequestId, ResponseChannel};
-use libp2p::{NetworkBehaviour, PeerId};
+use libp2p::{identity, NetworkBehaviour, PeerId};
 use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Debug)]
 pub enum OutEvent {
     QuoteReceived {
+        id: RequestId,
+        response: SignedBidQuote,
+    },
+    CapabilitiesReceived {
+        id: RequestId,
+        response: Capabilities,
+    },
+    QuoteSubscribed {
         id: RequestId,
         response: BidQuote,
     },
+    QuoteUpdated {
+        quote: BidQuote,
+        channel: ResponseChannel<()>,
+        peer: PeerId,
+    },
     SwapSetupCompleted(Box<Result<State2>>),
     TransferProofReceived {
         msg: Box<transfer_proof::Request>,
@@ -29,6 +47,19 @@ pub enum OutEvent {
     AllRedialAttemptsExhausted {
         peer: PeerId,
     },
+    AddressHealthChanged {
+        peer: PeerId,
+        address: Multiaddr,
+        health: AddressHealth,
+    },
+    Pinged {
+        peer: PeerId,
+        rtt: Duration,
+    },
+    Identified {
+        peer: PeerId,
+        listen_addresses: Vec<Multiaddr>,
+    },
     Failure {
         peer: PeerId,
         error: Error,
@@ -60,6 +91,9 @@ impl OutEvent {
 #[allow(missing_debug_implementations)]
 pub struct Behaviour {
     pub quote: quote::Behaviour,
+    pub quote_subscribe: quote_subscription::subscribe::Behaviour,
+    pub quote_update: quote_subscription::update::Behaviour,
+    pub capabilities: capabilities::Behaviour,
     pub swap_setup: bob::Behaviour,
     pub transfer_proof: transfer_proof::Behaviour,
     pub encrypted_signature: encrypted_signature::Behaviour,
@@ -69,6 +103,10 @@ pub struct Behaviour {
     /// still alive. If the ping fails a connection close event will be
     /// emitted that is picked up as swarm event.
     ping: Ping,
+
+    /// Learns the addresses Alice listens on, so we can still reach her if
+    /// the address we know stops working.
+    identify: Identify,
 }
 
 impl Behaviour {
@@ -76,27 +114,58 @@ impl Behaviour {
         alice: PeerId,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
+        public_key: identity::PublicKey,
+        address_preference: AddressPreference,
     ) -> Self {
         Self {
             quote: quote::cli(),
+            quote_subscribe: quote_subscription::subscribe::cli(),
+            quote_update: quote_subscription::update::cli(),
+            capabilities: capabilities::cli(),
             swap_setup: bob::Behaviour::new(env_config, bitcoin_wallet),
             transfer_proof: transfer_proof::bob(),
             encrypted_signature: encrypted_signature::bob(),
-            redial: redial::Behaviour::new(alice, Duration::from_secs(2)),
+            redial: redial::Behaviour::new(alice, Duration::from_secs(2), address_preference),
             ping: Ping::new(PingConfig::new().with_keep_alive(true)),
+            identify: identify::new(public_key),
         }
     }
 
-    /// Add a known address for the given peer
-    pub fn add_address(&mut self, peer_id: PeerId, address: Multiaddr) {
-        self.quote.add_address(&peer_id, address.clone());
-        self.transfer_proof.add_address(&peer_id, address.clone());
-        self.encrypted_signature.add_address(&peer_id, address);
+    /// Add a known address of Alice.
+    ///
+    /// All of Alice's addresses are handed to the swarm by the redial
+    /// behaviour, which orders them by how well they worked so far.
+    pub fn add_address(&mut self, address: Multiaddr) -> bool {
+        self.redial.add_address(address)
+    }
+
+    /// Add a known address of Alice with the health we recorded for it
+    /// earlier.
+    pub fn restore_address(&mut self, address: Multiaddr, health: AddressHealth) {
+        self.redial.restore_address(address, health);
     }
 }
 
 impl From<PingEvent> for OutEvent {
-    fn from(_: PingEvent) -> Self {
-        OutEvent::Other
+    fn from(event: PingEvent) -> Self {
+        match event.result {
+            Ok(PingSuccess::Ping { rtt }) => OutEvent::Pinged {
+                peer: event.peer,
+                rtt,
+            },
+            _ => OutEvent::Other,
+        }
+    }
+}
+
+impl From<IdentifyEvent> for OutEvent {
+    fn from(event: IdentifyEvent) -> Self {
+        match event {
+            IdentifyEvent::Received { peer_id, info } => OutEvent::Identified {
+                peer: peer_id,
+                listen_addresses: info.listen_addrs,
+            },
+            _ => OutEvent::Other,
+        }
     }
 }
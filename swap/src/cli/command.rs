@@ -1,11 +1,11 @@
-use crate::bitcoin::Amount;
-use crate::env::GetConfig;
+use crate::bitcoin::{address_network, Amount, CoinControl, RawCoinControl};
 use crate::fs::system_data_dir;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::{env, monero};
 use anyhow::{bail, Context, Result};
 use bitcoin::{Address, AddressType};
 use libp2p::core::Multiaddr;
+use monero_rpc::http::Credentials;
 use serde::Serialize;
 use std::ffi::OsString;
 use std::path::PathBuf;
@@ -15,13 +15,22 @@ use url::Url;
 use uuid::Uuid;
 
 // See: https://moneroworld.com/
-pub const DEFAULT_MONERO_DAEMON_ADDRESS: &str = "node.melo.tools:18081";
-pub const DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET: &str = "stagenet.melo.tools:38081";
+pub const DEFAULT_MONERO_DAEMON_ADDRESSES: &[&str] = &[
+    "node.melo.tools:18081",
+    "node.moneroworld.com:18089",
+    "xmr-node.cakewallet.com:18081",
+];
+pub const DEFAULT_MONERO_DAEMON_ADDRESSES_STAGENET: &[&str] = &[
+    "stagenet.melo.tools:38081",
+    "stagenet.community.rino.io:38081",
+];
 
 // See: https://1209k.com/bitcoin-eye/ele.php?chain=btc
 const DEFAULT_ELECTRUM_RPC_URL: &str = "ssl://blockstream.info:700";
 // See: https://1209k.com/bitcoin-eye/ele.php?chain=tbtc
 pub const DEFAULT_ELECTRUM_RPC_URL_TESTNET: &str = "ssl://electrum.blockstream.info:60002";
+// See: https://mempool.space/signet
+pub const DEFAULT_ELECTRUM_RPC_URL_SIGNET: &str = "ssl://mempool.space:60602";
 
 const DEFAULT_BITCOIN_CONFIRMATION_TARGET: usize = 3;
 const DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET: usize = 1;
@@ -66,7 +75,7 @@ where
 
     let debug = args.debug;
     let json = args.json;
-    let is_testnet = args.testnet;
+    let env_config = env::select(args.testnet, args.signet, args.env_config.as_deref())?;
     let data = args.data;
 
     let arguments = match args.cmd {
@@ -77,62 +86,67 @@ where
             monero,
             monero_receive_address,
             tor: Tor { tor_socks5_port },
+            coin_control,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
-                bitcoin.apply_defaults(is_testnet)?;
-            let monero_daemon_address = monero.apply_defaults(is_testnet);
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
+            let (monero_daemon_addresses, monero_daemon_login, monero_wallet_rpc) =
+                monero.apply_defaults(&env_config)?;
             let monero_receive_address =
-                validate_monero_address(monero_receive_address, is_testnet)?;
+                validate_monero_address(monero_receive_address, env_config.monero_network)?;
             let bitcoin_change_address =
-                validate_bitcoin_address(bitcoin_change_address, is_testnet)?;
+                validate_bitcoin_address(bitcoin_change_address, env_config.bitcoin_network)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
                 cmd: Command::BuyXmr {
                     seller,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                     bitcoin_change_address,
                     monero_receive_address,
-                    monero_daemon_address,
+                    monero_daemon_addresses,
+                    monero_daemon_login,
+                    monero_wallet_rpc,
                     tor_socks5_port,
+                    coin_control: coin_control.into(),
                 },
             }
         }
         RawCommand::History => Arguments {
-            env_config: env_config_from(is_testnet),
+            env_config,
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
             cmd: Command::History,
         },
         RawCommand::Config => Arguments {
-            env_config: env_config_from(is_testnet),
+            env_config,
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
             cmd: Command::Config,
         },
         RawCommand::Balance {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
         } => {
             let bitcoin = Bitcoin {
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 bitcoin_target_block: None,
             };
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
-                bitcoin.apply_defaults(is_testnet)?;
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
                 cmd: Command::Balance {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
@@ -141,20 +155,64 @@ where
             bitcoin,
             amount,
             address,
+            coin_control,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
-                bitcoin.apply_defaults(is_testnet)?;
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
                 cmd: Command::WithdrawBtc {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                     amount,
-                    address: bitcoin_address(address, is_testnet)?,
+                    address: bitcoin_address(address, env_config.bitcoin_network)?,
+                    coin_control: coin_control.into(),
+                },
+            }
+        }
+        RawCommand::ListUtxos {
+            bitcoin_electrum_rpc_urls,
+        } => {
+            let bitcoin = Bitcoin {
+                bitcoin_electrum_rpc_urls,
+                bitcoin_target_block: None,
+            };
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
+
+            Arguments {
+                env_config,
+                debug,
+                json,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+                cmd: Command::ListUtxos {
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                },
+            }
+        }
+        RawCommand::BitcoinHistory {
+            bitcoin_electrum_rpc_urls,
+        } => {
+            let bitcoin = Bitcoin {
+                bitcoin_electrum_rpc_urls,
+                bitcoin_target_block: None,
+            };
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
+
+            Arguments {
+                env_config,
+                debug,
+                json,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+                cmd: Command::BitcoinHistory {
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
                 },
             }
         }
@@ -164,20 +222,23 @@ where
             monero,
             tor: Tor { tor_socks5_port },
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
-                bitcoin.apply_defaults(is_testnet)?;
-            let monero_daemon_address = monero.apply_defaults(is_testnet);
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
+            let (monero_daemon_addresses, monero_daemon_login, monero_wallet_rpc) =
+                monero.apply_defaults(&env_config)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
                 cmd: Command::Resume {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
-                    monero_daemon_address,
+                    monero_daemon_addresses,
+                    monero_daemon_login,
+                    monero_wallet_rpc,
                     tor_socks5_port,
                 },
             }
@@ -186,17 +247,17 @@ where
             swap_id: SwapId { swap_id },
             bitcoin,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
-                bitcoin.apply_defaults(is_testnet)?;
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
                 cmd: Command::Cancel {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
@@ -205,55 +266,63 @@ where
             swap_id: SwapId { swap_id },
             bitcoin,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
-                bitcoin.apply_defaults(is_testnet)?;
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
                 cmd: Command::Refund {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
         }
         RawCommand::ListSellers {
             rendezvous_point,
+            dht_bootstrap_nodes,
             tor: Tor { tor_socks5_port },
-        } => Arguments {
-            env_config: env_config_from(is_testnet),
-            debug,
-            json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
-            cmd: Command::ListSellers {
-                rendezvous_point,
-                namespace: rendezvous_namespace_from(is_testnet),
-                tor_socks5_port,
-            },
-        },
+        } => {
+            if rendezvous_point.is_none() && dht_bootstrap_nodes.is_empty() {
+                bail!("Provide a rendezvous point or at least one DHT bootstrap node to discover sellers")
+            }
+
+            Arguments {
+                env_config,
+                debug,
+                json,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+                cmd: Command::ListSellers {
+                    rendezvous_point,
+                    dht_bootstrap_nodes,
+                    namespace: XmrBtcNamespace::for_network(env_config.bitcoin_network),
+                    tor_socks5_port,
+                },
+            }
+        }
         RawCommand::ExportBitcoinWallet { bitcoin } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
-                bitcoin.apply_defaults(is_testnet)?;
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(env_config.bitcoin_network)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config,
                 debug,
                 json,
-                data_dir: data::data_dir_from(data, is_testnet)?,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
                 cmd: Command::ExportBitcoinWallet {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
         }
         RawCommand::MoneroRecovery { swap_id } => Arguments {
-            env_config: env_config_from(is_testnet),
+            env_config,
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
             cmd: Command::MoneroRecovery {
                 swap_id: swap_id.swap_id,
             },
@@ -267,49 +336,64 @@ where
 pub enum Command {
     BuyXmr {
         seller: Multiaddr,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
         bitcoin_change_address: bitcoin::Address,
         monero_receive_address: monero::Address,
-        monero_daemon_address: String,
+        monero_daemon_addresses: Vec<String>,
+        monero_daemon_login: Option<Credentials>,
+        monero_wallet_rpc: monero::WalletRpcBinary,
         tor_socks5_port: u16,
+        coin_control: CoinControl,
     },
     History,
     Config,
     WithdrawBtc {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
         amount: Option<Amount>,
         address: Address,
+        coin_control: CoinControl,
     },
     Balance {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+    },
+    ListUtxos {
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+    },
+    BitcoinHistory {
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     Resume {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
-        monero_daemon_address: String,
+        monero_daemon_addresses: Vec<String>,
+        monero_daemon_login: Option<Credentials>,
+        monero_wallet_rpc: monero::WalletRpcBinary,
         tor_socks5_port: u16,
     },
     Cancel {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     Refund {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     ListSellers {
-        rendezvous_point: Multiaddr,
+        rendezvous_point: Option<Multiaddr>,
+        dht_bootstrap_nodes: Vec<Multiaddr>,
         namespace: XmrBtcNamespace,
         tor_socks5_port: u16,
     },
     ExportBitcoinWallet {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     MoneroRecovery {
@@ -333,9 +417,25 @@ struct RawArguments {
     )]
     testnet: bool,
 
+    #[structopt(
+        long,
+        help = "Swap on signet and assume signet defaults for data-dir and the blockchain related parameters",
+        global = true,
+        conflicts_with = "testnet"
+    )]
+    signet: bool,
+
+    #[structopt(
+        long = "env-config",
+        help = "Provide a custom path to a toml file that defines the timelocks, finality confirmations, timeouts and networks to use. Takes precedence over --testnet and --signet.",
+        global = true,
+        parse(from_os_str)
+    )]
+    env_config: Option<PathBuf>,
+
     #[structopt(
         long = "--data-base-dir",
-        help = "The base data directory to be used for mainnet / testnet specific data like database, wallets etc"
+        help = "The base data directory to be used for network specific data like database, wallets etc"
     )]
     data: Option<PathBuf>,
 
@@ -380,6 +480,9 @@ enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(flatten)]
+        coin_control: RawCoinControl,
     },
     /// Show a list of past, ongoing and completed swaps
     History,
@@ -397,11 +500,40 @@ enum RawCommand {
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
+
+        #[structopt(flatten)]
+        coin_control: RawCoinControl,
     },
     #[structopt(about = "Prints the Bitcoin balance.")]
     Balance {
-        #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
-        bitcoin_electrum_rpc_url: Option<Url>,
+        #[structopt(
+            long = "electrum-rpc",
+            number_of_values = 1,
+            help = "Provide the Bitcoin Electrum RPC URL. Repeat to configure fallback servers."
+        )]
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+    },
+    #[structopt(
+        about = "Prints the unspent outputs of the internal Bitcoin wallet together with the swap they belong to."
+    )]
+    ListUtxos {
+        #[structopt(
+            long = "electrum-rpc",
+            number_of_values = 1,
+            help = "Provide the Bitcoin Electrum RPC URL. Repeat to configure fallback servers."
+        )]
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+    },
+    #[structopt(
+        about = "Prints the transactions of the internal Bitcoin wallet together with their fees and the swap they belong to."
+    )]
+    BitcoinHistory {
+        #[structopt(
+            long = "electrum-rpc",
+            number_of_values = 1,
+            help = "Provide the Bitcoin Electrum RPC URL. Repeat to configure fallback servers."
+        )]
+        bitcoin_electrum_rpc_urls: Vec<Url>,
     },
     /// Resume a swap
     Resume {
@@ -441,7 +573,14 @@ enum RawCommand {
             long,
             help = "Address of the rendezvous point you want to use to discover ASBs"
         )]
-        rendezvous_point: Multiaddr,
+        rendezvous_point: Option<Multiaddr>,
+
+        #[structopt(
+            long = "dht-bootstrap-node",
+            help = "Address of a node to join the DHT through to discover ASBs, can be given multiple times",
+            number_of_values = 1
+        )]
+        dht_bootstrap_nodes: Vec<Multiaddr>,
 
         #[structopt(flatten)]
         tor: Tor,
@@ -464,27 +603,89 @@ enum RawCommand {
 struct Monero {
     #[structopt(
         long = "monero-daemon-address",
-        help = "Specify to connect to a monero daemon of your choice: <host>:<port>"
+        number_of_values = 1,
+        help = "Specify to connect to a monero daemon of your choice: <host>:<port>, prefix with https:// to use TLS. Repeat to configure fallback daemons."
+    )]
+    monero_daemon_addresses: Vec<String>,
+
+    #[structopt(
+        long = "monero-daemon-login",
+        help = "Log in to the monero daemons with <username>:<password>, for daemons started with --rpc-login"
     )]
-    monero_daemon_address: Option<String>,
+    monero_daemon_login: Option<Credentials>,
+
+    #[structopt(
+        long = "monero-wallet-rpc",
+        help = "Use this monero-wallet-rpc executable instead of downloading it",
+        parse(from_os_str)
+    )]
+    monero_wallet_rpc: Option<PathBuf>,
+
+    #[structopt(
+        long = "verify-monero-signature",
+        help = "Verify the downloaded monero-wallet-rpc against the hashes signed by the Monero release key. Requires gpg.",
+        conflicts_with = "monero-wallet-rpc"
+    )]
+    verify_monero_signature: bool,
 }
 
 impl Monero {
-    fn apply_defaults(self, testnet: bool) -> String {
-        if let Some(address) = self.monero_daemon_address {
-            address
-        } else if testnet {
-            DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string()
-        } else {
-            DEFAULT_MONERO_DAEMON_ADDRESS.to_string()
+    fn apply_defaults(
+        self,
+        env_config: &env::Config,
+    ) -> Result<(Vec<String>, Option<Credentials>, monero::WalletRpcBinary)> {
+        let wallet_rpc = match self.monero_wallet_rpc {
+            Some(path) => monero::WalletRpcBinary::Existing(path),
+            None => monero::WalletRpcBinary::Download {
+                verify_signature: self.verify_monero_signature,
+            },
+        };
+
+        if !self.monero_daemon_addresses.is_empty() {
+            return Ok((
+                self.monero_daemon_addresses,
+                self.monero_daemon_login,
+                wallet_rpc,
+            ));
+        }
+
+        if env_config.bitcoin_network == bitcoin::Network::Regtest {
+            bail!("There is no default Monero daemon for regtest, please provide one using --monero-daemon-address")
+        }
+
+        // The default daemons are public, never hand them the login meant
+        // for the user's own daemon.
+        if self.monero_daemon_login.is_some() {
+            bail!("--monero-daemon-login requires --monero-daemon-address, it is never sent to the default daemons")
         }
+
+        let addresses = match env_config.monero_network {
+            monero::Network::Mainnet => DEFAULT_MONERO_DAEMON_ADDRESSES,
+            monero::Network::Stagenet => DEFAULT_MONERO_DAEMON_ADDRESSES_STAGENET,
+            monero::Network::Testnet => {
+                bail!("There is no default Monero daemon for testnet, please provide one using --monero-daemon-address")
+            }
+        };
+
+        Ok((
+            addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+            None,
+            wallet_rpc,
+        ))
     }
 }
 
 #[derive(structopt::StructOpt, Debug)]
 struct Bitcoin {
-    #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
-    bitcoin_electrum_rpc_url: Option<Url>,
+    #[structopt(
+        long = "electrum-rpc",
+        number_of_values = 1,
+        help = "Provide the Bitcoin Electrum RPC URL. Repeat to configure fallback servers."
+    )]
+    bitcoin_electrum_rpc_urls: Vec<Url>,
 
     #[structopt(
         long = "bitcoin-target-block",
@@ -494,24 +695,31 @@ struct Bitcoin {
 }
 
 impl Bitcoin {
-    fn apply_defaults(self, testnet: bool) -> Result<(Url, usize)> {
-        let bitcoin_electrum_rpc_url = if let Some(url) = self.bitcoin_electrum_rpc_url {
-            url
-        } else if testnet {
-            Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?
+    fn apply_defaults(self, network: bitcoin::Network) -> Result<(Vec<Url>, usize)> {
+        let bitcoin_electrum_rpc_urls = if !self.bitcoin_electrum_rpc_urls.is_empty() {
+            self.bitcoin_electrum_rpc_urls
         } else {
-            Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?
+            let default = match network {
+                bitcoin::Network::Bitcoin => DEFAULT_ELECTRUM_RPC_URL,
+                bitcoin::Network::Testnet => DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                bitcoin::Network::Signet => DEFAULT_ELECTRUM_RPC_URL_SIGNET,
+                bitcoin::Network::Regtest => {
+                    bail!("There is no default Electrum server for regtest, please provide one using --electrum-rpc")
+                }
+            };
+
+            vec![Url::from_str(default)?]
         };
 
         let bitcoin_target_block = if let Some(target_block) = self.bitcoin_target_block {
             target_block
-        } else if testnet {
-            DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET
-        } else {
+        } else if network == bitcoin::Network::Bitcoin {
             DEFAULT_BITCOIN_CONFIRMATION_TARGET
+        } else {
+            DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET
         };
 
-        Ok((bitcoin_electrum_rpc_url, bitcoin_target_block))
+        Ok((bitcoin_electrum_rpc_urls, bitcoin_target_block))
     }
 }
 
@@ -546,13 +754,18 @@ struct Seller {
 mod data {
     use super::*;
 
-    pub fn data_dir_from(arg_dir: Option<PathBuf>, testnet: bool) -> Result<PathBuf> {
+    pub fn data_dir_from(arg_dir: Option<PathBuf>, network: bitcoin::Network) -> Result<PathBuf> {
         let base_dir = match arg_dir {
             Some(custom_base_dir) => custom_base_dir,
             None => os_default()?,
         };
 
-        let sub_directory = if testnet { "testnet" } else { "mainnet" };
+        let sub_directory = match network {
+            bitcoin::Network::Bitcoin => "mainnet",
+            bitcoin::Network::Testnet => "testnet",
+            bitcoin::Network::Signet => "signet",
+            bitcoin::Network::Regtest => "regtest",
+        };
 
         Ok(base_dir.join(sub_directory))
     }
@@ -562,28 +775,8 @@ mod data {
     }
 }
 
-fn rendezvous_namespace_from(is_testnet: bool) -> XmrBtcNamespace {
-    if is_testnet {
-        XmrBtcNamespace::Testnet
-    } else {
-        XmrBtcNamespace::Mainnet
-    }
-}
-
-fn env_config_from(testnet: bool) -> env::Config {
-    if testnet {
-        env::Testnet::get_config()
-    } else {
-        env::Mainnet::get_config()
-    }
-}
-
-fn bitcoin_address(address: Address, is_testnet: bool) -> Result<Address> {
-    let network = if is_testnet {
-        bitcoin::Network::Testnet
-    } else {
-        bitcoin::Network::Bitcoin
-    };
+fn bitcoin_address(address: Address, network: bitcoin::Network) -> Result<Address> {
+    let network = address_network(network);
 
     if address.network != network {
         bail!(BitcoinAddressNetworkMismatch {
@@ -597,14 +790,8 @@ fn bitcoin_address(address: Address, is_testnet: bool) -> Result<Address> {
 
 fn validate_monero_address(
     address: monero::Address,
-    testnet: bool,
+    expected_network: monero::Network,
 ) -> Result<monero::Address, MoneroAddressNetworkMismatch> {
-    let expected_network = if testnet {
-        monero::Network::Stagenet
-    } else {
-        monero::Network::Mainnet
-    };
-
     if address.network != expected_network {
         return Err(MoneroAddressNetworkMismatch {
             expected: expected_network,
@@ -615,12 +802,11 @@ fn validate_monero_address(
     Ok(address)
 }
 
-fn validate_bitcoin_address(address: bitcoin::Address, testnet: bool) -> Result<bitcoin::Address> {
-    let expected_network = if testnet {
-        bitcoin::Network::Testnet
-    } else {
-        bitcoin::Network::Bitcoin
-    };
+fn validate_bitcoin_address(
+    address: bitcoin::Address,
+    network: bitcoin::Network,
+) -> Result<bitcoin::Address> {
+    let expected_network = address_network(network);
 
     if address.network != expected_network {
         anyhow::bail!(
@@ -665,11 +851,14 @@ pub struct BitcoinAddressNetworkMismatch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::env::GetConfig;
     use crate::tor::DEFAULT_SOCKS5_PORT;
+    use bitcoin::OutPoint;
 
     const BINARY_NAME: &str = "swap";
 
     const TESTNET: &str = "testnet";
+    const SIGNET: &str = "signet";
     const MAINNET: &str = "mainnet";
 
     const MONERO_STAGENET_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
@@ -721,6 +910,230 @@ mod tests {
         );
     }
 
+    #[test]
+    fn given_buy_xmr_with_utxos_then_coin_control_is_set() {
+        let included = "0101010101010101010101010101010101010101010101010101010101010101:0";
+        let excluded = "0202020202020202020202020202020202020202020202020202020202020202:1";
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--change-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--utxo",
+            included,
+            "--exclude-utxo",
+            excluded,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let expected_coin_control = CoinControl {
+            include: vec![OutPoint::from_str(included).unwrap()],
+            exclude: vec![OutPoint::from_str(excluded).unwrap()],
+        };
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::BuyXmr { coin_control, .. },
+                ..
+            }) => assert_eq!(coin_control, expected_coin_control),
+            _ => panic!("expected buy-xmr command"),
+        }
+    }
+
+    #[test]
+    fn given_buy_xmr_with_monero_wallet_rpc_then_existing_executable_is_used() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--monero-wallet-rpc",
+            "/usr/local/bin/monero-wallet-rpc",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::BuyXmr {
+                    monero_wallet_rpc, ..
+                },
+                ..
+            }) => assert_eq!(
+                monero_wallet_rpc,
+                monero::WalletRpcBinary::Existing(PathBuf::from(
+                    "/usr/local/bin/monero-wallet-rpc"
+                ))
+            ),
+            _ => panic!("expected buy-xmr command"),
+        }
+    }
+
+    #[test]
+    fn given_resume_with_verify_monero_signature_then_signature_is_verified() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--verify-monero-signature",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::Resume {
+                    monero_wallet_rpc, ..
+                },
+                ..
+            }) => assert_eq!(
+                monero_wallet_rpc,
+                monero::WalletRpcBinary::Download {
+                    verify_signature: true
+                }
+            ),
+            _ => panic!("expected resume command"),
+        }
+    }
+
+    #[test]
+    fn given_resume_with_monero_daemon_login_then_login_is_used() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--monero-daemon-address",
+            "https://node.example.com:18089",
+            "--monero-daemon-login",
+            "monero:hunter2",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::Resume {
+                        monero_daemon_addresses,
+                        monero_daemon_login,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(
+                    monero_daemon_addresses,
+                    vec!["https://node.example.com:18089".to_owned()]
+                );
+                assert_eq!(
+                    monero_daemon_login,
+                    Some(Credentials {
+                        username: "monero".to_owned(),
+                        password: "hunter2".to_owned()
+                    })
+                );
+            }
+            _ => panic!("expected resume command"),
+        }
+    }
+
+    #[test]
+    fn given_monero_daemon_login_without_daemon_address_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--monero-daemon-login",
+            "monero:hunter2",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_monero_wallet_rpc_and_verify_monero_signature_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--monero-wallet-rpc",
+            "/usr/local/bin/monero-wallet-rpc",
+            "--verify-monero-signature",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_list_sellers_with_dht_bootstrap_nodes_then_rendezvous_point_is_optional() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "list-sellers",
+            "--dht-bootstrap-node",
+            "/ip4/127.0.0.1/tcp/8888/p2p/12D3KooWQUt9DkNZxEn2R5ymJzWj15MpG6mTW84kyd8vDaRZi46o",
+            "--dht-bootstrap-node",
+            "/ip4/127.0.0.1/tcp/9999/p2p/12D3KooWPZ69DRp4wbGB3wJsxxsg1XW1EVZ2evtVwcARCF3a1nrx",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::ListSellers {
+                        rendezvous_point,
+                        dht_bootstrap_nodes,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(rendezvous_point, None);
+                assert_eq!(dht_bootstrap_nodes.len(), 2);
+            }
+            _ => panic!("expected list-sellers command"),
+        }
+    }
+
+    #[test]
+    fn given_list_sellers_without_rendezvous_point_or_dht_bootstrap_node_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "list-sellers"];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_bitcoin_history_on_testnet_then_testnet_defaults_are_used() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "bitcoin-history"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::BitcoinHistory {
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                },
+            })
+        );
+    }
+
     #[test]
     fn given_buy_xmr_on_mainnet_with_testnet_address_then_fails() {
         let raw_ars = vec![
@@ -794,6 +1207,105 @@ mod tests {
         );
     }
 
+    #[test]
+    fn given_resume_on_signet_then_defaults_to_signet() {
+        let raw_ars = vec![BINARY_NAME, "--signet", "resume", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_signet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_testnet_and_signet_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "--signet",
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_env_config_for_regtest_without_electrum_rpc_then_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("env.toml");
+        std::fs::write(&path, toml::to_string(&env::Regtest::get_config()).unwrap()).unwrap();
+
+        let raw_ars = vec![
+            BINARY_NAME.to_owned(),
+            "--env-config".to_owned(),
+            path.display().to_string(),
+            "balance".to_owned(),
+        ];
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_env_config_then_custom_profile_is_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("env.toml");
+        let custom_env_config = env::Config {
+            bitcoin_finality_confirmations: 6,
+            ..env::Signet::get_config()
+        };
+        std::fs::write(&path, toml::to_string(&custom_env_config).unwrap()).unwrap();
+
+        let raw_ars = vec![
+            BINARY_NAME.to_owned(),
+            "--env-config".to_owned(),
+            path.display().to_string(),
+            "resume".to_owned(),
+            "--swap-id".to_owned(),
+            SWAP_ID.to_owned(),
+        ];
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let expected_args = Arguments {
+            env_config: custom_env_config,
+            ..Arguments::resume_signet_defaults()
+        };
+        assert_eq!(args, ParseResult::Arguments(expected_args));
+    }
+
+    #[test]
+    fn given_multiple_electrum_rpc_urls_then_all_are_used_in_order() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--electrum-rpc",
+            "ssl://primary.example:50002",
+            "--electrum-rpc",
+            "tcp://fallback.example:50001",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::resume_mainnet_defaults();
+        if let Command::Resume {
+            bitcoin_electrum_rpc_urls,
+            ..
+        } = &mut expected.cmd
+        {
+            *bitcoin_electrum_rpc_urls = vec![
+                Url::from_str("ssl://primary.example:50002").unwrap(),
+                Url::from_str("tcp://fallback.example:50001").unwrap(),
+            ];
+        }
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
     #[test]
     fn given_cancel_on_mainnet_then_defaults_to_mainnet() {
         let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID];
@@ -1158,6 +1670,17 @@ mod tests {
         assert!(matches!(result, ParseResult::Arguments(_)));
     }
 
+    fn electrum_rpc_urls(url: &str) -> Vec<Url> {
+        vec![Url::from_str(url).unwrap()]
+    }
+
+    fn monero_daemon_addresses(addresses: &[&str]) -> Vec<String> {
+        addresses
+            .iter()
+            .map(|address| address.to_string())
+            .collect()
+    }
+
     impl Arguments {
         pub fn buy_xmr_testnet_defaults() -> Self {
             Self {
@@ -1167,14 +1690,18 @@ mod tests {
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::BuyXmr {
                     seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
                     bitcoin_change_address: BITCOIN_TESTNET_ADDRESS.parse().unwrap(),
                     monero_receive_address: monero::Address::from_str(MONERO_STAGENET_ADDRESS)
                         .unwrap(),
-                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    monero_daemon_addresses: monero_daemon_addresses(
+                        DEFAULT_MONERO_DAEMON_ADDRESSES_STAGENET,
+                    ),
+                    monero_daemon_login: None,
+                    monero_wallet_rpc: Default::default(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    coin_control: Default::default(),
                 },
             }
         }
@@ -1187,13 +1714,18 @@ mod tests {
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::BuyXmr {
                     seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                     bitcoin_change_address: BITCOIN_MAINNET_ADDRESS.parse().unwrap(),
                     monero_receive_address: monero::Address::from_str(MONERO_MAINNET_ADDRESS)
                         .unwrap(),
-                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    monero_daemon_addresses: monero_daemon_addresses(
+                        DEFAULT_MONERO_DAEMON_ADDRESSES,
+                    ),
+                    monero_daemon_login: None,
+                    monero_wallet_rpc: Default::default(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    coin_control: Default::default(),
                 },
             }
         }
@@ -1206,10 +1738,33 @@ mod tests {
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    monero_daemon_addresses: monero_daemon_addresses(
+                        DEFAULT_MONERO_DAEMON_ADDRESSES_STAGENET,
+                    ),
+                    monero_daemon_login: None,
+                    monero_wallet_rpc: Default::default(),
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                },
+            }
+        }
+
+        pub fn resume_signet_defaults() -> Self {
+            Self {
+                env_config: env::Signet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(SIGNET),
+                cmd: Command::Resume {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_SIGNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
-                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    monero_daemon_addresses: monero_daemon_addresses(
+                        DEFAULT_MONERO_DAEMON_ADDRESSES_STAGENET,
+                    ),
+                    monero_daemon_login: None,
+                    monero_wallet_rpc: Default::default(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
                 },
             }
@@ -1223,9 +1778,13 @@ mod tests {
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
-                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    monero_daemon_addresses: monero_daemon_addresses(
+                        DEFAULT_MONERO_DAEMON_ADDRESSES,
+                    ),
+                    monero_daemon_login: None,
+                    monero_wallet_rpc: Default::default(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
                 },
             }
@@ -1239,8 +1798,7 @@ mod tests {
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
                 },
             }
@@ -1254,7 +1812,7 @@ mod tests {
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                 },
             }
@@ -1268,8 +1826,7 @@ mod tests {
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
                 },
             }
@@ -1283,7 +1840,7 @@ mod tests {
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                 },
             }
@@ -1,35 +1,50 @@
 use crate::bitcoin::EncryptedSignature;
 use crate::cli::behaviour::{Behaviour, OutEvent};
+use crate::network::address_book::{is_dialable_remotely, AddressHealth};
+use crate::network::capabilities::Capabilities;
 use crate::network::encrypted_signature;
-use crate::network::quote::BidQuote;
+use crate::network::quote::{BidQuote, SignedBidQuote};
 use crate::network::swap_setup::bob::NewSwap;
 use crate::protocol::bob::State2;
+use crate::protocol::Database;
 use crate::{env, monero};
 use anyhow::{Context, Result};
 use futures::future::{BoxFuture, OptionFuture};
 use futures::{FutureExt, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::{Multiaddr, PeerId, Swarm};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::watch;
 use uuid::Uuid;
 
+/// A signed quote is only referenced if Alice honours it for at least this
+/// long, so it does not expire while the swap is set up.
+const QUOTE_EXPIRY_MARGIN: Duration = Duration::from_secs(10);
+
 #[allow(missing_debug_implementations)]
 pub struct EventLoop {
     swap_id: Uuid,
     swarm: libp2p::Swarm<Behaviour>,
     alice_peer_id: PeerId,
+    /// Stores the addresses of Alice and how well they work.
+    db: Arc<dyn Database + Send + Sync>,
 
     // these streams represents outgoing requests that we have to make
-    quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
+    quote_requests: bmrng::RequestReceiverStream<(), SignedBidQuote>,
+    quote_subscriptions: bmrng::RequestReceiverStream<(), BidQuote>,
+    capabilities_requests: bmrng::RequestReceiverStream<(), Capabilities>,
     encrypted_signatures: bmrng::RequestReceiverStream<EncryptedSignature, ()>,
     swap_setup_requests: bmrng::RequestReceiverStream<NewSwap, Result<State2>>,
 
     // these represents requests that are currently in-flight.
     // once we get a response to a matching [`RequestId`], we will use the responder to relay the
     // response.
-    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<BidQuote>>,
+    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<SignedBidQuote>>,
+    inflight_quote_subscriptions: HashMap<RequestId, bmrng::Responder<BidQuote>>,
+    inflight_capabilities_requests: HashMap<RequestId, bmrng::Responder<Capabilities>>,
     inflight_encrypted_signature_requests: HashMap<RequestId, bmrng::Responder<()>>,
     inflight_swap_setup: Option<bmrng::Responder<Result<State2>>>,
 
@@ -43,6 +58,9 @@ pub struct EventLoop {
     /// resolves, we use the `ResponseChannel` returned from it to send an ACK
     /// to Alice that we have successfully processed the transfer proof.
     pending_transfer_proof: OptionFuture<BoxFuture<'static, ResponseChannel<()>>>,
+
+    /// The latest quote Alice pushed to us, `None` until we subscribed.
+    latest_quote: watch::Sender<Option<BidQuote>>,
 }
 
 impl EventLoop {
@@ -51,24 +69,34 @@ impl EventLoop {
         swarm: Swarm<Behaviour>,
         alice_peer_id: PeerId,
         env_config: env::Config,
+        db: Arc<dyn Database + Send + Sync>,
     ) -> Result<(Self, EventLoopHandle)> {
         let execution_setup = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let transfer_proof = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let encrypted_signature = bmrng::channel(1);
         let quote = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let quote_subscription = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let capabilities = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let (latest_quote, latest_quote_receiver) = watch::channel(None);
 
         let event_loop = EventLoop {
             swap_id,
             swarm,
             alice_peer_id,
+            db,
             swap_setup_requests: execution_setup.1.into(),
             transfer_proof: transfer_proof.0,
             encrypted_signatures: encrypted_signature.1.into(),
             quote_requests: quote.1.into(),
+            quote_subscriptions: quote_subscription.1.into(),
+            capabilities_requests: capabilities.1.into(),
             inflight_quote_requests: HashMap::default(),
+            inflight_quote_subscriptions: HashMap::default(),
+            inflight_capabilities_requests: HashMap::default(),
             inflight_swap_setup: None,
             inflight_encrypted_signature_requests: HashMap::default(),
             pending_transfer_proof: OptionFuture::from(None),
+            latest_quote,
         };
 
         let handle = EventLoopHandle {
@@ -76,6 +104,11 @@ impl EventLoop {
             transfer_proof: transfer_proof.1,
             encrypted_signature: encrypted_signature.0,
             quote: quote.0,
+            signed_quote: None,
+            alice_peer_id,
+            quote_subscription: quote_subscription.0,
+            latest_quote: latest_quote_receiver,
+            capabilities: capabilities.0,
             env_config,
         };
 
@@ -101,6 +134,28 @@ impl EventLoop {
                                 let _ = responder.respond(response);
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::CapabilitiesReceived { id, response }) => {
+                            if let Some(responder) = self.inflight_capabilities_requests.remove(&id) {
+                                let _ = responder.respond(response);
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::QuoteSubscribed { id, response }) => {
+                            let _ = self.latest_quote.send(Some(response));
+
+                            if let Some(responder) = self.inflight_quote_subscriptions.remove(&id) {
+                                let _ = responder.respond(response);
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::QuoteUpdated { quote, channel, peer }) => {
+                            if peer != self.alice_peer_id {
+                                tracing::warn!(%peer, "Ignoring quote update from peer we did not subscribe to");
+                                continue;
+                            }
+
+                            tracing::debug!(price = %quote.price, "Received quote update from Alice");
+                            let _ = self.latest_quote.send(Some(quote));
+                            let _ = self.swarm.behaviour_mut().quote_update.send_response(channel, ());
+                        }
                         SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted(response)) => {
                             if let Some(responder) = self.inflight_swap_setup.take() {
                                 let _ = responder.respond(*response);
@@ -151,6 +206,27 @@ impl EventLoop {
                             tracing::error!("Exhausted all re-dial attempts to Alice");
                             return;
                         }
+                        SwarmEvent::Behaviour(OutEvent::AddressHealthChanged { peer, address, health }) => {
+                            if let Err(error) = self.db.insert_address_health(peer, address.clone(), health).await {
+                                tracing::warn!(%address, "Failed to store health of Alice's address: {:#}", error);
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Pinged { peer, rtt }) if peer == self.alice_peer_id => {
+                            self.swarm.behaviour_mut().redial.record_latency(rtt);
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Identified { peer, listen_addresses }) if peer == self.alice_peer_id => {
+                            for address in listen_addresses.into_iter().filter(is_dialable_remotely) {
+                                if !self.swarm.behaviour_mut().add_address(address.clone()) {
+                                    continue;
+                                }
+
+                                tracing::debug!(%address, "Learned new address of Alice");
+
+                                if let Err(error) = self.db.insert_address(peer, address.clone()).await {
+                                    tracing::warn!(%address, "Failed to store address of Alice: {:#}", error);
+                                }
+                            }
+                        }
                         SwarmEvent::Behaviour(OutEvent::Failure { peer, error }) => {
                             tracing::warn!(%peer, "Communication error: {:#}", error);
                             return;
@@ -186,6 +262,14 @@ impl EventLoop {
                     let id = self.swarm.behaviour_mut().quote.send_request(&self.alice_peer_id, ());
                     self.inflight_quote_requests.insert(id, responder);
                 },
+                Some(((), responder)) = self.quote_subscriptions.next().fuse(), if self.is_connected_to_alice() => {
+                    let id = self.swarm.behaviour_mut().quote_subscribe.send_request(&self.alice_peer_id, ());
+                    self.inflight_quote_subscriptions.insert(id, responder);
+                },
+                Some(((), responder)) = self.capabilities_requests.next().fuse(), if self.is_connected_to_alice() => {
+                    let id = self.swarm.behaviour_mut().capabilities.send_request(&self.alice_peer_id, ());
+                    self.inflight_capabilities_requests.insert(id, responder);
+                },
                 Some((swap, responder)) = self.swap_setup_requests.next().fuse(), if self.is_connected_to_alice() => {
                     self.swarm.behaviour_mut().swap_setup.start(self.alice_peer_id, swap).await;
                     self.inflight_swap_setup = Some(responder);
@@ -214,12 +298,43 @@ impl EventLoop {
     }
 }
 
+/// Every address we know of the seller, together with how well it worked so
+/// far.
+pub async fn known_seller_addresses(
+    db: Arc<dyn Database + Send + Sync>,
+    seller_peer_id: PeerId,
+) -> Result<Vec<(Multiaddr, AddressHealth)>> {
+    let health = db
+        .get_address_health(seller_peer_id)
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let addresses = db
+        .get_addresses(seller_peer_id)
+        .await?
+        .into_iter()
+        .map(|address| {
+            let health = health.get(&address).copied().unwrap_or_default();
+            (address, health)
+        })
+        .collect();
+
+    Ok(addresses)
+}
+
 #[derive(Debug)]
 pub struct EventLoopHandle {
     swap_setup: bmrng::RequestSender<NewSwap, Result<State2>>,
     transfer_proof: bmrng::RequestReceiver<monero::TransferProof, ()>,
     encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
-    quote: bmrng::RequestSender<(), BidQuote>,
+    quote: bmrng::RequestSender<(), SignedBidQuote>,
+    /// The last quote Alice signed, kept until we ask her to honour it.
+    signed_quote: Option<SignedBidQuote>,
+    alice_peer_id: PeerId,
+    quote_subscription: bmrng::RequestSender<(), BidQuote>,
+    latest_quote: watch::Receiver<Option<BidQuote>>,
+    capabilities: bmrng::RequestSender<(), Capabilities>,
     env_config: env::Config,
 }
 
@@ -242,7 +357,73 @@ impl EventLoopHandle {
     }
 
     pub async fn request_quote(&mut self) -> Result<BidQuote> {
-        Ok(self.quote.send_receive(()).await?)
+        let quote = self.quote.send_receive(()).await?;
+
+        if quote.signature.is_none() {
+            tracing::debug!("Alice does not sign her quotes");
+        } else if let Err(error) = quote.verify(self.alice_peer_id) {
+            tracing::warn!("Ignoring signature of Alice's quote: {:#}", error);
+        } else {
+            self.signed_quote = Some(quote.clone());
+        }
+
+        Ok(quote.quote)
+    }
+
+    /// Returns the id of a signed quote that Alice honours for long enough to
+    /// set up a swap, requesting a new quote if the last one is about to
+    /// expire.
+    ///
+    /// The quote is logged in full so that it is kept as evidence of what
+    /// Alice offered.
+    pub async fn quote_to_honour(&mut self) -> Result<Option<Uuid>> {
+        if !self.has_fresh_signed_quote() {
+            self.request_quote().await?;
+        }
+
+        if !self.has_fresh_signed_quote() {
+            return Ok(None);
+        }
+
+        let quote = self.signed_quote.as_ref().expect("fresh signed quote");
+        let id = quote.signature.as_ref().expect("signed quote").id;
+        tracing::info!(
+            %id,
+            quote = %serde_json::to_string(quote)?,
+            "Asking Alice to honour signed quote"
+        );
+
+        Ok(Some(id))
+    }
+
+    fn has_fresh_signed_quote(&self) -> bool {
+        self.signed_quote
+            .as_ref()
+            .and_then(|quote| quote.signature.as_ref())
+            .map_or(false, |signature| {
+                !signature.expires_within(QUOTE_EXPIRY_MARGIN)
+            })
+    }
+
+    /// Subscribes to Alice's quote.
+    ///
+    /// The returned receiver always holds the latest quote. Alice pushes a new
+    /// one whenever her price or limits change, so there is no need to poll
+    /// with [`EventLoopHandle::request_quote`]. Alice has to support
+    /// [`QUOTE_SUBSCRIPTION`](crate::network::capabilities::QUOTE_SUBSCRIPTION),
+    /// see [`EventLoopHandle::request_capabilities`].
+    pub async fn subscribe_to_quotes(&mut self) -> Result<watch::Receiver<Option<BidQuote>>> {
+        self.quote_subscription.send_receive(()).await?;
+
+        Ok(self.latest_quote.clone())
+    }
+
+    /// Asks Alice what she supports.
+    ///
+    /// If Alice predates the capabilities protocol she is assumed to support
+    /// only what every version of the ASB does.
+    pub async fn request_capabilities(&mut self) -> Result<Capabilities> {
+        Ok(self.capabilities.send_receive(()).await?)
     }
 
     pub async fn send_encrypted_signature(
@@ -1,32 +1,44 @@
 use crate::network::quote::BidQuote;
 use crate::network::rendezvous::XmrBtcNamespace;
-use crate::network::{quote, swarm};
+use crate::network::{dht, quote, swarm};
 use anyhow::{Context, Result};
 use futures::StreamExt;
+use libp2p::kad::{GetProvidersOk, KademliaEvent, QueryResult};
 use libp2p::multiaddr::Protocol;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
 use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
+use libp2p::swarm::toggle::Toggle;
 use libp2p::swarm::SwarmEvent;
 use libp2p::{identity, rendezvous, Multiaddr, PeerId, Swarm};
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
 /// Returns sorted list of sellers, with [Online](Status::Online) listed first.
 ///
-/// First uses the rendezvous node to discover peers in the given namespace,
-/// then fetches a quote from each peer that was discovered. If fetching a quote
-/// from a discovered peer fails the seller's status will be
+/// First uses the rendezvous node and the DHT to discover peers in the given
+/// namespace, then fetches a quote from each peer that was discovered. Peers
+/// found through both are only listed once. If fetching a quote from a
+/// discovered peer fails the seller's status will be
 /// [Unreachable](Status::Unreachable).
 pub async fn list_sellers(
-    rendezvous_node_peer_id: PeerId,
-    rendezvous_node_addr: Multiaddr,
+    rendezvous_point: Option<(PeerId, Multiaddr)>,
+    dht_bootstrap_nodes: Vec<(PeerId, Multiaddr)>,
     namespace: XmrBtcNamespace,
     tor_socks5_port: u16,
     identity: identity::Keypair,
 ) -> Result<Vec<Seller>> {
+    let dht = if dht_bootstrap_nodes.is_empty() {
+        None
+    } else {
+        let mut dht = dht::new(identity.public().into(), dht_bootstrap_nodes);
+        dht.get_providers(dht::key(namespace));
+
+        Some(dht)
+    };
+
     let behaviour = Behaviour {
         rendezvous: rendezvous::client::Behaviour::new(identity.clone()),
         quote: quote::cli(),
@@ -35,23 +47,21 @@ pub async fn list_sellers(
                 .with_keep_alive(false)
                 .with_interval(Duration::from_secs(86_400)),
         ),
+        dht: Toggle::from(dht),
     };
     let mut swarm = swarm::cli(identity, tor_socks5_port, behaviour).await?;
 
-    swarm
-        .behaviour_mut()
-        .quote
-        .add_address(&rendezvous_node_peer_id, rendezvous_node_addr.clone());
-    swarm
-        .dial(&rendezvous_node_peer_id)
-        .context("Failed to dial rendezvous node")?;
-
-    let event_loop = EventLoop::new(
-        swarm,
-        rendezvous_node_peer_id,
-        rendezvous_node_addr,
-        namespace,
-    );
+    if let Some((rendezvous_node_peer_id, rendezvous_node_addr)) = &rendezvous_point {
+        swarm
+            .behaviour_mut()
+            .quote
+            .add_address(rendezvous_node_peer_id, rendezvous_node_addr.clone());
+        swarm
+            .dial(rendezvous_node_peer_id)
+            .context("Failed to dial rendezvous node")?;
+    }
+
+    let event_loop = EventLoop::new(swarm, rendezvous_point, namespace);
     let sellers = event_loop.run().await;
 
     Ok(sellers)
@@ -76,6 +86,7 @@ enum OutEvent {
     Rendezvous(rendezvous::client::Event),
     Quote(quote::OutEvent),
     Ping(PingEvent),
+    Dht(KademliaEvent),
 }
 
 impl From<rendezvous::client::Event> for OutEvent {
@@ -90,6 +101,12 @@ impl From<quote::OutEvent> for OutEvent {
     }
 }
 
+impl From<KademliaEvent> for OutEvent {
+    fn from(event: KademliaEvent) -> Self {
+        OutEvent::Dht(event)
+    }
+}
+
 #[derive(libp2p::NetworkBehaviour)]
 #[behaviour(event_process = false)]
 #[behaviour(out_event = "OutEvent")]
@@ -97,6 +114,7 @@ struct Behaviour {
     rendezvous: rendezvous::client::Behaviour,
     quote: quote::Behaviour,
     ping: Ping,
+    dht: Toggle<dht::Behaviour>,
 }
 
 #[derive(Debug)]
@@ -113,9 +131,11 @@ enum State {
 
 struct EventLoop {
     swarm: Swarm<Behaviour>,
-    rendezvous_peer_id: PeerId,
-    rendezvous_addr: Multiaddr,
+    rendezvous_peer_id: Option<PeerId>,
+    rendezvous_addr: Option<Multiaddr>,
     namespace: XmrBtcNamespace,
+    rendezvous_discovery_pending: bool,
+    dht_discovery_pending: bool,
     reachable_asb_address: HashMap<PeerId, Multiaddr>,
     unreachable_asb_address: HashMap<PeerId, Multiaddr>,
     asb_quote_status: HashMap<PeerId, QuoteStatus>,
@@ -125,15 +145,22 @@ struct EventLoop {
 impl EventLoop {
     fn new(
         swarm: Swarm<Behaviour>,
-        rendezvous_peer_id: PeerId,
-        rendezvous_addr: Multiaddr,
+        rendezvous_point: Option<(PeerId, Multiaddr)>,
         namespace: XmrBtcNamespace,
     ) -> Self {
+        let dht_discovery_pending = swarm.behaviour().dht.is_enabled();
+        let (rendezvous_peer_id, rendezvous_addr) = match rendezvous_point {
+            Some((peer_id, addr)) => (Some(peer_id), Some(addr)),
+            None => (None, None),
+        };
+
         Self {
             swarm,
+            rendezvous_discovery_pending: rendezvous_peer_id.is_some(),
             rendezvous_peer_id,
             rendezvous_addr,
             namespace,
+            dht_discovery_pending,
             reachable_asb_address: Default::default(),
             unreachable_asb_address: Default::default(),
             asb_quote_status: Default::default(),
@@ -147,7 +174,7 @@ impl EventLoop {
                 swarm_event = self.swarm.select_next_some() => {
                     match swarm_event {
                         SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
-                            if peer_id == self.rendezvous_peer_id{
+                            if Some(peer_id) == self.rendezvous_peer_id {
                                 tracing::info!(
                                     "Connected to rendezvous point, discovering nodes in '{}' namespace ...",
                                     self.namespace
@@ -157,7 +184,7 @@ impl EventLoop {
                                     Some(rendezvous::Namespace::new(self.namespace.to_string()).expect("our namespace to be a correct string")),
                                     None,
                                     None,
-                                    self.rendezvous_peer_id,
+                                    peer_id,
                                 );
                             } else {
                                 let address = endpoint.get_remote_address();
@@ -165,15 +192,15 @@ impl EventLoop {
                             }
                         }
                         SwarmEvent::UnreachableAddr { peer_id, error, address, .. } => {
-                            if address == self.rendezvous_addr {
+                            if Some(&address) == self.rendezvous_addr.as_ref() {
                                 tracing::error!(
                                     "Failed to connect to rendezvous point at {}: {}",
                                     address,
                                     error
                                 );
 
-                                // if the rendezvous node is unreachable we just stop
-                                return Vec::new();
+                                // if the rendezvous node is unreachable we only list what the DHT finds
+                                self.rendezvous_discovery_pending = false;
                             } else {
                                 tracing::debug!(
                                     "Failed to connect to peer at {}: {}",
@@ -195,7 +222,7 @@ impl EventLoop {
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(
                                                   libp2p::rendezvous::client::Event::Discovered { registrations, .. },
                                               )) => {
-                            self.state = State::WaitForQuoteCompletion;
+                            self.rendezvous_discovery_pending = false;
 
                             for registration in registrations {
                                 let peer = registration.record.peer_id();
@@ -221,12 +248,42 @@ impl EventLoop {
                                 let _request_id = self.swarm.behaviour_mut().quote.send_request(&peer, ());
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::Rendezvous(
+                                                  libp2p::rendezvous::client::Event::DiscoverFailed { error, .. },
+                                              )) => {
+                            tracing::error!("Failed to discover sellers at rendezvous point: {:?}", error);
+                            self.rendezvous_discovery_pending = false;
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Dht(KademliaEvent::OutboundQueryCompleted { result: QueryResult::GetProviders(result), .. })) => {
+                            self.dht_discovery_pending = false;
+
+                            let providers = match result {
+                                Ok(GetProvidersOk { providers, .. }) => providers,
+                                Err(error) => {
+                                    tracing::error!("Failed to discover sellers in the DHT: {:?}", error);
+                                    HashSet::new()
+                                }
+                            };
+
+                            for peer in providers {
+                                // sellers that are also registered at the rendezvous point are only asked once
+                                if self.asb_quote_status.contains_key(&peer) {
+                                    continue;
+                                }
+
+                                tracing::info!(peer_id=%peer, "Discovered peer in DHT");
+                                self.asb_quote_status.insert(peer, QuoteStatus::Pending);
+
+                                // the DHT knows the addresses of the peers it talked to during the lookup
+                                let _request_id = self.swarm.behaviour_mut().quote.send_request(&peer, ());
+                            }
+                        }
                         SwarmEvent::Behaviour(OutEvent::Quote(quote_response)) => {
                             match quote_response {
                                 RequestResponseEvent::Message { peer, message } => {
                                     match message {
                                         RequestResponseMessage::Response { response, .. } => {
-                                            if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response))).is_none() {
+                                            if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response.quote))).is_none() {
                                                 tracing::error!(%peer, "Received bid quote from unexpected peer, this record will be removed!");
                                                 self.asb_quote_status.remove(&peer);
                                             }
@@ -235,7 +292,7 @@ impl EventLoop {
                                     }
                                 }
                                 RequestResponseEvent::OutboundFailure { peer, error, .. } => {
-                                    if peer == self.rendezvous_peer_id {
+                                    if Some(peer) == self.rendezvous_peer_id {
                                         tracing::debug!(%peer, "Outbound failure when communicating with rendezvous node: {:#}", error);
                                     } else {
                                         tracing::debug!(%peer, "Ignoring seller, because unable to request quote: {:#}", error);
@@ -243,7 +300,7 @@ impl EventLoop {
                                     }
                                 }
                                 RequestResponseEvent::InboundFailure { peer, error, .. } => {
-                                    if peer == self.rendezvous_peer_id {
+                                    if Some(peer) == self.rendezvous_peer_id {
                                         tracing::debug!(%peer, "Inbound failure when communicating with rendezvous node: {:#}", error);
                                     } else {
                                         tracing::debug!(%peer, "Ignoring seller, because unable to request quote: {:#}", error);
@@ -258,6 +315,12 @@ impl EventLoop {
                 }
             }
 
+            if let State::WaitForDiscovery = self.state {
+                if !self.rendezvous_discovery_pending && !self.dht_discovery_pending {
+                    self.state = State::WaitForQuoteCompletion;
+                }
+            }
+
             match self.state {
                 State::WaitForDiscovery => {
                     continue;
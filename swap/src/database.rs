@@ -1,4 +1,5 @@
 pub use alice::Alice;
+pub use bitcoin_wallet::BitcoinWalletDatabase;
 pub use bob::Bob;
 pub use sqlite::SqliteDatabase;
 
@@ -11,6 +12,7 @@ use std::path::Path;
 use std::sync::Arc;
 
 mod alice;
+mod bitcoin_wallet;
 mod bob;
 mod sqlite;
 
@@ -0,0 +1,1095 @@
+use anyhow::{bail, Context, Result};
+use bdk::database::{BatchDatabase, BatchOperations, Database};
+use bdk::{KeychainKind, LocalUtxo, TransactionDetails};
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::{OutPoint, Script, Transaction, Txid};
+use futures::executor::block_on;
+use sqlx::{Connection, SqliteConnection};
+use std::convert::TryFrom;
+use std::sync::{Mutex, MutexGuard};
+
+/// The database of our bdk wallet, stored in the swap database with one row
+/// per script pubkey, UTXO and transaction.
+///
+/// bdk only talks to its database synchronously. Every connection to sqlite
+/// executes its statements on a thread of its own, so we can safely block on
+/// them from within the async runtime. The wallet has a connection to itself,
+/// requests of the swap database are never held up behind it.
+pub struct BitcoinWalletDatabase {
+    connection: Mutex<SqliteConnection>,
+}
+
+/// Changes to the wallet that are written in a single transaction.
+#[derive(Default)]
+pub struct Batch {
+    operations: Vec<Operation>,
+}
+
+enum Operation {
+    SetScriptPubkey(Script, KeychainKind, u32),
+    SetUtxo(LocalUtxo),
+    SetRawTx(Transaction),
+    SetTx(TransactionDetails),
+    SetLastIndex(KeychainKind, u32),
+    DelScriptPubkeyFromPath(KeychainKind, u32),
+    DelPathFromScriptPubkey(Script),
+    DelUtxo(OutPoint),
+    DelRawTx(Txid),
+    DelTx(Txid, bool),
+    DelLastIndex(KeychainKind),
+}
+
+impl BitcoinWalletDatabase {
+    pub fn new(connection: SqliteConnection) -> Self {
+        Self {
+            connection: Mutex::new(connection),
+        }
+    }
+
+    fn connection(&self) -> MutexGuard<'_, SqliteConnection> {
+        self.connection
+            .lock()
+            .expect("no panic while holding the Bitcoin wallet connection")
+    }
+}
+
+impl BatchOperations for BitcoinWalletDatabase {
+    fn set_script_pubkey(
+        &mut self,
+        script: &Script,
+        keychain: KeychainKind,
+        child: u32,
+    ) -> Result<(), bdk::Error> {
+        block_on(set_script_pubkey(
+            &mut self.connection(),
+            script,
+            keychain,
+            child,
+        ))
+        .map_err(to_bdk_error)
+    }
+
+    fn set_utxo(&mut self, utxo: &LocalUtxo) -> Result<(), bdk::Error> {
+        block_on(set_utxo(&mut self.connection(), utxo)).map_err(to_bdk_error)
+    }
+
+    fn set_raw_tx(&mut self, transaction: &Transaction) -> Result<(), bdk::Error> {
+        block_on(set_raw_tx(&mut self.connection(), transaction)).map_err(to_bdk_error)
+    }
+
+    fn set_tx(&mut self, transaction: &TransactionDetails) -> Result<(), bdk::Error> {
+        block_on(set_tx(&mut self.connection(), transaction)).map_err(to_bdk_error)
+    }
+
+    fn set_last_index(&mut self, keychain: KeychainKind, value: u32) -> Result<(), bdk::Error> {
+        block_on(set_last_index(&mut self.connection(), keychain, value)).map_err(to_bdk_error)
+    }
+
+    fn del_script_pubkey_from_path(
+        &mut self,
+        keychain: KeychainKind,
+        child: u32,
+    ) -> Result<Option<Script>, bdk::Error> {
+        block_on(del_script_pubkey_from_path(
+            &mut self.connection(),
+            keychain,
+            child,
+        ))
+        .map_err(to_bdk_error)
+    }
+
+    fn del_path_from_script_pubkey(
+        &mut self,
+        script: &Script,
+    ) -> Result<Option<(KeychainKind, u32)>, bdk::Error> {
+        block_on(del_path_from_script_pubkey(&mut self.connection(), script)).map_err(to_bdk_error)
+    }
+
+    fn del_utxo(&mut self, outpoint: &OutPoint) -> Result<Option<LocalUtxo>, bdk::Error> {
+        block_on(del_utxo(&mut self.connection(), outpoint)).map_err(to_bdk_error)
+    }
+
+    fn del_raw_tx(&mut self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        block_on(del_raw_tx(&mut self.connection(), txid)).map_err(to_bdk_error)
+    }
+
+    fn del_tx(
+        &mut self,
+        txid: &Txid,
+        include_raw: bool,
+    ) -> Result<Option<TransactionDetails>, bdk::Error> {
+        block_on(del_tx(&mut self.connection(), txid, include_raw)).map_err(to_bdk_error)
+    }
+
+    fn del_last_index(&mut self, keychain: KeychainKind) -> Result<Option<u32>, bdk::Error> {
+        block_on(del_last_index(&mut self.connection(), keychain)).map_err(to_bdk_error)
+    }
+}
+
+impl Database for BitcoinWalletDatabase {
+    fn check_descriptor_checksum<B: AsRef<[u8]>>(
+        &mut self,
+        keychain: KeychainKind,
+        bytes: B,
+    ) -> Result<(), bdk::Error> {
+        let checksum = bytes.as_ref().to_hex();
+        let stored = block_on(get_descriptor_checksum(&mut self.connection(), keychain))
+            .map_err(to_bdk_error)?;
+
+        match stored {
+            Some(stored) if stored == checksum => Ok(()),
+            Some(_) => Err(bdk::Error::ChecksumMismatch),
+            None => block_on(set_descriptor_checksum(
+                &mut self.connection(),
+                keychain,
+                &checksum,
+            ))
+            .map_err(to_bdk_error),
+        }
+    }
+
+    fn iter_script_pubkeys(
+        &self,
+        keychain: Option<KeychainKind>,
+    ) -> Result<Vec<Script>, bdk::Error> {
+        block_on(iter_script_pubkeys(&mut self.connection(), keychain)).map_err(to_bdk_error)
+    }
+
+    fn iter_utxos(&self) -> Result<Vec<LocalUtxo>, bdk::Error> {
+        block_on(iter_utxos(&mut self.connection())).map_err(to_bdk_error)
+    }
+
+    fn iter_raw_txs(&self) -> Result<Vec<Transaction>, bdk::Error> {
+        block_on(iter_raw_txs(&mut self.connection())).map_err(to_bdk_error)
+    }
+
+    fn iter_txs(&self, include_raw: bool) -> Result<Vec<TransactionDetails>, bdk::Error> {
+        block_on(iter_txs(&mut self.connection(), include_raw)).map_err(to_bdk_error)
+    }
+
+    fn get_script_pubkey_from_path(
+        &self,
+        keychain: KeychainKind,
+        child: u32,
+    ) -> Result<Option<Script>, bdk::Error> {
+        block_on(get_script_pubkey_from_path(
+            &mut self.connection(),
+            keychain,
+            child,
+        ))
+        .map_err(to_bdk_error)
+    }
+
+    fn get_path_from_script_pubkey(
+        &self,
+        script: &Script,
+    ) -> Result<Option<(KeychainKind, u32)>, bdk::Error> {
+        block_on(get_path_from_script_pubkey(&mut self.connection(), script)).map_err(to_bdk_error)
+    }
+
+    fn get_utxo(&self, outpoint: &OutPoint) -> Result<Option<LocalUtxo>, bdk::Error> {
+        block_on(get_utxo(&mut self.connection(), outpoint)).map_err(to_bdk_error)
+    }
+
+    fn get_raw_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        block_on(get_raw_tx(&mut self.connection(), txid)).map_err(to_bdk_error)
+    }
+
+    fn get_tx(
+        &self,
+        txid: &Txid,
+        include_raw: bool,
+    ) -> Result<Option<TransactionDetails>, bdk::Error> {
+        block_on(get_tx(&mut self.connection(), txid, include_raw)).map_err(to_bdk_error)
+    }
+
+    fn get_last_index(&self, keychain: KeychainKind) -> Result<Option<u32>, bdk::Error> {
+        block_on(get_last_index(&mut self.connection(), keychain)).map_err(to_bdk_error)
+    }
+
+    fn increment_last_index(&mut self, keychain: KeychainKind) -> Result<u32, bdk::Error> {
+        block_on(increment_last_index(&mut self.connection(), keychain)).map_err(to_bdk_error)
+    }
+
+    fn flush(&mut self) -> Result<(), bdk::Error> {
+        // Every change is written as soon as it is made.
+        Ok(())
+    }
+}
+
+impl BatchDatabase for BitcoinWalletDatabase {
+    type Batch = Batch;
+
+    fn begin_batch(&self) -> Self::Batch {
+        Batch::default()
+    }
+
+    fn commit_batch(&mut self, batch: Self::Batch) -> Result<(), bdk::Error> {
+        block_on(commit_batch(&mut self.connection(), batch)).map_err(to_bdk_error)
+    }
+}
+
+impl BatchOperations for Batch {
+    fn set_script_pubkey(
+        &mut self,
+        script: &Script,
+        keychain: KeychainKind,
+        child: u32,
+    ) -> Result<(), bdk::Error> {
+        self.operations
+            .push(Operation::SetScriptPubkey(script.clone(), keychain, child));
+        Ok(())
+    }
+
+    fn set_utxo(&mut self, utxo: &LocalUtxo) -> Result<(), bdk::Error> {
+        self.operations.push(Operation::SetUtxo(utxo.clone()));
+        Ok(())
+    }
+
+    fn set_raw_tx(&mut self, transaction: &Transaction) -> Result<(), bdk::Error> {
+        self.operations
+            .push(Operation::SetRawTx(transaction.clone()));
+        Ok(())
+    }
+
+    fn set_tx(&mut self, transaction: &TransactionDetails) -> Result<(), bdk::Error> {
+        self.operations.push(Operation::SetTx(transaction.clone()));
+        Ok(())
+    }
+
+    fn set_last_index(&mut self, keychain: KeychainKind, value: u32) -> Result<(), bdk::Error> {
+        self.operations
+            .push(Operation::SetLastIndex(keychain, value));
+        Ok(())
+    }
+
+    // Like the batches of bdk's own databases, deletions in a batch don't know
+    // what they are going to delete until the batch is committed.
+
+    fn del_script_pubkey_from_path(
+        &mut self,
+        keychain: KeychainKind,
+        child: u32,
+    ) -> Result<Option<Script>, bdk::Error> {
+        self.operations
+            .push(Operation::DelScriptPubkeyFromPath(keychain, child));
+        Ok(None)
+    }
+
+    fn del_path_from_script_pubkey(
+        &mut self,
+        script: &Script,
+    ) -> Result<Option<(KeychainKind, u32)>, bdk::Error> {
+        self.operations
+            .push(Operation::DelPathFromScriptPubkey(script.clone()));
+        Ok(None)
+    }
+
+    fn del_utxo(&mut self, outpoint: &OutPoint) -> Result<Option<LocalUtxo>, bdk::Error> {
+        self.operations.push(Operation::DelUtxo(*outpoint));
+        Ok(None)
+    }
+
+    fn del_raw_tx(&mut self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        self.operations.push(Operation::DelRawTx(*txid));
+        Ok(None)
+    }
+
+    fn del_tx(
+        &mut self,
+        txid: &Txid,
+        include_raw: bool,
+    ) -> Result<Option<TransactionDetails>, bdk::Error> {
+        self.operations.push(Operation::DelTx(*txid, include_raw));
+        Ok(None)
+    }
+
+    fn del_last_index(&mut self, keychain: KeychainKind) -> Result<Option<u32>, bdk::Error> {
+        self.operations.push(Operation::DelLastIndex(keychain));
+        Ok(None)
+    }
+}
+
+async fn commit_batch(conn: &mut SqliteConnection, batch: Batch) -> Result<()> {
+    let mut tx = conn.begin().await?;
+
+    for operation in batch.operations {
+        match operation {
+            Operation::SetScriptPubkey(script, keychain, child) => {
+                set_script_pubkey(&mut tx, &script, keychain, child).await?
+            }
+            Operation::SetUtxo(utxo) => set_utxo(&mut tx, &utxo).await?,
+            Operation::SetRawTx(transaction) => set_raw_tx(&mut tx, &transaction).await?,
+            Operation::SetTx(transaction) => set_tx(&mut tx, &transaction).await?,
+            Operation::SetLastIndex(keychain, value) => {
+                set_last_index(&mut tx, keychain, value).await?
+            }
+            Operation::DelScriptPubkeyFromPath(keychain, child) => {
+                del_script_pubkey_from_path(&mut tx, keychain, child).await?;
+            }
+            Operation::DelPathFromScriptPubkey(script) => {
+                del_path_from_script_pubkey(&mut tx, &script).await?;
+            }
+            Operation::DelUtxo(outpoint) => {
+                del_utxo(&mut tx, &outpoint).await?;
+            }
+            Operation::DelRawTx(txid) => {
+                del_raw_tx(&mut tx, &txid).await?;
+            }
+            Operation::DelTx(txid, include_raw) => {
+                del_tx(&mut tx, &txid, include_raw).await?;
+            }
+            Operation::DelLastIndex(keychain) => {
+                del_last_index(&mut tx, keychain).await?;
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn set_script_pubkey(
+    conn: &mut SqliteConnection,
+    script: &Script,
+    keychain: KeychainKind,
+    child: u32,
+) -> Result<()> {
+    let script = script.as_bytes().to_hex();
+    let keychain = keychain_to_str(keychain);
+    let child = i64::from(child);
+
+    sqlx::query!(
+        r#"
+        insert or replace into bitcoin_wallet_script_pubkeys (
+            script,
+            keychain,
+            child
+            ) values (?, ?, ?);
+        "#,
+        script,
+        keychain,
+        child
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_script_pubkey_from_path(
+    conn: &mut SqliteConnection,
+    keychain: KeychainKind,
+    child: u32,
+) -> Result<Option<Script>> {
+    let keychain = keychain_to_str(keychain);
+    let child = i64::from(child);
+
+    let row = sqlx::query!(
+        r#"
+        SELECT script
+        FROM bitcoin_wallet_script_pubkeys
+        WHERE keychain = ? AND child = ?
+        "#,
+        keychain,
+        child
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    row.map(|row| script_from_hex(&row.script)).transpose()
+}
+
+async fn get_path_from_script_pubkey(
+    conn: &mut SqliteConnection,
+    script: &Script,
+) -> Result<Option<(KeychainKind, u32)>> {
+    let script = script.as_bytes().to_hex();
+
+    let row = sqlx::query!(
+        r#"
+        SELECT keychain, child
+        FROM bitcoin_wallet_script_pubkeys
+        WHERE script = ?
+        "#,
+        script
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    row.map(|row| {
+        let keychain = keychain_from_str(&row.keychain)?;
+        let child = u32::try_from(row.child)?;
+
+        Ok((keychain, child))
+    })
+    .transpose()
+}
+
+async fn del_script_pubkey_from_path(
+    conn: &mut SqliteConnection,
+    keychain: KeychainKind,
+    child: u32,
+) -> Result<Option<Script>> {
+    let script = get_script_pubkey_from_path(&mut *conn, keychain, child).await?;
+
+    let keychain = keychain_to_str(keychain);
+    let child = i64::from(child);
+
+    sqlx::query!(
+        r#"
+        DELETE FROM bitcoin_wallet_script_pubkeys
+        WHERE keychain = ? AND child = ?
+        "#,
+        keychain,
+        child
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(script)
+}
+
+async fn del_path_from_script_pubkey(
+    conn: &mut SqliteConnection,
+    script: &Script,
+) -> Result<Option<(KeychainKind, u32)>> {
+    let path = get_path_from_script_pubkey(&mut *conn, script).await?;
+
+    let script = script.as_bytes().to_hex();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM bitcoin_wallet_script_pubkeys
+        WHERE script = ?
+        "#,
+        script
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(path)
+}
+
+async fn iter_script_pubkeys(
+    conn: &mut SqliteConnection,
+    keychain: Option<KeychainKind>,
+) -> Result<Vec<Script>> {
+    let scripts = match keychain {
+        Some(keychain) => {
+            let keychain = keychain_to_str(keychain);
+
+            sqlx::query!(
+                r#"
+        SELECT script
+        FROM bitcoin_wallet_script_pubkeys
+        WHERE keychain = ?
+        "#,
+                keychain
+            )
+            .fetch_all(conn)
+            .await?
+            .into_iter()
+            .map(|row| row.script)
+            .collect::<Vec<_>>()
+        }
+        None => sqlx::query!(
+            r#"
+        SELECT script
+        FROM bitcoin_wallet_script_pubkeys
+        "#
+        )
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|row| row.script)
+        .collect(),
+    };
+
+    scripts
+        .iter()
+        .map(|script| script_from_hex(script))
+        .collect()
+}
+
+async fn set_utxo(conn: &mut SqliteConnection, utxo: &LocalUtxo) -> Result<()> {
+    let outpoint = utxo.outpoint.to_string();
+    let utxo = serde_json::to_string(utxo)?;
+
+    sqlx::query!(
+        r#"
+        insert or replace into bitcoin_wallet_utxos (
+            outpoint,
+            utxo
+            ) values (?, ?);
+        "#,
+        outpoint,
+        utxo
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_utxo(conn: &mut SqliteConnection, outpoint: &OutPoint) -> Result<Option<LocalUtxo>> {
+    let outpoint = outpoint.to_string();
+
+    let row = sqlx::query!(
+        r#"
+        SELECT utxo
+        FROM bitcoin_wallet_utxos
+        WHERE outpoint = ?
+        "#,
+        outpoint
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    row.map(|row| serde_json::from_str(&row.utxo))
+        .transpose()
+        .context("Failed to deserialize UTXO of Bitcoin wallet")
+}
+
+async fn del_utxo(conn: &mut SqliteConnection, outpoint: &OutPoint) -> Result<Option<LocalUtxo>> {
+    let utxo = get_utxo(&mut *conn, outpoint).await?;
+
+    let outpoint = outpoint.to_string();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM bitcoin_wallet_utxos
+        WHERE outpoint = ?
+        "#,
+        outpoint
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(utxo)
+}
+
+async fn iter_utxos(conn: &mut SqliteConnection) -> Result<Vec<LocalUtxo>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT utxo
+        FROM bitcoin_wallet_utxos
+        "#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            serde_json::from_str(&row.utxo).context("Failed to deserialize UTXO of Bitcoin wallet")
+        })
+        .collect()
+}
+
+async fn set_raw_tx(conn: &mut SqliteConnection, transaction: &Transaction) -> Result<()> {
+    let txid = transaction.txid().to_string();
+    let transaction = serialize_hex(transaction);
+
+    sqlx::query!(
+        r#"
+        insert or replace into bitcoin_wallet_raw_txs (
+            txid,
+            tx
+            ) values (?, ?);
+        "#,
+        txid,
+        transaction
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_raw_tx(conn: &mut SqliteConnection, txid: &Txid) -> Result<Option<Transaction>> {
+    let txid = txid.to_string();
+
+    let row = sqlx::query!(
+        r#"
+        SELECT tx
+        FROM bitcoin_wallet_raw_txs
+        WHERE txid = ?
+        "#,
+        txid
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    row.map(|row| transaction_from_hex(&row.tx)).transpose()
+}
+
+async fn del_raw_tx(conn: &mut SqliteConnection, txid: &Txid) -> Result<Option<Transaction>> {
+    let transaction = get_raw_tx(&mut *conn, txid).await?;
+
+    let txid = txid.to_string();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM bitcoin_wallet_raw_txs
+        WHERE txid = ?
+        "#,
+        txid
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(transaction)
+}
+
+async fn iter_raw_txs(conn: &mut SqliteConnection) -> Result<Vec<Transaction>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT tx
+        FROM bitcoin_wallet_raw_txs
+        "#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    rows.iter()
+        .map(|row| transaction_from_hex(&row.tx))
+        .collect()
+}
+
+/// Stores the details of a transaction, and the transaction itself if it is
+/// part of them, in a row of its own.
+async fn set_tx(conn: &mut SqliteConnection, transaction: &TransactionDetails) -> Result<()> {
+    if let Some(raw) = &transaction.transaction {
+        set_raw_tx(&mut *conn, raw).await?;
+    }
+
+    let txid = transaction.txid.to_string();
+    let details = serde_json::to_string(&TransactionDetails {
+        transaction: None,
+        ..transaction.clone()
+    })?;
+
+    sqlx::query!(
+        r#"
+        insert or replace into bitcoin_wallet_txs (
+            txid,
+            details
+            ) values (?, ?);
+        "#,
+        txid,
+        details
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_tx(
+    conn: &mut SqliteConnection,
+    txid: &Txid,
+    include_raw: bool,
+) -> Result<Option<TransactionDetails>> {
+    let txid_str = txid.to_string();
+
+    let row = sqlx::query!(
+        r#"
+        SELECT details
+        FROM bitcoin_wallet_txs
+        WHERE txid = ?
+        "#,
+        txid_str
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let mut details = match row {
+        Some(row) => transaction_details_from_json(&row.details)?,
+        None => return Ok(None),
+    };
+
+    if include_raw {
+        details.transaction = get_raw_tx(conn, txid).await?;
+    }
+
+    Ok(Some(details))
+}
+
+async fn del_tx(
+    conn: &mut SqliteConnection,
+    txid: &Txid,
+    include_raw: bool,
+) -> Result<Option<TransactionDetails>> {
+    let mut details = get_tx(&mut *conn, txid, false).await?;
+
+    if include_raw {
+        let raw = del_raw_tx(&mut *conn, txid).await?;
+        if let Some(details) = details.as_mut() {
+            details.transaction = raw;
+        }
+    }
+
+    let txid = txid.to_string();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM bitcoin_wallet_txs
+        WHERE txid = ?
+        "#,
+        txid
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(details)
+}
+
+async fn iter_txs(
+    conn: &mut SqliteConnection,
+    include_raw: bool,
+) -> Result<Vec<TransactionDetails>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT details
+        FROM bitcoin_wallet_txs
+        "#
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut transactions = rows
+        .iter()
+        .map(|row| transaction_details_from_json(&row.details))
+        .collect::<Result<Vec<_>>>()?;
+
+    if include_raw {
+        for details in transactions.iter_mut() {
+            details.transaction = get_raw_tx(&mut *conn, &details.txid).await?;
+        }
+    }
+
+    Ok(transactions)
+}
+
+async fn set_last_index(
+    conn: &mut SqliteConnection,
+    keychain: KeychainKind,
+    value: u32,
+) -> Result<()> {
+    let keychain = keychain_to_str(keychain);
+    let value = i64::from(value);
+
+    sqlx::query!(
+        r#"
+        insert or replace into bitcoin_wallet_last_indices (
+            keychain,
+            last_index
+            ) values (?, ?);
+        "#,
+        keychain,
+        value
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_last_index(
+    conn: &mut SqliteConnection,
+    keychain: KeychainKind,
+) -> Result<Option<u32>> {
+    let keychain = keychain_to_str(keychain);
+
+    let row = sqlx::query!(
+        r#"
+        SELECT last_index
+        FROM bitcoin_wallet_last_indices
+        WHERE keychain = ?
+        "#,
+        keychain
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|row| u32::try_from(row.last_index)).transpose()?)
+}
+
+async fn del_last_index(
+    conn: &mut SqliteConnection,
+    keychain: KeychainKind,
+) -> Result<Option<u32>> {
+    let index = get_last_index(&mut *conn, keychain).await?;
+
+    let keychain = keychain_to_str(keychain);
+
+    sqlx::query!(
+        r#"
+        DELETE FROM bitcoin_wallet_last_indices
+        WHERE keychain = ?
+        "#,
+        keychain
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(index)
+}
+
+/// Hands out the next index of the keychain, starting at 0.
+async fn increment_last_index(conn: &mut SqliteConnection, keychain: KeychainKind) -> Result<u32> {
+    let mut tx = conn.begin().await?;
+
+    let next = match get_last_index(&mut tx, keychain).await? {
+        Some(index) => index.checked_add(1).context("Keychain is exhausted")?,
+        None => 0,
+    };
+    set_last_index(&mut tx, keychain, next).await?;
+
+    tx.commit().await?;
+
+    Ok(next)
+}
+
+async fn get_descriptor_checksum(
+    conn: &mut SqliteConnection,
+    keychain: KeychainKind,
+) -> Result<Option<String>> {
+    let keychain = keychain_to_str(keychain);
+
+    let row = sqlx::query!(
+        r#"
+        SELECT checksum
+        FROM bitcoin_wallet_checksums
+        WHERE keychain = ?
+        "#,
+        keychain
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|row| row.checksum))
+}
+
+async fn set_descriptor_checksum(
+    conn: &mut SqliteConnection,
+    keychain: KeychainKind,
+    checksum: &str,
+) -> Result<()> {
+    let keychain = keychain_to_str(keychain);
+
+    sqlx::query!(
+        r#"
+        insert into bitcoin_wallet_checksums (
+            keychain,
+            checksum
+            ) values (?, ?);
+        "#,
+        keychain,
+        checksum
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+fn keychain_to_str(keychain: KeychainKind) -> &'static str {
+    match keychain {
+        KeychainKind::External => "external",
+        KeychainKind::Internal => "internal",
+    }
+}
+
+fn keychain_from_str(keychain: &str) -> Result<KeychainKind> {
+    match keychain {
+        "external" => Ok(KeychainKind::External),
+        "internal" => Ok(KeychainKind::Internal),
+        other => bail!("Unknown keychain {}", other),
+    }
+}
+
+fn script_from_hex(script: &str) -> Result<Script> {
+    Ok(Script::from(Vec::<u8>::from_hex(script)?))
+}
+
+fn transaction_from_hex(transaction: &str) -> Result<Transaction> {
+    Ok(deserialize(&Vec::<u8>::from_hex(transaction)?)?)
+}
+
+fn transaction_details_from_json(details: &str) -> Result<TransactionDetails> {
+    serde_json::from_str(details).context("Failed to deserialize transaction of Bitcoin wallet")
+}
+
+fn to_bdk_error(error: anyhow::Error) -> bdk::Error {
+    bdk::Error::Generic(format!("{:#}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SqliteDatabase;
+    use crate::protocol::Database as _;
+    use bitcoin::{Address, TxIn, TxOut};
+    use std::fs::File;
+    use std::str::FromStr;
+    use tempfile::tempdir;
+
+    async fn wallet_database() -> BitcoinWalletDatabase {
+        let path = tempdir().unwrap().into_path().join("sqlite");
+        File::create(&path).unwrap();
+
+        SqliteDatabase::open(path)
+            .await
+            .unwrap()
+            .open_bitcoin_wallet()
+            .await
+            .unwrap()
+    }
+
+    fn transaction(script: &Script) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: script.clone(),
+            }],
+        }
+    }
+
+    fn script() -> Script {
+        Address::from_str("bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw")
+            .unwrap()
+            .script_pubkey()
+    }
+
+    #[tokio::test]
+    async fn script_pubkeys_are_found_by_path_and_script() {
+        let mut database = wallet_database().await;
+        let script = script();
+
+        database
+            .set_script_pubkey(&script, KeychainKind::External, 7)
+            .unwrap();
+
+        assert_eq!(
+            database
+                .get_script_pubkey_from_path(KeychainKind::External, 7)
+                .unwrap(),
+            Some(script.clone())
+        );
+        assert_eq!(
+            database.get_path_from_script_pubkey(&script).unwrap(),
+            Some((KeychainKind::External, 7))
+        );
+        assert_eq!(
+            database
+                .iter_script_pubkeys(Some(KeychainKind::Internal))
+                .unwrap(),
+            vec![]
+        );
+
+        assert_eq!(
+            database.del_path_from_script_pubkey(&script).unwrap(),
+            Some((KeychainKind::External, 7))
+        );
+        assert_eq!(database.iter_script_pubkeys(None).unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn raw_transaction_is_stored_apart_from_its_details() {
+        let mut database = wallet_database().await;
+        let tx = transaction(&script());
+        let details = TransactionDetails {
+            transaction: Some(tx.clone()),
+            txid: tx.txid(),
+            received: 10_000,
+            fee: Some(500),
+            ..Default::default()
+        };
+
+        database.set_tx(&details).unwrap();
+
+        assert_eq!(database.get_raw_tx(&tx.txid()).unwrap(), Some(tx.clone()));
+        assert_eq!(
+            database
+                .get_tx(&tx.txid(), false)
+                .unwrap()
+                .unwrap()
+                .transaction,
+            None
+        );
+        assert_eq!(
+            database.get_tx(&tx.txid(), true).unwrap(),
+            Some(details.clone())
+        );
+
+        assert_eq!(database.del_tx(&tx.txid(), true).unwrap(), Some(details));
+        assert_eq!(database.iter_raw_txs().unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn batch_is_written_on_commit() {
+        let mut database = wallet_database().await;
+        let tx = transaction(&script());
+
+        let mut batch = database.begin_batch();
+        batch.set_raw_tx(&tx).unwrap();
+        batch.set_last_index(KeychainKind::Internal, 3).unwrap();
+        assert_eq!(database.iter_raw_txs().unwrap(), vec![]);
+
+        database.commit_batch(batch).unwrap();
+
+        assert_eq!(database.iter_raw_txs().unwrap(), vec![tx]);
+        assert_eq!(
+            database.get_last_index(KeychainKind::Internal).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[tokio::test]
+    async fn last_index_starts_at_zero() {
+        let mut database = wallet_database().await;
+
+        assert_eq!(
+            database
+                .increment_last_index(KeychainKind::External)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            database
+                .increment_last_index(KeychainKind::External)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            database.get_last_index(KeychainKind::Internal).unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn given_other_descriptor_then_checksum_does_not_match() {
+        let mut database = wallet_database().await;
+
+        database
+            .check_descriptor_checksum(KeychainKind::External, b"checksum")
+            .unwrap();
+        database
+            .check_descriptor_checksum(KeychainKind::External, b"checksum")
+            .unwrap();
+
+        assert!(matches!(
+            database.check_descriptor_checksum(KeychainKind::External, b"other"),
+            Err(bdk::Error::ChecksumMismatch)
+        ));
+        database
+            .check_descriptor_checksum(KeychainKind::Internal, b"other")
+            .unwrap();
+    }
+}
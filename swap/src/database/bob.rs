@@ -14,6 +14,8 @@ pub enum Bob {
         btc_amount: bitcoin::Amount,
         #[serde_as(as = "DisplayFromStr")]
         change_address: bitcoin::Address,
+        #[serde(default)]
+        coin_control: crate::bitcoin::CoinControl,
     },
     ExecutionSetupDone {
         state2: bob::State2,
@@ -53,9 +55,11 @@ impl From<BobState> for Bob {
             BobState::Started {
                 btc_amount,
                 change_address,
+                coin_control,
             } => Bob::Started {
                 btc_amount,
                 change_address,
+                coin_control,
             },
             BobState::SwapSetupCompleted(state2) => Bob::ExecutionSetupDone { state2 },
             BobState::BtcLocked {
@@ -97,9 +101,11 @@ impl From<Bob> for BobState {
             Bob::Started {
                 btc_amount,
                 change_address,
+                coin_control,
             } => BobState::Started {
                 btc_amount,
                 change_address,
+                coin_control,
             },
             Bob::ExecutionSetupDone { state2 } => BobState::SwapSetupCompleted(state2),
             Bob::BtcLocked {
@@ -1,18 +1,24 @@
-use crate::database::Swap;
+use crate::asb::limits::Ban;
+use crate::bitcoin::{Label, Labelled, SwapLabels, Txid, WalletState};
+use crate::database::{BitcoinWalletDatabase, Swap};
 use crate::monero::Address;
+use crate::network::address_book::AddressHealth;
 use crate::protocol::{Database, State};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use libp2p::{Multiaddr, PeerId};
 use sqlx::sqlite::Sqlite;
-use sqlx::{Pool, SqlitePool};
+use sqlx::{Connection, Pool, SqliteConnection, SqlitePool};
+use std::convert::TryFrom;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
 pub struct SqliteDatabase {
     pool: Pool<Sqlite>,
+    url: String,
 }
 
 impl SqliteDatabase {
@@ -22,7 +28,10 @@ impl SqliteDatabase {
     {
         let path_str = format!("sqlite:{}", path.as_ref().display());
         let pool = SqlitePool::connect(&path_str).await?;
-        let mut sqlite = Self { pool };
+        let mut sqlite = Self {
+            pool,
+            url: path_str,
+        };
         sqlite.run_migrations().await?;
         Ok(sqlite)
     }
@@ -169,6 +178,79 @@ impl Database for SqliteDatabase {
         addresses
     }
 
+    async fn insert_address_health(
+        &self,
+        peer_id: PeerId,
+        address: Multiaddr,
+        health: AddressHealth,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+        let address = address.to_string();
+        let last_success = health.last_success.map(i64::try_from).transpose()?;
+        let failures = i64::from(health.failures);
+        let latency_ms = health
+            .latency
+            .map(|latency| i64::try_from(latency.as_millis()))
+            .transpose()?;
+
+        sqlx::query!(
+            r#"
+            insert or replace into peer_address_health (
+                peer_id,
+                address,
+                last_success,
+                failures,
+                latency_ms
+                ) values (?, ?, ?, ?, ?);
+            "#,
+            peer_id,
+            address,
+            last_success,
+            failures,
+            latency_ms
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_address_health(&self, peer_id: PeerId) -> Result<Vec<(Multiaddr, AddressHealth)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT address, last_success, failures, latency_ms
+        FROM peer_address_health
+        WHERE peer_id = ?
+        "#,
+            peer_id,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let address = Multiaddr::from_str(&row.address)?;
+                let health = AddressHealth {
+                    last_success: row.last_success.map(u64::try_from).transpose()?,
+                    failures: u32::try_from(row.failures)?,
+                    latency: row
+                        .latency_ms
+                        .map(u64::try_from)
+                        .transpose()?
+                        .map(Duration::from_millis),
+                };
+
+                Ok((address, health))
+            })
+            .collect()
+    }
+
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
         let entered_at = OffsetDateTime::now_utc();
@@ -249,6 +331,237 @@ impl Database for SqliteDatabase {
 
         result
     }
+
+    async fn insert_swap_labels(&self, swap_id: Uuid, labels: SwapLabels) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let swap_id = swap_id.to_string();
+
+        for (address, label) in labels.addresses {
+            let address = address.to_string();
+            let label = label.to_string();
+
+            sqlx::query!(
+                r#"
+            insert or replace into bitcoin_address_labels (
+                address,
+                label,
+                swap_id
+                ) values (?, ?, ?);
+            "#,
+                address,
+                label,
+                swap_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for (txid, label) in labels.transactions {
+            let txid = txid.to_string();
+            let label = label.to_string();
+
+            sqlx::query!(
+                r#"
+            insert or replace into bitcoin_transaction_labels (
+                txid,
+                label,
+                swap_id
+                ) values (?, ?, ?);
+            "#,
+                txid,
+                label,
+                swap_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert_bitcoin_transaction_label(
+        &self,
+        txid: Txid,
+        label: Label,
+        swap_id: Option<Uuid>,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let txid = txid.to_string();
+        let label = label.to_string();
+        let swap_id = swap_id.map(|swap_id| swap_id.to_string());
+
+        sqlx::query!(
+            r#"
+            insert or replace into bitcoin_transaction_labels (
+                txid,
+                label,
+                swap_id
+                ) values (?, ?, ?);
+            "#,
+            txid,
+            label,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_bitcoin_address_labels(&self) -> Result<Vec<Labelled<bitcoin::Address>>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT address, label, swap_id
+        FROM bitcoin_address_labels
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(Labelled {
+                    inner: bitcoin::Address::from_str(&row.address)?,
+                    label: Label::from_str(&row.label)?,
+                    swap_id: row.swap_id.as_deref().map(Uuid::from_str).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_bitcoin_transaction_labels(&self) -> Result<Vec<Labelled<Txid>>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT txid, label, swap_id
+        FROM bitcoin_transaction_labels
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(Labelled {
+                    inner: Txid::from_str(&row.txid)?,
+                    label: Label::from_str(&row.label)?,
+                    swap_id: row.swap_id.as_deref().map(Uuid::from_str).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    async fn open_bitcoin_wallet(&self) -> Result<BitcoinWalletDatabase> {
+        let mut conn = SqliteConnection::connect(&self.url).await?;
+
+        // Earlier versions kept the whole wallet in a single row. It is moved
+        // into the tables of the wallet database the first time it is opened.
+        let row = sqlx::query!(
+            r#"
+        SELECT state
+        FROM bitcoin_wallet
+        WHERE id = 0
+        "#
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+        let legacy_state = row
+            .map(|row| serde_json::from_str::<WalletState>(&row.state))
+            .transpose()
+            .context("Failed to deserialize Bitcoin wallet state")?;
+
+        let mut database = BitcoinWalletDatabase::new(conn);
+
+        if let Some(state) = legacy_state {
+            state.write_to(&mut database)?;
+
+            let mut conn = self.pool.acquire().await?;
+            sqlx::query!(
+                r#"
+            DELETE FROM bitcoin_wallet
+            WHERE id = 0
+            "#
+            )
+            .execute(&mut conn)
+            .await?;
+
+            tracing::info!("Moved Bitcoin wallet into its own tables");
+        }
+
+        Ok(database)
+    }
+
+    async fn insert_ban(&self, ban: Ban) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = ban.peer_id.to_string();
+        let expires_at = i64::try_from(ban.expires_at)?;
+
+        sqlx::query!(
+            r#"
+            insert or replace into banned_peers (
+                peer_id,
+                expires_at,
+                reason
+                ) values (?, ?, ?);
+            "#,
+            peer_id,
+            expires_at,
+            ban.reason
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_bans(&self) -> Result<Vec<Ban>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT peer_id, expires_at, reason
+        FROM banned_peers
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Ban {
+                    peer_id: PeerId::from_str(&row.peer_id)?,
+                    expires_at: u64::try_from(row.expires_at)?,
+                    reason: row.reason,
+                })
+            })
+            .collect()
+    }
+
+    async fn remove_ban(&self, peer_id: PeerId) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+
+        sqlx::query!(
+            r#"
+            delete from banned_peers
+            where peer_id = ?
+            "#,
+            peer_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -256,6 +569,8 @@ mod tests {
     use super::*;
     use crate::protocol::alice::AliceState;
     use crate::protocol::bob::BobState;
+    use bdk::database::{BatchOperations, Database as _, MemoryDatabase};
+    use bdk::KeychainKind;
     use std::fs::File;
     use tempfile::tempdir;
 
@@ -370,6 +685,127 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_insert_and_load_bitcoin_labels() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let address = "bcrt1q08pfqpsyrt7acllzyjm8q5qsz5capvyahm49rw".parse::<bitcoin::Address>()?;
+        let tx_lock_id =
+            Txid::from_str("0101010101010101010101010101010101010101010101010101010101010101")?;
+        let tx_withdraw_id =
+            Txid::from_str("0202020202020202020202020202020202020202020202020202020202020202")?;
+
+        db.insert_swap_labels(swap_id, SwapLabels {
+            addresses: vec![(address.clone(), Label::Refund)],
+            transactions: vec![(tx_lock_id, Label::Lock)],
+        })
+        .await?;
+        db.insert_bitcoin_transaction_label(tx_withdraw_id, Label::Withdraw, None)
+            .await?;
+
+        let address_labels = db.get_bitcoin_address_labels().await?;
+        let transaction_labels = db.get_bitcoin_transaction_labels().await?;
+
+        assert_eq!(address_labels, vec![Labelled {
+            inner: address,
+            label: Label::Refund,
+            swap_id: Some(swap_id),
+        }]);
+        assert_eq!(transaction_labels.len(), 2);
+        assert!(transaction_labels.contains(&Labelled {
+            inner: tx_lock_id,
+            label: Label::Lock,
+            swap_id: Some(swap_id),
+        }));
+        assert!(transaction_labels.contains(&Labelled {
+            inner: tx_withdraw_id,
+            label: Label::Withdraw,
+            swap_id: None,
+        }));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_load_and_remove_bans() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let ban = Ban {
+            peer_id: PeerId::random(),
+            expires_at: 1_642_000_000,
+            reason: "Aborted 3 swap setups within 3600s".to_owned(),
+        };
+        let other_ban = Ban {
+            peer_id: PeerId::random(),
+            expires_at: 1_642_000_060,
+            reason: "Aborted 3 swap setups within 3600s".to_owned(),
+        };
+
+        db.insert_ban(ban.clone()).await?;
+        db.insert_ban(other_ban.clone()).await?;
+        db.remove_ban(other_ban.peer_id).await?;
+
+        assert_eq!(db.get_bans().await?, vec![ban]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_address_health() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let address = "/ip4/127.0.0.1/tcp/9939".parse::<Multiaddr>()?;
+        let other_address = "/ip4/127.0.0.2/tcp/9939".parse::<Multiaddr>()?;
+        let failing = AddressHealth {
+            last_success: Some(1_642_000_000),
+            failures: 1,
+            latency: Some(Duration::from_millis(120)),
+        };
+        let recovered = AddressHealth {
+            last_success: Some(1_642_000_060),
+            failures: 0,
+            latency: Some(Duration::from_millis(80)),
+        };
+
+        db.insert_address_health(peer_id, address.clone(), failing)
+            .await?;
+        db.insert_address_health(peer_id, address.clone(), recovered)
+            .await?;
+        db.insert_address_health(PeerId::random(), other_address, AddressHealth::default())
+            .await?;
+
+        assert_eq!(
+            db.get_address_health(peer_id).await?,
+            vec![(address, recovered)]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_wallet_state_in_single_row_then_moves_it_into_tables() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let mut memory = MemoryDatabase::new();
+        memory.set_last_index(KeychainKind::External, 7)?;
+        let state = serde_json::to_string(&WalletState::read_from(&memory)?)?;
+        sqlx::query("insert into bitcoin_wallet (id, state) values (0, ?)")
+            .bind(state)
+            .execute(&db.pool)
+            .await?;
+
+        let wallet = db.open_bitcoin_wallet().await?;
+        assert_eq!(wallet.get_last_index(KeychainKind::External)?, Some(7));
+        drop(wallet);
+
+        let wallet = db.open_bitcoin_wallet().await?;
+        assert_eq!(wallet.get_last_index(KeychainKind::External)?, Some(7));
+
+        Ok(())
+    }
+
     async fn setup_test_db() -> Result<SqliteDatabase> {
         let temp_db = tempdir().unwrap().into_path().join("tempdb");
 
@@ -1,26 +1,56 @@
 use crate::asb;
 use crate::bitcoin::{CancelTimelock, PunishTimelock};
-use serde::Serialize;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
 use std::cmp::max;
+use std::path::Path;
 use std::time::Duration;
 use time::ext::NumericalStdDuration;
 
-#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde_as]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    #[serde_as(as = "DurationSeconds<u64>")]
     pub bitcoin_lock_mempool_timeout: Duration,
+    #[serde_as(as = "DurationSeconds<u64>")]
     pub bitcoin_lock_confirmed_timeout: Duration,
     pub bitcoin_finality_confirmations: u32,
+    #[serde_as(as = "DurationSeconds<u64>")]
     pub bitcoin_avg_block_time: Duration,
     pub bitcoin_cancel_timelock: CancelTimelock,
     pub bitcoin_punish_timelock: PunishTimelock,
+    #[serde(with = "crate::bitcoin::network")]
     pub bitcoin_network: bitcoin::Network,
+    #[serde_as(as = "DurationSeconds<u64>")]
     pub monero_avg_block_time: Duration,
     pub monero_finality_confirmations: u64,
-    #[serde(with = "monero_network")]
+    #[serde(with = "crate::monero::network")]
     pub monero_network: monero::Network,
 }
 
 impl Config {
+    /// Reads a custom environment configuration from the given TOML file.
+    ///
+    /// All fields have to be present, durations are given in seconds.
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut config = config::Config::new();
+        config.merge(config::File::from(path).format(config::FileFormat::Toml))?;
+        let config: Config = config.try_into().with_context(|| {
+            format!(
+                "Failed to read environment configuration from {}",
+                path.display()
+            )
+        })?;
+
+        if config.bitcoin_finality_confirmations == 0 {
+            bail!("Bitcoin finality confirmations must be at least 1")
+        }
+
+        Ok(config)
+    }
+
     pub fn bitcoin_sync_interval(&self) -> Duration {
         sync_interval(self.bitcoin_avg_block_time)
     }
@@ -40,6 +70,9 @@ pub struct Mainnet;
 #[derive(Clone, Copy)]
 pub struct Testnet;
 
+#[derive(Clone, Copy)]
+pub struct Signet;
+
 #[derive(Clone, Copy)]
 pub struct Regtest;
 
@@ -77,6 +110,15 @@ impl GetConfig for Testnet {
     }
 }
 
+impl GetConfig for Signet {
+    fn get_config() -> Config {
+        Config {
+            bitcoin_network: bitcoin::Network::Signet,
+            ..Testnet::get_config()
+        }
+    }
+}
+
 impl GetConfig for Regtest {
     fn get_config() -> Config {
         Config {
@@ -98,6 +140,21 @@ fn sync_interval(avg_block_time: Duration) -> Duration {
     max(avg_block_time / 10, Duration::from_secs(1))
 }
 
+/// Selects the environment configuration based on the network switches given
+/// on the command line.
+///
+/// A custom configuration file takes precedence over the network switches.
+pub fn select(testnet: bool, signet: bool, custom: Option<&Path>) -> Result<Config> {
+    let config = match custom {
+        Some(path) => Config::read(path)?,
+        None if signet => Signet::get_config(),
+        None if testnet => Testnet::get_config(),
+        None => Mainnet::get_config(),
+    };
+
+    Ok(config)
+}
+
 pub fn new(is_testnet: bool, asb_config: &asb::config::Config) -> Config {
     let env_config = if is_testnet {
         Testnet::get_config()
@@ -125,26 +182,11 @@ pub fn new(is_testnet: bool, asb_config: &asb::config::Config) -> Config {
     }
 }
 
-mod monero_network {
-    use crate::monero::Network;
-    use serde::Serializer;
-
-    pub fn serialize<S>(x: &monero::Network, s: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let str = match x {
-            Network::Mainnet => "mainnet",
-            Network::Stagenet => "stagenet",
-            Network::Testnet => "testnet",
-        };
-        s.serialize_str(&str)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+    use tempfile::tempdir;
 
     #[test]
     fn check_interval_is_one_second_if_avg_blocktime_is_one_second() {
@@ -159,4 +201,70 @@ mod tests {
 
         assert_eq!(interval, Duration::from_secs(10))
     }
+
+    #[test]
+    fn signet_pairs_with_monero_stagenet() {
+        let config = Signet::get_config();
+
+        assert_eq!(config.bitcoin_network, bitcoin::Network::Signet);
+        assert_eq!(config.monero_network, monero::Network::Stagenet);
+    }
+
+    #[test]
+    fn custom_config_overrides_all_fields() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env.toml");
+        fs::write(
+            &path,
+            r#"
+bitcoin_lock_mempool_timeout = 60
+bitcoin_lock_confirmed_timeout = 600
+bitcoin_finality_confirmations = 3
+bitcoin_avg_block_time = 30
+bitcoin_cancel_timelock = 20
+bitcoin_punish_timelock = 10
+bitcoin_network = "Regtest"
+monero_avg_block_time = 5
+monero_finality_confirmations = 4
+monero_network = "Testnet"
+"#,
+        )
+        .unwrap();
+
+        let config = select(false, false, Some(&path)).unwrap();
+
+        assert_eq!(config, Config {
+            bitcoin_lock_mempool_timeout: 1.std_minutes(),
+            bitcoin_lock_confirmed_timeout: 10.std_minutes(),
+            bitcoin_finality_confirmations: 3,
+            bitcoin_avg_block_time: 30.std_seconds(),
+            bitcoin_cancel_timelock: CancelTimelock::new(20),
+            bitcoin_punish_timelock: PunishTimelock::new(10),
+            bitcoin_network: bitcoin::Network::Regtest,
+            monero_avg_block_time: 5.std_seconds(),
+            monero_finality_confirmations: 4,
+            monero_network: monero::Network::Testnet,
+        });
+    }
+
+    #[test]
+    fn custom_config_with_missing_field_is_rejected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env.toml");
+        fs::write(&path, "bitcoin_network = \"Regtest\"\n").unwrap();
+
+        assert!(Config::read(&path).is_err());
+    }
+
+    #[test]
+    fn custom_config_roundtrips_through_toml() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env.toml");
+        let expected = Regtest::get_config();
+        fs::write(&path, toml::to_string(&expected).unwrap()).unwrap();
+
+        let actual = Config::read(&path).unwrap();
+
+        assert_eq!(actual, expected);
+    }
 }
@@ -3,6 +3,7 @@ use libp2p::{Multiaddr, PeerId};
 
 pub trait MultiAddrExt {
     fn extract_peer_id(&self) -> Option<PeerId>;
+    fn without_peer_id(&self) -> Multiaddr;
 }
 
 impl MultiAddrExt for Multiaddr {
@@ -12,4 +13,13 @@ impl MultiAddrExt for Multiaddr {
             _ => None,
         }
     }
+
+    fn without_peer_id(&self) -> Multiaddr {
+        let mut address = self.clone();
+        if let Some(Protocol::P2p(_)) = address.iter().last() {
+            address.pop();
+        }
+
+        address
+    }
 }
@@ -1,11 +1,15 @@
+mod daemon;
+mod lock_verification;
 pub mod wallet;
+mod wallet_pool;
 mod wallet_rpc;
 
 pub use ::monero::network::Network;
 pub use ::monero::{Address, PrivateKey, PublicKey};
 pub use curve25519_dalek::scalar::Scalar;
+pub use daemon::{DaemonStatus, RemoteDaemon};
 pub use wallet::Wallet;
-pub use wallet_rpc::{WalletRpc, WalletRpcProcess};
+pub use wallet_rpc::{WalletRpc, WalletRpcBinary, WalletRpcProcess};
 
 use crate::bitcoin;
 use anyhow::Result;
@@ -0,0 +1,310 @@
+//! Choosing a remote monerod out of a list of candidates and falling back to
+//! another one if it stops responding.
+
+use anyhow::{Context, Result};
+use monero_rpc::http::{Connection, Credentials};
+use monero_rpc::monerod::{self, DaemonAddress, MonerodRpc as _};
+use monero_rpc::wallet::{self, MoneroWalletRpc as _};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long a daemon has to answer a probe before it is considered dead.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Daemons that are more than this many blocks behind the highest daemon are
+/// considered to be lagging.
+const MAX_LAG: u64 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DaemonStatus {
+    pub address: String,
+    pub height: u64,
+    pub synchronized: bool,
+    pub latency: Duration,
+}
+
+/// Asks the daemon at `address` for its height and sync status.
+pub async fn probe(address: &str, connection: Connection) -> Result<DaemonStatus> {
+    let client = monerod::Client::connect(&address.parse()?, connection)?;
+
+    let started = Instant::now();
+    let info = tokio::time::timeout(PROBE_TIMEOUT, client.get_info())
+        .await
+        .context("Monero daemon did not respond in time")??;
+    let latency = started.elapsed();
+
+    Ok(DaemonStatus {
+        address: address.to_owned(),
+        height: info.height,
+        synchronized: info.synchronized && !info.offline,
+        latency,
+    })
+}
+
+/// Probes all candidates and returns the usable ones, best first.
+pub async fn probe_all(candidates: &[String], connection: &Connection) -> Vec<DaemonStatus> {
+    let statuses = futures::future::join_all(candidates.iter().map(|address| async move {
+        match probe(address, connection.clone()).await {
+            Ok(status) => Some(status),
+            Err(e) => {
+                tracing::debug!(%address, "Failed to probe Monero daemon: {:#}", e);
+                None
+            }
+        }
+    }))
+    .await;
+
+    rank(statuses.into_iter().flatten().collect())
+}
+
+/// Drops daemons that are not synchronized or lag behind the others and
+/// orders the remaining ones by latency.
+fn rank(mut statuses: Vec<DaemonStatus>) -> Vec<DaemonStatus> {
+    statuses.retain(|status| status.synchronized);
+
+    let best_height = statuses
+        .iter()
+        .map(|status| status.height)
+        .max()
+        .unwrap_or_default();
+    statuses.retain(|status| status.height + MAX_LAG >= best_height);
+
+    statuses.sort_by_key(|status| status.latency);
+
+    statuses
+}
+
+/// Whether the daemon at `address` was dropped from the `ranked` daemons,
+/// because it did not respond, is not synchronized or lags behind the tip.
+fn lags_behind(address: &str, ranked: &[DaemonStatus]) -> bool {
+    !ranked.iter().any(|status| status.address == address)
+}
+
+/// The remote monerod used by the swap, picked from a list of candidates.
+#[derive(Debug)]
+pub struct RemoteDaemon {
+    candidates: Vec<String>,
+    connection: Connection,
+    current: Mutex<Current>,
+    wallet_rpc: Option<wallet::Client>,
+}
+
+#[derive(Debug, Clone)]
+struct Current {
+    address: String,
+    client: monerod::Client,
+}
+
+impl RemoteDaemon {
+    /// Probes all candidates and connects to the best one.
+    pub async fn choose(candidates: Vec<String>, connection: Connection) -> Result<Self> {
+        let best = probe_all(&candidates, &connection)
+            .await
+            .into_iter()
+            .next()
+            .context("None of the Monero daemons is reachable and synchronized")?;
+
+        tracing::info!(
+            address = %best.address,
+            height = %best.height,
+            latency = ?best.latency,
+            "Using Monero daemon"
+        );
+
+        Self::new(candidates, best.address, connection)
+    }
+
+    /// Connects to `address` without probing it.
+    pub fn new(candidates: Vec<String>, address: String, connection: Connection) -> Result<Self> {
+        let client = monerod::Client::connect(&address.parse()?, connection.clone())?;
+
+        Ok(Self {
+            candidates,
+            connection,
+            current: Mutex::new(Current { address, client }),
+            wallet_rpc: None,
+        })
+    }
+
+    /// Also switch the given `monero-wallet-rpc` whenever we fail over to
+    /// another daemon.
+    pub fn with_wallet_rpc(self, wallet_rpc: wallet::Client) -> Self {
+        Self {
+            wallet_rpc: Some(wallet_rpc),
+            ..self
+        }
+    }
+
+    pub async fn address(&self) -> String {
+        self.current.lock().await.address.clone()
+    }
+
+    pub async fn client(&self) -> monerod::Client {
+        self.current.lock().await.client.clone()
+    }
+
+    /// The login for the daemons, if they require one.
+    pub fn login(&self) -> Option<&Credentials> {
+        self.connection.login.as_ref()
+    }
+
+    /// Probes all candidates to find out whether the current daemon lags
+    /// behind the tip.
+    ///
+    /// Without other candidates there is nothing to compare against, so the
+    /// current daemon is assumed to be up to date.
+    pub async fn is_lagging(&self) -> bool {
+        if self.candidates.len() < 2 {
+            return false;
+        }
+
+        let address = self.address().await;
+        let ranked = probe_all(&self.candidates, &self.connection).await;
+
+        lags_behind(&address, &ranked)
+    }
+
+    /// Switches to the best candidate other than the current daemon.
+    pub async fn fail_over(&self) -> Result<()> {
+        let mut current = self.current.lock().await;
+
+        let others = self
+            .candidates
+            .iter()
+            .filter(|address| **address != current.address)
+            .cloned()
+            .collect::<Vec<_>>();
+        let best = probe_all(&others, &self.connection)
+            .await
+            .into_iter()
+            .next()
+            .context("No other Monero daemon is reachable and synchronized")?;
+
+        let address = best.address.parse::<DaemonAddress>()?;
+        if let Some(wallet_rpc) = &self.wallet_rpc {
+            set_wallet_rpc_daemon(wallet_rpc, &address, self.login())
+                .await
+                .context("Failed to switch monero-wallet-rpc to another daemon")?;
+        }
+
+        tracing::warn!(
+            from = %current.address,
+            to = %best.address,
+            "Switched to another Monero daemon"
+        );
+
+        *current = Current {
+            client: monerod::Client::connect(&address, self.connection.clone())?,
+            address: best.address,
+        };
+
+        Ok(())
+    }
+}
+
+/// Points `monero-wallet-rpc` to the daemon at `address`.
+///
+/// The login is passed over RPC rather than on the command line of
+/// `monero-wallet-rpc`, where every user of the machine could read it.
+pub async fn set_wallet_rpc_daemon(
+    wallet_rpc: &wallet::Client,
+    address: &DaemonAddress,
+    login: Option<&Credentials>,
+) -> Result<()> {
+    let (username, password) = match login {
+        Some(login) => (login.username.clone(), login.password.clone()),
+        None => (String::new(), String::new()),
+    };
+    let ssl_support = if address.tls { "enabled" } else { "autodetect" };
+
+    wallet_rpc
+        .set_daemon(
+            address.host_and_port(),
+            false,
+            username,
+            password,
+            ssl_support.to_owned(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(address: &str, height: u64, synchronized: bool, latency_ms: u64) -> DaemonStatus {
+        DaemonStatus {
+            address: address.to_owned(),
+            height,
+            synchronized,
+            latency: Duration::from_millis(latency_ms),
+        }
+    }
+
+    fn addresses(statuses: &[DaemonStatus]) -> Vec<&str> {
+        statuses
+            .iter()
+            .map(|status| status.address.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn prefers_daemons_with_lower_latency() {
+        let ranked = rank(vec![
+            status("slow:18081", 1000, true, 800),
+            status("fast:18081", 1000, true, 50),
+            status("medium:18081", 999, true, 200),
+        ]);
+
+        assert_eq!(
+            addresses(&ranked),
+            vec!["fast:18081", "medium:18081", "slow:18081"]
+        );
+    }
+
+    #[test]
+    fn drops_daemons_that_are_not_synchronized() {
+        let ranked = rank(vec![
+            status("syncing:18081", 500, false, 10),
+            status("synced:18081", 1000, true, 100),
+        ]);
+
+        assert_eq!(addresses(&ranked), vec!["synced:18081"]);
+    }
+
+    #[test]
+    fn drops_daemons_that_lag_behind() {
+        let ranked = rank(vec![
+            status("lagging:18081", 1000 - MAX_LAG - 1, true, 10),
+            status("tip:18081", 1000, true, 100),
+            status("almost-tip:18081", 1000 - MAX_LAG, true, 50),
+        ]);
+
+        assert_eq!(addresses(&ranked), vec!["almost-tip:18081", "tip:18081"]);
+    }
+
+    #[test]
+    fn daemon_behind_the_tip_of_the_others_is_lagging() {
+        let ranked = rank(vec![
+            status("lagging:18081", 1000 - MAX_LAG - 1, true, 10),
+            status("tip:18081", 1000, true, 100),
+        ]);
+
+        assert!(lags_behind("lagging:18081", &ranked));
+        assert!(!lags_behind("tip:18081", &ranked));
+    }
+
+    #[test]
+    fn unreachable_daemon_is_lagging() {
+        let ranked = rank(vec![status("tip:18081", 1000, true, 100)]);
+
+        assert!(lags_behind("unreachable:18081", &ranked));
+    }
+
+    #[test]
+    fn given_no_daemons_then_ranking_is_empty() {
+        assert!(rank(vec![status("syncing:18081", 500, false, 10)]).is_empty());
+    }
+}
@@ -0,0 +1,438 @@
+//! Verifies a Monero transfer using only monerod.
+//!
+//! The sender hands us the transaction key `r` as part of the transfer proof.
+//! Together with the public view key `V` of the receiving address this gives
+//! the shared secret `8rV`, from which each output's one-time key and amount
+//! can be recomputed, the same way `check_tx_key` does in `monero-wallet-rpc`.
+
+use crate::monero::{
+    Amount, InsufficientFunds, PrivateKey, PublicKey, PublicViewKey, RemoteDaemon, TransferProof,
+};
+use ::monero::blockdata::transaction::TxOutTarget;
+use ::monero::consensus::deserialize;
+use ::monero::cryptonote::hash::Hash;
+use ::monero::util::ringct::EcdhInfo;
+use ::monero::Transaction;
+use anyhow::{bail, Context, Result};
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use monero_rpc::monerod;
+use tokio::time::Interval;
+
+/// The second generator `H` used by Pedersen commitments on amounts.
+const H: [u8; 32] = [
+    0x8b, 0x65, 0x59, 0x70, 0x15, 0x37, 0x99, 0xaf, 0x2a, 0xea, 0xdc, 0x9f, 0xf1, 0xad, 0xd0, 0xea,
+    0x6c, 0x72, 0x51, 0xd5, 0x41, 0x54, 0xcf, 0xa9, 0x2c, 0x17, 0x3a, 0x0d, 0xd3, 0x9c, 0x1f, 0x94,
+];
+
+/// Fail over to another daemon after this many failed requests in a row.
+const FAILURES_BEFORE_FAIL_OVER: u32 = 3;
+
+/// Only ask the other daemons whether ours lags behind once the transaction
+/// has been missing for this many checks in a row. Each check probes every
+/// candidate daemon, so we must not do it on every tick.
+const MISSING_CHECKS_BEFORE_LAG_CHECK: u32 = 5;
+
+/// An output of a RingCT transaction as far as the receiver is concerned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncryptedOutput {
+    pub one_time_key: PublicKey,
+    pub encrypted_amount: [u8; 8],
+    pub commitment: [u8; 32],
+}
+
+/// Extracts the outputs of a RingCT transaction that uses compact amount
+/// encoding, i.e. any transaction created since the Bulletproofs hard fork.
+pub fn encrypted_outputs(tx: &Transaction) -> Result<Vec<EncryptedOutput>> {
+    let rct = tx
+        .rct_signatures
+        .sig
+        .as_ref()
+        .context("Transaction has no RingCT signature")?;
+
+    if rct.ecdh_info.len() != tx.prefix.outputs.len() || rct.out_pk.len() != tx.prefix.outputs.len()
+    {
+        bail!("Transaction has a different number of outputs and amounts")
+    }
+
+    tx.prefix
+        .outputs
+        .iter()
+        .zip(&rct.ecdh_info)
+        .zip(&rct.out_pk)
+        .map(|((output, ecdh_info), out_pk)| {
+            let one_time_key = match output.target {
+                TxOutTarget::ToKey { key } => key,
+                _ => bail!("Unsupported output type"),
+            };
+            let encrypted_amount = match ecdh_info {
+                EcdhInfo::Bulletproof { amount } => amount.to_fixed_bytes(),
+                _ => bail!("Transaction does not use compact amount encoding"),
+            };
+
+            Ok(EncryptedOutput {
+                one_time_key,
+                encrypted_amount,
+                commitment: out_pk.mask.key,
+            })
+        })
+        .collect()
+}
+
+/// Sums up the amounts sent to the address made up of the given keys.
+///
+/// Fails if an output belongs to the address but its amount does not match
+/// its commitment, because then the sender could claim any amount.
+pub fn received_amount(
+    outputs: &[EncryptedOutput],
+    tx_key: PrivateKey,
+    public_view_key: PublicViewKey,
+    public_spend_key: PublicKey,
+) -> Result<Amount> {
+    let view_key = decompress(&public_view_key.into())?;
+    let spend_key = decompress(&public_spend_key)?;
+    let derivation = (tx_key.scalar * view_key).mul_by_cofactor();
+
+    let mut received = 0u64;
+    for (index, output) in outputs.iter().enumerate() {
+        let shared_secret = derivation_to_scalar(&derivation, index as u64);
+
+        let expected_key = &shared_secret * &ED25519_BASEPOINT_TABLE + spend_key;
+        if expected_key.compress() != output.one_time_key.point {
+            continue;
+        }
+
+        let amount = decrypt_amount(&shared_secret, output.encrypted_amount);
+        if commit(&shared_secret, amount)?.compress().to_bytes() != output.commitment {
+            bail!("Amount of output {} does not match its commitment", index)
+        }
+
+        received = received
+            .checked_add(amount)
+            .context("Received amount overflows")?;
+    }
+
+    Ok(Amount::from_piconero(received))
+}
+
+/// Waits until the transfer has `conf_target` confirmations on monerod.
+pub async fn wait_for_confirmations(
+    daemon: &RemoteDaemon,
+    transfer_proof: TransferProof,
+    public_spend_key: PublicKey,
+    public_view_key: PublicViewKey,
+    expected: Amount,
+    conf_target: u64,
+    mut check_interval: Interval,
+) -> Result<(), InsufficientFunds> {
+    let txid = transfer_proof.tx_hash().to_string();
+    let mut received = None;
+    let mut seen_confirmations = 0u64;
+    let mut failures = 0;
+    let mut missing = 0;
+
+    while seen_confirmations < conf_target {
+        check_interval.tick().await; // tick() at the beginning of the loop so every `continue` tick()s as well
+
+        if failures >= FAILURES_BEFORE_FAIL_OVER {
+            if let Err(e) = daemon.fail_over().await {
+                tracing::warn!("Failed to switch to another Monero daemon: {:#}", e);
+            }
+            failures = 0;
+        }
+
+        let client = daemon.client().await;
+
+        let tx = match fetch_transaction(&client, &txid).await {
+            Ok(Some(tx)) => tx,
+            Ok(None) => {
+                tracing::debug!(%txid, "Transaction not found on monerod yet");
+                // A daemon that lags behind the tip does not know about
+                // transactions that are already mined on the other daemons.
+                missing += 1;
+                if missing >= MISSING_CHECKS_BEFORE_LAG_CHECK {
+                    missing = 0;
+                    if daemon.is_lagging().await {
+                        failures = FAILURES_BEFORE_FAIL_OVER;
+                        continue;
+                    }
+                }
+                failures = 0;
+                continue;
+            }
+            Err(e) => {
+                tracing::debug!(%txid, "Failed to fetch transaction from monerod: {:#}", e);
+                failures += 1;
+                continue;
+            }
+        };
+        failures = 0;
+        missing = 0;
+
+        // The amount cannot change once the transaction has been decoded.
+        let amount = match received {
+            Some(amount) => amount,
+            None => {
+                // A transaction we cannot decode tells us nothing about the
+                // amount. It may have been mangled by the daemon, so we retry
+                // and eventually switch to another one. If Alice's transaction
+                // itself is broken we keep waiting until the swap is
+                // cancelled.
+                let amount = match encrypted_outputs(&tx.transaction).and_then(|outputs| {
+                    received_amount(
+                        &outputs,
+                        transfer_proof.tx_key(),
+                        public_view_key,
+                        public_spend_key,
+                    )
+                }) {
+                    Ok(amount) => amount,
+                    Err(e) => {
+                        tracing::warn!(%txid, "Failed to decode Monero lock transaction: {:#}", e);
+                        failures += 1;
+                        continue;
+                    }
+                };
+                received = Some(amount);
+                amount
+            }
+        };
+
+        if amount != expected {
+            return Err(InsufficientFunds {
+                expected,
+                actual: amount,
+            });
+        }
+
+        let confirmations = match tx.block_height {
+            None => 0,
+            Some(block_height) => match client.get_block_count().await {
+                Ok(block_count) => u64::from(block_count.count).saturating_sub(block_height),
+                Err(e) => {
+                    tracing::debug!("Failed to fetch block count from monerod: {:#}", e);
+                    failures += 1;
+                    continue;
+                }
+            },
+        };
+
+        if confirmations > seen_confirmations {
+            seen_confirmations = confirmations;
+            tracing::info!(
+                %txid,
+                %seen_confirmations,
+                needed_confirmations = %conf_target,
+                "Received new confirmation for Monero lock tx"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+struct FetchedTransaction {
+    transaction: Transaction,
+    /// `None` while the transaction is in the pool.
+    block_height: Option<u64>,
+}
+
+async fn fetch_transaction(
+    client: &monerod::Client,
+    txid: &str,
+) -> Result<Option<FetchedTransaction>> {
+    let response = client.get_transactions(vec![txid.to_owned()]).await?;
+
+    let entry = match response.txs.into_iter().find(|tx| tx.tx_hash == txid) {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let transaction =
+        deserialize(&hex::decode(&entry.as_hex)?).context("Failed to deserialize transaction")?;
+    let block_height = if entry.in_pool {
+        None
+    } else {
+        Some(entry.block_height)
+    };
+
+    Ok(Some(FetchedTransaction {
+        transaction,
+        block_height,
+    }))
+}
+
+fn decompress(key: &PublicKey) -> Result<EdwardsPoint> {
+    key.point
+        .decompress()
+        .context("Public key is not a valid curve point")
+}
+
+/// `Hs(8rV || index)`, the scalar shared between sender and receiver for the
+/// output at `index`.
+fn derivation_to_scalar(derivation: &EdwardsPoint, index: u64) -> Scalar {
+    let mut bytes = derivation.compress().to_bytes().to_vec();
+    write_varint(&mut bytes, index);
+
+    hash_to_scalar(&bytes)
+}
+
+fn decrypt_amount(shared_secret: &Scalar, encrypted_amount: [u8; 8]) -> u64 {
+    let mut bytes = b"amount".to_vec();
+    bytes.extend_from_slice(shared_secret.as_bytes());
+    let key = Hash::new(&bytes).to_fixed_bytes();
+
+    let mut amount = encrypted_amount;
+    for (byte, key) in amount.iter_mut().zip(key.iter()) {
+        *byte ^= key;
+    }
+
+    u64::from_le_bytes(amount)
+}
+
+fn commit(shared_secret: &Scalar, amount: u64) -> Result<EdwardsPoint> {
+    let mut bytes = b"commitment_mask".to_vec();
+    bytes.extend_from_slice(shared_secret.as_bytes());
+    let mask = hash_to_scalar(&bytes);
+
+    let h = CompressedEdwardsY(H)
+        .decompress()
+        .context("H is a valid curve point")?;
+
+    Ok(&mask * &ED25519_BASEPOINT_TABLE + Scalar::from(amount) * h)
+}
+
+fn hash_to_scalar(bytes: &[u8]) -> Scalar {
+    Scalar::from_bytes_mod_order(Hash::new(bytes).to_fixed_bytes())
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monero::PrivateViewKey;
+    use rand::rngs::OsRng;
+
+    struct Receiver {
+        view_key: PrivateViewKey,
+        spend_key: PublicKey,
+    }
+
+    impl Receiver {
+        fn random() -> Self {
+            Self {
+                view_key: PrivateViewKey::new_random(&mut OsRng),
+                spend_key: PublicKey::from_private_key(&PrivateKey {
+                    scalar: Scalar::random(&mut OsRng),
+                }),
+            }
+        }
+    }
+
+    /// Creates an output the way a sending wallet does.
+    fn send(tx_key: PrivateKey, receiver: &Receiver, index: u64, amount: u64) -> EncryptedOutput {
+        let view_key = decompress(&receiver.view_key.public().into()).unwrap();
+        let derivation = (tx_key.scalar * view_key).mul_by_cofactor();
+        let shared_secret = derivation_to_scalar(&derivation, index);
+
+        let one_time_key =
+            &shared_secret * &ED25519_BASEPOINT_TABLE + decompress(&receiver.spend_key).unwrap();
+
+        EncryptedOutput {
+            one_time_key: PublicKey {
+                point: one_time_key.compress(),
+            },
+            // XOR with the same key encrypts and decrypts.
+            encrypted_amount: decrypt_amount(&shared_secret, amount.to_le_bytes()).to_le_bytes(),
+            commitment: commit(&shared_secret, amount)
+                .unwrap()
+                .compress()
+                .to_bytes(),
+        }
+    }
+
+    fn random_tx_key() -> PrivateKey {
+        PrivateKey {
+            scalar: Scalar::random(&mut OsRng),
+        }
+    }
+
+    #[test]
+    fn sums_up_outputs_to_receiver() {
+        let tx_key = random_tx_key();
+        let receiver = Receiver::random();
+        let someone_else = Receiver::random();
+
+        let outputs = vec![
+            send(tx_key, &receiver, 0, 1_000_000),
+            send(tx_key, &someone_else, 1, 5_000_000),
+            send(tx_key, &receiver, 2, 234),
+        ];
+
+        let received = received_amount(
+            &outputs,
+            tx_key,
+            receiver.view_key.public(),
+            receiver.spend_key,
+        )
+        .unwrap();
+
+        assert_eq!(received, Amount::from_piconero(1_000_234));
+    }
+
+    #[test]
+    fn given_wrong_tx_key_then_nothing_is_received() {
+        let receiver = Receiver::random();
+        let outputs = vec![send(random_tx_key(), &receiver, 0, 1_000_000)];
+
+        let received = received_amount(
+            &outputs,
+            random_tx_key(),
+            receiver.view_key.public(),
+            receiver.spend_key,
+        )
+        .unwrap();
+
+        assert_eq!(received, Amount::ZERO);
+    }
+
+    #[test]
+    fn given_amount_that_does_not_match_commitment_then_fails() {
+        let tx_key = random_tx_key();
+        let receiver = Receiver::random();
+
+        let mut output = send(tx_key, &receiver, 0, 1_000_000);
+        output.commitment = send(tx_key, &receiver, 0, 1).commitment;
+
+        let result = received_amount(
+            &[output],
+            tx_key,
+            receiver.view_key.public(),
+            receiver.spend_key,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn encodes_varints_like_monero() {
+        let encode = |n| {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, n);
+            bytes
+        };
+
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(127), vec![0x7f]);
+        assert_eq!(encode(128), vec![0x80, 0x01]);
+        assert_eq!(encode(300), vec![0xac, 0x02]);
+    }
+}
@@ -1,31 +1,46 @@
 use crate::env::Config;
+use crate::monero::lock_verification;
+use crate::monero::wallet_pool::WalletRpcPool;
 use crate::monero::{
-    Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
+    Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, RemoteDaemon, TransferProof, TxHash,
 };
 use ::monero::{Address, Network, PrivateKey, PublicKey};
 use anyhow::{Context, Result};
 use monero_rpc::wallet::{BlockHeight, MoneroWalletRpc as _, Refreshed};
 use monero_rpc::{jsonrpc, wallet};
+use std::future::Future;
 use std::str::FromStr;
 use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::time::Interval;
-use url::Url;
 
 #[derive(Debug)]
 pub struct Wallet {
+    /// The connection on which the main wallet is open.
+    ///
+    /// Transfers spend from the main wallet and therefore take turns on it,
+    /// two of them at once could pick the same outputs. Watching for a
+    /// transfer with `check_tx_key` only holds it for one request per check
+    /// and never switches the open wallet. Generated wallets are only opened
+    /// here if there are no `ephemeral` connections.
     inner: Mutex<wallet::Client>,
     network: Network,
     name: String,
     main_address: monero::Address,
     sync_interval: Duration,
+    daemon: Option<RemoteDaemon>,
+    /// Connections on which generated wallets are opened, if any. Without
+    /// them generated wallets are opened on the main connection.
+    ephemeral: Option<WalletRpcPool<wallet::Client>>,
 }
 
 impl Wallet {
-    /// Connect to a wallet RPC and load the given wallet by name.
-    pub async fn open_or_create(url: Url, name: String, env_config: Config) -> Result<Self> {
-        let client = wallet::Client::new(url)?;
-
+    /// Load the given wallet by name, creating it if it does not exist yet.
+    pub async fn open_or_create(
+        client: wallet::Client,
+        name: String,
+        env_config: Config,
+    ) -> Result<Self> {
         let open_wallet_response = client.open_wallet(name.clone()).await;
         if open_wallet_response.is_err() {
             client.create_wallet(name.clone(), "English".to_owned()).await.context(
@@ -50,9 +65,33 @@ impl Wallet {
             name,
             main_address,
             sync_interval: env_config.monero_sync_interval(),
+            daemon: None,
+            ephemeral: None,
         })
     }
 
+    /// Verify incoming transfers against the given monerod instead of asking
+    /// `monero-wallet-rpc` to check the transaction key.
+    pub fn with_daemon(self, daemon: RemoteDaemon) -> Self {
+        Self {
+            daemon: Some(daemon),
+            ..self
+        }
+    }
+
+    /// Open generated wallets on the given `monero-wallet-rpc` instances, so
+    /// that the main wallet stays open and swaps can sweep in parallel.
+    pub fn with_ephemeral_wallet_rpcs(self, clients: Vec<wallet::Client>) -> Self {
+        if clients.is_empty() {
+            return self;
+        }
+
+        Self {
+            ephemeral: Some(WalletRpcPool::new(clients)),
+            ..self
+        }
+    }
+
     /// Re-open the wallet using the internally stored name.
     pub async fn re_open(&self) -> Result<()> {
         self.inner
@@ -107,10 +146,11 @@ impl Wallet {
         Ok(())
     }
 
-    /// Close the wallet and open (load) another wallet by generating it from
-    /// keys. The generated wallet will be opened, all funds sweeped to the
-    /// main_address and then the wallet will be re-loaded using the internally
-    /// stored name.
+    /// Generate a wallet from keys and sweep all of its funds to the main
+    /// address.
+    ///
+    /// Failing to sweep is only logged, the funds remain in the generated
+    /// wallet.
     pub async fn create_from(
         &self,
         file_name: String,
@@ -118,58 +158,93 @@ impl Wallet {
         private_view_key: PrivateViewKey,
         restore_height: BlockHeight,
     ) -> Result<()> {
-        let public_spend_key = PublicKey::from_private_key(&private_spend_key);
-        let public_view_key = PublicKey::from_private_key(&private_view_key.into());
-
-        let temp_wallet_address =
-            Address::standard(self.network, public_spend_key, public_view_key);
-
-        let wallet = self.inner.lock().await;
-
-        // Close the default wallet before generating the other wallet to ensure that
-        // it saves its state correctly
-        let _ = wallet.close_wallet().await?;
-
-        let _ = wallet
-            .generate_from_keys(
+        let tx_hashes = self
+            .sweep_from_keys(
                 file_name,
-                temp_wallet_address.to_string(),
-                private_spend_key.to_string(),
-                PrivateKey::from(private_view_key).to_string(),
-                restore_height.height,
-                String::from(""),
-                true,
+                private_spend_key,
+                private_view_key,
+                restore_height,
+                self.main_address,
             )
-            .await?;
-
-        // Try to send all the funds from the generated wallet to the default wallet
-        match wallet.refresh().await {
-            Ok(_) => match wallet.sweep_all(self.main_address.to_string()).await {
-                Ok(sweep_all) => {
-                    for tx in sweep_all.tx_hash_list {
-                        tracing::info!(
-                            %tx,
-                            monero_address = %self.main_address,
-                            "Monero transferred back to default wallet");
-                    }
-                }
-                Err(error) => {
-                    tracing::warn!(
-                        address = %self.main_address,
-                        "Failed to transfer Monero to default wallet: {:#}", error
-                    );
+            .await;
+
+        match tx_hashes {
+            Ok(tx_hashes) => {
+                for tx in tx_hashes {
+                    tracing::info!(
+                        %tx,
+                        monero_address = %self.main_address,
+                        "Monero transferred back to default wallet");
                 }
-            },
+            }
             Err(error) => {
-                tracing::warn!("Failed to refresh generated wallet: {:#}", error);
+                tracing::warn!(
+                    address = %self.main_address,
+                    "Failed to transfer Monero to default wallet: {:#}", error
+                );
             }
         }
 
-        let _ = wallet.open_wallet(self.name.clone()).await?;
-
         Ok(())
     }
 
+    /// Generate a wallet from keys, or open it if it was generated before,
+    /// and sweep all of its funds to `destination`.
+    ///
+    /// The generated wallet is opened on an ephemeral `monero-wallet-rpc` if
+    /// there is one. Otherwise the main wallet is closed for the duration of
+    /// the sweep and re-opened afterwards.
+    pub async fn sweep_from_keys(
+        &self,
+        file_name: String,
+        private_spend_key: PrivateKey,
+        private_view_key: PrivateViewKey,
+        restore_height: BlockHeight,
+        destination: Address,
+    ) -> Result<Vec<TxHash>> {
+        let public_spend_key = PublicKey::from_private_key(&private_spend_key);
+        let public_view_key = PublicKey::from_private_key(&private_view_key.into());
+
+        let keys = GeneratedWallet {
+            file_name,
+            address: Address::standard(self.network, public_spend_key, public_view_key),
+            private_spend_key,
+            private_view_key,
+            restore_height,
+        };
+
+        let tx_hashes = self
+            .retry_on_other_daemon(|| self.sweep_generated(keys.clone(), destination))
+            .await?;
+
+        Ok(tx_hashes.into_iter().map(TxHash).collect())
+    }
+
+    async fn sweep_generated(
+        &self,
+        keys: GeneratedWallet,
+        destination: Address,
+    ) -> Result<Vec<String>> {
+        match &self.ephemeral {
+            Some(pool) => sweep_on_pool(pool, keys, destination).await,
+            None => {
+                let client = self.inner.lock().await;
+
+                // Close the default wallet before generating the other wallet to ensure that
+                // it saves its state correctly
+                let _ = client.close_wallet().await?;
+
+                let tx_hashes = sweep_generated_wallet(&*client, keys, destination).await;
+                client
+                    .open_wallet(self.name.clone())
+                    .await
+                    .with_context(|| format!("Failed to re-open wallet {}", self.name))?;
+
+                tx_hashes
+            }
+        }
+    }
+
     pub async fn transfer(&self, request: TransferRequest) -> Result<TransferProof> {
         let inner = self.inner.lock().await;
 
@@ -187,9 +262,19 @@ impl Wallet {
         let destination_address =
             Address::standard(self.network, public_spend_key, public_view_key.into());
 
-        let res = inner
+        let res = match inner
             .transfer_single(0, amount.as_piconero(), &destination_address.to_string())
-            .await?;
+            .await
+        {
+            Ok(res) => res,
+            Err(error) => {
+                // The transfer is not retried, it might have been relayed
+                // before the error.
+                let error = anyhow::Error::from(error);
+                self.fail_over(&error).await;
+                return Err(error);
+            }
+        };
 
         tracing::debug!(
             %amount,
@@ -222,10 +307,23 @@ impl Wallet {
             "Waiting for Monero transaction finality"
         );
 
-        let address = Address::standard(self.network, public_spend_key, public_view_key.into());
-
         let check_interval = tokio::time::interval(self.sync_interval);
 
+        if let Some(daemon) = &self.daemon {
+            return lock_verification::wait_for_confirmations(
+                daemon,
+                transfer_proof,
+                public_spend_key,
+                public_view_key,
+                expected,
+                conf_target,
+                check_interval,
+            )
+            .await;
+        }
+
+        let address = Address::standard(self.network, public_spend_key, public_view_key.into());
+
         wait_for_confirmations(
             &self.inner,
             transfer_proof,
@@ -268,8 +366,122 @@ impl Wallet {
     }
 
     pub async fn refresh(&self) -> Result<Refreshed> {
-        Ok(self.inner.lock().await.refresh().await?)
+        self.retry_on_other_daemon(|| async { Ok(self.inner.lock().await.refresh().await?) })
+            .await
+    }
+
+    /// Runs `operation` once more on another monerod if it fails.
+    ///
+    /// `monero-wallet-rpc` fails to refresh or to relay transactions if its
+    /// daemon stops responding, so it is switched over before the retry.
+    async fn retry_on_other_daemon<T, F, Fut>(&self, operation: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let error = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+
+        if self.fail_over(&error).await {
+            operation().await
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Switches to another monerod after `error`, if there is one to switch
+    /// to. Returns whether we switched.
+    async fn fail_over(&self, error: &anyhow::Error) -> bool {
+        let daemon = match &self.daemon {
+            Some(daemon) => daemon,
+            None => return false,
+        };
+
+        tracing::warn!(
+            "Monero wallet operation failed, switching to another daemon: {:#}",
+            error
+        );
+
+        match daemon.fail_over().await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to switch to another Monero daemon: {:#}", e);
+                false
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GeneratedWallet {
+    file_name: String,
+    address: Address,
+    private_spend_key: PrivateKey,
+    private_view_key: PrivateViewKey,
+    restore_height: BlockHeight,
+}
+
+/// Sweeps the generated wallet on a connection of its own and closes it
+/// again, leaving the connection ready for the next wallet.
+async fn sweep_on_pool<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync>(
+    pool: &WalletRpcPool<C>,
+    wallet: GeneratedWallet,
+    destination: Address,
+) -> Result<Vec<String>> {
+    let client = pool.acquire().await;
+
+    let tx_hashes = sweep_generated_wallet(&*client, wallet, destination).await;
+    let _ = client.close_wallet().await;
+
+    tx_hashes
+}
+
+/// Loads the generated wallet on `client` and sweeps it to `destination`.
+async fn sweep_generated_wallet<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync>(
+    client: &C,
+    wallet: GeneratedWallet,
+    destination: Address,
+) -> Result<Vec<String>> {
+    let generated = client
+        .generate_from_keys(
+            wallet.file_name.clone(),
+            wallet.address.to_string(),
+            wallet.private_spend_key.to_string(),
+            PrivateKey::from(wallet.private_view_key).to_string(),
+            wallet.restore_height.height,
+            String::from(""),
+            true,
+        )
+        .await;
+
+    if let Err(e) = generated {
+        // In case we failed to refresh/sweep, when resuming the wallet might already
+        // exist! This is a very unlikely scenario, but if we don't take care of it we
+        // might not be able to ever transfer the Monero.
+        tracing::warn!("Failed to generate monero wallet from keys: {:#}", e);
+        tracing::info!(wallet_file_name = %wallet.file_name,
+            "Falling back to trying to open the the wallet if it already exists",
+        );
+        client
+            .open_wallet(wallet.file_name.clone())
+            .await
+            .with_context(|| format!("Failed to open wallet {}", wallet.file_name))?;
     }
+
+    // Ensure that the generated wallet is synced so we have a proper balance
+    client
+        .refresh()
+        .await
+        .context("Failed to refresh generated wallet")?;
+    // Sweep (transfer all funds) to the given address
+    let sweep_all = client
+        .sweep_all(destination.to_string())
+        .await
+        .context("Failed to sweep generated wallet")?;
+
+    Ok(sweep_all.tx_hash_list)
 }
 
 #[derive(Debug)]
@@ -367,7 +579,8 @@ mod tests {
     use super::*;
     use crate::tracing_ext::capture_logs;
     use monero_rpc::wallet::CheckTxKey;
-    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex as StdMutex};
     use tracing::metadata::LevelFilter;
 
     #[tokio::test]
@@ -507,6 +720,191 @@ DEBUG swap::monero::wallet: Opening wallet `foo-wallet` because no wallet is loa
         );
     }
 
+    #[tokio::test]
+    async fn sweeps_refund_wallets_in_parallel_without_mixing_them_up() {
+        let sweeps = Arc::new(StdMutex::new(Vec::new()));
+        let in_use = Arc::new(AtomicUsize::new(0));
+        let max_in_use = Arc::new(AtomicUsize::new(0));
+        let pool = WalletRpcPool::new(
+            (0..3)
+                .map(|_| FakeWalletRpc::new(&sweeps, &in_use, &max_in_use))
+                .collect(),
+        );
+        let destination = "53H3QthYLckeCXh9u38vohb2gZ4QgEG3FMWHNxccR6MqV1LdDVYwF1FKsRJPj4tTupWLf9JtGPBcn2MVN6c9oR7p5Uf7JdJ".parse::<Address>().unwrap();
+
+        let refunds = (0..6).map(|i| {
+            let private_spend_key =
+                PrivateKey::from_scalar(crate::monero::Scalar::random(&mut rand::thread_rng()));
+            let private_view_key = PrivateViewKey::new_random(&mut rand::thread_rng());
+            let wallet = GeneratedWallet {
+                file_name: format!("refund-{}", i),
+                address: Address::standard(
+                    Network::Mainnet,
+                    PublicKey::from_private_key(&private_spend_key),
+                    PublicKey::from_private_key(&private_view_key.into()),
+                ),
+                private_spend_key,
+                private_view_key,
+                restore_height: BlockHeight { height: 0 },
+            };
+
+            sweep_on_pool(&pool, wallet, destination)
+        });
+        let results = futures::future::join_all(refunds).await;
+
+        for result in results {
+            result.unwrap();
+        }
+        let mut sweeps = sweeps.lock().unwrap().clone();
+        sweeps.sort();
+        assert_eq!(
+            sweeps,
+            (0..6)
+                .map(|i| (format!("refund-{}", i), destination.to_string()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(max_in_use.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn given_all_connections_in_use_then_waits_for_one_to_be_returned() {
+        let pool = WalletRpcPool::new(vec![1]);
+
+        let first = pool.acquire().await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), pool.acquire())
+                .await
+                .is_err()
+        );
+
+        drop(first);
+        assert_eq!(*pool.acquire().await, 1);
+    }
+
+    /// Behaves like a `monero-wallet-rpc` in that it has at most one wallet
+    /// open, and fails if it is used by more than one task at a time.
+    struct FakeWalletRpc {
+        open: StdMutex<Option<String>>,
+        busy: AtomicBool,
+        sweeps: Arc<StdMutex<Vec<(String, String)>>>,
+        in_use: Arc<AtomicUsize>,
+        max_in_use: Arc<AtomicUsize>,
+    }
+
+    impl FakeWalletRpc {
+        fn new(
+            sweeps: &Arc<StdMutex<Vec<(String, String)>>>,
+            in_use: &Arc<AtomicUsize>,
+            max_in_use: &Arc<AtomicUsize>,
+        ) -> Self {
+            Self {
+                open: StdMutex::new(None),
+                busy: AtomicBool::new(false),
+                sweeps: sweeps.clone(),
+                in_use: in_use.clone(),
+                max_in_use: max_in_use.clone(),
+            }
+        }
+
+        /// Marks the connection as used by the wallet that is being opened
+        /// and yields, to give other tasks the chance to interfere.
+        async fn open(&self, filename: String) {
+            assert!(
+                !self.busy.swap(true, Ordering::SeqCst),
+                "connection used by two tasks at the same time"
+            );
+            let in_use = self.in_use.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_use.fetch_max(in_use, Ordering::SeqCst);
+
+            *self.open.lock().unwrap() = Some(filename);
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> for FakeWalletRpc {
+        async fn open_wallet(
+            &self,
+            filename: String,
+        ) -> Result<wallet::WalletOpened, monero_rpc::jsonrpc::Error<reqwest::Error>> {
+            self.open(filename).await;
+
+            Ok(monero_rpc::wallet::Empty {})
+        }
+
+        async fn close_wallet(
+            &self,
+        ) -> Result<wallet::WalletClosed, monero_rpc::jsonrpc::Error<reqwest::Error>> {
+            *self.open.lock().unwrap() = None;
+            self.in_use.fetch_sub(1, Ordering::SeqCst);
+            self.busy.store(false, Ordering::SeqCst);
+
+            Ok(monero_rpc::wallet::Empty {})
+        }
+
+        async fn generate_from_keys(
+            &self,
+            filename: String,
+            address: String,
+            _: String,
+            _: String,
+            _: u32,
+            _: String,
+            _: bool,
+        ) -> Result<wallet::GenerateFromKeys, monero_rpc::jsonrpc::Error<reqwest::Error>> {
+            self.open(filename).await;
+
+            Ok(wallet::GenerateFromKeys {
+                address,
+                info: String::new(),
+            })
+        }
+
+        async fn refresh(
+            &self,
+        ) -> Result<wallet::Refreshed, monero_rpc::jsonrpc::Error<reqwest::Error>> {
+            tokio::task::yield_now().await;
+
+            Ok(wallet::Refreshed {
+                blocks_fetched: 1,
+                received_money: true,
+            })
+        }
+
+        async fn sweep_all(
+            &self,
+            address: String,
+        ) -> Result<wallet::SweepAll, monero_rpc::jsonrpc::Error<reqwest::Error>> {
+            let open = self.open.lock().unwrap().clone().expect("a wallet is open");
+            self.sweeps.lock().unwrap().push((open, address));
+
+            Ok(serde_json::from_value(serde_json::json!({
+                "amount_list": [],
+                "fee_list": [],
+                "multisig_txset": "",
+                "tx_hash_list": [],
+                "unsigned_txset": "",
+                "weight_list": []
+            }))
+            .unwrap())
+        }
+
+        async fn send_request<P>(
+            &self,
+            _: String,
+        ) -> Result<monero_rpc::jsonrpc::Response<P>, reqwest::Error>
+        where
+            P: serde::de::DeserializeOwned,
+        {
+            // Only the requests above are used when sweeping, anything else
+            // fails like a request that cannot be sent.
+            Err(reqwest::Client::new()
+                .post("")
+                .build()
+                .expect_err("an empty URL is invalid"))
+        }
+    }
+
     type ErrorCode = i64;
     type ErrorMessage = String;
 
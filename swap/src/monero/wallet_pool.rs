@@ -0,0 +1,74 @@
+use std::ops::Deref;
+use std::sync::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Connections to `monero-wallet-rpc` instances that are handed out for
+/// exclusive use.
+///
+/// A `monero-wallet-rpc` can only have one wallet open at a time. Generated
+/// wallets are therefore opened on a connection from this pool, so that
+/// sweeping one of them never switches the wallet another swap is using.
+#[derive(Debug)]
+pub struct WalletRpcPool<C> {
+    clients: Mutex<Vec<C>>,
+    available: Semaphore,
+}
+
+impl<C> WalletRpcPool<C> {
+    pub fn new(clients: Vec<C>) -> Self {
+        Self {
+            available: Semaphore::new(clients.len()),
+            clients: Mutex::new(clients),
+        }
+    }
+
+    /// Waits until a connection is available and takes it out of the pool.
+    ///
+    /// The connection is put back once the returned guard is dropped.
+    pub async fn acquire(&self) -> PooledClient<'_, C> {
+        let permit = self
+            .available
+            .acquire()
+            .await
+            .expect("we never close the semaphore");
+        let client = self
+            .clients
+            .lock()
+            .expect("lock is not poisoned")
+            .pop()
+            .expect("a permit guarantees an available client");
+
+        PooledClient {
+            pool: self,
+            client: Some(client),
+            _permit: permit,
+        }
+    }
+}
+
+pub struct PooledClient<'a, C> {
+    pool: &'a WalletRpcPool<C>,
+    client: Option<C>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl<C> Deref for PooledClient<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.client.as_ref().expect("client is only taken on drop")
+    }
+}
+
+impl<C> Drop for PooledClient<'_, C> {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            // Return the client before the permit is released.
+            self.pool
+                .clients
+                .lock()
+                .expect("lock is not poisoned")
+                .push(client);
+        }
+    }
+}
@@ -1,24 +1,27 @@
+mod signature;
+
+use crate::monero::daemon::set_wallet_rpc_daemon;
 use ::monero::Network;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use big_bytes::BigByte;
-use futures::{StreamExt, TryStreamExt};
+use futures::StreamExt;
+use monero_rpc::http::Credentials;
+use monero_rpc::monerod::DaemonAddress;
 use monero_rpc::wallet::{Client, MoneroWalletRpc as _};
 use reqwest::header::CONTENT_LENGTH;
 use reqwest::Url;
-use std::io::ErrorKind;
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::fs::{remove_file, OpenOptions};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::fs::{remove_file, File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio_util::codec::{BytesCodec, FramedRead};
-use tokio_util::io::StreamReader;
 
 #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
 compile_error!("unsupported operating system");
 
 #[cfg(target_os = "macos")]
-const DOWNLOAD_URL: &str = "http://downloads.getmonero.org/cli/monero-mac-x64-v0.17.2.0.tar.bz2";
+const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-mac-x64-v0.17.2.0.tar.bz2";
 
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
 const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-linux-x64-v0.17.2.0.tar.bz2";
@@ -30,6 +33,17 @@ const DOWNLOAD_URL: &str =
 #[cfg(target_os = "windows")]
 const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-win-x64-v0.17.2.0.zip";
 
+// SHA-256 of the release archives as listed in the signed `hashes.txt` of the
+// release. Archives without a pinned hash are always checked against the
+// signed `hashes.txt` instead.
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const DOWNLOAD_HASH: Option<&str> =
+    Some("59e16c53b2aff8d9ab7a8ba3279ee826ac1f2480fbb98e79a149e6be23dd9086");
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+const DOWNLOAD_HASH: Option<&str> = None;
+
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 const PACKED_FILE: &str = "monero-wallet-rpc";
 
@@ -40,6 +54,13 @@ const PACKED_FILE: &str = "monero-wallet-rpc.exe";
 #[error("monero wallet rpc executable not found in downloaded archive")]
 pub struct ExecutableNotFoundInArchive;
 
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("hash of the downloaded monero-wallet-rpc archive is {actual}, expected {expected}")]
+pub struct HashMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+
 pub struct WalletRpcProcess {
     _child: Child,
     port: u16,
@@ -52,20 +73,56 @@ impl WalletRpcProcess {
     }
 }
 
+/// Where to get the `monero-wallet-rpc` executable from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WalletRpcBinary {
+    /// Download the release archive and check it against the pinned hash
+    /// and, if requested, the signed `hashes.txt` of the release.
+    Download { verify_signature: bool },
+    /// Use an executable that is already installed.
+    Existing(PathBuf),
+}
+
+impl Default for WalletRpcBinary {
+    fn default() -> Self {
+        WalletRpcBinary::Download {
+            verify_signature: false,
+        }
+    }
+}
+
 pub struct WalletRpc {
     working_dir: PathBuf,
+    exec_path: PathBuf,
 }
 
 impl WalletRpc {
-    pub async fn new(working_dir: impl AsRef<Path>) -> Result<WalletRpc> {
+    pub async fn new(working_dir: impl AsRef<Path>, binary: WalletRpcBinary) -> Result<WalletRpc> {
         let working_dir = working_dir.as_ref();
 
         if !working_dir.exists() {
             tokio::fs::create_dir(working_dir).await?;
         }
 
+        let verify_signature = match binary {
+            WalletRpcBinary::Existing(exec_path) => {
+                if !exec_path.is_file() {
+                    bail!("monero-wallet-rpc not found at {}", exec_path.display())
+                }
+
+                tracing::debug!(path = %exec_path.display(), "Using existing monero-wallet-rpc");
+
+                return Ok(WalletRpc {
+                    working_dir: working_dir.to_path_buf(),
+                    exec_path,
+                });
+            }
+            WalletRpcBinary::Download { verify_signature } => verify_signature,
+        };
+
         let monero_wallet_rpc = WalletRpc {
             working_dir: working_dir.to_path_buf(),
+            exec_path: working_dir.join(PACKED_FILE),
         };
 
         if monero_wallet_rpc.archive_path().exists() {
@@ -73,54 +130,51 @@ impl WalletRpc {
         }
 
         if !monero_wallet_rpc.exec_path().exists() {
-            let mut options = OpenOptions::new();
-            let mut file = options
-                .read(true)
-                .write(true)
-                .create_new(true)
-                .open(monero_wallet_rpc.archive_path())
-                .await?;
-
-            let response = reqwest::get(DOWNLOAD_URL).await?;
-
-            let content_length = response.headers()[CONTENT_LENGTH]
-                .to_str()
-                .context("Failed to convert content-length to string")?
-                .parse::<u64>()?;
-
-            tracing::info!(
-                "Downloading monero-wallet-rpc ({}) from {}",
-                content_length.big_byte(2),
-                DOWNLOAD_URL
-            );
-
-            let byte_stream = response
-                .bytes_stream()
-                .map_err(|err| std::io::Error::new(ErrorKind::Other, err));
-
-            #[cfg(not(target_os = "windows"))]
-            let mut stream = FramedRead::new(
-                async_compression::tokio::bufread::BzDecoder::new(StreamReader::new(byte_stream)),
-                BytesCodec::new(),
-            )
-            .map_ok(|bytes| bytes.freeze());
+            let archive_path = monero_wallet_rpc.archive_path();
 
-            #[cfg(target_os = "windows")]
-            let mut stream = FramedRead::new(StreamReader::new(byte_stream), BytesCodec::new())
-                .map_ok(|bytes| bytes.freeze());
+            download(DOWNLOAD_URL, &archive_path).await?;
 
-            while let Some(chunk) = stream.next().await {
-                file.write(&chunk?).await?;
+            let installed = monero_wallet_rpc.install(verify_signature).await;
+            if archive_path.exists() {
+                remove_file(&archive_path).await?;
             }
+            installed?;
+        }
 
-            file.flush().await?;
+        Ok(monero_wallet_rpc)
+    }
+
+    /// Verifies the downloaded archive and extracts the executable from it.
+    async fn install(&self, verify_signature: bool) -> Result<()> {
+        self.install_pinned(DOWNLOAD_HASH, verify_signature).await
+    }
+
+    async fn install_pinned(&self, pinned_hash: Option<&str>, verify_signature: bool) -> Result<()> {
+        let archive_path = self.archive_path();
+        let hash = sha256_of_file(&archive_path).await?;
 
-            Self::extract_archive(&monero_wallet_rpc).await?;
+        if let Some(pinned_hash) = pinned_hash {
+            verify_hash(pinned_hash, &hash)?;
         }
-        Ok(monero_wallet_rpc)
+
+        if verify_signature || pinned_hash.is_none() {
+            let signed_hash =
+                signature::signed_hash(&self.working_dir, archive_name(DOWNLOAD_URL)).await?;
+            verify_hash(&signed_hash, &hash)?;
+
+            tracing::info!("Verified monero-wallet-rpc against the signed hashes of the release");
+        }
+
+        extract_archive(&archive_path, &self.exec_path).await
     }
 
-    pub async fn run(&self, network: Network, daemon_address: &str) -> Result<WalletRpcProcess> {
+    pub async fn run(
+        &self,
+        network: Network,
+        daemon_address: &str,
+        daemon_login: Option<&Credentials>,
+    ) -> Result<WalletRpcProcess> {
+        let daemon_address = daemon_address.parse::<DaemonAddress>()?;
         let port = tokio::net::TcpListener::bind("127.0.0.1:0")
             .await?
             .local_addr()?
@@ -143,19 +197,24 @@ impl WalletRpc {
             }
         };
 
-        let mut child = Command::new(self.exec_path())
+        let mut command = Command::new(self.exec_path());
+        command
             .env("LANG", "en_AU.UTF-8")
             .stdout(Stdio::piped())
             .kill_on_drop(true)
             .args(network_flag)
             .arg("--daemon-address")
-            .arg(daemon_address)
+            .arg(daemon_address.host_and_port())
             .arg("--rpc-bind-port")
             .arg(format!("{}", port))
             .arg("--disable-rpc-login")
             .arg("--wallet-dir")
-            .arg(self.working_dir.join("monero-data"))
-            .spawn()?;
+            .arg(self.working_dir.join("monero-data"));
+        if daemon_address.tls {
+            command.arg("--daemon-ssl").arg("enabled");
+        }
+
+        let mut child = command.spawn()?;
 
         let stdout = child
             .stdout
@@ -181,7 +240,14 @@ impl WalletRpc {
         }
 
         // Send a json rpc request to make sure monero_wallet_rpc is ready
-        Client::localhost(port)?.get_version().await?;
+        let client = Client::localhost(port)?;
+        client.get_version().await?;
+
+        if daemon_login.is_some() {
+            set_wallet_rpc_daemon(&client, &daemon_address, daemon_login)
+                .await
+                .context("Failed to log in to the Monero daemon")?;
+        }
 
         Ok(WalletRpcProcess {
             _child: child,
@@ -193,74 +259,267 @@ impl WalletRpc {
         self.working_dir.join("monero-cli-wallet.archive")
     }
 
-    fn exec_path(&self) -> PathBuf {
-        self.working_dir.join(PACKED_FILE)
+    fn exec_path(&self) -> &Path {
+        &self.exec_path
+    }
+}
+
+/// Downloads the release archive to `archive_path` as is.
+async fn download(url: &str, archive_path: &Path) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(archive_path)
+        .await?;
+
+    let response = reqwest::get(url).await?;
+
+    let content_length = response.headers()[CONTENT_LENGTH]
+        .to_str()
+        .context("Failed to convert content-length to string")?
+        .parse::<u64>()?;
+
+    tracing::info!(
+        "Downloading monero-wallet-rpc ({}) from {}",
+        content_length.big_byte(2),
+        url
+    );
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        file.write_all(&chunk?).await?;
     }
 
-    #[cfg(not(target_os = "windows"))]
-    async fn extract_archive(monero_wallet_rpc: &Self) -> Result<()> {
-        use anyhow::bail;
-        use tokio_tar::Archive;
-
-        let mut options = OpenOptions::new();
-        let file = options
-            .read(true)
-            .open(monero_wallet_rpc.archive_path())
-            .await?;
-
-        let mut ar = Archive::new(file);
-        let mut entries = ar.entries()?;
-
-        loop {
-            match entries.next().await {
-                Some(file) => {
-                    let mut f = file?;
-                    if f.path()?
-                        .to_str()
-                        .context("Could not find convert path to str in tar ball")?
-                        .contains(PACKED_FILE)
-                    {
-                        f.unpack(monero_wallet_rpc.exec_path()).await?;
-                        break;
-                    }
+    file.flush().await?;
+
+    Ok(())
+}
+
+async fn sha256_of_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn verify_hash(expected: &str, actual: &str) -> Result<(), HashMismatch> {
+    if !expected.eq_ignore_ascii_case(actual) {
+        return Err(HashMismatch {
+            expected: expected.to_owned(),
+            actual: actual.to_owned(),
+        });
+    }
+
+    Ok(())
+}
+
+fn archive_name(url: &str) -> &str {
+    url.rsplit('/').next().unwrap_or(url)
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn extract_archive(archive_path: &Path, exec_path: &Path) -> Result<()> {
+    use async_compression::tokio::bufread::BzDecoder;
+    use tokio_tar::Archive;
+
+    let file = File::open(archive_path).await?;
+
+    let mut ar = Archive::new(BzDecoder::new(BufReader::new(file)));
+    let mut entries = ar.entries()?;
+
+    loop {
+        match entries.next().await {
+            Some(file) => {
+                let mut f = file?;
+                if f.path()?
+                    .to_str()
+                    .context("Could not find convert path to str in tar ball")?
+                    .contains(PACKED_FILE)
+                {
+                    f.unpack(exec_path).await?;
+                    break;
                 }
-                None => bail!(ExecutableNotFoundInArchive),
             }
+            None => bail!(ExecutableNotFoundInArchive),
         }
+    }
 
-        remove_file(monero_wallet_rpc.archive_path()).await?;
+    Ok(())
+}
 
+#[cfg(target_os = "windows")]
+async fn extract_archive(archive_path: &Path, exec_path: &Path) -> Result<()> {
+    use std::fs::File;
+    use tokio::task::JoinHandle;
+    use zip::ZipArchive;
+
+    let archive_path = archive_path.to_path_buf();
+    let exec_path = exec_path.to_path_buf();
+
+    let extract: JoinHandle<Result<()>> = tokio::task::spawn_blocking(|| {
+        let file = File::open(archive_path)?;
+        let mut zip = ZipArchive::new(file)?;
+
+        let name = zip
+            .file_names()
+            .find(|name| name.contains(PACKED_FILE))
+            .context(ExecutableNotFoundInArchive)?
+            .to_string();
+
+        let mut rpc = zip.by_name(&name)?;
+        let mut file = File::create(exec_path)?;
+        std::io::copy(&mut rpc, &mut file)?;
         Ok(())
+    });
+    extract.await??;
+
+    Ok(())
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+    use async_compression::tokio::write::BzEncoder;
+    use tempfile::tempdir;
+
+    const EXECUTABLE: &[u8] = b"#!/bin/sh\necho monero-wallet-rpc\n";
+
+    async fn fixture_archive(dir: &Path) -> PathBuf {
+        let mut header = tokio_tar::Header::new_gnu();
+        header.set_size(EXECUTABLE.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+
+        let mut builder = tokio_tar::Builder::new(Vec::new());
+        builder
+            .append_data(
+                &mut header,
+                format!("monero-x86_64-linux-gnu-v0.17.2.0/{}", PACKED_FILE),
+                EXECUTABLE,
+            )
+            .await
+            .unwrap();
+        let tar = builder.into_inner().await.unwrap();
+
+        let mut encoder = BzEncoder::new(Vec::new());
+        encoder.write_all(&tar).await.unwrap();
+        encoder.shutdown().await.unwrap();
+
+        let archive_path = dir.join("monero-cli-wallet.archive");
+        tokio::fs::write(&archive_path, encoder.into_inner())
+            .await
+            .unwrap();
+
+        archive_path
     }
 
-    #[cfg(target_os = "windows")]
-    async fn extract_archive(monero_wallet_rpc: &Self) -> Result<()> {
-        use std::fs::File;
-        use tokio::task::JoinHandle;
-        use zip::ZipArchive;
-
-        let archive_path = monero_wallet_rpc.archive_path();
-        let exec_path = monero_wallet_rpc.exec_path();
-
-        let extract: JoinHandle<Result<()>> = tokio::task::spawn_blocking(|| {
-            let file = File::open(archive_path)?;
-            let mut zip = ZipArchive::new(file)?;
-
-            let name = zip
-                .file_names()
-                .find(|name| name.contains(PACKED_FILE))
-                .context(ExecutableNotFoundInArchive)?
-                .to_string();
-
-            let mut rpc = zip.by_name(&name)?;
-            let mut file = File::create(exec_path)?;
-            std::io::copy(&mut rpc, &mut file)?;
-            Ok(())
-        });
-        extract.await??;
+    #[tokio::test]
+    async fn given_matching_hash_then_extracts_executable() {
+        let dir = tempdir().unwrap();
+        let archive_path = fixture_archive(dir.path()).await;
+        let exec_path = dir.path().join(PACKED_FILE);
 
-        remove_file(monero_wallet_rpc.archive_path()).await?;
+        let hash = sha256_of_file(&archive_path).await.unwrap();
+        verify_hash(&hash.to_uppercase(), &hash).unwrap();
+        extract_archive(&archive_path, &exec_path).await.unwrap();
 
-        Ok(())
+        assert_eq!(tokio::fs::read(&exec_path).await.unwrap(), EXECUTABLE);
+    }
+
+    #[tokio::test]
+    async fn given_wrong_hash_then_refuses_to_install() {
+        let dir = tempdir().unwrap();
+        fixture_archive(dir.path()).await;
+
+        let wallet_rpc = WalletRpc {
+            working_dir: dir.path().to_path_buf(),
+            exec_path: dir.path().join(PACKED_FILE),
+        };
+        let error = wallet_rpc
+            .install_pinned(
+                Some("59e16c53b2aff8d9ab7a8ba3279ee826ac1f2480fbb98e79a149e6be23dd9086"),
+                false,
+            )
+            .await
+            .unwrap_err();
+
+        assert!(error.is::<HashMismatch>());
+        assert!(!wallet_rpc.exec_path().exists());
+    }
+
+    #[test]
+    fn given_different_hash_then_reports_mismatch() {
+        let error = verify_hash(
+            "59e16c53b2aff8d9ab7a8ba3279ee826ac1f2480fbb98e79a149e6be23dd9086",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.actual,
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
+    }
+
+    #[tokio::test]
+    async fn given_archive_without_executable_then_fails() {
+        let dir = tempdir().unwrap();
+        let archive_path = dir.path().join("empty.archive");
+
+        let mut encoder = BzEncoder::new(Vec::new());
+        encoder
+            .write_all(&tokio_tar::Builder::new(Vec::new()).into_inner().await.unwrap())
+            .await
+            .unwrap();
+        encoder.shutdown().await.unwrap();
+        tokio::fs::write(&archive_path, encoder.into_inner())
+            .await
+            .unwrap();
+
+        let error = extract_archive(&archive_path, &dir.path().join(PACKED_FILE))
+            .await
+            .unwrap_err();
+
+        assert!(error.is::<ExecutableNotFoundInArchive>());
+    }
+
+    #[tokio::test]
+    async fn given_existing_executable_then_does_not_download() {
+        let dir = tempdir().unwrap();
+        let exec_path = dir.path().join("my-monero-wallet-rpc");
+        tokio::fs::write(&exec_path, EXECUTABLE).await.unwrap();
+
+        let wallet_rpc = WalletRpc::new(
+            dir.path().join("monero"),
+            WalletRpcBinary::Existing(exec_path.clone()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(wallet_rpc.exec_path(), exec_path);
+        assert!(!wallet_rpc.archive_path().exists());
+    }
+
+    #[tokio::test]
+    async fn given_missing_executable_then_fails() {
+        let dir = tempdir().unwrap();
+
+        let result = WalletRpc::new(
+            dir.path().join("monero"),
+            WalletRpcBinary::Existing(dir.path().join("does-not-exist")),
+        )
+        .await;
+
+        assert!(result.is_err());
     }
 }
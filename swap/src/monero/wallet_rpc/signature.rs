@@ -0,0 +1,171 @@
+//! Verification of the `hashes.txt` that is signed for every Monero release.
+//!
+//! None of our dependencies implement OpenPGP, so this shells out to `gpg`
+//! with a throwaway home directory. The signing key is fetched from the Monero
+//! repository and only trusted if its fingerprint matches the pinned one.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use tokio::process::Command;
+
+const HASHES_URL: &str = "https://www.getmonero.org/downloads/hashes.txt";
+const SIGNING_KEY_URL: &str =
+    "https://raw.githubusercontent.com/monero-project/monero/master/utils/gpg_keys/binaryfate.asc";
+
+/// Fingerprint of binaryFate's key, which signs the Monero releases.
+const SIGNING_KEY_FINGERPRINT: &str = "81AC591FE9C4B65C5806AFC3F0AF4D462A0BDF92";
+
+/// Returns the SHA-256 of `archive_name` from the signed `hashes.txt`.
+pub async fn signed_hash(working_dir: &Path, archive_name: &str) -> Result<String> {
+    let gpg_home = working_dir.join("gnupg");
+    if gpg_home.exists() {
+        tokio::fs::remove_dir_all(&gpg_home).await?;
+    }
+    tokio::fs::create_dir(&gpg_home).await?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&gpg_home, std::fs::Permissions::from_mode(0o700)).await?;
+    }
+
+    let result = verify_hashes_txt(&gpg_home).await;
+    tokio::fs::remove_dir_all(&gpg_home).await?;
+    let hashes_txt = result?;
+
+    hash_of(&hashes_txt, archive_name).with_context(|| {
+        format!(
+            "Signed hashes.txt does not list {}, it only covers the latest Monero release. Provide monero-wallet-rpc with --monero-wallet-rpc instead",
+            archive_name
+        )
+    })
+}
+
+/// Downloads `hashes.txt` and returns its content if the signature is valid.
+async fn verify_hashes_txt(gpg_home: &Path) -> Result<String> {
+    let key_path = gpg_home.join("binaryfate.asc");
+    let hashes_path = gpg_home.join("hashes.txt");
+
+    tokio::fs::write(&key_path, download(SIGNING_KEY_URL).await?).await?;
+    tokio::fs::write(&hashes_path, download(HASHES_URL).await?).await?;
+
+    let import = gpg(gpg_home)
+        .arg("--import")
+        .arg(&key_path)
+        .output()
+        .await
+        .context("Failed to run gpg, please make sure it is installed")?;
+    if !import.status.success() {
+        bail!(
+            "Failed to import Monero signing key: {}",
+            String::from_utf8_lossy(&import.stderr)
+        )
+    }
+
+    let verify = gpg(gpg_home)
+        .args(&["--status-fd", "2", "--output", "-", "--decrypt"])
+        .arg(&hashes_path)
+        .output()
+        .await?;
+
+    let status = String::from_utf8_lossy(&verify.stderr);
+    let fingerprint = match valid_signature_fingerprint(&status) {
+        Some(fingerprint) if verify.status.success() => fingerprint,
+        _ => bail!("Signature of hashes.txt is not valid"),
+    };
+    if !fingerprint.eq_ignore_ascii_case(SIGNING_KEY_FINGERPRINT) {
+        bail!(
+            "hashes.txt is signed by {}, expected {}",
+            fingerprint,
+            SIGNING_KEY_FINGERPRINT
+        )
+    }
+
+    String::from_utf8(verify.stdout).context("Signed hashes.txt is not valid UTF-8")
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let bytes = reqwest::get(url)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?;
+
+    Ok(bytes.to_vec())
+}
+
+fn gpg(gpg_home: &Path) -> Command {
+    let mut command = Command::new("gpg");
+    command.arg("--homedir").arg(gpg_home).arg("--batch");
+
+    command
+}
+
+/// Extracts the fingerprint of the primary key from gpg's `VALIDSIG` status
+/// line.
+fn valid_signature_fingerprint(status: &str) -> Option<&str> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+        .and_then(|fields| {
+            let fields = fields.split_whitespace().collect::<Vec<_>>();
+
+            // The primary key fingerprint is only present if the signature was
+            // made with a subkey.
+            fields.get(9).or_else(|| fields.first()).copied()
+        })
+}
+
+fn hash_of(hashes_txt: &str, archive_name: &str) -> Option<String> {
+    hashes_txt.lines().find_map(|line| {
+        let mut fields = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|field| !field.is_empty());
+
+        match (fields.next(), fields.next()) {
+            (Some(name), Some(hash)) if name == archive_name => Some(hash.to_lowercase()),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASHES_TXT: &str = "## CLI
+monero-android-armv7-v0.17.2.0.tar.bz2, 3c5a0b5bd5e6a1b7dbbfbc7d8a1a6d3c7a3b7ae3ce9ba3a0c5f6e0f1e8b4a9c2
+monero-linux-x64-v0.17.2.0.tar.bz2, 59E16C53B2AFF8D9AB7A8BA3279EE826AC1F2480FBB98E79A149E6BE23DD9086
+";
+
+    #[test]
+    fn finds_hash_of_archive() {
+        assert_eq!(
+            hash_of(HASHES_TXT, "monero-linux-x64-v0.17.2.0.tar.bz2").as_deref(),
+            Some("59e16c53b2aff8d9ab7a8ba3279ee826ac1f2480fbb98e79a149e6be23dd9086")
+        );
+        assert_eq!(hash_of(HASHES_TXT, "monero-win-x64-v0.17.2.0.zip"), None);
+    }
+
+    #[test]
+    fn extracts_primary_key_fingerprint_from_status() {
+        let status = "[GNUPG:] NEWSIG
+[GNUPG:] GOODSIG F0AF4D462A0BDF92 binaryFate <binaryfate@getmonero.org>
+[GNUPG:] VALIDSIG 2DA6D3CFA8B8F3B69C5D3A9D7A6F3E0B11C5E2D4 2021-04-14 1618407180 0 4 0 1 10 01 81AC591FE9C4B65C5806AFC3F0AF4D462A0BDF92
+[GNUPG:] TRUST_UNDEFINED 0 pgp";
+
+        assert_eq!(
+            valid_signature_fingerprint(status),
+            Some(SIGNING_KEY_FINGERPRINT)
+        );
+    }
+
+    #[test]
+    fn given_no_valid_signature_then_no_fingerprint() {
+        let status = "[GNUPG:] NEWSIG
+[GNUPG:] BADSIG F0AF4D462A0BDF92 binaryFate <binaryfate@getmonero.org>";
+
+        assert_eq!(valid_signature_fingerprint(status), None);
+    }
+}
@@ -1,9 +1,14 @@
 mod impl_from_rr_event;
 
+pub mod address_book;
+pub mod capabilities;
 pub mod cbor_request_response;
+pub mod dht;
 pub mod encrypted_signature;
+pub mod identify;
 pub mod json_pull_codec;
 pub mod quote;
+pub mod quote_subscription;
 pub mod redial;
 pub mod rendezvous;
 pub mod swap_setup;
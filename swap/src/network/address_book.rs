@@ -0,0 +1,347 @@
+//! Keeps track of how well each known address of a peer works, so we dial the
+//! most promising one first.
+
+use crate::libp2p_ext::MultiAddrExt;
+use crate::tor;
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AddressHealth {
+    /// Unix timestamp of the last time we connected through this address.
+    pub last_success: Option<u64>,
+    /// The number of failed dials since the last successful one.
+    pub failures: u32,
+    /// The latest round-trip time measured on a connection through this
+    /// address.
+    pub latency: Option<Duration>,
+}
+
+impl AddressHealth {
+    fn record_success(&mut self, now: u64) {
+        self.last_success = Some(now);
+        self.failures = 0;
+    }
+
+    fn record_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+}
+
+/// Which kind of address we would rather dial.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressPreference {
+    /// Tor is running, onion addresses go first.
+    Onion,
+    /// Tor is not running, onion addresses cannot be dialed at all.
+    Clearnet,
+}
+
+impl AddressPreference {
+    pub async fn detect(tor_socks5_port: u16) -> Self {
+        match tor::Client::new(tor_socks5_port).assert_tor_running().await {
+            Ok(()) => AddressPreference::Onion,
+            Err(_) => AddressPreference::Clearnet,
+        }
+    }
+}
+
+/// The known addresses of a peer and their health.
+///
+/// Addresses are compared without their `/p2p` suffix, so the address a
+/// seller was given with on the command line and the same address learned
+/// through identify are one entry.
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    addresses: HashMap<Multiaddr, AddressHealth>,
+}
+
+impl AddressBook {
+    /// Adds an address we have not seen before, returning whether it was new.
+    pub fn add(&mut self, address: Multiaddr) -> bool {
+        if self.find(&address).is_some() {
+            return false;
+        }
+
+        self.addresses.insert(address, AddressHealth::default());
+        true
+    }
+
+    /// Adds an address with the health we recorded for it earlier.
+    pub fn restore(&mut self, address: Multiaddr, health: AddressHealth) {
+        let address = self.find(&address).cloned().unwrap_or(address);
+        self.addresses.insert(address, health);
+    }
+
+    /// The addresses to dial, healthiest first.
+    ///
+    /// Addresses that failed since they last worked go last, ordered by how
+    /// often they failed. Otherwise addresses matching our preference go
+    /// first, followed by the one that worked most recently and the one with
+    /// the lowest latency.
+    pub fn ranked(&self, preference: AddressPreference) -> Vec<Multiaddr> {
+        let mut addresses = self
+            .addresses
+            .iter()
+            .filter(|(address, _)| preference == AddressPreference::Onion || !is_onion(address))
+            .collect::<Vec<_>>();
+
+        addresses.sort_by_key(|(address, health)| {
+            (
+                health.failures,
+                is_onion(address) != (preference == AddressPreference::Onion),
+                Reverse(health.last_success),
+                health.latency.unwrap_or(Duration::MAX),
+            )
+        });
+
+        addresses
+            .into_iter()
+            .map(|(address, _)| address.clone())
+            .collect()
+    }
+
+    /// Records that we connected through `address`, returning the address as
+    /// it is known to us and its new health.
+    pub fn record_success(&mut self, address: &Multiaddr, now: u64) -> (Multiaddr, AddressHealth) {
+        let address = self
+            .find(address)
+            .cloned()
+            .unwrap_or_else(|| address.clone());
+        let health = self.addresses.entry(address.clone()).or_default();
+        health.record_success(now);
+
+        (address, *health)
+    }
+
+    /// Records that dialing `address` failed, returning the address as it is
+    /// known to us and its new health.
+    pub fn record_failure(&mut self, address: &Multiaddr) -> Option<(Multiaddr, AddressHealth)> {
+        let address = self.find(address)?.clone();
+        let health = self.addresses.get_mut(&address)?;
+        health.record_failure();
+
+        Some((address, *health))
+    }
+
+    /// Records the round-trip time measured on a connection through
+    /// `address`, returning the address as it is known to us and its new
+    /// health.
+    pub fn record_latency(
+        &mut self,
+        address: &Multiaddr,
+        latency: Duration,
+    ) -> Option<(Multiaddr, AddressHealth)> {
+        let address = self.find(address)?.clone();
+        let health = self.addresses.get_mut(&address)?;
+        health.latency = Some(latency);
+
+        Some((address, *health))
+    }
+
+    fn find(&self, address: &Multiaddr) -> Option<&Multiaddr> {
+        let address = address.without_peer_id();
+
+        self.addresses
+            .keys()
+            .find(|known| known.without_peer_id() == address)
+    }
+}
+
+/// Whether an address a peer told us about is worth dialing later.
+///
+/// Loopback and unspecified addresses only work on the peer's own machine.
+/// Private, shared (carrier-grade NAT) and link-local addresses, which
+/// includes the ones of docker networks, only work within the peer's own
+/// network.
+pub fn is_dialable_remotely(address: &Multiaddr) -> bool {
+    match address.iter().next() {
+        Some(Protocol::Ip4(ip)) => is_global_ipv4(ip),
+        Some(Protocol::Ip6(ip)) => is_global_ipv6(ip),
+        _ => true,
+    }
+}
+
+fn is_global_ipv4(ip: Ipv4Addr) -> bool {
+    let is_shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0b1100_0000) == 0b0100_0000;
+
+    !ip.is_loopback()
+        && !ip.is_unspecified()
+        && !ip.is_private()
+        && !ip.is_link_local()
+        && !is_shared
+}
+
+fn is_global_ipv6(ip: Ipv6Addr) -> bool {
+    let is_unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+    let is_link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+
+    !ip.is_loopback() && !ip.is_unspecified() && !is_unique_local && !is_link_local
+}
+
+fn is_onion(address: &Multiaddr) -> bool {
+    address
+        .iter()
+        .any(|protocol| matches!(protocol, Protocol::Onion(..) | Protocol::Onion3(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(address: &str) -> Multiaddr {
+        address.parse().unwrap()
+    }
+
+    fn book(addresses: Vec<(&str, AddressHealth)>) -> AddressBook {
+        let mut book = AddressBook::default();
+        for (a, health) in addresses {
+            book.restore(address(a), health);
+        }
+
+        book
+    }
+
+    const ONION: &str = "/onion3/vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd:9939";
+
+    #[test]
+    fn addresses_that_failed_go_last() {
+        let book = book(vec![
+            ("/ip4/1.1.1.1/tcp/9939", AddressHealth {
+                last_success: Some(1_000),
+                failures: 2,
+                latency: Some(Duration::from_millis(10)),
+            }),
+            ("/ip4/2.2.2.2/tcp/9939", AddressHealth::default()),
+            ("/ip4/3.3.3.3/tcp/9939", AddressHealth {
+                last_success: Some(900),
+                failures: 1,
+                latency: None,
+            }),
+        ]);
+
+        assert_eq!(book.ranked(AddressPreference::Clearnet), vec![
+            address("/ip4/2.2.2.2/tcp/9939"),
+            address("/ip4/3.3.3.3/tcp/9939"),
+            address("/ip4/1.1.1.1/tcp/9939"),
+        ]);
+    }
+
+    #[test]
+    fn most_recently_working_address_goes_first_then_lowest_latency() {
+        let book = book(vec![
+            ("/ip4/1.1.1.1/tcp/9939", AddressHealth {
+                last_success: Some(1_000),
+                failures: 0,
+                latency: Some(Duration::from_millis(300)),
+            }),
+            ("/ip4/2.2.2.2/tcp/9939", AddressHealth {
+                last_success: Some(2_000),
+                failures: 0,
+                latency: Some(Duration::from_millis(100)),
+            }),
+            ("/ip4/3.3.3.3/tcp/9939", AddressHealth {
+                last_success: Some(2_000),
+                failures: 0,
+                latency: Some(Duration::from_millis(50)),
+            }),
+            ("/ip4/4.4.4.4/tcp/9939", AddressHealth::default()),
+        ]);
+
+        assert_eq!(book.ranked(AddressPreference::Clearnet), vec![
+            address("/ip4/3.3.3.3/tcp/9939"),
+            address("/ip4/2.2.2.2/tcp/9939"),
+            address("/ip4/1.1.1.1/tcp/9939"),
+            address("/ip4/4.4.4.4/tcp/9939"),
+        ]);
+    }
+
+    #[test]
+    fn given_tor_then_onion_addresses_go_first() {
+        let book = book(vec![
+            ("/ip4/1.1.1.1/tcp/9939", AddressHealth {
+                last_success: Some(1_000),
+                failures: 0,
+                latency: None,
+            }),
+            (ONION, AddressHealth::default()),
+        ]);
+
+        assert_eq!(book.ranked(AddressPreference::Onion), vec![
+            address(ONION),
+            address("/ip4/1.1.1.1/tcp/9939"),
+        ]);
+    }
+
+    #[test]
+    fn given_no_tor_then_onion_addresses_are_skipped() {
+        let book = book(vec![
+            ("/ip4/1.1.1.1/tcp/9939", AddressHealth::default()),
+            (ONION, AddressHealth::default()),
+        ]);
+
+        assert_eq!(book.ranked(AddressPreference::Clearnet), vec![address(
+            "/ip4/1.1.1.1/tcp/9939"
+        )]);
+    }
+
+    #[test]
+    fn addresses_are_known_regardless_of_peer_id_suffix() {
+        let mut book = AddressBook::default();
+        let with_peer_id = address(
+            "/ip4/1.1.1.1/tcp/9939/p2p/12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi",
+        );
+        book.add(with_peer_id.clone());
+
+        assert!(!book.add(address("/ip4/1.1.1.1/tcp/9939")));
+
+        book.record_failure(&address("/ip4/1.1.1.1/tcp/9939"));
+        let (known, health) = book.record_success(&address("/ip4/1.1.1.1/tcp/9939"), 1_000);
+
+        assert_eq!(known, with_peer_id);
+        assert_eq!(health, AddressHealth {
+            last_success: Some(1_000),
+            failures: 0,
+            latency: None,
+        });
+    }
+
+    #[test]
+    fn failures_are_counted_until_the_next_success() {
+        let mut book = AddressBook::default();
+        let a = address("/ip4/1.1.1.1/tcp/9939");
+        book.add(a.clone());
+
+        book.record_failure(&a);
+        let (_, health) = book.record_failure(&a).unwrap();
+        assert_eq!(health.failures, 2);
+
+        let (_, health) = book.record_success(&a, 1_000);
+        assert_eq!(health.failures, 0);
+    }
+
+    #[test]
+    fn local_addresses_are_not_dialable_remotely() {
+        assert!(!is_dialable_remotely(&address("/ip4/127.0.0.1/tcp/9939")));
+        assert!(!is_dialable_remotely(&address("/ip6/::1/tcp/9939")));
+        assert!(!is_dialable_remotely(&address("/ip4/0.0.0.0/tcp/9939")));
+        assert!(!is_dialable_remotely(&address("/ip4/10.0.0.5/tcp/9939")));
+        assert!(!is_dialable_remotely(&address("/ip4/172.17.0.2/tcp/9939")));
+        assert!(!is_dialable_remotely(&address(
+            "/ip4/192.168.1.10/tcp/9939"
+        )));
+        assert!(!is_dialable_remotely(&address("/ip4/100.64.0.1/tcp/9939")));
+        assert!(!is_dialable_remotely(&address("/ip4/169.254.1.1/tcp/9939")));
+        assert!(!is_dialable_remotely(&address("/ip6/fe80::1/tcp/9939")));
+        assert!(!is_dialable_remotely(&address("/ip6/fd00::1/tcp/9939")));
+        assert!(is_dialable_remotely(&address("/ip4/1.1.1.1/tcp/9939")));
+        assert!(is_dialable_remotely(&address("/ip4/172.32.0.1/tcp/9939")));
+        assert!(is_dialable_remotely(&address("/ip6/2a01:4f8::1/tcp/9939")));
+        assert!(is_dialable_remotely(&address("/dns4/example.com/tcp/9939")));
+        assert!(is_dialable_remotely(&address(ONION)));
+    }
+}
@@ -0,0 +1,312 @@
+//! Lets a CLI find out what an ASB supports before relying on it.
+//!
+//! The swap setup version is negotiated when the substream is opened, see
+//! [`Version`]. This protocol additionally advertises optional features, so a
+//! CLI does not have to try them to find out whether they are there.
+//!
+//! Fee ladders are not advertised, neither the ASB nor the CLI implement
+//! them yet. Like any other feature they can be added to
+//! [`Capabilities::features`] without a new version of this protocol.
+
+use crate::network::json_pull_codec::JsonPullCodec;
+use crate::network::swap_setup::protocol::Version;
+use crate::{asb, cli};
+use anyhow::anyhow;
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    OutboundFailure, ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+    RequestResponseMessage,
+};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+const PROTOCOL: &str = "/comit/xmr/btc/capabilities/1.0.0";
+pub type OutEvent = RequestResponseEvent<(), Capabilities>;
+pub type Message = RequestResponseMessage<(), Capabilities>;
+
+pub type Behaviour = RequestResponse<JsonPullCodec<CapabilitiesProtocol, Capabilities>>;
+
+/// The ASB sells Monero for Bitcoin, i.e. the CLI locks its Bitcoin first.
+///
+/// This is the only swap variant so far, every ASB supports it.
+pub const XMR_FOR_BTC: &str = "xmr-for-btc";
+
+/// The ASB signs its quotes and honours them at swap setup.
+pub const SIGNED_QUOTES: &str = "signed-quotes";
+
+/// The ASB pushes quote updates to subscribers.
+pub const QUOTE_SUBSCRIPTION: &str = "quote-subscription";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapabilitiesProtocol;
+
+impl ProtocolName for CapabilitiesProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+/// What an ASB supports.
+///
+/// Unknown swap setup versions and features are ignored, so new ones can be
+/// advertised without a new version of this protocol.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// The swap setup protocols the ASB supports, newest first.
+    #[serde(default)]
+    pub swap_setup: Vec<String>,
+    /// The kinds of swaps the ASB offers.
+    #[serde(default = "legacy_swap_variants")]
+    pub swap_variants: Vec<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+fn legacy_swap_variants() -> Vec<String> {
+    vec![XMR_FOR_BTC.to_owned()]
+}
+
+impl Capabilities {
+    /// The capabilities of this version of the ASB.
+    pub fn ours() -> Self {
+        Self {
+            swap_setup: Version::SUPPORTED
+                .iter()
+                .map(|version| version.name().to_owned())
+                .collect(),
+            swap_variants: vec![XMR_FOR_BTC.to_owned()],
+            features: vec![SIGNED_QUOTES.to_owned(), QUOTE_SUBSCRIPTION.to_owned()],
+        }
+    }
+
+    /// The capabilities of ASBs that predate this protocol.
+    pub fn legacy() -> Self {
+        Self {
+            swap_setup: vec![Version::V1_0_0.name().to_owned()],
+            swap_variants: legacy_swap_variants(),
+            features: vec![],
+        }
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|supported| supported == feature)
+    }
+
+    pub fn offers(&self, swap_variant: &str) -> bool {
+        self.swap_variants
+            .iter()
+            .any(|offered| offered == swap_variant)
+    }
+
+    /// The newest swap setup version both we and the ASB support.
+    pub fn swap_setup_version(&self) -> Option<Version> {
+        Version::SUPPORTED.iter().copied().find(|version| {
+            self.swap_setup
+                .iter()
+                .any(|supported| supported == version.name())
+        })
+    }
+}
+
+/// Constructs a new instance of the `capabilities` behaviour to be used by
+/// the ASB.
+pub fn asb() -> Behaviour {
+    Behaviour::new(
+        JsonPullCodec::default(),
+        vec![(CapabilitiesProtocol, ProtocolSupport::Inbound)],
+        RequestResponseConfig::default(),
+    )
+}
+
+/// Constructs a new instance of the `capabilities` behaviour to be used by
+/// the CLI.
+pub fn cli() -> Behaviour {
+    Behaviour::new(
+        JsonPullCodec::default(),
+        vec![(CapabilitiesProtocol, ProtocolSupport::Outbound)],
+        RequestResponseConfig::default(),
+    )
+}
+
+impl From<(PeerId, Message)> for asb::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request { channel, .. } => Self::CapabilitiesRequested { channel, peer },
+            Message::Response { .. } => Self::unexpected_response(peer),
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+impl From<OutEvent> for cli::OutEvent {
+    fn from(event: OutEvent) -> Self {
+        match event {
+            RequestResponseEvent::Message {
+                message:
+                    Message::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            } => Self::CapabilitiesReceived {
+                id: request_id,
+                response,
+            },
+            RequestResponseEvent::Message {
+                peer,
+                message: Message::Request { .. },
+            } => Self::unexpected_request(peer),
+            // ASBs that predate this protocol do not know about it.
+            RequestResponseEvent::OutboundFailure {
+                request_id,
+                error: OutboundFailure::UnsupportedProtocols,
+                ..
+            } => Self::CapabilitiesReceived {
+                id: request_id,
+                response: Capabilities::legacy(),
+            },
+            RequestResponseEvent::OutboundFailure { peer, error, .. } => Self::Failure {
+                peer,
+                error: anyhow!("{} failed: {:?}", PROTOCOL, error),
+            },
+            RequestResponseEvent::InboundFailure { peer, error, .. } => Self::Failure {
+                peer,
+                error: anyhow!("{} failed: {:?}", PROTOCOL, error),
+            },
+            RequestResponseEvent::ResponseSent { .. } => Self::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::quote;
+    use crate::network::test::{new_swarm, SwarmExt};
+    use futures::StreamExt;
+    use libp2p::swarm::SwarmEvent;
+
+    #[test]
+    fn new_asb_is_understood_by_new_cli() {
+        let capabilities = Capabilities::ours();
+
+        assert_eq!(capabilities.swap_setup_version(), Some(Version::V1_1_0));
+        assert!(capabilities.offers(XMR_FOR_BTC));
+        assert!(capabilities.supports(SIGNED_QUOTES));
+        assert!(capabilities.supports(QUOTE_SUBSCRIPTION));
+    }
+
+    #[test]
+    fn legacy_asb_only_supports_first_swap_setup_version() {
+        let capabilities = Capabilities::legacy();
+
+        assert_eq!(capabilities.swap_setup_version(), Some(Version::V1_0_0));
+        assert!(capabilities.offers(XMR_FOR_BTC));
+        assert!(!capabilities.supports(SIGNED_QUOTES));
+    }
+
+    #[test]
+    fn given_no_swap_variants_then_assumes_the_only_one_there_was() {
+        let json = r#"{
+            "swap_setup": ["/comit/xmr/btc/swap_setup/1.1.0"],
+            "features": ["signed-quotes", "quote-subscription"]
+        }"#;
+
+        let capabilities = serde_json::from_str::<Capabilities>(json).unwrap();
+
+        assert!(capabilities.offers(XMR_FOR_BTC));
+    }
+
+    #[test]
+    fn given_other_swap_variants_only_then_does_not_offer_ours() {
+        let json = r#"{
+            "swap_setup": ["/comit/xmr/btc/swap_setup/1.1.0"],
+            "swap_variants": ["btc-for-xmr"]
+        }"#;
+
+        let capabilities = serde_json::from_str::<Capabilities>(json).unwrap();
+
+        assert!(!capabilities.offers(XMR_FOR_BTC));
+    }
+
+    #[test]
+    fn unknown_versions_and_features_are_ignored() {
+        let json = r#"{
+            "swap_setup": ["/comit/xmr/btc/swap_setup/9.0.0", "/comit/xmr/btc/swap_setup/1.0.0"],
+            "features": ["fee-ladder", "signed-quotes"],
+            "something_new": true
+        }"#;
+
+        let capabilities = serde_json::from_str::<Capabilities>(json).unwrap();
+
+        assert_eq!(capabilities.swap_setup_version(), Some(Version::V1_0_0));
+        assert!(capabilities.supports(SIGNED_QUOTES));
+        assert!(!capabilities.supports(QUOTE_SUBSCRIPTION));
+    }
+
+    #[test]
+    fn given_no_common_swap_setup_version_then_none() {
+        let capabilities = Capabilities {
+            swap_setup: vec!["/comit/xmr/btc/swap_setup/9.0.0".to_owned()],
+            swap_variants: vec![XMR_FOR_BTC.to_owned()],
+            features: vec![],
+        };
+
+        assert_eq!(capabilities.swap_setup_version(), None);
+    }
+
+    #[tokio::test]
+    async fn new_cli_gets_capabilities_of_new_asb() {
+        let mut asb = new_swarm(|_, _| asb());
+        asb.listen_on_random_memory_address().await;
+        let mut cli = new_swarm(|_, _| cli());
+        cli.block_on_connection(&mut asb).await;
+
+        cli.behaviour_mut().send_request(asb.local_peer_id(), ());
+
+        let capabilities = loop {
+            tokio::select! {
+                event = asb.select_next_some() => {
+                    if let SwarmEvent::Behaviour(RequestResponseEvent::Message { message: Message::Request { channel, .. }, .. }) = event {
+                        asb.behaviour_mut().send_response(channel, Capabilities::ours()).unwrap();
+                    }
+                }
+                event = cli.select_next_some() => {
+                    if let SwarmEvent::Behaviour(event) = event {
+                        if let cli::OutEvent::CapabilitiesReceived { response, .. } = cli::OutEvent::from(event) {
+                            break response;
+                        }
+                    }
+                }
+            }
+        };
+
+        assert_eq!(capabilities, Capabilities::ours());
+    }
+
+    #[tokio::test]
+    async fn new_cli_assumes_legacy_capabilities_of_old_asb() {
+        // An ASB that predates the capabilities protocol only speaks the others.
+        let mut asb = new_swarm(|_, _| quote::asb());
+        asb.listen_on_random_memory_address().await;
+        let mut cli = new_swarm(|_, _| cli());
+        cli.block_on_connection(&mut asb).await;
+
+        cli.behaviour_mut().send_request(asb.local_peer_id(), ());
+
+        let capabilities = loop {
+            tokio::select! {
+                _ = asb.select_next_some() => {}
+                event = cli.select_next_some() => {
+                    if let SwarmEvent::Behaviour(event) = event {
+                        if let cli::OutEvent::CapabilitiesReceived { response, .. } = cli::OutEvent::from(event) {
+                            break response;
+                        }
+                    }
+                }
+            }
+        };
+
+        assert_eq!(capabilities, Capabilities::legacy());
+    }
+}
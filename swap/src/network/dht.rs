@@ -0,0 +1,95 @@
+//! Discovery of sellers through a Kademlia DHT.
+//!
+//! ASBs announce themselves as providers of the key of their
+//! [`XmrBtcNamespace`], takers look up the providers of that key. Unlike a
+//! rendezvous point, the DHT keeps working as long as any of its nodes is
+//! reachable.
+
+use crate::libp2p_ext::MultiAddrExt;
+use crate::network::rendezvous::XmrBtcNamespace;
+use anyhow::{Context, Result};
+use libp2p::kad::record::store::MemoryStore;
+use libp2p::kad::record::Key;
+use libp2p::kad::{Kademlia, KademliaConfig};
+use libp2p::{Multiaddr, PeerId};
+use std::time::Duration;
+
+/// Keeps our DHT separate from the IPFS one.
+const PROTOCOL_NAME: &[u8] = b"/xmr-btc-swap/kad/1.0.0";
+
+/// How often sellers re-announce themselves. Much shorter than the default
+/// of 12 hours so that a seller that failed to reach the DHT on startup shows
+/// up soon after it becomes reachable.
+const PROVIDER_PUBLICATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub type Behaviour = Kademlia<MemoryStore>;
+
+/// The key sellers in `namespace` are providers of.
+pub fn key(namespace: XmrBtcNamespace) -> Key {
+    Key::new(&namespace.to_string())
+}
+
+/// Creates a DHT node that joins the DHT through the given bootstrap nodes.
+pub fn new(local_peer_id: PeerId, bootstrap_nodes: Vec<(PeerId, Multiaddr)>) -> Behaviour {
+    let mut config = KademliaConfig::default();
+    config
+        .set_protocol_name(PROTOCOL_NAME)
+        .set_provider_publication_interval(Some(PROVIDER_PUBLICATION_INTERVAL));
+
+    let mut kademlia =
+        Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), config);
+    for (peer_id, address) in bootstrap_nodes {
+        kademlia.add_address(&peer_id, address);
+    }
+
+    kademlia
+}
+
+/// Creates a DHT node that announces us as a seller in `namespace`.
+///
+/// The announcement is repeated every [`PROVIDER_PUBLICATION_INTERVAL`] for as
+/// long as the node is running.
+pub fn provider(
+    local_peer_id: PeerId,
+    bootstrap_nodes: Vec<(PeerId, Multiaddr)>,
+    namespace: XmrBtcNamespace,
+) -> Behaviour {
+    let mut kademlia = new(local_peer_id, bootstrap_nodes);
+    kademlia
+        .start_providing(key(namespace))
+        .expect("an empty store to accept our provider record");
+
+    kademlia
+}
+
+/// Splits the addresses of bootstrap nodes into their peer ID and address.
+pub fn bootstrap_nodes(addresses: Vec<Multiaddr>) -> Result<Vec<(PeerId, Multiaddr)>> {
+    addresses
+        .into_iter()
+        .map(|address| {
+            let peer_id = address
+                .extract_peer_id()
+                .context("DHT bootstrap node address must contain peer ID")?;
+
+            Ok((peer_id, address))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_have_distinct_keys() {
+        assert_ne!(key(XmrBtcNamespace::Mainnet), key(XmrBtcNamespace::Testnet));
+        assert_ne!(key(XmrBtcNamespace::Mainnet), key(XmrBtcNamespace::Signet));
+    }
+
+    #[test]
+    fn given_address_without_peer_id_then_fails() {
+        let result = bootstrap_nodes(vec!["/ip4/127.0.0.1/tcp/9939".parse().unwrap()]);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,12 @@
+use libp2p::identify::{Identify, IdentifyConfig};
+use libp2p::identity;
+
+const PROTOCOL_VERSION: &str = "/comit/xmr/btc/1.0.0";
+
+/// Lets peers tell each other which addresses they listen on.
+///
+/// The CLI uses this to learn addresses of a seller it did not know yet, so it
+/// can still reach the seller after the address it was given stopped working.
+pub fn new(public_key: identity::PublicKey) -> Identify {
+    Identify::new(IdentifyConfig::new(PROTOCOL_VERSION.to_owned(), public_key))
+}
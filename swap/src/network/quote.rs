@@ -1,18 +1,26 @@
 use crate::network::json_pull_codec::JsonPullCodec;
 use crate::{asb, bitcoin, cli};
+use anyhow::{bail, Context, Result};
 use libp2p::core::ProtocolName;
+use libp2p::identity::{self, PublicKey};
 use libp2p::request_response::{
     ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
     RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 
 const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
-pub type OutEvent = RequestResponseEvent<(), BidQuote>;
-pub type Message = RequestResponseMessage<(), BidQuote>;
+pub type OutEvent = RequestResponseEvent<(), SignedBidQuote>;
+pub type Message = RequestResponseMessage<(), SignedBidQuote>;
 
-pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, BidQuote>>;
+pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, SignedBidQuote>>;
+
+/// Prefixed to the signed bytes so a quote signature cannot be mistaken for
+/// a signature over anything else made with the same identity.
+const SIGNATURE_DOMAIN: &[u8] = b"xmr-btc-swap/bid-quote/1";
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct BidQuoteProtocol;
@@ -37,6 +45,111 @@ pub struct BidQuote {
     pub max_quantity: bitcoin::Amount,
 }
 
+/// A [`BidQuote`] as handed out by the quote protocol.
+///
+/// The fields of the quote are kept at the top level, so CLIs that do not know
+/// about signatures can still read it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedBidQuote {
+    #[serde(flatten)]
+    pub quote: BidQuote,
+    /// `None` if the ASB does not sign its quotes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<QuoteSignature>,
+}
+
+/// The commitment of an ASB to honour a quote until it expires.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuoteSignature {
+    /// Referenced in the spot price request to ask the ASB to honour the
+    /// quote.
+    pub id: Uuid,
+    /// Unix timestamp in seconds after which the ASB no longer honours the
+    /// quote.
+    pub expires_at: u64,
+    /// Protobuf encoding of the libp2p public key of the ASB.
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SignedBidQuote {
+    pub fn sign(
+        quote: BidQuote,
+        id: Uuid,
+        expires_at: u64,
+        identity: &identity::Keypair,
+    ) -> Result<Self> {
+        let signature = identity
+            .sign(&signed_bytes(&quote, id, expires_at))
+            .context("Failed to sign quote")?;
+
+        Ok(Self {
+            quote,
+            signature: Some(QuoteSignature {
+                id,
+                expires_at,
+                public_key: identity.public().into_protobuf_encoding(),
+                signature,
+            }),
+        })
+    }
+
+    /// Checks that the quote was signed by `signer`.
+    ///
+    /// Does not check whether the quote expired, an expired quote is still
+    /// evidence of what the ASB offered.
+    pub fn verify(&self, signer: PeerId) -> Result<&QuoteSignature> {
+        let signature = self.signature.as_ref().context("Quote is not signed")?;
+
+        let public_key = PublicKey::from_protobuf_encoding(&signature.public_key)
+            .context("Failed to decode public key of quote signature")?;
+        if PeerId::from(public_key.clone()) != signer {
+            bail!("Quote was not signed by {}", signer)
+        }
+
+        let bytes = signed_bytes(&self.quote, signature.id, signature.expires_at);
+        if !public_key.verify(&bytes, &signature.signature) {
+            bail!("Quote signature is invalid")
+        }
+
+        Ok(signature)
+    }
+}
+
+impl QuoteSignature {
+    /// Whether the quote expires before `duration` has passed.
+    pub fn expires_within(&self, duration: Duration) -> bool {
+        unix_timestamp() + duration.as_secs() > self.expires_at
+    }
+}
+
+impl From<BidQuote> for SignedBidQuote {
+    fn from(quote: BidQuote) -> Self {
+        Self {
+            quote,
+            signature: None,
+        }
+    }
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time to be after the unix epoch")
+        .as_secs()
+}
+
+fn signed_bytes(quote: &BidQuote, id: Uuid, expires_at: u64) -> Vec<u8> {
+    let mut bytes = SIGNATURE_DOMAIN.to_vec();
+    bytes.extend_from_slice(id.as_bytes());
+    bytes.extend_from_slice(&expires_at.to_be_bytes());
+    bytes.extend_from_slice(&quote.price.as_sat().to_be_bytes());
+    bytes.extend_from_slice(&quote.min_quantity.as_sat().to_be_bytes());
+    bytes.extend_from_slice(&quote.max_quantity.as_sat().to_be_bytes());
+
+    bytes
+}
+
 /// Constructs a new instance of the `quote` behaviour to be used by the ASB.
 ///
 /// The ASB is always listening and only supports inbound connections, i.e.
@@ -86,3 +199,77 @@ impl From<(PeerId, Message)> for cli::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote() -> BidQuote {
+        BidQuote {
+            price: bitcoin::Amount::from_sat(1337),
+            min_quantity: bitcoin::Amount::from_sat(42),
+            max_quantity: bitcoin::Amount::from_sat(9001),
+        }
+    }
+
+    #[test]
+    fn signed_quote_is_verified_for_signer() {
+        let identity = identity::Keypair::generate_ed25519();
+        let id = Uuid::new_v4();
+
+        let signed = SignedBidQuote::sign(quote(), id, 1_000, &identity).unwrap();
+        let signature = signed.verify(identity.public().into()).unwrap();
+
+        assert_eq!(signature.id, id);
+        assert_eq!(signature.expires_at, 1_000);
+    }
+
+    #[test]
+    fn given_other_signer_then_verification_fails() {
+        let identity = identity::Keypair::generate_ed25519();
+
+        let signed = SignedBidQuote::sign(quote(), Uuid::new_v4(), 1_000, &identity).unwrap();
+
+        assert!(signed.verify(PeerId::random()).is_err());
+    }
+
+    #[test]
+    fn given_tampered_quote_then_verification_fails() {
+        let identity = identity::Keypair::generate_ed25519();
+        let signed = SignedBidQuote::sign(quote(), Uuid::new_v4(), 1_000, &identity).unwrap();
+
+        let mut cheaper = signed.clone();
+        cheaper.quote.price = bitcoin::Amount::from_sat(1);
+        let mut extended = signed;
+        extended.signature.as_mut().unwrap().expires_at = 2_000;
+
+        assert!(cheaper.verify(identity.public().into()).is_err());
+        assert!(extended.verify(identity.public().into()).is_err());
+    }
+
+    #[test]
+    fn given_unsigned_quote_then_verification_fails() {
+        let unsigned = SignedBidQuote::from(quote());
+
+        assert!(unsigned.verify(PeerId::random()).is_err());
+    }
+
+    #[test]
+    fn signed_quote_is_readable_as_plain_quote() {
+        let identity = identity::Keypair::generate_ed25519();
+        let signed = SignedBidQuote::sign(quote(), Uuid::new_v4(), 1_000, &identity).unwrap();
+
+        let json = serde_json::to_string(&signed).unwrap();
+        let plain = serde_json::from_str::<BidQuote>(&json).unwrap();
+
+        assert_eq!(plain, quote());
+    }
+
+    #[test]
+    fn plain_quote_is_readable_as_unsigned_quote() {
+        let json = serde_json::to_string(&quote()).unwrap();
+        let signed = serde_json::from_str::<SignedBidQuote>(&json).unwrap();
+
+        assert_eq!(signed, SignedBidQuote::from(quote()));
+    }
+}
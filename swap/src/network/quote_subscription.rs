@@ -0,0 +1,324 @@
+//! Pushing quotes to takers instead of having them poll [`quote`].
+//!
+//! A CLI subscribes with a request on the `subscribe` protocol and receives
+//! the current quote as response. From then on the ASB sends every changed
+//! quote as a request on the `update` protocol until the connection is closed.
+//! Both protocols exist next to the pull-based [`quote`] protocol, which is
+//! kept for compatibility.
+//!
+//! [`quote`]: crate::network::quote
+
+use crate::network::quote::BidQuote;
+use libp2p::PeerId;
+use std::collections::HashMap;
+
+/// Tracks the subscribers of an ASB together with the last quote each of them
+/// received.
+#[derive(Debug, Default)]
+pub struct Subscribers {
+    last_sent: HashMap<PeerId, BidQuote>,
+}
+
+impl Subscribers {
+    /// Adds a subscriber that was just sent `quote`.
+    pub fn insert(&mut self, peer: PeerId, quote: BidQuote) {
+        self.last_sent.insert(peer, quote);
+    }
+
+    pub fn remove(&mut self, peer: &PeerId) {
+        self.last_sent.remove(peer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last_sent.is_empty()
+    }
+
+    /// Returns the subscribers that have not received `quote` yet and
+    /// records that they are about to.
+    pub fn outdated(&mut self, quote: BidQuote) -> Vec<PeerId> {
+        self.last_sent
+            .iter_mut()
+            .filter(|(_, last_sent)| **last_sent != quote)
+            .map(|(peer, last_sent)| {
+                *last_sent = quote;
+                *peer
+            })
+            .collect()
+    }
+}
+
+pub mod subscribe {
+    use crate::network::cbor_request_response::CborCodec;
+    use crate::network::quote::BidQuote;
+    use crate::{asb, cli};
+    use libp2p::core::ProtocolName;
+    use libp2p::request_response::{
+        ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+        RequestResponseMessage,
+    };
+    use libp2p::PeerId;
+
+    const PROTOCOL: &str = "/comit/xmr/btc/bid-quote-subscribe/1.0.0";
+    type OutEvent = RequestResponseEvent<(), BidQuote>;
+    type Message = RequestResponseMessage<(), BidQuote>;
+
+    pub type Behaviour = RequestResponse<CborCodec<SubscribeProtocol, (), BidQuote>>;
+
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SubscribeProtocol;
+
+    impl ProtocolName for SubscribeProtocol {
+        fn protocol_name(&self) -> &[u8] {
+            PROTOCOL.as_bytes()
+        }
+    }
+
+    pub fn asb() -> Behaviour {
+        Behaviour::new(
+            CborCodec::default(),
+            vec![(SubscribeProtocol, ProtocolSupport::Inbound)],
+            RequestResponseConfig::default(),
+        )
+    }
+
+    pub fn cli() -> Behaviour {
+        Behaviour::new(
+            CborCodec::default(),
+            vec![(SubscribeProtocol, ProtocolSupport::Outbound)],
+            RequestResponseConfig::default(),
+        )
+    }
+
+    impl From<(PeerId, Message)> for asb::OutEvent {
+        fn from((peer, message): (PeerId, Message)) -> Self {
+            match message {
+                Message::Request { channel, .. } => {
+                    Self::QuoteSubscriptionRequested { channel, peer }
+                }
+                Message::Response { .. } => Self::unexpected_response(peer),
+            }
+        }
+    }
+    crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+    impl From<(PeerId, Message)> for cli::OutEvent {
+        fn from((peer, message): (PeerId, Message)) -> Self {
+            match message {
+                Message::Request { .. } => Self::unexpected_request(peer),
+                Message::Response {
+                    response,
+                    request_id,
+                } => Self::QuoteSubscribed {
+                    id: request_id,
+                    response,
+                },
+            }
+        }
+    }
+    crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+}
+
+pub mod update {
+    use crate::network::cbor_request_response::CborCodec;
+    use crate::network::quote::BidQuote;
+    use crate::{asb, cli};
+    use libp2p::core::ProtocolName;
+    use libp2p::request_response::{
+        ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+        RequestResponseMessage,
+    };
+    use libp2p::PeerId;
+
+    const PROTOCOL: &str = "/comit/xmr/btc/bid-quote-update/1.0.0";
+    type OutEvent = RequestResponseEvent<BidQuote, ()>;
+    type Message = RequestResponseMessage<BidQuote, ()>;
+
+    pub type Behaviour = RequestResponse<CborCodec<UpdateProtocol, BidQuote, ()>>;
+
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct UpdateProtocol;
+
+    impl ProtocolName for UpdateProtocol {
+        fn protocol_name(&self) -> &[u8] {
+            PROTOCOL.as_bytes()
+        }
+    }
+
+    pub fn asb() -> Behaviour {
+        Behaviour::new(
+            CborCodec::default(),
+            vec![(UpdateProtocol, ProtocolSupport::Outbound)],
+            RequestResponseConfig::default(),
+        )
+    }
+
+    pub fn cli() -> Behaviour {
+        Behaviour::new(
+            CborCodec::default(),
+            vec![(UpdateProtocol, ProtocolSupport::Inbound)],
+            RequestResponseConfig::default(),
+        )
+    }
+
+    impl From<(PeerId, Message)> for asb::OutEvent {
+        fn from((peer, message): (PeerId, Message)) -> Self {
+            match message {
+                Message::Request { .. } => Self::unexpected_request(peer),
+                Message::Response { .. } => Self::Other,
+            }
+        }
+    }
+    crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+    impl From<(PeerId, Message)> for cli::OutEvent {
+        fn from((peer, message): (PeerId, Message)) -> Self {
+            match message {
+                Message::Request {
+                    request, channel, ..
+                } => Self::QuoteUpdated {
+                    quote: request,
+                    channel,
+                    peer,
+                },
+                Message::Response { .. } => Self::unexpected_response(peer),
+            }
+        }
+    }
+    crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test::{new_swarm, SwarmExt};
+    use crate::{asb, bitcoin, cli};
+    use futures::StreamExt;
+    use libp2p::swarm::SwarmEvent;
+    use libp2p::NetworkBehaviour;
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+
+    #[derive(NetworkBehaviour)]
+    #[behaviour(out_event = "asb::OutEvent", event_process = false)]
+    #[allow(missing_debug_implementations)]
+    struct AsbBehaviour {
+        subscribe: subscribe::Behaviour,
+        update: update::Behaviour,
+    }
+
+    #[derive(NetworkBehaviour)]
+    #[behaviour(out_event = "cli::OutEvent", event_process = false)]
+    #[allow(missing_debug_implementations)]
+    struct CliBehaviour {
+        subscribe: subscribe::Behaviour,
+        update: update::Behaviour,
+    }
+
+    fn quote(price: u64) -> BidQuote {
+        BidQuote {
+            price: bitcoin::Amount::from_sat(price),
+            min_quantity: bitcoin::Amount::from_sat(42),
+            max_quantity: bitcoin::Amount::from_sat(9001),
+        }
+    }
+
+    #[test]
+    fn given_unchanged_quote_then_no_subscriber_is_updated() {
+        let mut subscribers = Subscribers::default();
+        subscribers.insert(PeerId::random(), quote(1337));
+
+        assert!(subscribers.outdated(quote(1337)).is_empty());
+    }
+
+    #[test]
+    fn given_changed_quote_then_every_subscriber_is_updated_once() {
+        let mut subscribers = Subscribers::default();
+        let alice = PeerId::random();
+        let bob = PeerId::random();
+        subscribers.insert(alice, quote(1337));
+        subscribers.insert(bob, quote(1337));
+
+        let outdated = subscribers.outdated(quote(1338));
+
+        assert_eq!(
+            outdated.into_iter().collect::<HashSet<_>>(),
+            HashSet::from_iter([alice, bob])
+        );
+        assert!(subscribers.outdated(quote(1338)).is_empty());
+    }
+
+    #[test]
+    fn only_updates_subscribers_that_have_not_seen_the_quote() {
+        let mut subscribers = Subscribers::default();
+        let early = PeerId::random();
+        let late = PeerId::random();
+        subscribers.insert(early, quote(1337));
+        subscribers.insert(late, quote(1338));
+
+        assert_eq!(subscribers.outdated(quote(1338)), vec![early]);
+    }
+
+    #[test]
+    fn removed_subscribers_are_not_updated() {
+        let mut subscribers = Subscribers::default();
+        let peer = PeerId::random();
+        subscribers.insert(peer, quote(1337));
+
+        subscribers.remove(&peer);
+
+        assert!(subscribers.is_empty());
+        assert!(subscribers.outdated(quote(1338)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn asb_pushes_changed_quote_to_subscribed_cli() {
+        let mut asb = new_swarm(|_, _| AsbBehaviour {
+            subscribe: subscribe::asb(),
+            update: update::asb(),
+        });
+        asb.listen_on_random_memory_address().await;
+        let mut cli = new_swarm(|_, _| CliBehaviour {
+            subscribe: subscribe::cli(),
+            update: update::cli(),
+        });
+        cli.block_on_connection(&mut asb).await;
+
+        cli.behaviour_mut()
+            .subscribe
+            .send_request(asb.local_peer_id(), ());
+
+        let mut subscribers = Subscribers::default();
+        let mut subscribed = None;
+        let mut updated = None;
+
+        while subscribed.is_none() || updated.is_none() {
+            tokio::select! {
+                event = asb.select_next_some() => {
+                    if let SwarmEvent::Behaviour(asb::OutEvent::QuoteSubscriptionRequested { channel, peer }) = event {
+                        asb.behaviour_mut().subscribe.send_response(channel, quote(1337)).unwrap();
+                        subscribers.insert(peer, quote(1337));
+
+                        for peer in subscribers.outdated(quote(1338)) {
+                            asb.behaviour_mut().update.send_request(&peer, quote(1338));
+                        }
+                    }
+                }
+                event = cli.select_next_some() => {
+                    match event {
+                        SwarmEvent::Behaviour(cli::OutEvent::QuoteSubscribed { response, .. }) => {
+                            subscribed = Some(response);
+                        }
+                        SwarmEvent::Behaviour(cli::OutEvent::QuoteUpdated { quote, channel, .. }) => {
+                            cli.behaviour_mut().update.send_response(channel, ()).unwrap();
+                            updated = Some(quote);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        assert_eq!(subscribed, Some(quote(1337)));
+        assert_eq!(updated, Some(quote(1338)));
+    }
+}
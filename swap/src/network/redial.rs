@@ -1,12 +1,16 @@
 use crate::cli;
+use crate::network::address_book::{AddressBook, AddressHealth, AddressPreference};
+use crate::network::quote::unix_timestamp;
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
 use futures::future::FutureExt;
 use libp2p::core::connection::ConnectionId;
-use libp2p::core::Multiaddr;
+use libp2p::core::{ConnectedPoint, Multiaddr};
 use libp2p::swarm::protocols_handler::DummyProtocolsHandler;
 use libp2p::swarm::{DialPeerCondition, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
 use libp2p::PeerId;
+use std::collections::VecDeque;
+use std::error::Error;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
@@ -14,15 +18,31 @@ use tokio::time::{Instant, Sleep};
 use void::Void;
 
 pub enum OutEvent {
-    AllAttemptsExhausted { peer: PeerId },
+    AllAttemptsExhausted {
+        peer: PeerId,
+    },
+    AddressHealthChanged {
+        peer: PeerId,
+        address: Multiaddr,
+        health: AddressHealth,
+    },
 }
 
 /// A [`NetworkBehaviour`] that tracks whether we are connected to the given
 /// peer and attempts to re-establish a connection with an exponential backoff
 /// if we lose the connection.
+///
+/// It provides the addresses of the peer to the swarm, healthiest first, and
+/// keeps track of how well each of them works.
 pub struct Behaviour {
     /// The peer we are interested in.
     peer: PeerId,
+    /// The known addresses of the peer and their health.
+    addresses: AddressBook,
+    preference: AddressPreference,
+    /// The address of our current connection to the peer, if any.
+    connected_address: Option<Multiaddr>,
+    events: VecDeque<OutEvent>,
     /// If present, tracks for how long we need to sleep until we dial again.
     sleep: Option<Pin<Box<Sleep>>>,
     /// Tracks the current backoff state.
@@ -30,9 +50,13 @@ pub struct Behaviour {
 }
 
 impl Behaviour {
-    pub fn new(peer: PeerId, interval: Duration) -> Self {
+    pub fn new(peer: PeerId, interval: Duration, preference: AddressPreference) -> Self {
         Self {
             peer,
+            addresses: AddressBook::default(),
+            preference,
+            connected_address: None,
+            events: VecDeque::new(),
             sleep: None,
             backoff: ExponentialBackoff {
                 initial_interval: interval,
@@ -53,6 +77,34 @@ impl Behaviour {
 
         Some(until_next_redial)
     }
+
+    /// Adds an address of the peer, returning whether we did not know it yet.
+    pub fn add_address(&mut self, address: Multiaddr) -> bool {
+        self.addresses.add(address)
+    }
+
+    /// Adds an address of the peer with the health we recorded for it
+    /// earlier.
+    pub fn restore_address(&mut self, address: Multiaddr, health: AddressHealth) {
+        self.addresses.restore(address, health);
+    }
+
+    /// Records the round-trip time measured on our current connection to the
+    /// peer.
+    pub fn record_latency(&mut self, latency: Duration) {
+        let connected_address = match self.connected_address.as_ref() {
+            Some(address) => address,
+            None => return,
+        };
+
+        if let Some((address, health)) = self.addresses.record_latency(connected_address, latency) {
+            self.events.push_back(OutEvent::AddressHealthChanged {
+                peer: self.peer,
+                address,
+                health,
+            });
+        }
+    }
 }
 
 impl NetworkBehaviour for Behaviour {
@@ -63,8 +115,52 @@ impl NetworkBehaviour for Behaviour {
         DummyProtocolsHandler::default()
     }
 
-    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
-        Vec::new()
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        if peer_id != &self.peer {
+            return Vec::new();
+        }
+
+        self.addresses.ranked(self.preference)
+    }
+
+    fn inject_connection_established(
+        &mut self,
+        peer_id: &PeerId,
+        _: &ConnectionId,
+        endpoint: &ConnectedPoint,
+    ) {
+        if peer_id != &self.peer || !endpoint.is_dialer() {
+            return;
+        }
+
+        let (address, health) = self
+            .addresses
+            .record_success(endpoint.get_remote_address(), unix_timestamp());
+        self.connected_address = Some(address.clone());
+        self.events.push_back(OutEvent::AddressHealthChanged {
+            peer: self.peer,
+            address,
+            health,
+        });
+    }
+
+    fn inject_addr_reach_failure(
+        &mut self,
+        peer_id: Option<&PeerId>,
+        address: &Multiaddr,
+        _: &dyn Error,
+    ) {
+        if peer_id != Some(&self.peer) {
+            return;
+        }
+
+        if let Some((address, health)) = self.addresses.record_failure(address) {
+            self.events.push_back(OutEvent::AddressHealthChanged {
+                peer: self.peer,
+                address,
+                health,
+            });
+        }
     }
 
     fn inject_connected(&mut self, peer_id: &PeerId) {
@@ -83,6 +179,7 @@ impl NetworkBehaviour for Behaviour {
 
         // lost connection to the configured peer, trigger re-dialling with an
         // exponential backoff
+        self.connected_address = None;
         self.backoff.reset();
         self.sleep = Some(Box::pin(tokio::time::sleep(self.backoff.initial_interval)));
     }
@@ -94,6 +191,10 @@ impl NetworkBehaviour for Behaviour {
         cx: &mut Context<'_>,
         _: &mut impl PollParameters,
     ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
         let sleep = match self.sleep.as_mut() {
             None => return Poll::Pending, // early exit if we shouldn't be re-dialling
             Some(future) => future,
@@ -126,6 +227,15 @@ impl From<OutEvent> for cli::OutEvent {
             OutEvent::AllAttemptsExhausted { peer } => {
                 cli::OutEvent::AllRedialAttemptsExhausted { peer }
             }
+            OutEvent::AddressHealthChanged {
+                peer,
+                address,
+                health,
+            } => cli::OutEvent::AddressHealthChanged {
+                peer,
+                address,
+                health,
+            },
         }
     }
 }
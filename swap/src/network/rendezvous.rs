@@ -5,16 +5,44 @@ use std::fmt;
 pub enum XmrBtcNamespace {
     Mainnet,
     Testnet,
+    Signet,
 }
 
 const MAINNET: &str = "xmr-btc-swap-mainnet";
 const TESTNET: &str = "xmr-btc-swap-testnet";
+const SIGNET: &str = "xmr-btc-swap-signet";
+
+impl XmrBtcNamespace {
+    /// The namespace sellers swapping on the given Bitcoin network register
+    /// in.
+    pub fn for_network(network: bitcoin::Network) -> Self {
+        match network {
+            bitcoin::Network::Bitcoin => XmrBtcNamespace::Mainnet,
+            bitcoin::Network::Signet => XmrBtcNamespace::Signet,
+            bitcoin::Network::Testnet | bitcoin::Network::Regtest => XmrBtcNamespace::Testnet,
+        }
+    }
+
+    /// Returns the xmr-btc-swap namespace that `namespace` stands for, if
+    /// any.
+    pub fn from_namespace(namespace: &Namespace) -> Option<Self> {
+        [
+            XmrBtcNamespace::Mainnet,
+            XmrBtcNamespace::Testnet,
+            XmrBtcNamespace::Signet,
+        ]
+        .iter()
+        .copied()
+        .find(|candidate| Namespace::from(*candidate) == *namespace)
+    }
+}
 
 impl fmt::Display for XmrBtcNamespace {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             XmrBtcNamespace::Mainnet => write!(f, "{}", MAINNET),
             XmrBtcNamespace::Testnet => write!(f, "{}", TESTNET),
+            XmrBtcNamespace::Signet => write!(f, "{}", SIGNET),
         }
     }
 }
@@ -24,6 +52,7 @@ impl From<XmrBtcNamespace> for Namespace {
         match namespace {
             XmrBtcNamespace::Mainnet => Namespace::from_static(MAINNET),
             XmrBtcNamespace::Testnet => Namespace::from_static(TESTNET),
+            XmrBtcNamespace::Signet => Namespace::from_static(SIGNET),
         }
     }
 }
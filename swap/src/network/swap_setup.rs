@@ -4,6 +4,7 @@ use libp2p::core::upgrade;
 use libp2p::swarm::NegotiatedSubstream;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
@@ -12,29 +13,157 @@ pub const BUF_SIZE: usize = 1024 * 1024;
 
 pub mod protocol {
     use futures::future;
-    use libp2p::core::upgrade::{from_fn, FromFnUpgrade};
-    use libp2p::core::Endpoint;
-    use libp2p::swarm::NegotiatedSubstream;
+    use libp2p::core::upgrade::{InboundUpgrade, OutboundUpgrade, ProtocolName, UpgradeInfo};
+    use std::vec;
     use void::Void;
 
+    /// A version of the swap setup protocol.
+    ///
+    /// Versions are negotiated by libp2p when the substream is opened. Bob
+    /// proposes the versions he supports newest first and Alice picks the
+    /// first one she supports, so both sides end up with the highest version
+    /// they have in common. Peers that only know a single version keep working
+    /// as long as the other side still supports it.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Version {
+        V1_0_0,
+        /// The spot price request can reference a signed quote.
+        V1_1_0,
+    }
+
+    impl Version {
+        /// The versions we support, newest first.
+        pub const SUPPORTED: [Version; 2] = [Version::V1_1_0, Version::V1_0_0];
+
+        pub fn name(&self) -> &'static str {
+            match self {
+                Version::V1_0_0 => "/comit/xmr/btc/swap_setup/1.0.0",
+                Version::V1_1_0 => "/comit/xmr/btc/swap_setup/1.1.0",
+            }
+        }
+
+        pub fn supports_signed_quotes(&self) -> bool {
+            *self >= Version::V1_1_0
+        }
+    }
+
+    impl ProtocolName for Version {
+        fn protocol_name(&self) -> &[u8] {
+            self.name().as_bytes()
+        }
+    }
+
     pub fn new() -> SwapSetup {
-        from_fn(
-            b"/comit/xmr/btc/swap_setup/1.0.0",
-            Box::new(|socket, _| future::ready(Ok(socket))),
-        )
+        SwapSetup::new(Version::SUPPORTED.to_vec())
+    }
+
+    /// Negotiates one of `versions` and hands out the substream together with
+    /// the negotiated version.
+    #[derive(Debug, Clone)]
+    pub struct SwapSetup {
+        versions: Vec<Version>,
     }
 
-    pub type SwapSetup = FromFnUpgrade<
-        &'static [u8],
-        Box<
-            dyn Fn(
-                    NegotiatedSubstream,
-                    Endpoint,
-                ) -> future::Ready<Result<NegotiatedSubstream, Void>>
-                + Send
-                + 'static,
-        >,
-    >;
+    impl SwapSetup {
+        pub fn new(versions: Vec<Version>) -> Self {
+            Self { versions }
+        }
+    }
+
+    impl UpgradeInfo for SwapSetup {
+        type Info = Version;
+        type InfoIter = vec::IntoIter<Version>;
+
+        fn protocol_info(&self) -> Self::InfoIter {
+            self.versions.clone().into_iter()
+        }
+    }
+
+    impl<C> InboundUpgrade<C> for SwapSetup {
+        type Output = (C, Version);
+        type Error = Void;
+        type Future = future::Ready<Result<Self::Output, Void>>;
+
+        fn upgrade_inbound(self, socket: C, version: Version) -> Self::Future {
+            future::ready(Ok((socket, version)))
+        }
+    }
+
+    impl<C> OutboundUpgrade<C> for SwapSetup {
+        type Output = (C, Version);
+        type Error = Void;
+        type Future = future::Ready<Result<Self::Output, Void>>;
+
+        fn upgrade_outbound(self, socket: C, version: Version) -> Self::Future {
+            future::ready(Ok((socket, version)))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use futures::StreamExt;
+        use libp2p::core::transport::{ListenerEvent, MemoryTransport};
+        use libp2p::core::{upgrade, Multiaddr, Transport};
+
+        async fn negotiate(dialer: Vec<Version>, listener: Vec<Version>) -> (Version, Version) {
+            let address = format!("/memory/{}", rand::random::<u64>())
+                .parse::<Multiaddr>()
+                .unwrap();
+            let mut listener_transport = MemoryTransport::default()
+                .listen_on(address.clone())
+                .unwrap();
+            let dial = MemoryTransport::default().dial(address).unwrap();
+
+            let inbound = async {
+                let socket = loop {
+                    if let ListenerEvent::Upgrade { upgrade, .. } =
+                        listener_transport.next().await.unwrap().unwrap()
+                    {
+                        break upgrade.await.unwrap();
+                    }
+                };
+                let (_, version) = upgrade::apply_inbound(socket, SwapSetup::new(listener))
+                    .await
+                    .unwrap();
+
+                version
+            };
+            let outbound = async {
+                let socket = dial.await.unwrap();
+                let (_, version) =
+                    upgrade::apply_outbound(socket, SwapSetup::new(dialer), upgrade::Version::V1)
+                        .await
+                        .unwrap();
+
+                version
+            };
+
+            future::join(outbound, inbound).await
+        }
+
+        #[tokio::test]
+        async fn new_peers_negotiate_newest_version() {
+            let versions =
+                negotiate(Version::SUPPORTED.to_vec(), Version::SUPPORTED.to_vec()).await;
+
+            assert_eq!(versions, (Version::V1_1_0, Version::V1_1_0));
+        }
+
+        #[tokio::test]
+        async fn new_cli_falls_back_to_version_of_old_asb() {
+            let versions = negotiate(Version::SUPPORTED.to_vec(), vec![Version::V1_0_0]).await;
+
+            assert_eq!(versions, (Version::V1_0_0, Version::V1_0_0));
+        }
+
+        #[tokio::test]
+        async fn old_cli_is_served_by_new_asb() {
+            let versions = negotiate(vec![Version::V1_0_0], Version::SUPPORTED.to_vec()).await;
+
+            assert_eq!(versions, (Version::V1_0_0, Version::V1_0_0));
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
@@ -50,6 +179,9 @@ pub struct SpotPriceRequest {
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub btc: bitcoin::Amount,
     pub blockchain_network: BlockchainNetwork,
+    /// A signed quote the seller is asked to honour.
+    #[serde(default)]
+    pub quote_id: Option<Uuid>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -81,6 +213,18 @@ pub enum SpotPriceError {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    /// The referenced quote expired or was never handed out by the seller.
+    QuoteExpired {
+        id: Uuid,
+    },
+    /// The market moved too far against the referenced quote for the seller
+    /// to honour it.
+    QuoteBeyondTolerance {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        quoted: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        ask: bitcoin::Amount,
+    },
     /// To be used for errors that cannot be explained on the CLI side (e.g.
     /// rate update problems on the seller side)
     Other,
@@ -1,4 +1,4 @@
-use crate::asb::LatestRate;
+use crate::asb::{quote_book, LatestRate, QuoteBook, Rate};
 use crate::network::swap_setup;
 use crate::network::swap_setup::{
     protocol, BlockchainNetwork, SpotPriceError, SpotPriceRequest, SpotPriceResponse,
@@ -27,6 +27,7 @@ use void::Void;
 #[allow(clippy::large_enum_variant)]
 pub enum OutEvent {
     Initiated {
+        peer_id: PeerId,
         send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
     },
     Completed {
@@ -34,12 +35,25 @@ pub enum OutEvent {
         swap_id: Uuid,
         state3: State3,
     },
+    /// Bob stopped the setup after we agreed on a price.
+    Aborted {
+        peer_id: PeerId,
+        error: anyhow::Error,
+    },
     Error {
         peer_id: PeerId,
         error: anyhow::Error,
     },
 }
 
+/// Marks failures of a swap setup that Bob caused after we agreed on a price,
+/// i.e. he closed the substream or sent us a message we could not accept.
+///
+/// Our own failures and running into the setup timeout are not marked.
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Bob aborted the swap setup after agreeing on the price")]
+pub struct Aborted;
+
 #[derive(Debug)]
 pub struct WalletSnapshot {
     balance: monero::Amount,
@@ -85,8 +99,10 @@ impl From<OutEvent> for asb::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
             OutEvent::Initiated {
+                peer_id,
                 send_wallet_snapshot,
             } => asb::OutEvent::SwapSetupInitiated {
+                peer: peer_id,
                 send_wallet_snapshot,
             },
             OutEvent::Completed {
@@ -98,6 +114,10 @@ impl From<OutEvent> for asb::OutEvent {
                 swap_id,
                 state3,
             },
+            OutEvent::Aborted { peer_id, error } => asb::OutEvent::SwapSetupAborted {
+                peer: peer_id,
+                error,
+            },
             OutEvent::Error { peer_id, error } => asb::OutEvent::Failure {
                 peer: peer_id,
                 error: anyhow!(error),
@@ -114,6 +134,7 @@ pub struct Behaviour<LR> {
     env_config: env::Config,
 
     latest_rate: LR,
+    quote_book: QuoteBook,
     resume_only: bool,
 }
 
@@ -123,6 +144,7 @@ impl<LR> Behaviour<LR> {
         max_buy: bitcoin::Amount,
         env_config: env::Config,
         latest_rate: LR,
+        quote_book: QuoteBook,
         resume_only: bool,
     ) -> Self {
         Self {
@@ -131,6 +153,7 @@ impl<LR> Behaviour<LR> {
             max_buy,
             env_config,
             latest_rate,
+            quote_book,
             resume_only,
         }
     }
@@ -149,6 +172,7 @@ where
             self.max_buy,
             self.env_config,
             self.latest_rate.clone(),
+            self.quote_book.clone(),
             self.resume_only,
         )
     }
@@ -165,6 +189,7 @@ where
         match event {
             HandlerOutEvent::Initiated(send_wallet_snapshot) => {
                 self.events.push_back(OutEvent::Initiated {
+                    peer_id,
                     send_wallet_snapshot,
                 })
             }
@@ -175,6 +200,9 @@ where
                     state3,
                 })
             }
+            HandlerOutEvent::Completed(Err(error)) if error.is::<Aborted>() => {
+                self.events.push_back(OutEvent::Aborted { peer_id, error })
+            }
             HandlerOutEvent::Completed(Err(error)) => {
                 self.events.push_back(OutEvent::Error { peer_id, error })
             }
@@ -205,6 +233,7 @@ pub struct Handler<LR> {
     env_config: env::Config,
 
     latest_rate: LR,
+    quote_book: QuoteBook,
     resume_only: bool,
 
     timeout: Duration,
@@ -217,6 +246,7 @@ impl<LR> Handler<LR> {
         max_buy: bitcoin::Amount,
         env_config: env::Config,
         latest_rate: LR,
+        quote_book: QuoteBook,
         resume_only: bool,
     ) -> Self {
         Self {
@@ -226,6 +256,7 @@ impl<LR> Handler<LR> {
             max_buy,
             env_config,
             latest_rate,
+            quote_book,
             resume_only,
             timeout: Duration::from_secs(120),
             keep_alive: KeepAlive::Until(Instant::now() + Duration::from_secs(10)),
@@ -258,10 +289,11 @@ where
 
     fn inject_fully_negotiated_inbound(
         &mut self,
-        mut substream: NegotiatedSubstream,
+        (mut substream, version): (NegotiatedSubstream, protocol::Version),
         _: Self::InboundOpenInfo,
     ) {
         self.keep_alive = KeepAlive::Yes;
+        tracing::debug!(?version, "Negotiated swap setup protocol");
 
         let (sender, receiver) = bmrng::channel_with_timeout::<bitcoin::Amount, WalletSnapshot>(
             1,
@@ -271,6 +303,7 @@ where
         let min_buy = self.min_buy;
         let max_buy = self.max_buy;
         let latest_rate = self.latest_rate.latest_rate();
+        let quote_book = self.quote_book.clone();
         let env_config = self.env_config;
 
         let protocol = tokio::time::timeout(self.timeout, async move {
@@ -319,9 +352,22 @@ where
                 }
 
                 let rate = latest_rate.map_err(|e| Error::LatestRateFetchFailed(Box::new(e)))?;
-                let xmr = rate
-                    .sell_quote(btc)
-                    .map_err(Error::SellQuoteCalculationFailed)?;
+                // Only versions that know about signed quotes can reference them.
+                let quote_id = request
+                    .quote_id
+                    .filter(|_| version.supports_signed_quotes());
+                let xmr = match quote_id {
+                    Some(id) => {
+                        let ask = rate.ask().map_err(Error::SellQuoteCalculationFailed)?;
+                        let price = quote_book.honoured_price(id, ask)?;
+
+                        Rate::sell_quote_at(price, btc)
+                            .map_err(Error::SellQuoteCalculationFailed)?
+                    }
+                    None => rate
+                        .sell_quote(btc)
+                        .map_err(Error::SellQuoteCalculationFailed)?,
+                };
 
                 if wallet_snapshot.balance < xmr + wallet_snapshot.lock_fee {
                     return Err(Error::BalanceTooLow {
@@ -357,10 +403,12 @@ where
 
             let message0 = swap_setup::read_cbor_message::<Message0>(&mut substream)
                 .await
-                .context("Failed to read message0")?;
+                .context("Failed to read message0")
+                .context(Aborted)?;
             let (swap_id, state1) = state0
                 .receive(message0)
-                .context("Failed to transition state0 -> state1 using message0")?;
+                .context("Failed to transition state0 -> state1 using message0")
+                .context(Aborted)?;
 
             swap_setup::write_cbor_message(&mut substream, state1.next_message())
                 .await
@@ -368,10 +416,12 @@ where
 
             let message2 = swap_setup::read_cbor_message::<Message2>(&mut substream)
                 .await
-                .context("Failed to read message2")?;
+                .context("Failed to read message2")
+                .context(Aborted)?;
             let state2 = state1
                 .receive(message2)
-                .context("Failed to transition state1 -> state2 using message2")?;
+                .context("Failed to transition state1 -> state2 using message2")
+                .context(Aborted)?;
 
             swap_setup::write_cbor_message(&mut substream, state2.next_message())
                 .await
@@ -379,10 +429,12 @@ where
 
             let message4 = swap_setup::read_cbor_message::<Message4>(&mut substream)
                 .await
-                .context("Failed to read message4")?;
+                .context("Failed to read message4")
+                .context(Aborted)?;
             let state3 = state2
                 .receive(message4)
-                .context("Failed to transition state2 -> state3 using message4")?;
+                .context("Failed to transition state2 -> state3 using message4")
+                .context(Aborted)?;
 
             substream
                 .flush()
@@ -399,9 +451,12 @@ where
         let max_seconds = self.timeout.as_secs();
         self.inbound_stream = OptionFuture::from(Some(
             async move {
-                protocol.await.with_context(|| {
-                    format!("Failed to complete execution setup within {}s", max_seconds)
-                })?
+                protocol
+                    .await
+                    .with_context(|| {
+                        format!("Failed to complete execution setup within {}s", max_seconds)
+                    })
+                    .and_then(|result| result)
             }
             .boxed(),
         ));
@@ -493,6 +548,8 @@ pub enum Error {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    #[error("Failed to honour quote")]
+    Quote(#[from] quote_book::Error),
 }
 
 impl Error {
@@ -514,6 +571,15 @@ impl Error {
                     asb: *asb,
                 }
             }
+            Error::Quote(quote_book::Error::UnknownOrExpired(id)) => {
+                SpotPriceError::QuoteExpired { id: *id }
+            }
+            Error::Quote(quote_book::Error::BeyondTolerance { quoted, ask }) => {
+                SpotPriceError::QuoteBeyondTolerance {
+                    quoted: *quoted,
+                    ask: *ask,
+                }
+            }
             Error::LatestRateFetchFailed(_) | Error::SellQuoteCalculationFailed(_) => {
                 SpotPriceError::Other
             }
@@ -124,6 +124,9 @@ pub struct NewSwap {
     pub tx_refund_fee: bitcoin::Amount,
     pub tx_cancel_fee: bitcoin::Amount,
     pub bitcoin_refund_address: bitcoin::Address,
+    pub coin_control: bitcoin::CoinControl,
+    /// The signed quote we ask Alice to honour.
+    pub quote_id: Option<Uuid>,
 }
 
 #[derive(Debug)]
@@ -148,12 +151,21 @@ impl ProtocolsHandler for Handler {
 
     fn inject_fully_negotiated_outbound(
         &mut self,
-        mut substream: NegotiatedSubstream,
+        (mut substream, version): (NegotiatedSubstream, protocol::Version),
         info: Self::OutboundOpenInfo,
     ) {
         let bitcoin_wallet = self.bitcoin_wallet.clone();
         let env_config = self.env_config;
 
+        tracing::debug!(?version, "Negotiated swap setup protocol");
+        let quote_id = match info.quote_id {
+            Some(id) if !version.supports_signed_quotes() => {
+                tracing::warn!(%id, "Seller does not support honouring signed quotes, it will use its current price");
+                None
+            }
+            quote_id => quote_id,
+        };
+
         let protocol = tokio::time::timeout(self.timeout, async move {
             write_cbor_message(&mut substream, SpotPriceRequest {
                 btc: info.btc,
@@ -161,6 +173,7 @@ impl ProtocolsHandler for Handler {
                     bitcoin: env_config.bitcoin_network,
                     monero: env_config.monero_network,
                 },
+                quote_id,
             })
             .await?;
 
@@ -181,7 +194,9 @@ impl ProtocolsHandler for Handler {
 
             write_cbor_message(&mut substream, state0.next_message()).await?;
             let message1 = read_cbor_message::<Message1>(&mut substream).await?;
-            let state1 = state0.receive(bitcoin_wallet.as_ref(), message1).await?;
+            let state1 = state0
+                .receive(bitcoin_wallet.as_ref(), message1, &info.coin_control)
+                .await?;
 
             write_cbor_message(&mut substream, state1.next_message()).await?;
             let message3 = read_cbor_message::<Message3>(&mut substream).await?;
@@ -281,6 +296,15 @@ pub enum Error {
         asb: BlockchainNetwork,
     },
 
+    #[error("Seller no longer honours quote {id} because it expired")]
+    QuoteExpired { id: Uuid },
+
+    #[error("Seller no longer honours the quoted price {quoted}, it now asks {ask}")]
+    QuoteBeyondTolerance {
+        quoted: bitcoin::Amount,
+        ask: bitcoin::Amount,
+    },
+
     #[error("Failed to complete swap setup within {seconds}s")]
     Timeout { seconds: u64 },
 
@@ -304,6 +328,10 @@ impl From<SpotPriceError> for Error {
             SpotPriceError::BlockchainNetworkMismatch { cli, asb } => {
                 Error::BlockchainNetworkMismatch { cli, asb }
             }
+            SpotPriceError::QuoteExpired { id } => Error::QuoteExpired { id },
+            SpotPriceError::QuoteBeyondTolerance { quoted, ask } => {
+                Error::QuoteBeyondTolerance { quoted, ask }
+            }
             SpotPriceError::Other => Error::Other,
         }
     }
@@ -1,8 +1,9 @@
-use crate::asb::LatestRate;
+use crate::asb::{LatestRate, QuoteBook};
 use crate::libp2p_ext::MultiAddrExt;
+use crate::network::dht;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::seed::Seed;
-use crate::{asb, bitcoin, cli, env, tor};
+use crate::{asb, bitcoin, cli, env, rendezvous_node, tor};
 use anyhow::{Context, Result};
 use libp2p::swarm::{NetworkBehaviour, SwarmBuilder};
 use libp2p::{identity, Multiaddr, Swarm};
@@ -14,37 +15,80 @@ pub fn asb<LR>(
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
     latest_rate: LR,
+    quote_book: QuoteBook,
     resume_only: bool,
     env_config: env::Config,
-    rendezvous_params: Option<(Multiaddr, XmrBtcNamespace)>,
+    rendezvous_params: Option<(Vec<Multiaddr>, XmrBtcNamespace)>,
+    dht_params: Option<(Vec<Multiaddr>, XmrBtcNamespace)>,
 ) -> Result<Swarm<asb::Behaviour<LR>>>
 where
     LR: LatestRate + Send + 'static + Debug + Clone,
 {
     let identity = seed.derive_libp2p_identity();
 
-    let rendezvous_params = if let Some((address, namespace)) = rendezvous_params {
-        let peer_id = address
-            .extract_peer_id()
-            .context("Rendezvous node address must contain peer ID")?;
+    let rendezvous_params = if let Some((addresses, namespace)) = rendezvous_params {
+        let rendezvous_nodes = addresses
+            .into_iter()
+            .map(|address| {
+                let peer_id = address
+                    .extract_peer_id()
+                    .context("Rendezvous node address must contain peer ID")?;
 
-        Some((identity.clone(), peer_id, address, namespace))
+                Ok((peer_id, address))
+            })
+            .collect::<Result<_>>()?;
+
+        Some((identity.clone(), rendezvous_nodes, namespace))
     } else {
         None
     };
 
+    let peer_id = identity.public().into();
+
+    let dht = match dht_params {
+        Some((bootstrap_nodes, namespace)) => Some(dht::provider(
+            peer_id,
+            dht::bootstrap_nodes(bootstrap_nodes)?,
+            namespace,
+        )),
+        None => None,
+    };
+
     let behaviour = asb::Behaviour::new(
         min_buy,
         max_buy,
         latest_rate,
+        quote_book,
         resume_only,
         env_config,
         rendezvous_params,
+        dht,
+        identity.public(),
     );
 
     let transport = asb::transport::new(&identity)?;
+
+    let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
+        .executor(Box::new(|f| {
+            tokio::spawn(f);
+        }))
+        .build();
+
+    Ok(swarm)
+}
+
+pub fn rendezvous_node(
+    seed: &Seed,
+    config: rendezvous_node::Config,
+) -> Result<Swarm<rendezvous_node::Behaviour>> {
+    let identity = seed.derive_libp2p_identity();
+
     let peer_id = identity.public().into();
 
+    let behaviour = rendezvous_node::Behaviour::new(peer_id, config);
+
+    let transport = asb::transport::new(&identity)?;
+
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
         .executor(Box::new(|f| {
             tokio::spawn(f);
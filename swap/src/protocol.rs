@@ -1,3 +1,6 @@
+use crate::asb::limits::Ban;
+use crate::database::BitcoinWalletDatabase;
+use crate::network::address_book::AddressHealth;
 use crate::protocol::alice::swap::is_complete as alice_is_complete;
 use crate::protocol::alice::AliceState;
 use crate::protocol::bob::swap::is_complete as bob_is_complete;
@@ -140,7 +143,27 @@ pub trait Database {
     async fn get_monero_address(&self, swap_id: Uuid) -> Result<monero::Address>;
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()>;
     async fn get_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>>;
+    async fn insert_address_health(
+        &self,
+        peer_id: PeerId,
+        address: Multiaddr,
+        health: AddressHealth,
+    ) -> Result<()>;
+    async fn get_address_health(&self, peer_id: PeerId) -> Result<Vec<(Multiaddr, AddressHealth)>>;
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()>;
     async fn get_state(&self, swap_id: Uuid) -> Result<State>;
     async fn all(&self) -> Result<Vec<(Uuid, State)>>;
+    async fn insert_swap_labels(&self, swap_id: Uuid, labels: bitcoin::SwapLabels) -> Result<()>;
+    async fn insert_bitcoin_transaction_label(
+        &self,
+        txid: bitcoin::Txid,
+        label: bitcoin::Label,
+        swap_id: Option<Uuid>,
+    ) -> Result<()>;
+    async fn get_bitcoin_address_labels(&self) -> Result<Vec<bitcoin::Labelled<bitcoin::Address>>>;
+    async fn get_bitcoin_transaction_labels(&self) -> Result<Vec<bitcoin::Labelled<bitcoin::Txid>>>;
+    async fn open_bitcoin_wallet(&self) -> Result<BitcoinWalletDatabase>;
+    async fn insert_ban(&self, ban: Ban) -> Result<()>;
+    async fn get_bans(&self) -> Result<Vec<Ban>>;
+    async fn remove_ban(&self, peer_id: PeerId) -> Result<()>;
 }
@@ -1,6 +1,6 @@
 use crate::bitcoin::{
-    current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxPunish, TxRedeem, TxRefund, Txid,
+    current_epoch, CancelTimelock, ExpiredTimelocks, Label, PunishTimelock, SwapLabels,
+    Transaction, TxCancel, TxPunish, TxRedeem, TxRefund, Txid,
 };
 use crate::env::Config;
 use crate::monero::wallet::{TransferRequest, WatchRequest};
@@ -465,6 +465,22 @@ impl State3 {
         TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee)
     }
 
+    /// The transactions that pay to our wallet. Bob funds the lock
+    /// transaction and the cancel and refund transactions only move his
+    /// Bitcoin, so they never show up in our wallet.
+    pub fn bitcoin_labels(&self) -> SwapLabels {
+        SwapLabels {
+            addresses: vec![
+                (self.redeem_address.clone(), Label::Redeem),
+                (self.punish_address.clone(), Label::Punish),
+            ],
+            transactions: vec![
+                (self.tx_redeem().txid(), Label::Redeem),
+                (self.tx_punish().txid(), Label::Punish),
+            ],
+        }
+    }
+
     pub fn extract_monero_private_key(
         &self,
         published_refund_tx: bitcoin::Transaction,
@@ -529,8 +545,12 @@ impl State3 {
     pub async fn punish_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
         let signed_tx_punish = self.signed_punish_transaction()?;
 
-        let (txid, subscription) = bitcoin_wallet.broadcast(signed_tx_punish, "punish").await?;
-        subscription.wait_until_final().await?;
+        let (txid, _) = bitcoin_wallet
+            .broadcast(signed_tx_punish.clone(), "punish")
+            .await?;
+        bitcoin_wallet
+            .ensure_final(signed_tx_punish, "punish")
+            .await?;
 
         Ok(txid)
     }
@@ -28,6 +28,12 @@ where
 {
     let mut current_state = swap.state;
 
+    if let AliceState::Started { state3 } = &current_state {
+        swap.db
+            .insert_swap_labels(swap.swap_id, state3.bitcoin_labels())
+            .await?;
+    }
+
     while !is_complete(&current_state) && !exit_early(&current_state) {
         current_state = next_state(
             swap.swap_id,
@@ -111,6 +117,18 @@ where
             }
         }
         AliceState::BtcLocked { state3 } => {
+            // The lock transaction might have been reorged out of the chain since we saw it
+            // reaching finality, e.g. while we were offline. Never lock Monero in that case.
+            let tx_lock_status = bitcoin_wallet.status_of_script(&state3.tx_lock).await?;
+            if !tx_lock_status.is_confirmed_with(env_config.bitcoin_finality_confirmations) {
+                tracing::warn!(
+                    %tx_lock_status,
+                    "Bitcoin lock transaction is no longer final, waiting for finality again"
+                );
+
+                return Ok(AliceState::BtcLockTransactionSeen { state3 });
+            }
+
             match state3.expired_timelocks(bitcoin_wallet).await? {
                 ExpiredTimelocks::None => {
                     // Record the current monero wallet block height so we don't have to scan from
@@ -270,9 +288,22 @@ where
             },
         },
         AliceState::BtcRedeemTransactionPublished { state3 } => {
-            let subscription = bitcoin_wallet.subscribe_to(state3.tx_redeem()).await;
+            // Hold on to the signed transaction so we can publish it again in case it gets
+            // reorged out.
+            let finality = match bitcoin_wallet
+                .get_raw_transaction(state3.tx_redeem().txid())
+                .await
+            {
+                Ok(tx_redeem) => bitcoin_wallet.ensure_final(tx_redeem, "redeem").await,
+                Err(error) => {
+                    tracing::debug!("Failed to fetch published redeem transaction: {:#}", error);
+
+                    let subscription = bitcoin_wallet.subscribe_to(state3.tx_redeem()).await;
+                    subscription.wait_until_final().await
+                }
+            };
 
-            match subscription.wait_until_final().await {
+            match finality {
                 Ok(_) => AliceState::BtcRedeemed,
                 Err(e) => {
                     bail!("The Bitcoin redeem transaction was seen in mempool, but waiting for finality timed out with {}. Manual investigation might be needed to ensure that the transaction was included.", e)
@@ -36,11 +36,13 @@ impl Swap {
         monero_receive_address: monero::Address,
         bitcoin_change_address: bitcoin::Address,
         btc_amount: bitcoin::Amount,
+        coin_control: bitcoin::CoinControl,
     ) -> Self {
         Self {
             state: BobState::Started {
                 btc_amount,
                 change_address: bitcoin_change_address,
+                coin_control,
             },
             event_loop_handle,
             db,
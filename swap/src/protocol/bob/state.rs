@@ -1,7 +1,7 @@
 use crate::bitcoin::wallet::EstimateFeeRate;
 use crate::bitcoin::{
-    self, current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxLock, Txid,
+    self, current_epoch, CancelTimelock, ExpiredTimelocks, Label, PunishTimelock, SwapLabels,
+    Transaction, TxCancel, TxLock, Txid,
 };
 use crate::monero;
 use crate::monero::wallet::WatchRequest;
@@ -26,6 +26,7 @@ pub enum BobState {
     Started {
         btc_amount: bitcoin::Amount,
         change_address: bitcoin::Address,
+        coin_control: bitcoin::CoinControl,
     },
     SwapSetupCompleted(State2),
     BtcLocked {
@@ -153,6 +154,7 @@ impl State0 {
         self,
         wallet: &bitcoin::Wallet<B, D, C>,
         msg: Message1,
+        coin_control: &bitcoin::CoinControl,
     ) -> Result<State1>
     where
         C: EstimateFeeRate,
@@ -179,6 +181,7 @@ impl State0 {
             msg.A,
             self.b.public(),
             self.refund_address.clone(),
+            coin_control,
         )
         .await?;
         let v = msg.v_a + self.v_b;
@@ -331,6 +334,27 @@ impl State2 {
         }
     }
 
+    pub fn bitcoin_labels(&self) -> SwapLabels {
+        let tx_cancel = TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            self.tx_cancel_fee,
+        );
+        let tx_refund =
+            bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, self.tx_refund_fee);
+
+        SwapLabels {
+            addresses: vec![(self.refund_address.clone(), Label::Refund)],
+            transactions: vec![
+                (self.tx_lock.txid(), Label::Lock),
+                (tx_cancel.txid(), Label::Cancel),
+                (tx_refund.txid(), Label::Refund),
+            ],
+        }
+    }
+
     pub async fn lock_btc(self) -> Result<(State3, TxLock)> {
         Ok((
             State3 {
@@ -498,6 +522,8 @@ impl State4 {
             bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
         let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
 
+        // Seeing the redeem transaction is enough, a reorg cannot take back the
+        // signature it revealed.
         bitcoin_wallet
             .subscribe_to(tx_redeem.clone())
             .await
@@ -659,8 +685,12 @@ impl State6 {
 
     pub async fn publish_refund_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
         let signed_tx_refund = self.signed_refund_transaction()?;
-        let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
-        subscription.wait_until_final().await?;
+        bitcoin_wallet
+            .broadcast(signed_tx_refund.clone(), "refund")
+            .await?;
+        bitcoin_wallet
+            .ensure_final(signed_tx_refund, "refund")
+            .await?;
 
         Ok(())
     }
@@ -1,3 +1,4 @@
+use crate::bitcoin::wallet::Subscription;
 use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
 use crate::cli::EventLoopHandle;
 use crate::network::swap_setup::bob::NewSwap;
@@ -5,6 +6,7 @@ use crate::protocol::bob;
 use crate::protocol::bob::state::*;
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
+use std::convert::Infallible;
 use tokio::select;
 use uuid::Uuid;
 
@@ -40,6 +42,12 @@ pub async fn run_until(
         )
         .await?;
 
+        if let BobState::SwapSetupCompleted(state2) = &current_state {
+            swap.db
+                .insert_swap_labels(swap.id, state2.bitcoin_labels())
+                .await?;
+        }
+
         swap.db
             .insert_latest_state(swap.id, current_state.clone().into())
             .await?;
@@ -62,6 +70,7 @@ async fn next_state(
         BobState::Started {
             btc_amount,
             change_address,
+            coin_control,
         } => {
             let tx_refund_fee = bitcoin_wallet
                 .estimate_fee(TxRefund::weight(), btc_amount)
@@ -69,6 +78,7 @@ async fn next_state(
             let tx_cancel_fee = bitcoin_wallet
                 .estimate_fee(TxCancel::weight(), btc_amount)
                 .await?;
+            let quote_id = event_loop_handle.quote_to_honour().await?;
 
             let state2 = event_loop_handle
                 .setup_swap(NewSwap {
@@ -77,6 +87,8 @@ async fn next_state(
                     tx_refund_fee,
                     tx_cancel_fee,
                     bitcoin_refund_address: change_address,
+                    coin_control,
+                    quote_id,
                 })
                 .await?;
 
@@ -143,6 +155,7 @@ async fn next_state(
                         let state4 = state3.cancel();
                         BobState::CancelTimelockExpired(state4)
                     },
+                    result = republish_lock_on_reorg(bitcoin_wallet, state3.tx_lock.clone(), &tx_lock_status) => match result? {},
                 }
             } else {
                 let state4 = state3.cancel();
@@ -177,6 +190,7 @@ async fn next_state(
                         let _ = result?;
                         BobState::CancelTimelockExpired(state.cancel())
                     }
+                    result = republish_lock_on_reorg(bitcoin_wallet, state.tx_lock.clone(), &tx_lock_status) => match result? {}
                 }
             } else {
                 BobState::CancelTimelockExpired(state.cancel())
@@ -201,6 +215,7 @@ async fn next_state(
                         let _ = result?;
                         BobState::CancelTimelockExpired(state.cancel())
                     }
+                    result = republish_lock_on_reorg(bitcoin_wallet, state.tx_lock.clone(), &tx_lock_status) => match result? {}
                 }
             } else {
                 BobState::CancelTimelockExpired(state.cancel())
@@ -218,6 +233,7 @@ async fn next_state(
                         let _ = result?;
                         BobState::CancelTimelockExpired(state.cancel())
                     }
+                    result = republish_lock_on_reorg(bitcoin_wallet, state.tx_lock.clone(), &tx_lock_status) => match result? {}
                 }
             } else {
                 BobState::CancelTimelockExpired(state.cancel())
@@ -226,30 +242,15 @@ async fn next_state(
         BobState::BtcRedeemed(state) => {
             let (spend_key, view_key) = state.xmr_keys();
 
-            let wallet_file_name = swap_id.to_string();
-            if let Err(e) = monero_wallet
-                .create_from_and_load(
-                    wallet_file_name.clone(),
+            let tx_hashes = monero_wallet
+                .sweep_from_keys(
+                    swap_id.to_string(),
                     spend_key,
                     view_key,
                     state.monero_wallet_restore_blockheight,
+                    monero_receive_address,
                 )
-                .await
-            {
-                // In case we failed to refresh/sweep, when resuming the wallet might already
-                // exist! This is a very unlikely scenario, but if we don't take care of it we
-                // might not be able to ever transfer the Monero.
-                tracing::warn!("Failed to generate monero wallet from keys: {:#}", e);
-                tracing::info!(%wallet_file_name,
-                    "Falling back to trying to open the the wallet if it already exists",
-                );
-                monero_wallet.open(wallet_file_name).await?;
-            }
-
-            // Ensure that the generated wallet is synced so we have a proper balance
-            monero_wallet.refresh().await?;
-            // Sweep (transfer all funds) to the given address
-            let tx_hashes = monero_wallet.sweep_all(monero_receive_address).await?;
+                .await?;
 
             for tx_hash in tx_hashes {
                 tracing::info!(%monero_receive_address, txid=%tx_hash.0, "Successfully transferred XMR to wallet");
@@ -289,3 +290,24 @@ async fn next_state(
         BobState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
     })
 }
+
+/// Publishes our lock transaction again whenever it loses its confirmations
+/// to a chain reorganization, for as long as the swap waits on it.
+///
+/// The lock transaction is signed deterministically, signing it again yields
+/// the same transaction.
+async fn republish_lock_on_reorg(
+    bitcoin_wallet: &bitcoin::Wallet,
+    tx_lock: bitcoin::TxLock,
+    tx_lock_status: &Subscription,
+) -> Result<Infallible> {
+    loop {
+        tx_lock_status.wait_until_reorged().await?;
+
+        let signed_tx = bitcoin_wallet
+            .sign_and_finalize(tx_lock.clone().into())
+            .await
+            .context("Failed to sign Bitcoin lock transaction")?;
+        bitcoin_wallet.republish(signed_tx, "lock").await;
+    }
+}
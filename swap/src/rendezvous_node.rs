@@ -0,0 +1,415 @@
+//! A rendezvous point that sellers register at and takers discover them
+//! through.
+//!
+//! Anyone can run one of these to provide discovery for their community
+//! instead of relying on a single, well-known rendezvous point. It also takes
+//! part in the DHT, so it can serve as a bootstrap node for it.
+
+use crate::network::dht;
+use crate::network::rendezvous::XmrBtcNamespace;
+use futures::StreamExt;
+use libp2p::kad::KademliaEvent;
+use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::rendezvous::{server, Namespace};
+use libp2p::swarm::SwarmEvent;
+use libp2p::{NetworkBehaviour, PeerId, Swarm};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Registrations with a TTL below this are refused. Same as the default of
+/// the rendezvous protocol.
+pub const DEFAULT_MIN_TTL: u64 = 60 * 60 * 2;
+
+/// Registrations with a TTL above this are refused. Same as the default of
+/// the rendezvous protocol.
+pub const DEFAULT_MAX_TTL: u64 = 60 * 60 * 72;
+
+pub const DEFAULT_MAX_REGISTRATIONS: usize = 1000;
+
+/// How often we check for bans that can be lifted.
+const LIFT_BANS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    pub min_ttl: u64,
+    pub max_ttl: u64,
+    pub max_registrations: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_ttl: DEFAULT_MIN_TTL,
+            max_ttl: DEFAULT_MAX_TTL,
+            max_registrations: DEFAULT_MAX_REGISTRATIONS,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OutEvent {
+    Rendezvous(server::Event),
+    Other,
+}
+
+impl From<server::Event> for OutEvent {
+    fn from(event: server::Event) -> Self {
+        OutEvent::Rendezvous(event)
+    }
+}
+
+impl From<PingEvent> for OutEvent {
+    fn from(_: PingEvent) -> Self {
+        OutEvent::Other
+    }
+}
+
+impl From<KademliaEvent> for OutEvent {
+    fn from(_: KademliaEvent) -> Self {
+        OutEvent::Other
+    }
+}
+
+/// A `NetworkBehaviour` that serves registrations and discovery requests in
+/// the xmr-btc-swap namespaces as well as the DHT.
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "OutEvent", event_process = false)]
+#[allow(missing_debug_implementations)]
+pub struct Behaviour {
+    pub rendezvous: server::Behaviour,
+    pub dht: dht::Behaviour,
+
+    /// Ping behaviour that ensures that the underlying network connection
+    /// is still alive. If the ping fails a connection close event
+    /// will be emitted that is picked up as swarm event.
+    ping: Ping,
+}
+
+impl Behaviour {
+    pub fn new(local_peer_id: PeerId, config: Config) -> Self {
+        Self {
+            rendezvous: server::Behaviour::new(
+                server::Config::default()
+                    .with_min_ttl(config.min_ttl)
+                    .with_max_ttl(config.max_ttl),
+            ),
+            dht: dht::new(local_peer_id, vec![]),
+            ping: Ping::new(PingConfig::new().with_keep_alive(true)),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum Rejection {
+    #[error("Namespace {0:?} is not an xmr-btc-swap namespace")]
+    UnknownNamespace(Namespace),
+    #[error("Already serving the maximum of {0} registrations")]
+    TooManyRegistrations(usize),
+}
+
+/// Keeps track of the registrations we serve and decides which ones we
+/// accept.
+///
+/// The rendezvous server of libp2p stores a registration before we get to see
+/// it and has no way to drop it again, so a rejected registration is only
+/// dropped once it expires. Until then the peer is banned, so it cannot renew
+/// it or register again.
+#[derive(Debug)]
+pub struct Registrations {
+    active: HashSet<(PeerId, Namespace)>,
+    max_registrations: usize,
+}
+
+impl Registrations {
+    pub fn new(max_registrations: usize) -> Self {
+        Self {
+            active: HashSet::new(),
+            max_registrations,
+        }
+    }
+
+    pub fn admit(&mut self, peer: PeerId, namespace: Namespace) -> Result<(), Rejection> {
+        if XmrBtcNamespace::from_namespace(&namespace).is_none() {
+            return Err(Rejection::UnknownNamespace(namespace));
+        }
+
+        // A peer renewing its registration does not count towards the limit.
+        let key = (peer, namespace);
+        if !self.active.contains(&key) && self.active.len() >= self.max_registrations {
+            return Err(Rejection::TooManyRegistrations(self.max_registrations));
+        }
+
+        self.active.insert(key);
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, peer: PeerId, namespace: Namespace) {
+        self.active.remove(&(peer, namespace));
+    }
+
+    pub fn len(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+}
+
+pub struct EventLoop {
+    swarm: Swarm<Behaviour>,
+    registrations: Registrations,
+    /// Peers banned for a rejected registration and when that registration
+    /// expires.
+    bans: HashMap<PeerId, Instant>,
+}
+
+impl EventLoop {
+    pub fn new(swarm: Swarm<Behaviour>, config: Config) -> Self {
+        Self {
+            swarm,
+            registrations: Registrations::new(config.max_registrations),
+            bans: HashMap::new(),
+        }
+    }
+
+    pub async fn run(mut self) {
+        let mut lift_bans = tokio::time::interval(LIFT_BANS_INTERVAL);
+
+        loop {
+            tokio::select! {
+                swarm_event = self.swarm.select_next_some() => {
+                    match swarm_event {
+                        SwarmEvent::Behaviour(OutEvent::Rendezvous(event)) => {
+                            self.handle_rendezvous_event(event)
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Other) => {}
+                        SwarmEvent::NewListenAddr { address, .. } => {
+                            tracing::info!(%address, "New listen address reported");
+                        }
+                        SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                            tracing::debug!(peer = %peer_id, "Connection established");
+                        }
+                        SwarmEvent::ConnectionClosed { peer_id, .. } => {
+                            tracing::debug!(peer = %peer_id, "Connection closed");
+                        }
+                        _ => {}
+                    }
+                }
+                _ = lift_bans.tick() => {
+                    self.lift_expired_bans(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Bans `peer` until the registration we rejected expires.
+    fn ban(&mut self, peer: PeerId, ttl: u64) {
+        let expires_at = Instant::now() + Duration::from_secs(ttl);
+        let banned_until = self.bans.entry(peer).or_insert(expires_at);
+        *banned_until = (*banned_until).max(expires_at);
+
+        self.swarm.ban_peer_id(peer);
+    }
+
+    fn lift_expired_bans(&mut self, now: Instant) {
+        let expired = self
+            .bans
+            .iter()
+            .filter(|(_, banned_until)| now >= **banned_until)
+            .map(|(peer, _)| *peer)
+            .collect::<Vec<_>>();
+
+        for peer in expired {
+            self.bans.remove(&peer);
+            self.swarm.unban_peer_id(peer);
+            tracing::info!(%peer, "Lifted ban after the rejected registration expired");
+        }
+    }
+
+    fn handle_rendezvous_event(&mut self, event: server::Event) {
+        match event {
+            server::Event::PeerRegistered { peer, registration } => {
+                let ttl = registration.ttl;
+
+                match self.registrations.admit(peer, registration.namespace) {
+                    Ok(()) => {
+                        tracing::info!(
+                            %peer,
+                            %ttl,
+                            registrations = self.registrations.len(),
+                            "Peer registered"
+                        );
+                    }
+                    Err(rejection) => {
+                        tracing::warn!(
+                            %peer,
+                            %ttl,
+                            "Banning peer until its registration expires: {}",
+                            rejection
+                        );
+                        self.ban(peer, ttl);
+                    }
+                }
+            }
+            server::Event::PeerUnregistered { peer, namespace } => {
+                self.registrations.remove(peer, namespace);
+                tracing::info!(%peer, "Peer unregistered");
+            }
+            server::Event::RegistrationExpired(registration) => {
+                let peer = registration.record.peer_id();
+                self.registrations.remove(peer, registration.namespace);
+                tracing::debug!(%peer, "Registration expired");
+            }
+            server::Event::PeerNotRegistered { peer, error, .. } => {
+                tracing::debug!(%peer, "Refused registration: {:?}", error);
+            }
+            server::Event::DiscoverServed {
+                enquirer,
+                registrations,
+            } => {
+                tracing::debug!(
+                    peer = %enquirer,
+                    sellers = registrations.len(),
+                    "Served discover request"
+                );
+            }
+            server::Event::DiscoverNotServed { enquirer, error } => {
+                tracing::debug!(peer = %enquirer, "Failed to serve discover request: {:?}", error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test::{new_swarm, SwarmExt};
+    use libp2p::rendezvous;
+
+    #[test]
+    fn admits_registrations_in_xmr_btc_namespaces() {
+        let mut registrations = Registrations::new(10);
+
+        for namespace in [
+            XmrBtcNamespace::Mainnet,
+            XmrBtcNamespace::Testnet,
+            XmrBtcNamespace::Signet,
+        ]
+        .iter()
+        {
+            let result = registrations.admit(PeerId::random(), Namespace::from(*namespace));
+
+            assert_eq!(result, Ok(()));
+        }
+        assert_eq!(registrations.len(), 3);
+    }
+
+    #[test]
+    fn given_foreign_namespace_then_rejects_registration() {
+        let mut registrations = Registrations::new(10);
+        let namespace = Namespace::from_static("some-other-app");
+
+        let result = registrations.admit(PeerId::random(), namespace.clone());
+
+        assert_eq!(result, Err(Rejection::UnknownNamespace(namespace)));
+        assert!(registrations.is_empty());
+    }
+
+    #[test]
+    fn given_limit_reached_then_rejects_new_peers_but_not_renewals() {
+        let mut registrations = Registrations::new(1);
+        let namespace = Namespace::from(XmrBtcNamespace::Mainnet);
+        let registered = PeerId::random();
+        registrations.admit(registered, namespace.clone()).unwrap();
+
+        let new_peer = registrations.admit(PeerId::random(), namespace.clone());
+        let renewal = registrations.admit(registered, namespace);
+
+        assert_eq!(new_peer, Err(Rejection::TooManyRegistrations(1)));
+        assert_eq!(renewal, Ok(()));
+    }
+
+    #[test]
+    fn given_registration_removed_then_admits_new_peer() {
+        let mut registrations = Registrations::new(1);
+        let namespace = Namespace::from(XmrBtcNamespace::Mainnet);
+        let registered = PeerId::random();
+        registrations.admit(registered, namespace.clone()).unwrap();
+
+        registrations.remove(registered, namespace.clone());
+        let result = registrations.admit(PeerId::random(), namespace);
+
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn given_foreign_namespace_then_bans_peer_until_its_registration_expires() {
+        let config = Config {
+            min_ttl: 1,
+            ..Config::default()
+        };
+        let mut rendezvous_node = new_swarm(|peer_id, _| Behaviour::new(peer_id, config));
+        let rendezvous_address = rendezvous_node.listen_on_random_memory_address().await;
+        let rendezvous_peer_id = *rendezvous_node.local_peer_id();
+
+        let mut peer = new_swarm(|_, identity| rendezvous::client::Behaviour::new(identity));
+        peer.listen_on_random_memory_address().await;
+        peer.block_on_connection(&mut rendezvous_node).await;
+
+        tokio::spawn(EventLoop::new(rendezvous_node, config).run());
+
+        peer.behaviour_mut().register(
+            Namespace::from_static("some-other-app"),
+            rendezvous_peer_id,
+            Some(2),
+        );
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let SwarmEvent::ConnectionClosed { peer_id, .. } = peer.select_next_some().await
+                {
+                    if peer_id == rendezvous_peer_id {
+                        break;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("rendezvous node to ban the peer");
+
+        tokio::time::sleep(Duration::from_secs(4)).await;
+
+        peer.dial_addr(rendezvous_address).unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let SwarmEvent::ConnectionEstablished { peer_id, .. } =
+                    peer.select_next_some().await
+                {
+                    if peer_id == rendezvous_peer_id {
+                        break;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("peer to connect again once its registration expired");
+
+        peer.behaviour_mut().register(
+            Namespace::from(XmrBtcNamespace::Testnet),
+            rendezvous_peer_id,
+            None,
+        );
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let SwarmEvent::Behaviour(rendezvous::client::Event::Registered { .. }) =
+                    peer.select_next_some().await
+                {
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("registration in an xmr-btc-swap namespace to be accepted");
+    }
+}
@@ -9,20 +9,24 @@ use get_port::get_port;
 use libp2p::core::Multiaddr;
 use libp2p::PeerId;
 use monero_harness::{image, Monero};
+use serde_json::{json, Value};
 use std::cmp::Ordering;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use swap::asb::FixedRate;
+use swap::asb::config::Limits;
+use swap::asb::quote_book::{DEFAULT_QUOTE_TOLERANCE, DEFAULT_QUOTE_VALIDITY};
+use swap::asb::{FixedRate, QuoteBook};
 use swap::bitcoin::{CancelTimelock, PunishTimelock, TxCancel, TxPunish, TxRedeem, TxRefund};
 use swap::database::SqliteDatabase;
 use swap::env::{Config, GetConfig};
 use swap::fs::ensure_directory_exists;
+use swap::network::address_book::AddressPreference;
 use swap::network::swarm;
 use swap::protocol::alice::{AliceState, Swap};
 use swap::protocol::bob::BobState;
-use swap::protocol::{alice, bob};
+use swap::protocol::{alice, bob, Database};
 use swap::seed::Seed;
 use swap::{asb, bitcoin, cli, env, monero};
 use tempfile::{tempdir, NamedTempFile};
@@ -99,7 +103,7 @@ where
 
     let (bob_bitcoin_wallet, bob_monero_wallet) = init_test_wallets(
         MONERO_WALLET_NAME_BOB,
-        containers.bitcoind_url,
+        containers.bitcoind_url.clone(),
         &monero,
         bob_starting_balances.clone(),
         tempdir().unwrap().path(),
@@ -137,6 +141,7 @@ where
         bob_starting_balances,
         bob_bitcoin_wallet,
         bob_monero_wallet,
+        bitcoind_url: containers.bitcoind_url,
     };
 
     testfn(test).await.unwrap()
@@ -235,15 +240,22 @@ async fn start_alice(
     let max_buy = bitcoin::Amount::from_sat(u64::MAX);
     let latest_rate = FixedRate::default();
     let resume_only = false;
+    let quote_book = QuoteBook::new(
+        seed.derive_libp2p_identity(),
+        DEFAULT_QUOTE_VALIDITY,
+        DEFAULT_QUOTE_TOLERANCE,
+    );
 
     let mut swarm = swarm::asb(
         &seed,
         min_buy,
         max_buy,
         latest_rate,
+        quote_book.clone(),
         resume_only,
         env_config,
         None,
+        None,
     )
     .unwrap();
     swarm.listen_on(listen_address).unwrap();
@@ -255,8 +267,12 @@ async fn start_alice(
         monero_wallet,
         db,
         FixedRate::default(),
+        quote_book,
         min_buy,
         max_buy,
+        Limits::default().quote_rate_limiter(),
+        Limits::default().swap_setup_rate_limiter(),
+        Limits::default().ban_list(vec![]),
     )
     .unwrap();
 
@@ -295,15 +311,25 @@ async fn init_test_wallets(
         env_config,
     )
     .await
-    .unwrap();
+    .unwrap()
+    .with_daemon({
+        let address = format!("127.0.0.1:{}", monero.monerod().rpc_port());
+        swap::monero::RemoteDaemon::new(vec![address.clone()], address, Default::default())
+            .unwrap()
+    });
 
     let electrum_rpc_url = {
         let input = format!("tcp://@localhost:{}", electrum_rpc_port);
         Url::parse(&input).unwrap()
     };
 
+    let db_path = NamedTempFile::new().unwrap().path().to_path_buf();
+    tokio::fs::File::create(&db_path).await.unwrap();
+    let db = Arc::new(SqliteDatabase::open(&db_path).await.unwrap());
+
     let btc_wallet = swap::bitcoin::Wallet::new(
-        electrum_rpc_url,
+        vec![electrum_rpc_url],
+        db,
         datadir,
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
@@ -312,6 +338,9 @@ async fn init_test_wallets(
     )
     .await
     .expect("could not init btc wallet");
+    btc_wallet
+        .start_processing_notifications()
+        .expect("could not process Electrum notifications");
 
     if starting_balances.btc != bitcoin::Amount::ZERO {
         mint(
@@ -408,8 +437,6 @@ struct BobParams {
 
 impl BobParams {
     pub async fn new_swap_from_db(&self, swap_id: Uuid) -> Result<(bob::Swap, cli::EventLoop)> {
-        let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-
         if let Some(parent_dir) = self.db_path.parent() {
             ensure_directory_exists(parent_dir)?;
         }
@@ -418,6 +445,8 @@ impl BobParams {
         }
         let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
 
+        let (event_loop, handle) = self.new_eventloop(swap_id, db.clone()).await?;
+
         let swap = bob::Swap::from_db(
             db,
             swap_id,
@@ -438,8 +467,6 @@ impl BobParams {
     ) -> Result<(bob::Swap, cli::EventLoop)> {
         let swap_id = Uuid::new_v4();
 
-        let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-
         if let Some(parent_dir) = self.db_path.parent() {
             ensure_directory_exists(parent_dir)?;
         }
@@ -447,6 +474,10 @@ impl BobParams {
             tokio::fs::File::create(&self.db_path).await?;
         }
         let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
+        db.insert_address(self.alice_peer_id, self.alice_address.clone())
+            .await?;
+
+        let (event_loop, handle) = self.new_eventloop(swap_id, db.clone()).await?;
 
         let swap = bob::Swap::new(
             db,
@@ -458,6 +489,7 @@ impl BobParams {
             self.monero_wallet.get_main_address(),
             self.bitcoin_wallet.new_address().await?,
             btc_amount,
+            bitcoin::CoinControl::default(),
         );
 
         Ok((swap, event_loop))
@@ -466,26 +498,25 @@ impl BobParams {
     pub async fn new_eventloop(
         &self,
         swap_id: Uuid,
+        db: Arc<SqliteDatabase>,
     ) -> Result<(cli::EventLoop, cli::EventLoopHandle)> {
         let tor_socks5_port = get_port()
             .expect("We don't care about Tor in the tests so we get a free port to disable it.");
 
+        let identity = self.seed.derive_libp2p_identity();
         let behaviour = cli::Behaviour::new(
             self.alice_peer_id,
             self.env_config,
             self.bitcoin_wallet.clone(),
+            identity.public(),
+            AddressPreference::Clearnet,
         );
-        let mut swarm = swarm::cli(
-            self.seed.derive_libp2p_identity(),
-            tor_socks5_port,
-            behaviour,
-        )
-        .await?;
-        swarm
-            .behaviour_mut()
-            .add_address(self.alice_peer_id, self.alice_address.clone());
+        let mut swarm = swarm::cli(identity, tor_socks5_port, behaviour).await?;
+        for (address, health) in cli::known_seller_addresses(db.clone(), self.alice_peer_id).await? {
+            swarm.behaviour_mut().restore_address(address, health);
+        }
 
-        cli::EventLoop::new(swap_id, swarm, self.alice_peer_id, self.env_config)
+        cli::EventLoop::new(swap_id, swarm, self.alice_peer_id, self.env_config, db)
     }
 }
 
@@ -528,6 +559,8 @@ pub struct TestContext {
     bob_starting_balances: StartingBalances,
     bob_bitcoin_wallet: Arc<bitcoin::Wallet>,
     bob_monero_wallet: Arc<monero::Wallet>,
+
+    bitcoind_url: Url,
 }
 
 impl TestContext {
@@ -548,6 +581,33 @@ impl TestContext {
         self.alice_swap_handle = alice_swap_handle;
     }
 
+    /// Restarts Alice on a new address that she did not listen on before.
+    ///
+    /// The new address is stored in Bob's database, as if Bob had learned it
+    /// through identify. Bob does not store loopback addresses learned through
+    /// identify, so the test cannot go through that.
+    pub async fn move_alice_to_new_address(&mut self) {
+        self.alice_handle.abort();
+
+        let alice_listen_port = get_port().expect("Failed to find a free port");
+        self.alice_listen_address = format!("/ip4/127.0.0.1/tcp/{}", alice_listen_port)
+            .parse()
+            .expect("failed to parse Alice's address");
+
+        let bob_db = SqliteDatabase::open(&self.bob_params.db_path)
+            .await
+            .unwrap();
+        bob_db
+            .insert_address(
+                self.bob_params.alice_peer_id,
+                self.alice_listen_address.clone(),
+            )
+            .await
+            .unwrap();
+
+        self.restart_alice().await;
+    }
+
     pub async fn alice_next_swap(&mut self) -> alice::Swap {
         timeout(Duration::from_secs(20), self.alice_swap_handle.recv())
             .await
@@ -580,6 +640,32 @@ impl TestContext {
         (swap, BobApplicationHandle(join_handle))
     }
 
+    /// Waits until the given transaction has a few confirmations and then
+    /// replaces the block it was included in with a new one, leaving the
+    /// transaction with a single confirmation at the same height.
+    ///
+    /// The Electrum server reports the same history for the transaction before
+    /// and after, so only the wallet's reorg detection notices this.
+    ///
+    /// Returns once our wallet has noticed that the transaction lost its
+    /// confirmations.
+    pub async fn reorg_out_bitcoin_transaction(&self, tx: bitcoin::TxLock) {
+        let txid = tx.txid();
+        let subscription = self.bob_bitcoin_wallet.subscribe_to(tx).await;
+
+        subscription.wait_until_confirmed_with(3u32).await.unwrap();
+
+        let (reorged, invalidated) = tokio::join!(
+            timeout(Duration::from_secs(60), subscription.wait_until_reorged()),
+            invalidate_block_containing(self.bitcoind_url.clone(), txid)
+        );
+
+        invalidated.unwrap();
+        reorged
+            .expect("Wallet did not notice the reorg within 60 seconds")
+            .unwrap();
+    }
+
     pub async fn assert_alice_redeemed(&mut self, state: AliceState) {
         assert!(matches!(state, AliceState::BtcRedeemed));
 
@@ -948,6 +1034,72 @@ pub async fn mint(node_url: Url, address: bitcoin::Address, amount: bitcoin::Amo
     Ok(())
 }
 
+/// Invalidates the most recent block that includes the given transaction and
+/// mines a replacement, which includes the transaction again.
+///
+/// We talk to bitcoind directly because the harness client does not expose
+/// `invalidateblock`.
+async fn invalidate_block_containing(node_url: Url, txid: bitcoin::Txid) -> Result<()> {
+    let block_count = bitcoind_rpc(&node_url, "getblockcount", json!([]))
+        .await?
+        .as_u64()
+        .context("Block count is not a number")?;
+
+    for height in (0..=block_count).rev().take(20) {
+        let block_hash = bitcoind_rpc(&node_url, "getblockhash", json!([height])).await?;
+        let block = bitcoind_rpc(&node_url, "getblock", json!([block_hash, 1])).await?;
+
+        let includes_tx = block["tx"]
+            .as_array()
+            .context("Block does not have a transaction list")?
+            .iter()
+            .any(|tx| tx.as_str() == Some(txid.to_string().as_str()));
+
+        if includes_tx {
+            bitcoind_rpc(&node_url, "invalidateblock", json!([block_hash])).await?;
+
+            let bitcoind_client = Client::new(node_url.clone());
+            let reward_address = bitcoind_client
+                .with_wallet(BITCOIN_TEST_WALLET_NAME)?
+                .getnewaddress(None, None)
+                .await?;
+            bitcoind_client
+                .generatetoaddress(1, reward_address, None)
+                .await?;
+
+            return Ok(());
+        }
+    }
+
+    bail!("Transaction {} is not included in any recent block", txid)
+}
+
+async fn bitcoind_rpc(node_url: &Url, method: &str, params: Value) -> Result<Value> {
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "harness",
+        "method": method,
+        "params": params,
+    });
+
+    let response = reqwest::Client::new()
+        .post(node_url.clone())
+        .basic_auth(bitcoind::RPC_USER, Some(bitcoind::RPC_PASSWORD))
+        .header("content-type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .text()
+        .await?;
+    let mut response = serde_json::from_str::<Value>(&response)?;
+
+    if !response["error"].is_null() {
+        bail!("{} failed: {}", method, response["error"])
+    }
+
+    Ok(response["result"].take())
+}
+
 // This is just to keep the containers alive
 #[allow(dead_code)]
 struct Containers<'a> {